@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
 use clap::Parser;
+use coprocessor::gc::{collect_garbage, GcArgs};
 use coprocessor::server::{
     common::FheOperation,
     coprocessor::{
@@ -8,6 +9,7 @@ use coprocessor::server::{
         AsyncComputeRequest, GetCiphertextBatch, TrivialEncryptBatch, TrivialEncryptRequestSingle,
     },
 };
+use coprocessor::tiering::{ColdStore, TieringArgs};
 use rand::Rng;
 use sqlx::types::Uuid;
 use tonic::metadata::MetadataValue;
@@ -48,6 +50,15 @@ pub enum Args {
         #[arg(long)]
         coprocessor_url: String,
     },
+    /// Collects the garbage of the specified database once, see --gc-dry-run
+    Gc {
+        #[command(flatten)]
+        gc: GcArgs,
+        // only the storage backend and bucket are used, to collect the
+        // tiered versions
+        #[command(flatten)]
+        tiering: TieringArgs,
+    },
 }
 
 fn main() {
@@ -78,9 +89,52 @@ fn main() {
         } => {
             smoke_test(tenant_api_key, coprocessor_url);
         }
+        Args::Gc { gc, tiering } => {
+            run_gc(gc, tiering);
+        }
     }
 }
 
+fn run_gc(gc: GcArgs, tiering: TieringArgs) {
+    let db_url =
+        std::env::var("DATABASE_URL").expect("DATABASE_URL environment variable is undefined");
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async move {
+            let pool = sqlx::postgres::PgPoolOptions::new()
+                .max_connections(1)
+                .connect(&db_url)
+                .await
+                .expect("Can't connect to postgres instance");
+
+            let cold = ColdStore::connect(&tiering)
+                .await
+                .expect("Can't connect to the tiering object store");
+
+            let report = collect_garbage(&pool, cold.as_ref(), &gc)
+                .await
+                .expect("Garbage collection failed");
+
+            let verb = if gc.gc_dry_run { "Collectable" } else { "Collected" };
+            println!("{verb} ct128 blobs: {} ({} bytes)", report.ct128_cleared, report.ct128_bytes);
+            println!(
+                "{verb} superseded ciphertext versions: {} ({} bytes)",
+                report.versions_deleted, report.versions_bytes
+            );
+            println!(
+                "{verb} superseded tiered ciphertext versions: {}",
+                report.cold_versions_deleted
+            );
+            println!(
+                "{verb} input blobs: {} ({} bytes)",
+                report.input_blobs_archived, report.input_blobs_bytes
+            );
+        });
+}
+
 fn smoke_test(tenant_api_key: String, coprocessor_url: String) {
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
@@ -1,4 +1,5 @@
 use clap::Parser;
+use fhevm_engine_common::signer::SignerSource;
 
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
@@ -67,14 +68,25 @@ pub struct Args {
     #[arg(long)]
     pub database_url: Option<String>,
 
-    /// Coprocessor private key file path.
+    /// Coprocessor signer: a private key file path (optionally prefixed
+    /// with file:), env:<variable> or remote:<url> of a RemoteSigner service.
     /// Private key is in plain text 0x1234.. format.
     #[arg(long, default_value = "./coprocessor.key")]
-    pub coprocessor_private_key: String,
+    pub coprocessor_private_key: SignerSource,
 
     /// Coprocessor service name in OTLP traces
     #[arg(long, default_value = "coprocessor")]
     pub service_name: String,
+
+    #[command(flatten)]
+    pub gc: crate::gc::GcArgs,
+
+    #[command(flatten)]
+    pub tiering: crate::tiering::TieringArgs,
+
+    #[cfg(feature = "debug_decrypt")]
+    #[command(flatten)]
+    pub debug_decrypt: crate::debug_decrypt::DebugDecryptArgs,
 }
 
 pub fn parse_args() -> Args {
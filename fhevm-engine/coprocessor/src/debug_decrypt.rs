@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use fhevm_engine_common::object_store::{connect, ObjectStore, StorageBackend};
+use fhevm_engine_common::types::SupportedFheCiphertexts;
+use fhevm_engine_common::utils::safe_deserialize;
+use sns_executor::{decrypt_ct128, Ct128Format, Ct128List};
+use sqlx::PgPool;
+use tokio::sync::OnceCell;
+
+use crate::server::coprocessor::{DebugDecryptBatch, DebugDecryptSingleResponse};
+use crate::tiering::{rehydrate_ciphertexts, ColdStore};
+use crate::types::CoprocessorError;
+
+#[cfg(not(debug_assertions))]
+compile_error!(
+    "the debug_decrypt feature decrypts with the tenant client keys and is refused in release builds"
+);
+
+/// Object store of the ct128 already uploaded by the sns-executor
+#[derive(clap::Args, Debug, Clone)]
+pub struct DebugDecryptArgs {
+    /// Where the sns-executor uploads the ct128: s3, local:<dir> or memory
+    #[arg(long, default_value = "s3")]
+    pub debug_decrypt_storage_backend: StorageBackend,
+
+    /// Bucket of the ct128 uploaded by the sns-executor
+    #[arg(long, default_value = "ct128")]
+    pub debug_decrypt_bucket_ct128: String,
+}
+
+impl Default for DebugDecryptArgs {
+    fn default() -> Self {
+        Self {
+            debug_decrypt_storage_backend: StorageBackend::S3,
+            debug_decrypt_bucket_ct128: "ct128".to_owned(),
+        }
+    }
+}
+
+// connected on the first ct128 decryption only
+static CT128_STORE: OnceCell<Arc<dyn ObjectStore>> = OnceCell::const_new();
+
+fn failure(error: impl ToString) -> CoprocessorError {
+    CoprocessorError::DebugDecryptionFailure {
+        error: error.to_string(),
+    }
+}
+
+/// Decrypts the latest ct64, or the ct128, of the handles with the tenant
+/// client key, in the order of the handles.
+pub async fn debug_decrypt(
+    pool: &PgPool,
+    cold: Option<&ColdStore>,
+    args: &DebugDecryptArgs,
+    tenant_id: i32,
+    server_key: tfhe::ServerKey,
+    req: &DebugDecryptBatch,
+) -> Result<Vec<DebugDecryptSingleResponse>, CoprocessorError> {
+    debug_decrypt_with_store(pool, cold, &CT128_STORE, args, tenant_id, server_key, req).await
+}
+
+pub(crate) async fn debug_decrypt_with_store(
+    pool: &PgPool,
+    cold: Option<&ColdStore>,
+    ct128_store: &OnceCell<Arc<dyn ObjectStore>>,
+    args: &DebugDecryptArgs,
+    tenant_id: i32,
+    server_key: tfhe::ServerKey,
+    req: &DebugDecryptBatch,
+) -> Result<Vec<DebugDecryptSingleResponse>, CoprocessorError> {
+    // the ciphertexts tiered to the object store are needed for both
+    if let Some(cold) = cold {
+        rehydrate_ciphertexts(pool, cold, tenant_id, &req.handles)
+            .await
+            .map_err(|e| CoprocessorError::CiphertextRehydrationFailure {
+                error: e.to_string(),
+            })?;
+    }
+
+    let cks_key = sqlx::query_scalar!(
+        "SELECT cks_key FROM tenants WHERE tenant_id = $1",
+        tenant_id
+    )
+    .fetch_one(pool)
+    .await?
+    .ok_or_else(|| failure("tenant has no client key"))?;
+
+    let ciphertexts = if req.ct128 {
+        fetch_ct128(pool, ct128_store, args, tenant_id, &req.handles).await?
+    } else {
+        fetch_ct64(pool, tenant_id, &req.handles).await?
+    };
+    let handles = req.handles.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let client_key: tfhe::ClientKey = safe_deserialize(&cks_key).map_err(failure)?;
+        tfhe::set_server_key(server_key);
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                let Some(ciphertext) = ciphertexts.get(&handle) else {
+                    return Ok(DebugDecryptSingleResponse {
+                        handle,
+                        plaintext: None,
+                        ciphertext_type: 0,
+                    });
+                };
+                Ok(DebugDecryptSingleResponse {
+                    handle,
+                    plaintext: Some(ciphertext.decrypt(&client_key)?),
+                    ciphertext_type: ciphertext.ct_type() as i32,
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(failure)?
+}
+
+enum Ciphertext {
+    Ct64 { ct_type: i16, data: Vec<u8> },
+    Ct128 { ct_type: i16, data: Vec<u8> },
+}
+
+impl Ciphertext {
+    fn ct_type(&self) -> i16 {
+        match self {
+            Self::Ct64 { ct_type, .. } | Self::Ct128 { ct_type, .. } => *ct_type,
+        }
+    }
+
+    fn decrypt(&self, client_key: &tfhe::ClientKey) -> Result<String, CoprocessorError> {
+        Ok(match self {
+            Self::Ct64 { ct_type, data } => SupportedFheCiphertexts::decompress(*ct_type, data)
+                .map_err(failure)?
+                .decrypt(client_key),
+            Self::Ct128 { ct_type, data } => decrypt_ct128(client_key, *ct_type, data)
+                .map_err(failure)?
+                .to_string(),
+        })
+    }
+}
+
+type Ciphertexts = BTreeMap<Vec<u8>, Ciphertext>;
+
+async fn fetch_ct64(
+    pool: &PgPool,
+    tenant_id: i32,
+    handles: &[Vec<u8>],
+) -> Result<Ciphertexts, CoprocessorError> {
+    let rows = sqlx::query!(
+        "
+        SELECT DISTINCT ON (handle) handle, ciphertext_type, ciphertext
+        FROM ciphertexts
+        WHERE tenant_id = $1
+        AND handle = ANY($2::BYTEA[])
+        ORDER BY handle, ciphertext_version DESC
+        ",
+        tenant_id,
+        handles,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let ciphertext = Ciphertext::Ct64 {
+                ct_type: r.ciphertext_type,
+                data: r.ciphertext,
+            };
+            (r.handle, ciphertext)
+        })
+        .collect())
+}
+
+/// Fetches the ct128 still in the database, or else the uploaded ones, from
+/// the list of their decryption batch if packed
+async fn fetch_ct128(
+    pool: &PgPool,
+    ct128_store: &OnceCell<Arc<dyn ObjectStore>>,
+    args: &DebugDecryptArgs,
+    tenant_id: i32,
+    handles: &[Vec<u8>],
+) -> Result<Ciphertexts, CoprocessorError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT DISTINCT ON (c.handle)
+            c.handle,
+            c.ciphertext_type,
+            c.ciphertext128,
+            c.ciphertext128_format,
+            d.ciphertext128 AS "digest?",
+            d.ciphertext128_format AS "digest_format?",
+            d.ciphertext128_list
+        FROM ciphertexts c
+        LEFT JOIN ciphertext_digest d ON d.tenant_id = c.tenant_id AND d.handle = c.handle
+        WHERE c.tenant_id = $1
+        AND c.handle = ANY($2::BYTEA[])
+        ORDER BY c.handle, c.ciphertext_version DESC
+        "#,
+        tenant_id,
+        handles,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut ciphertexts = Ciphertexts::new();
+    for row in rows {
+        let (data, format) = match (row.ciphertext128, row.digest, row.digest_format) {
+            (Some(data), _, _) => (data, row.ciphertext128_format),
+            (None, Some(digest), Some(format)) => {
+                let store = ct128_store
+                    .get_or_try_init(|| connect(&args.debug_decrypt_storage_backend))
+                    .await
+                    .map_err(failure)?;
+                fetch_uploaded_ct128(
+                    store.as_ref(),
+                    &args.debug_decrypt_bucket_ct128,
+                    &row.handle,
+                    (&digest, format),
+                    row.ciphertext128_list.as_deref(),
+                )
+                .await?
+            }
+            // not squashed yet
+            _ => continue,
+        };
+        Ct128Format::try_from(format).map_err(failure)?;
+        let ciphertext = Ciphertext::Ct128 {
+            ct_type: row.ciphertext_type,
+            data,
+        };
+        ciphertexts.insert(row.handle, ciphertext);
+    }
+    Ok(ciphertexts)
+}
+
+/// Gets the ct128 from the list of its decryption batch if packed, or else
+/// uploaded alone under its digest
+async fn fetch_uploaded_ct128(
+    store: &dyn ObjectStore,
+    bucket: &str,
+    handle: &[u8],
+    (digest, format): (&[u8], i16),
+    list_digest: Option<&[u8]>,
+) -> Result<(Vec<u8>, i16), CoprocessorError> {
+    let Some(list_digest) = list_digest else {
+        let key = hex::encode(digest);
+        let object = store
+            .get(bucket, &key)
+            .await
+            .map_err(failure)?
+            .ok_or_else(|| failure(format!("ct128 object {key} not found")))?;
+        return Ok((object, format));
+    };
+    let list_key = hex::encode(list_digest);
+    let object = store
+        .get(bucket, &list_key)
+        .await
+        .map_err(failure)?
+        .ok_or_else(|| failure(format!("ct128 list object {list_key} not found")))?;
+    let list = Ct128List::decode(&object).map_err(failure)?;
+    let data = list
+        .find(handle)
+        .ok_or_else(|| failure(format!("no ct128 of the handle in {list_key}")))?;
+    Ok((data.to_vec(), Ct128Format::Raw.as_i16()))
+}
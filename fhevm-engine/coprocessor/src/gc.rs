@@ -0,0 +1,459 @@
+use std::path::{Path, PathBuf};
+
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::tiering::ColdStore;
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_CIPHERTEXT_VERSION_RETENTION_SECS: u64 = 7 * 24 * 3600;
+const DEFAULT_INPUT_BLOB_RETENTION_SECS: u64 = 30 * 24 * 3600;
+const DEFAULT_BATCH_SIZE: i64 = 1000;
+
+/// Retention of the ct128 blobs, superseded ciphertext versions and input
+/// blobs stored in the database
+#[derive(clap::Args, Debug, Clone)]
+pub struct GcArgs {
+    /// Interval in seconds between two garbage collections of the coprocessor
+    /// daemon, disabled if 0
+    #[arg(long, default_value_t = 0)]
+    pub gc_interval_secs: u64,
+
+    /// Time in seconds after which a ciphertext version superseded by a newer
+    /// one is deleted
+    #[arg(long, default_value_t = DEFAULT_CIPHERTEXT_VERSION_RETENTION_SECS)]
+    pub gc_ciphertext_version_retention_secs: u64,
+
+    /// Time in seconds after which an input blob is archived and deleted
+    #[arg(long, default_value_t = DEFAULT_INPUT_BLOB_RETENTION_SECS)]
+    pub gc_input_blob_retention_secs: u64,
+
+    /// Directory where the input blobs are archived before being deleted, as
+    /// <dir>/<tenant_id>/<blob_hash>.bin. If unspecified the input blobs are
+    /// kept
+    #[arg(long)]
+    pub gc_input_blob_archive_dir: Option<PathBuf>,
+
+    /// Rows updated or deleted per transaction
+    #[arg(long, default_value_t = DEFAULT_BATCH_SIZE)]
+    pub gc_batch_size: i64,
+
+    /// Only report what would be collected
+    #[arg(long)]
+    pub gc_dry_run: bool,
+}
+
+impl Default for GcArgs {
+    fn default() -> Self {
+        Self {
+            gc_interval_secs: 0,
+            gc_ciphertext_version_retention_secs: DEFAULT_CIPHERTEXT_VERSION_RETENTION_SECS,
+            gc_input_blob_retention_secs: DEFAULT_INPUT_BLOB_RETENTION_SECS,
+            gc_input_blob_archive_dir: None,
+            gc_batch_size: DEFAULT_BATCH_SIZE,
+            gc_dry_run: false,
+        }
+    }
+}
+
+/// Rows collected, or collectable in dry-run, and their size in bytes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcReport {
+    pub ct128_cleared: u64,
+    pub ct128_bytes: u64,
+    pub versions_deleted: u64,
+    pub versions_bytes: u64,
+    pub cold_versions_deleted: u64,
+    pub input_blobs_archived: u64,
+    pub input_blobs_bytes: u64,
+}
+
+/// Runs the garbage collection every `gc_interval_secs`
+pub async fn run_gc(
+    args: crate::daemon_cli::Args,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db_url = crate::utils::db_url(&args);
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&db_url)
+        .await?;
+    let cold = ColdStore::connect(&args.tiering).await?;
+
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+        args.gc.gc_interval_secs.max(1),
+    ));
+    loop {
+        interval.tick().await;
+        // here we log the errors and retry on the next tick
+        if let Err(err) = collect_garbage(&pool, cold.as_ref(), &args.gc).await {
+            error!(target: "gc", { error = err }, "Error in garbage collection");
+        }
+    }
+}
+
+/// Clears the ct128 whose upload is confirmed by a digest, deletes the
+/// ciphertext versions superseded for longer than the retention, with their
+/// tiered objects if the cold store is set, and archives then deletes the
+/// input blobs older than the retention.
+pub async fn collect_garbage(
+    pool: &PgPool,
+    cold: Option<&ColdStore>,
+    args: &GcArgs,
+) -> Result<GcReport, Box<dyn std::error::Error + Send + Sync>> {
+    let mut report = GcReport::default();
+
+    (report.ct128_cleared, report.ct128_bytes) = if args.gc_dry_run {
+        count_confirmed_ct128(pool).await?
+    } else {
+        collect_batches(args.gc_batch_size, || {
+            clear_confirmed_ct128(pool, args.gc_batch_size)
+        })
+        .await?
+    };
+
+    let retention = args.gc_ciphertext_version_retention_secs as f64;
+    (report.versions_deleted, report.versions_bytes) = if args.gc_dry_run {
+        count_superseded_versions(pool, retention).await?
+    } else {
+        collect_batches(args.gc_batch_size, || {
+            delete_superseded_versions(pool, retention, args.gc_batch_size)
+        })
+        .await?
+    };
+    (report.cold_versions_deleted, _) = match (cold, args.gc_dry_run) {
+        (None, _) => (0, 0),
+        (Some(_), true) => (count_superseded_cold_versions(pool, retention).await?, 0),
+        (Some(cold), false) => {
+            collect_batches(args.gc_batch_size, || {
+                delete_superseded_cold_versions(pool, cold, retention, args.gc_batch_size)
+            })
+            .await?
+        }
+    };
+
+    let retention = args.gc_input_blob_retention_secs as f64;
+    (report.input_blobs_archived, report.input_blobs_bytes) =
+        match (&args.gc_input_blob_archive_dir, args.gc_dry_run) {
+            (None, _) => (0, 0),
+            (Some(_), true) => count_old_input_blobs(pool, retention).await?,
+            (Some(archive_dir), false) => {
+                collect_batches(args.gc_batch_size, || {
+                    archive_old_input_blobs(pool, retention, archive_dir, args.gc_batch_size)
+                })
+                .await?
+            }
+        };
+
+    info!(target: "gc", { dry_run = args.gc_dry_run }, "Garbage collected: {:?}", report);
+    Ok(report)
+}
+
+/// Runs the batches until one is not full, returns the total rows and bytes
+async fn collect_batches<F, Fut, E>(batch_size: i64, mut batch: F) -> Result<(u64, u64), E>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(u64, u64), E>>,
+{
+    let mut total = (0, 0);
+    loop {
+        let (count, bytes) = batch().await?;
+        total = (total.0 + count, total.1 + bytes);
+        if count < batch_size as u64 {
+            return Ok(total);
+        }
+    }
+}
+
+async fn count_confirmed_ct128(pool: &PgPool) -> Result<(u64, u64), sqlx::Error> {
+    let res = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!", COALESCE(SUM(octet_length(c.ciphertext128)), 0)::BIGINT AS "bytes!"
+        FROM ciphertexts c
+        JOIN ciphertext_digest d ON d.tenant_id = c.tenant_id AND d.handle = c.handle
+        WHERE c.ciphertext128 IS NOT NULL
+        AND d.ciphertext128 IS NOT NULL
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((res.count as u64, res.bytes as u64))
+}
+
+async fn clear_confirmed_ct128(pool: &PgPool, limit: i64) -> Result<(u64, u64), sqlx::Error> {
+    let res = sqlx::query!(
+        r#"
+        WITH confirmed AS (
+            SELECT c.tenant_id, c.handle, c.ciphertext_version, octet_length(c.ciphertext128) AS size
+            FROM ciphertexts c
+            JOIN ciphertext_digest d ON d.tenant_id = c.tenant_id AND d.handle = c.handle
+            WHERE c.ciphertext128 IS NOT NULL
+            AND d.ciphertext128 IS NOT NULL
+            LIMIT $1
+            FOR UPDATE OF c SKIP LOCKED
+        ),
+        cleared AS (
+            UPDATE ciphertexts c
+            SET ciphertext128 = NULL
+            FROM confirmed
+            WHERE c.tenant_id = confirmed.tenant_id
+            AND c.handle = confirmed.handle
+            AND c.ciphertext_version = confirmed.ciphertext_version
+            RETURNING confirmed.size
+        )
+        SELECT COUNT(*) AS "count!", COALESCE(SUM(size), 0)::BIGINT AS "bytes!"
+        FROM cleared
+        "#,
+        limit,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((res.count as u64, res.bytes as u64))
+}
+
+async fn count_superseded_versions(
+    pool: &PgPool,
+    retention_secs: f64,
+) -> Result<(u64, u64), sqlx::Error> {
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "count!",
+            COALESCE(SUM(octet_length(c.ciphertext) + COALESCE(octet_length(c.ciphertext128), 0)), 0)::BIGINT AS "bytes!"
+        FROM ciphertexts c
+        WHERE EXISTS (
+            SELECT 1 FROM ciphertexts n
+            WHERE n.tenant_id = c.tenant_id
+            AND n.handle = c.handle
+            AND n.ciphertext_version > c.ciphertext_version
+            AND n.created_at < NOW() - make_interval(secs => $1)
+        )
+        OR EXISTS (
+            SELECT 1 FROM ciphertexts_cold n
+            WHERE n.tenant_id = c.tenant_id
+            AND n.handle = c.handle
+            AND n.ciphertext_version > c.ciphertext_version
+            AND n.created_at < NOW() - make_interval(secs => $1)
+        )
+        "#,
+        retention_secs,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((res.count as u64, res.bytes as u64))
+}
+
+async fn delete_superseded_versions(
+    pool: &PgPool,
+    retention_secs: f64,
+    limit: i64,
+) -> Result<(u64, u64), sqlx::Error> {
+    let res = sqlx::query!(
+        r#"
+        WITH superseded AS (
+            SELECT
+                c.tenant_id,
+                c.handle,
+                c.ciphertext_version,
+                octet_length(c.ciphertext) + COALESCE(octet_length(c.ciphertext128), 0) AS size
+            FROM ciphertexts c
+            WHERE EXISTS (
+                SELECT 1 FROM ciphertexts n
+                WHERE n.tenant_id = c.tenant_id
+                AND n.handle = c.handle
+                AND n.ciphertext_version > c.ciphertext_version
+                AND n.created_at < NOW() - make_interval(secs => $1)
+            )
+            OR EXISTS (
+                SELECT 1 FROM ciphertexts_cold n
+                WHERE n.tenant_id = c.tenant_id
+                AND n.handle = c.handle
+                AND n.ciphertext_version > c.ciphertext_version
+                AND n.created_at < NOW() - make_interval(secs => $1)
+            )
+            LIMIT $2
+            FOR UPDATE OF c SKIP LOCKED
+        ),
+        deleted AS (
+            DELETE FROM ciphertexts c
+            USING superseded
+            WHERE c.tenant_id = superseded.tenant_id
+            AND c.handle = superseded.handle
+            AND c.ciphertext_version = superseded.ciphertext_version
+            RETURNING superseded.size
+        )
+        SELECT COUNT(*) AS "count!", COALESCE(SUM(size), 0)::BIGINT AS "bytes!"
+        FROM deleted
+        "#,
+        retention_secs,
+        limit,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((res.count as u64, res.bytes as u64))
+}
+
+async fn count_superseded_cold_versions(
+    pool: &PgPool,
+    retention_secs: f64,
+) -> Result<u64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM ciphertexts_cold c
+        WHERE EXISTS (
+            SELECT 1 FROM ciphertexts n
+            WHERE n.tenant_id = c.tenant_id
+            AND n.handle = c.handle
+            AND n.ciphertext_version > c.ciphertext_version
+            AND n.created_at < NOW() - make_interval(secs => $1)
+        )
+        OR EXISTS (
+            SELECT 1 FROM ciphertexts_cold n
+            WHERE n.tenant_id = c.tenant_id
+            AND n.handle = c.handle
+            AND n.ciphertext_version > c.ciphertext_version
+            AND n.created_at < NOW() - make_interval(secs => $1)
+        )
+        "#,
+        retention_secs,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(count as u64)
+}
+
+/// Deletes a batch of superseded tiered versions, then their objects unless
+/// another tiered ciphertext has the same digest. The size of the objects is
+/// not known, only the deleted rows are counted.
+async fn delete_superseded_cold_versions(
+    pool: &PgPool,
+    cold: &ColdStore,
+    retention_secs: f64,
+    limit: i64,
+) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>> {
+    let digests = sqlx::query_scalar!(
+        "
+        WITH superseded AS (
+            SELECT c.tenant_id, c.handle, c.ciphertext_version
+            FROM ciphertexts_cold c
+            WHERE EXISTS (
+                SELECT 1 FROM ciphertexts n
+                WHERE n.tenant_id = c.tenant_id
+                AND n.handle = c.handle
+                AND n.ciphertext_version > c.ciphertext_version
+                AND n.created_at < NOW() - make_interval(secs => $1)
+            )
+            OR EXISTS (
+                SELECT 1 FROM ciphertexts_cold n
+                WHERE n.tenant_id = c.tenant_id
+                AND n.handle = c.handle
+                AND n.ciphertext_version > c.ciphertext_version
+                AND n.created_at < NOW() - make_interval(secs => $1)
+            )
+            LIMIT $2
+            FOR UPDATE OF c SKIP LOCKED
+        )
+        DELETE FROM ciphertexts_cold c
+        USING superseded
+        WHERE c.tenant_id = superseded.tenant_id
+        AND c.handle = superseded.handle
+        AND c.ciphertext_version = superseded.ciphertext_version
+        RETURNING c.digest
+        ",
+        retention_secs,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let count = digests.len() as u64;
+    let mut orphans = digests;
+    orphans.sort();
+    orphans.dedup();
+    for digest in orphans {
+        // the objects are keyed by digest, identical ciphertexts share one
+        let shared = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM ciphertexts_cold WHERE digest = $1) AS "shared!""#,
+            digest,
+        )
+        .fetch_one(pool)
+        .await?;
+        if !shared {
+            cold.delete(&digest).await?;
+        }
+    }
+    Ok((count, 0))
+}
+
+async fn count_old_input_blobs(
+    pool: &PgPool,
+    retention_secs: f64,
+) -> Result<(u64, u64), sqlx::Error> {
+    let res = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!", COALESCE(SUM(octet_length(blob_data)), 0)::BIGINT AS "bytes!"
+        FROM input_blobs
+        WHERE created_at < NOW() - make_interval(secs => $1)
+        "#,
+        retention_secs,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((res.count as u64, res.bytes as u64))
+}
+
+/// Writes a batch of old input blobs to the archive directory then deletes
+/// them, once the archived files are durable.
+async fn archive_old_input_blobs(
+    pool: &PgPool,
+    retention_secs: f64,
+    archive_dir: &Path,
+    limit: i64,
+) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>> {
+    let blobs = sqlx::query!(
+        "
+        SELECT tenant_id, blob_hash, blob_data
+        FROM input_blobs
+        WHERE created_at < NOW() - make_interval(secs => $1)
+        ORDER BY created_at
+        LIMIT $2
+        ",
+        retention_secs,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let (mut count, mut bytes) = (0, 0);
+    for blob in blobs {
+        let dir = archive_dir.join(blob.tenant_id.to_string());
+        if !tokio::fs::try_exists(&dir).await? {
+            tokio::fs::create_dir_all(&dir).await?;
+            sync_dir(archive_dir).await?;
+        }
+        let path = dir.join(format!("{}.bin", hex::encode(&blob.blob_hash)));
+        let tmp_path = path.with_extension("tmp");
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        file.write_all(&blob.blob_data).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        // the renamed entry is durable once its directory is synced
+        sync_dir(&dir).await?;
+
+        sqlx::query!(
+            "DELETE FROM input_blobs WHERE tenant_id = $1 AND blob_hash = $2",
+            blob.tenant_id,
+            blob.blob_hash,
+        )
+        .execute(pool)
+        .await?;
+
+        count += 1;
+        bytes += blob.blob_data.len() as u64;
+    }
+    Ok((count, bytes))
+}
+
+async fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    tokio::fs::File::open(dir).await?.sync_all().await
+}
@@ -6,11 +6,15 @@ use tokio::task::JoinSet;
 
 pub mod daemon_cli;
 mod db_queries;
+#[cfg(feature = "debug_decrypt")]
+pub mod debug_decrypt;
+pub mod gc;
 pub mod metrics;
 pub mod server;
 #[cfg(test)]
 mod tests;
 pub mod tfhe_worker;
+pub mod tiering;
 pub mod types;
 mod utils;
 
@@ -71,6 +75,16 @@ pub async fn async_main(
         set.spawn(tfhe_worker::run_tfhe_worker(args.clone()));
     }
 
+    if args.gc.gc_interval_secs > 0 {
+        info!(target: "async_main", "Initializing garbage collector");
+        set.spawn(gc::run_gc(args.clone()));
+    }
+
+    if args.tiering.tiering_interval_secs > 0 {
+        info!(target: "async_main", "Initializing ciphertext tiering");
+        set.spawn(tiering::run_tiering(args.clone()));
+    }
+
     if !args.metrics_addr.is_empty() {
         info!(target: "async_main", "Initializing metrics server");
         set.spawn(metrics::run_metrics_server(args.clone()));
@@ -5,10 +5,9 @@ use std::str::FromStr;
 
 use crate::db_queries::{check_if_api_key_is_valid, fetch_tenant_server_key};
 use crate::server::coprocessor::GenericResponse;
+use crate::tiering::{rehydrate_ciphertexts, touch_ciphertexts, ColdStore};
 use crate::types::{CoprocessorError, TfheTenantKeys};
 use crate::utils::sort_computations_by_dependencies;
-use alloy::signers::local::PrivateKeySigner;
-use alloy::signers::SignerSync;
 use alloy::sol_types::{Eip712Domain, SolStruct};
 use coprocessor::async_computation_input::Input;
 use coprocessor::{
@@ -16,6 +15,7 @@ use coprocessor::{
     InputCiphertextResponseHandle, InputUploadBatch, InputUploadResponse,
 };
 pub use fhevm_engine_common::common;
+use fhevm_engine_common::signer::KeySigner;
 use fhevm_engine_common::tfhe_ops::{
     check_fhe_operand_types, current_ciphertext_version, trivial_encrypt_be_bytes,
     try_expand_ciphertext_list, validate_fhe_type,
@@ -82,8 +82,9 @@ struct CoprocessorService {
     pool: sqlx::Pool<sqlx::Postgres>,
     args: crate::daemon_cli::Args,
     tenant_key_cache: std::sync::Arc<tokio::sync::RwLock<lru::LruCache<i32, TfheTenantKeys>>>,
-    signer: PrivateKeySigner,
+    signer: KeySigner,
     get_ciphertext_eip712_domain: Eip712Domain,
+    cold_store: Option<ColdStore>,
 }
 
 pub async fn run_server(
@@ -107,9 +108,7 @@ pub async fn run_server_iteration(
         .expect("Can't parse server address");
     let db_url = crate::utils::db_url(&args);
 
-    let coprocessor_key_file = tokio::fs::read_to_string(&args.coprocessor_private_key).await?;
-
-    let signer = PrivateKeySigner::from_str(coprocessor_key_file.trim())?;
+    let signer = KeySigner::new(&args.coprocessor_private_key).await?;
     info!(target: "grpc_server", { address = signer.address().to_string() }, "Coprocessor signer initiated");
 
     info!("Coprocessor listening on {}", addr);
@@ -123,7 +122,9 @@ pub async fn run_server_iteration(
             NonZeroUsize::new(args.tenant_key_cache_size as usize).unwrap(),
         )));
 
-    let service = CoprocessorService::new(pool, args, tenant_key_cache, signer);
+    let cold_store = ColdStore::connect(&args.tiering).await?;
+
+    let service = CoprocessorService::new(pool, args, tenant_key_cache, signer, cold_store);
 
     Server::builder()
         .add_service(
@@ -277,6 +278,17 @@ impl coprocessor::fhevm_coprocessor_server::FhevmCoprocessor for CoprocessorServ
                 GET_CIPHERTEXTS_ERRORS.inc();
             })
     }
+
+    async fn debug_decrypt(
+        &self,
+        request: tonic::Request<coprocessor::DebugDecryptBatch>,
+    ) -> std::result::Result<tonic::Response<coprocessor::DebugDecryptResponse>, tonic::Status>
+    {
+        let mut tracer = grpc_tracer("debug_decrypt");
+        self.debug_decrypt_impl(request, &tracer)
+            .await
+            .inspect_err(|e| tracer.set_error(e))
+    }
 }
 
 impl CoprocessorService {
@@ -284,7 +296,8 @@ impl CoprocessorService {
         pool: sqlx::Pool<sqlx::Postgres>,
         args: crate::daemon_cli::Args,
         tenant_key_cache: std::sync::Arc<tokio::sync::RwLock<lru::LruCache<i32, TfheTenantKeys>>>,
-        signer: PrivateKeySigner,
+        signer: KeySigner,
+        cold_store: Option<ColdStore>,
     ) -> Self {
         let get_ciphertext_eip712_domain = alloy::sol_types::eip712_domain! {
             name: "GetCiphertextResponse",
@@ -296,6 +309,7 @@ impl CoprocessorService {
             tenant_key_cache,
             signer,
             get_ciphertext_eip712_domain,
+            cold_store,
         }
     }
 
@@ -581,7 +595,7 @@ impl CoprocessorService {
             let mut span = tracer.child_span("eip_712_signature");
             span.set_attributes(vec![KeyValue::new("blob_idx", idx as i64)]);
             let signing_hash = ct_verification.eip712_signing_hash(&eip_712_domain);
-            let eip_712_signature = self.signer.sign_hash_sync(&signing_hash).map_err(|e| {
+            let eip_712_signature = self.signer.sign_hash(&signing_hash).await.map_err(|e| {
                 CoprocessorError::Eip712SigningFailure {
                     error: e.to_string(),
                 }
@@ -810,6 +824,60 @@ impl CoprocessorService {
         Ok(tonic::Response::new(GenericResponse { response_code: 0 }))
     }
 
+    #[cfg(feature = "debug_decrypt")]
+    async fn debug_decrypt_impl(
+        &self,
+        request: tonic::Request<coprocessor::DebugDecryptBatch>,
+        tracer: &GrpcTracer,
+    ) -> std::result::Result<tonic::Response<coprocessor::DebugDecryptResponse>, tonic::Status>
+    {
+        let tenant_id = check_if_api_key_is_valid(&request, &self.pool, tracer).await?;
+        let req = request.get_ref();
+
+        if req.handles.len() > self.args.server_maximum_ciphertexts_to_get {
+            return Err(tonic::Status::from_error(Box::new(
+                CoprocessorError::MoreThanMaximumCiphertextsAttemptedToDownload {
+                    input_count: req.handles.len(),
+                    maximum_allowed: self.args.server_maximum_ciphertexts_to_get,
+                },
+            )));
+        }
+
+        let server_key = fetch_tenant_server_key(tenant_id, &self.pool, &self.tenant_key_cache)
+            .await
+            .map_err(tonic::Status::from_error)?
+            .server_key;
+
+        let mut span = tracer.child_span("debug_decrypt");
+        span.set_attribute(KeyValue::new("count", req.handles.len() as i64));
+        let responses = crate::debug_decrypt::debug_decrypt(
+            &self.pool,
+            self.cold_store.as_ref(),
+            &self.args.debug_decrypt,
+            tenant_id,
+            server_key,
+            req,
+        )
+        .await?;
+        span.end();
+
+        Ok(tonic::Response::new(coprocessor::DebugDecryptResponse {
+            responses,
+        }))
+    }
+
+    #[cfg(not(feature = "debug_decrypt"))]
+    async fn debug_decrypt_impl(
+        &self,
+        _request: tonic::Request<coprocessor::DebugDecryptBatch>,
+        _tracer: &GrpcTracer,
+    ) -> std::result::Result<tonic::Response<coprocessor::DebugDecryptResponse>, tonic::Status>
+    {
+        Err(tonic::Status::unimplemented(
+            "debug decryption requires a coprocessor built with the debug_decrypt feature",
+        ))
+    }
+
     async fn get_ciphertexts_impl(
         &self,
         request: tonic::Request<coprocessor::GetCiphertextBatch>,
@@ -839,6 +907,16 @@ impl CoprocessorService {
 
         let cts: Vec<Vec<u8>> = set.into_iter().collect();
 
+        if let Some(cold_store) = &self.cold_store {
+            let mut span = tracer.child_span("rehydrate_ciphertexts");
+            rehydrate_ciphertexts(&self.pool, cold_store, tenant_id, &cts)
+                .await
+                .map_err(|e| CoprocessorError::CiphertextRehydrationFailure {
+                    error: e.to_string(),
+                })?;
+            span.end();
+        }
+
         let mut span = tracer.child_span("query_ciphertexts");
         span.set_attribute(KeyValue::new("count", cts.len() as i64));
         let db_cts = query!(
@@ -854,6 +932,9 @@ impl CoprocessorService {
         .fetch_all(&self.pool)
         .await
         .map_err(Into::<CoprocessorError>::into)?;
+        touch_ciphertexts(&self.pool, &[tenant_id], &cts)
+            .await
+            .map_err(Into::<CoprocessorError>::into)?;
         span.end();
 
         let mut the_map: BTreeMap<Vec<u8>, _> = BTreeMap::new();
@@ -862,33 +943,31 @@ impl CoprocessorService {
         }
 
         for h in &req.handles {
-            let ciphertext: Result<Option<FetchedCiphertext>, tonic::Status> = the_map
-                .get(h)
-                .map(|res| {
+            let ciphertext = match the_map.get(h) {
+                Some(res) => {
                     let signature_data = GetCiphertextResponseSignatureData {
                         handle: alloy::primitives::U256::from_be_slice(h),
-                        ciphertext_digest: Keccak256::digest(&the_map.get(h).unwrap().ciphertext)
-                            .to_vec()
-                            .into(),
+                        ciphertext_digest: Keccak256::digest(&res.ciphertext).to_vec().into(),
                     };
                     let signing_hash =
                         signature_data.eip712_signing_hash(&self.get_ciphertext_eip712_domain);
-                    let signature = self.signer.sign_hash_sync(&signing_hash).map_err(|e| {
+                    let signature = self.signer.sign_hash(&signing_hash).await.map_err(|e| {
                         CoprocessorError::Eip712SigningFailure {
                             error: e.to_string(),
                         }
                     })?;
-                    Ok(FetchedCiphertext {
+                    Some(FetchedCiphertext {
                         ciphertext_bytes: res.ciphertext.clone(),
                         ciphertext_type: res.ciphertext_type as i32,
                         ciphertext_version: res.ciphertext_version as i32,
                         signature: signature.into(),
                     })
-                })
-                .transpose();
+                }
+                None => None,
+            };
             result.responses.push(GetCiphertextSingleResponse {
                 handle: h.clone(),
-                ciphertext: ciphertext?,
+                ciphertext,
             });
         }
 
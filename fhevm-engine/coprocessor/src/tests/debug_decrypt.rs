@@ -0,0 +1,230 @@
+use std::str::FromStr;
+
+use tonic::metadata::MetadataValue;
+
+use crate::server::coprocessor::fhevm_coprocessor_client::FhevmCoprocessorClient;
+use crate::server::coprocessor::DebugDecryptBatch;
+use crate::tests::utils::{default_api_key, random_handle, setup_test_app};
+
+fn with_api_key<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    request.metadata_mut().append(
+        "authorization",
+        MetadataValue::from_str(&format!("bearer {}", default_api_key())).unwrap(),
+    );
+    request
+}
+
+#[cfg(feature = "debug_decrypt")]
+mod enabled {
+    use std::sync::Arc;
+
+    use fhevm_engine_common::object_store::{InMemoryStore, ObjectStore};
+    use fhevm_engine_common::types::SupportedFheCiphertexts;
+    use sha3::{Digest, Keccak256};
+    use sns_executor::{Ct128Format, Ct128List};
+    use tokio::sync::OnceCell;
+
+    use super::{
+        random_handle, setup_test_app, with_api_key, DebugDecryptBatch, FhevmCoprocessorClient,
+    };
+    use crate::debug_decrypt::{debug_decrypt_with_store, DebugDecryptArgs};
+    use crate::server::coprocessor::{TrivialEncryptBatch, TrivialEncryptRequestSingle};
+    use crate::tests::utils::default_tenant_id;
+    use crate::tiering::ColdStore;
+
+    #[tokio::test]
+    async fn test_debug_decrypt() -> Result<(), Box<dyn std::error::Error>> {
+        let app = setup_test_app().await?;
+        let mut client = FhevmCoprocessorClient::connect(app.app_url().to_string()).await?;
+        let ct_type = 4; // i32
+
+        let h1 = random_handle().to_be_bytes().to_vec();
+        let h2 = random_handle().to_be_bytes().to_vec();
+        // unused, non existing
+        let h3 = random_handle().to_be_bytes().to_vec();
+
+        client
+            .trivial_encrypt_ciphertexts(with_api_key(TrivialEncryptBatch {
+                values: vec![
+                    TrivialEncryptRequestSingle {
+                        handle: h1.clone(),
+                        be_value: vec![123],
+                        output_type: ct_type,
+                    },
+                    TrivialEncryptRequestSingle {
+                        handle: h2.clone(),
+                        be_value: vec![1, 0],
+                        output_type: ct_type,
+                    },
+                ],
+            }))
+            .await?;
+
+        let resp = client
+            .debug_decrypt(with_api_key(DebugDecryptBatch {
+                handles: vec![h2.clone(), h3.clone(), h1.clone()],
+                ct128: false,
+            }))
+            .await?
+            .into_inner();
+        let decrypted: Vec<_> = resp
+            .responses
+            .into_iter()
+            .map(|r| (r.handle, r.plaintext, r.ciphertext_type))
+            .collect();
+        assert_eq!(
+            decrypted,
+            vec![
+                (h2, Some("256".to_string()), ct_type),
+                (h3, None, 0),
+                (h1, Some("123".to_string()), ct_type),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_debug_decrypt_ct128() -> Result<(), Box<dyn std::error::Error>> {
+        let app = setup_test_app().await?;
+        let mut client = FhevmCoprocessorClient::connect(app.app_url().to_string()).await?;
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(2)
+            .connect(app.db_url())
+            .await?;
+        let tenant_id = default_tenant_id();
+        let ct_type = 4;
+
+        // uploaded alone, and only inside the list of its decryption batch
+        let alone = random_handle().to_be_bytes().to_vec();
+        let packed = random_handle().to_be_bytes().to_vec();
+        client
+            .trivial_encrypt_ciphertexts(with_api_key(TrivialEncryptBatch {
+                values: vec![
+                    TrivialEncryptRequestSingle {
+                        handle: alone.clone(),
+                        be_value: vec![42],
+                        output_type: ct_type,
+                    },
+                    TrivialEncryptRequestSingle {
+                        handle: packed.clone(),
+                        be_value: vec![1, 7],
+                        output_type: ct_type,
+                    },
+                ],
+            }))
+            .await?;
+
+        // squash the ciphertexts as the sns-executor does
+        let (_, sns_key, _) = sns_executor::fetch_keys(&pool, tenant_id).await?;
+        let mut ct64s = vec![];
+        for handle in [&alone, &packed] {
+            let ct64: Vec<u8> = sqlx::query_scalar(
+                "SELECT ciphertext FROM ciphertexts WHERE tenant_id = $1 AND handle = $2",
+            )
+            .bind(tenant_id)
+            .bind(handle)
+            .fetch_one(&pool)
+            .await?;
+            ct64s.push(ct64);
+        }
+        let squash_key = sns_key.clone();
+        let ct128s = tokio::task::spawn_blocking(move || {
+            tfhe::set_server_key(squash_key);
+            ct64s
+                .iter()
+                .map(|ct64| {
+                    let ct = SupportedFheCiphertexts::decompress(ct_type as i16, ct64).unwrap();
+                    sns_executor::squash_noise(&ct).unwrap()
+                })
+                .collect::<Vec<_>>()
+        })
+        .await?;
+
+        let store = InMemoryStore::default();
+        let bucket = DebugDecryptArgs::default().debug_decrypt_bucket_ct128;
+        let mut list = Ct128List::default();
+        let mut handles = vec![(alone.clone(), &ct128s[0]), (packed.clone(), &ct128s[1])];
+        handles.sort();
+        for (handle, ct128) in &handles {
+            list.push(handle.clone(), ct128.to_vec());
+        }
+        let list_object = list.encode()?;
+        let list_digest = Keccak256::digest(&list_object).to_vec();
+        store
+            .put(&bucket, &hex::encode(&list_digest), list_object)
+            .await?;
+        for (handle, ct128) in [(&alone, &ct128s[0]), (&packed, &ct128s[1])] {
+            let digest = Keccak256::digest(ct128).to_vec();
+            if handle == &alone {
+                store
+                    .put(&bucket, &hex::encode(&digest), ct128.clone())
+                    .await?;
+            }
+            sqlx::query(
+                "INSERT INTO ciphertext_digest (tenant_id, handle, ciphertext128, ciphertext128_format,
+                    ciphertext128_list, ciphertext128_list_index)
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(tenant_id)
+            .bind(handle)
+            .bind(digest)
+            .bind(Ct128Format::Raw.as_i16())
+            .bind((handle == &packed).then_some(&list_digest))
+            .bind(
+                (handle == &packed)
+                    .then(|| handles.iter().position(|(h, _)| h == handle).unwrap() as i32),
+            )
+            .execute(&pool)
+            .await?;
+        }
+
+        let ct128_store = OnceCell::new_with(Some(Arc::new(store) as Arc<dyn ObjectStore>));
+        let cold = ColdStore::new(Arc::new(InMemoryStore::default()), "ct64-cold".to_owned());
+        let responses = debug_decrypt_with_store(
+            &pool,
+            Some(&cold),
+            &ct128_store,
+            &DebugDecryptArgs::default(),
+            tenant_id,
+            sns_key,
+            &DebugDecryptBatch {
+                handles: vec![packed.clone(), alone.clone()],
+                ct128: true,
+            },
+        )
+        .await?;
+        let decrypted: Vec<_> = responses
+            .into_iter()
+            .map(|r| (r.handle, r.plaintext, r.ciphertext_type))
+            .collect();
+        assert_eq!(
+            decrypted,
+            vec![
+                (packed, Some("263".to_string()), ct_type),
+                (alone, Some("42".to_string()), ct_type),
+            ]
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "debug_decrypt"))]
+#[tokio::test]
+async fn test_debug_decrypt_disabled() -> Result<(), Box<dyn std::error::Error>> {
+    let app = setup_test_app().await?;
+    let mut client = FhevmCoprocessorClient::connect(app.app_url().to_string()).await?;
+
+    let err = client
+        .debug_decrypt(with_api_key(DebugDecryptBatch {
+            handles: vec![random_handle().to_be_bytes().to_vec()],
+            ct128: false,
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unimplemented);
+
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use fhevm_engine_common::object_store::{InMemoryStore, ObjectStore};
+
+use crate::{
+    gc::{collect_garbage, GcArgs},
+    tests::utils::{default_tenant_id, random_handle, setup_test_app},
+    tiering::ColdStore,
+};
+
+#[tokio::test]
+async fn test_garbage_collection() -> Result<(), Box<dyn std::error::Error>> {
+    let app = setup_test_app().await?;
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(app.db_url())
+        .await?;
+    let tenant_id = default_tenant_id();
+    let versioned_handle = random_handle().to_be_bytes().to_vec();
+    let uploaded_handle = random_handle().to_be_bytes().to_vec();
+    let blob_hash = random_handle().to_be_bytes().to_vec();
+    let archive_dir = std::env::temp_dir().join(format!("coprocessor-gc-{}", random_handle()));
+    let store = InMemoryStore::default();
+    let cold = ColdStore::new(Arc::new(store.clone()), "ct64-cold".to_owned());
+    let cold_handle = random_handle().to_be_bytes().to_vec();
+    let shared_handle = random_handle().to_be_bytes().to_vec();
+    let (digest_a, digest_b) = (
+        random_handle().to_be_bytes().to_vec(),
+        random_handle().to_be_bytes().to_vec(),
+    );
+
+    // two versions of a handle, the newest created two days ago
+    for version in [0i16, 1] {
+        sqlx::query(
+            "INSERT INTO ciphertexts(tenant_id, handle, ciphertext, ciphertext_version, ciphertext_type, created_at)
+             VALUES ($1, $2, $3, $4, 4, NOW() - INTERVAL '2 days')",
+        )
+        .bind(tenant_id)
+        .bind(&versioned_handle)
+        .bind(vec![version as u8; 16])
+        .bind(version)
+        .execute(&pool)
+        .await?;
+    }
+    // a ct128 whose upload is confirmed by its digest
+    sqlx::query(
+        "INSERT INTO ciphertexts(tenant_id, handle, ciphertext, ciphertext_version, ciphertext_type, ciphertext128)
+         VALUES ($1, $2, $3, 0, 4, $4)",
+    )
+    .bind(tenant_id)
+    .bind(&uploaded_handle)
+    .bind(vec![2u8; 16])
+    .bind(vec![3u8; 128])
+    .execute(&pool)
+    .await?;
+    sqlx::query(
+        "INSERT INTO ciphertext_digest(tenant_id, handle, ciphertext, ciphertext128) VALUES ($1, $2, $3, $3)",
+    )
+    .bind(tenant_id)
+    .bind(&uploaded_handle)
+    .bind(vec![4u8; 32])
+    .execute(&pool)
+    .await?;
+    sqlx::query(
+        "INSERT INTO input_blobs(tenant_id, blob_hash, blob_data, blob_ciphertext_count, created_at)
+         VALUES ($1, $2, $3, 1, NOW() - INTERVAL '2 days')",
+    )
+    .bind(tenant_id)
+    .bind(&blob_hash)
+    .bind(vec![5u8; 64])
+    .execute(&pool)
+    .await?;
+
+    // two tiered versions of a handle, and a tiered version superseded by a
+    // newer one in the database, whose object is shared with the newest
+    // tiered version
+    for (handle, version, digest) in [
+        (&cold_handle, 0i16, &digest_a),
+        (&cold_handle, 1, &digest_b),
+        (&shared_handle, 0, &digest_b),
+    ] {
+        sqlx::query(
+            "INSERT INTO ciphertexts_cold(tenant_id, handle, ciphertext_version, ciphertext_type, created_at, digest)
+             VALUES ($1, $2, $3, 4, NOW() - INTERVAL '2 days', $4)",
+        )
+        .bind(tenant_id)
+        .bind(handle)
+        .bind(version)
+        .bind(digest)
+        .execute(&pool)
+        .await?;
+        store
+            .put("ct64-cold", &hex::encode(digest), digest.clone())
+            .await?;
+    }
+    sqlx::query(
+        "INSERT INTO ciphertexts(tenant_id, handle, ciphertext, ciphertext_version, ciphertext_type, created_at)
+         VALUES ($1, $2, $3, 1, 4, NOW() - INTERVAL '2 days')",
+    )
+    .bind(tenant_id)
+    .bind(&shared_handle)
+    .bind(vec![6u8; 16])
+    .execute(&pool)
+    .await?;
+
+    let args = GcArgs {
+        gc_ciphertext_version_retention_secs: 24 * 3600,
+        gc_input_blob_retention_secs: 24 * 3600,
+        gc_input_blob_archive_dir: Some(archive_dir.clone()),
+        gc_batch_size: 2,
+        gc_dry_run: true,
+        ..Default::default()
+    };
+
+    // dry-run only reports
+    let report = collect_garbage(&pool, Some(&cold), &args)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    assert!(report.ct128_cleared >= 1 && report.ct128_bytes >= 128);
+    assert!(report.versions_deleted >= 1 && report.versions_bytes >= 16);
+    assert!(report.cold_versions_deleted >= 2);
+    assert!(report.input_blobs_archived >= 1 && report.input_blobs_bytes >= 64);
+    assert_eq!(versions(&pool, &versioned_handle).await?, vec![0, 1]);
+    assert!(ct128(&pool, &uploaded_handle).await?.is_some());
+    assert!(!archive_dir.exists());
+    assert_eq!(cold_versions(&pool, &cold_handle).await?, vec![0, 1]);
+    assert_eq!(store.keys("ct64-cold").len(), 2);
+
+    let report = collect_garbage(
+        &pool,
+        Some(&cold),
+        &GcArgs {
+            gc_dry_run: false,
+            ..args
+        },
+    )
+    .await
+    .map_err(|e| e as Box<dyn std::error::Error>)?;
+    assert!(report.ct128_cleared >= 1);
+    assert!(report.versions_deleted >= 1);
+    assert!(report.cold_versions_deleted >= 2);
+    assert!(report.input_blobs_archived >= 1);
+    assert_eq!(versions(&pool, &versioned_handle).await?, vec![1]);
+    assert_eq!(cold_versions(&pool, &cold_handle).await?, vec![1]);
+    assert!(cold_versions(&pool, &shared_handle).await?.is_empty());
+    assert_eq!(versions(&pool, &shared_handle).await?, vec![1]);
+    assert_eq!(store.get("ct64-cold", &hex::encode(&digest_a)).await?, None);
+    assert_eq!(
+        store.get("ct64-cold", &hex::encode(&digest_b)).await?,
+        Some(digest_b.clone())
+    );
+    assert!(ct128(&pool, &uploaded_handle).await?.is_none());
+
+    let blob_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM input_blobs WHERE tenant_id = $1 AND blob_hash = $2",
+    )
+    .bind(tenant_id)
+    .bind(&blob_hash)
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(blob_count, 0);
+    let archived = std::fs::read(
+        archive_dir
+            .join(tenant_id.to_string())
+            .join(format!("{}.bin", hex::encode(&blob_hash))),
+    )?;
+    assert_eq!(archived, vec![5u8; 64]);
+
+    std::fs::remove_dir_all(archive_dir)?;
+    Ok(())
+}
+
+async fn versions(pool: &sqlx::PgPool, handle: &[u8]) -> Result<Vec<i16>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT ciphertext_version FROM ciphertexts WHERE handle = $1 ORDER BY ciphertext_version",
+    )
+    .bind(handle)
+    .fetch_all(pool)
+    .await
+}
+
+async fn cold_versions(pool: &sqlx::PgPool, handle: &[u8]) -> Result<Vec<i16>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT ciphertext_version FROM ciphertexts_cold WHERE handle = $1 ORDER BY ciphertext_version",
+    )
+    .bind(handle)
+    .fetch_all(pool)
+    .await
+}
+
+async fn ct128(pool: &sqlx::PgPool, handle: &[u8]) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT ciphertext128 FROM ciphertexts WHERE handle = $1")
+        .bind(handle)
+        .fetch_one(pool)
+        .await
+}
@@ -13,12 +13,15 @@ use utils::{
     decrypt_ciphertexts, default_api_key, random_handle, wait_until_all_ciphertexts_computed,
 };
 
+mod debug_decrypt;
 mod errors;
+mod gc;
 mod inputs;
 mod operators;
 mod operators_from_events;
 mod random;
 mod scheduling_bench;
+mod tiering;
 mod utils;
 
 #[tokio::test]
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use fhevm_engine_common::object_store::InMemoryStore;
+
+use crate::{
+    tests::utils::{default_tenant_id, random_handle, setup_test_app},
+    tiering::{
+        rehydrate_ciphertexts, rehydrate_pending_dependencies, tier_cold_ciphertexts,
+        touch_ciphertexts, ColdStore, TieringArgs,
+    },
+};
+
+#[tokio::test]
+async fn test_tiering() -> Result<(), Box<dyn std::error::Error>> {
+    let app = setup_test_app().await?;
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(app.db_url())
+        .await?;
+    let tenant_id = default_tenant_id();
+    let store = InMemoryStore::default();
+    let args = TieringArgs {
+        tiering_cold_after_secs: 365 * 24 * 3600,
+        tiering_batch_size: 1000,
+        ..Default::default()
+    };
+    let cold = ColdStore::new(Arc::new(store.clone()), args.tiering_bucket.clone());
+
+    // old ciphertexts, one of them input of a pending computation and one
+    // recently read
+    let cold_handle = random_handle().to_be_bytes().to_vec();
+    let input_handle = random_handle().to_be_bytes().to_vec();
+    let used_handle = random_handle().to_be_bytes().to_vec();
+    let output_handle = random_handle().to_be_bytes().to_vec();
+    // never computed, so that the tfhe worker leaves the computation pending
+    let missing_handle = random_handle().to_be_bytes().to_vec();
+    for (handle, byte) in [
+        (&cold_handle, 1u8),
+        (&input_handle, 2u8),
+        (&used_handle, 3u8),
+    ] {
+        sqlx::query(
+            "INSERT INTO ciphertexts(tenant_id, handle, ciphertext, ciphertext_version, ciphertext_type, created_at, last_used_at)
+             VALUES ($1, $2, $3, 0, 4, NOW() - INTERVAL '400 days', NOW() - INTERVAL '400 days')",
+        )
+        .bind(tenant_id)
+        .bind(handle)
+        .bind(vec![byte; 64])
+        .execute(&pool)
+        .await?;
+    }
+    sqlx::query(
+        "INSERT INTO computations(tenant_id, output_handle, dependencies, fhe_operation, is_scalar)
+         VALUES ($1, $2, $3, 0, false)",
+    )
+    .bind(tenant_id)
+    .bind(&output_handle)
+    .bind(vec![input_handle.clone(), missing_handle.clone()])
+    .execute(&pool)
+    .await?;
+
+    touch_ciphertexts(&pool, &[tenant_id], &[used_handle.clone()]).await?;
+
+    // only the ciphertext neither used nor needed by a computation is tiered
+    tier_cold_ciphertexts(&pool, &cold, &args)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    assert!(ciphertext(&pool, &cold_handle).await?.is_none());
+    assert!(ciphertext(&pool, &input_handle).await?.is_some());
+    assert!(ciphertext(&pool, &used_handle).await?.is_some());
+    assert!(is_cold(&pool, &cold_handle).await?);
+    assert!(!store.keys("ct64-cold").is_empty());
+
+    // a computation needing the tiered ciphertext rehydrates it
+    sqlx::query(
+        "UPDATE computations SET dependencies = $3 WHERE tenant_id = $1 AND output_handle = $2",
+    )
+    .bind(tenant_id)
+    .bind(&output_handle)
+    .bind(vec![cold_handle.clone(), input_handle.clone()])
+    .execute(&pool)
+    .await?;
+    let count = rehydrate_pending_dependencies(&pool, &cold, 1000)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    assert!(count >= 1);
+    assert_eq!(ciphertext(&pool, &cold_handle).await?, Some(vec![1u8; 64]));
+    assert!(!is_cold(&pool, &cold_handle).await?);
+
+    // recently rehydrated ciphertexts are not tiered again
+    sqlx::query("DELETE FROM computations WHERE tenant_id = $1 AND output_handle = $2")
+        .bind(tenant_id)
+        .bind(&output_handle)
+        .execute(&pool)
+        .await?;
+    tier_cold_ciphertexts(&pool, &cold, &args)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    assert!(ciphertext(&pool, &cold_handle).await?.is_some());
+    assert!(is_cold(&pool, &input_handle).await?);
+
+    // a squash needing the tiered ciphertext, for a decryption allowed after
+    // the tiering, rehydrates it
+    sqlx::query("INSERT INTO pbs_computations(tenant_id, handle) VALUES ($1, $2)")
+        .bind(tenant_id)
+        .bind(&input_handle)
+        .execute(&pool)
+        .await?;
+    let count = rehydrate_pending_dependencies(&pool, &cold, 1000)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    assert!(count >= 1);
+    assert_eq!(ciphertext(&pool, &input_handle).await?, Some(vec![2u8; 64]));
+    assert!(!is_cold(&pool, &input_handle).await?);
+
+    // tiered again once squashed
+    sqlx::query(
+        "UPDATE pbs_computations SET is_completed = TRUE WHERE tenant_id = $1 AND handle = $2",
+    )
+    .bind(tenant_id)
+    .bind(&input_handle)
+    .execute(&pool)
+    .await?;
+    sqlx::query(
+        "UPDATE ciphertexts SET last_used_at = NOW() - INTERVAL '400 days' WHERE tenant_id = $1 AND handle = $2",
+    )
+    .bind(tenant_id)
+    .bind(&input_handle)
+    .execute(&pool)
+    .await?;
+    tier_cold_ciphertexts(&pool, &cold, &args)
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    assert!(is_cold(&pool, &input_handle).await?);
+
+    // explicit rehydration, as done by GetCiphertexts
+    let count = rehydrate_ciphertexts(&pool, &cold, tenant_id, &[input_handle.clone()])
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)?;
+    assert_eq!(count, 1);
+    assert_eq!(ciphertext(&pool, &input_handle).await?, Some(vec![2u8; 64]));
+    assert!(!is_cold(&pool, &input_handle).await?);
+
+    Ok(())
+}
+
+async fn ciphertext(pool: &sqlx::PgPool, handle: &[u8]) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT ciphertext FROM ciphertexts WHERE tenant_id = $1 AND handle = $2")
+        .bind(default_tenant_id())
+        .bind(handle)
+        .fetch_optional(pool)
+        .await
+}
+
+async fn is_cold(pool: &sqlx::PgPool, handle: &[u8]) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM ciphertexts_cold WHERE tenant_id = $1 AND handle = $2)",
+    )
+    .bind(default_tenant_id())
+    .bind(handle)
+    .fetch_one(pool)
+    .await
+}
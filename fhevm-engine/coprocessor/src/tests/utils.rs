@@ -1,4 +1,5 @@
 use crate::daemon_cli::Args;
+use fhevm_engine_common::signer::SignerSource;
 use fhevm_engine_common::tfhe_ops::current_ciphertext_version;
 use fhevm_engine_common::types::SupportedFheCiphertexts;
 use fhevm_engine_common::utils::{safe_deserialize, safe_deserialize_key};
@@ -100,8 +101,12 @@ async fn start_coprocessor(rx: Receiver<bool>, app_port: u16, db_url: &str) {
         metrics_addr: "".to_string(),
         database_url: Some(db_url.to_string()),
         maximimum_compact_inputs_upload: 10,
-        coprocessor_private_key: "./coprocessor.key".to_string(),
+        coprocessor_private_key: SignerSource::File("./coprocessor.key".to_owned()),
         service_name: "coprocessor".to_string(),
+        gc: Default::default(),
+        tiering: Default::default(),
+        #[cfg(feature = "debug_decrypt")]
+        debug_decrypt: Default::default(),
     };
 
     std::thread::spawn(move || {
@@ -1,3 +1,4 @@
+use crate::tiering::{rehydrate_pending_dependencies, touch_ciphertexts, ColdStore};
 use crate::types::CoprocessorError;
 use crate::{db_queries::populate_cache_with_tenant_keys, types::TfheTenantKeys};
 use fhevm_engine_common::types::{FhevmError, Handle, SupportedFheCiphertexts};
@@ -77,6 +78,8 @@ async fn tfhe_worker_cycle(
         .connect(&db_url)
         .await?;
 
+    let cold_store = ColdStore::connect(&args.tiering).await?;
+
     let mut listener = PgListener::connect_with(&pool).await.unwrap();
     listener.listen("work_available").await?;
 
@@ -97,6 +100,17 @@ async fn tfhe_worker_cycle(
         }
         let loop_span = tracer.start("worker_iteration");
         let loop_ctx = opentelemetry::Context::current_with_span(loop_span);
+        // inputs tiered to the object store must be back before scheduling
+        if let Some(cold_store) = &cold_store {
+            let mut s = tracer.start_with_context("rehydrate_dependencies", &loop_ctx);
+            if let Err(err) =
+                rehydrate_pending_dependencies(&pool, cold_store, args.work_items_batch_size as i64)
+                    .await
+            {
+                error!(target: "tfhe_worker", { error = err }, "Error while rehydrating tiered dependencies");
+            }
+            s.end();
+        }
         let mut s = tracer.start_with_context("acquire_connection", &loop_ctx);
         let mut conn = pool.acquire().await?;
         s.end();
@@ -223,6 +237,10 @@ async fn tfhe_worker_cycle(
         .fetch_all(trx.as_mut())
         .await?;
         s.end();
+        // used as inputs, the ciphertexts are not tiered for a while
+        if let Err(err) = touch_ciphertexts(&pool, &tenants_to_query, &cts_to_query).await {
+            error!(target: "tfhe_worker", { error = %err }, "Error while recording the use of ciphertexts");
+        }
         // index ciphertexts in hashmap
         let mut ciphertext_map: HashMap<(i32, &[u8]), _> =
             HashMap::with_capacity(ciphertexts_rows.len());
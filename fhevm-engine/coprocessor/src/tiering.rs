@@ -0,0 +1,383 @@
+use std::sync::Arc;
+
+use fhevm_engine_common::object_store::{connect, ObjectStore, ObjectStoreError, StorageBackend};
+use sha3::{Digest, Keccak256};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+const DEFAULT_COLD_AFTER_SECS: u64 = 30 * 24 * 3600;
+const DEFAULT_TIERING_BUCKET: &str = "ct64-cold";
+
+/// Minimum time between two updates of the last use of a ciphertext
+const TOUCH_INTERVAL_SECS: f64 = 60.0;
+
+/// Tiering of the rarely used ciphertexts to the object store
+#[derive(clap::Args, Debug, Clone)]
+pub struct TieringArgs {
+    /// Interval in seconds between two tierings of the coprocessor daemon,
+    /// disabled if 0. The tiered ciphertexts are rehydrated on demand even if
+    /// disabled, as long as the storage backend is set
+    #[arg(long, default_value_t = 0)]
+    pub tiering_interval_secs: u64,
+
+    /// Time in seconds since their last use, by GetCiphertexts or as a
+    /// computation input, after which the ciphertexts are moved to the object
+    /// store
+    #[arg(long, default_value_t = DEFAULT_COLD_AFTER_SECS)]
+    pub tiering_cold_after_secs: u64,
+
+    /// Object store of the tiered ciphertexts: s3, local:<dir> or memory, as
+    /// for the sns-executor. Required by the tiering, without it no
+    /// ciphertext is tiered nor rehydrated
+    #[arg(long)]
+    pub tiering_storage_backend: Option<StorageBackend>,
+
+    /// Bucket of the tiered ciphertexts, keyed by their hex-encoded keccak256
+    /// digest
+    #[arg(long, default_value = DEFAULT_TIERING_BUCKET)]
+    pub tiering_bucket: String,
+
+    /// Ciphertexts tiered per transaction
+    #[arg(long, default_value_t = 100)]
+    pub tiering_batch_size: i64,
+}
+
+impl Default for TieringArgs {
+    fn default() -> Self {
+        Self {
+            tiering_interval_secs: 0,
+            tiering_cold_after_secs: DEFAULT_COLD_AFTER_SECS,
+            tiering_storage_backend: None,
+            tiering_bucket: DEFAULT_TIERING_BUCKET.to_owned(),
+            tiering_batch_size: 100,
+        }
+    }
+}
+
+/// Bucket of the object store holding the tiered ciphertexts
+#[derive(Clone)]
+pub struct ColdStore {
+    store: Arc<dyn ObjectStore>,
+    bucket: String,
+}
+
+impl ColdStore {
+    pub fn new(store: Arc<dyn ObjectStore>, bucket: String) -> Self {
+        Self { store, bucket }
+    }
+
+    /// Connects to the object store of the tiered ciphertexts, if configured
+    pub async fn connect(
+        args: &TieringArgs,
+    ) -> Result<Option<Self>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(backend) = &args.tiering_storage_backend else {
+            return Ok(None);
+        };
+        let store = connect(backend).await?;
+        Ok(Some(Self::new(store, args.tiering_bucket.clone())))
+    }
+
+    /// Deletes the tiered ciphertext of the digest
+    pub async fn delete(&self, digest: &[u8]) -> Result<(), ObjectStoreError> {
+        self.store.delete(&self.bucket, &hex::encode(digest)).await
+    }
+}
+
+struct ColdCiphertext {
+    tenant_id: i32,
+    handle: Vec<u8>,
+    ciphertext_version: i16,
+    ciphertext_type: i16,
+    input_blob_hash: Option<Vec<u8>>,
+    input_blob_index: i32,
+    created_at: Option<sqlx::types::time::PrimitiveDateTime>,
+    digest: Vec<u8>,
+}
+
+/// Runs the tiering every `tiering_interval_secs`
+pub async fn run_tiering(
+    args: crate::daemon_cli::Args,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db_url = crate::utils::db_url(&args);
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&db_url)
+        .await?;
+    let cold = ColdStore::connect(&args.tiering)
+        .await?
+        .ok_or("ciphertext tiering requires --tiering-storage-backend")?;
+
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+        args.tiering.tiering_interval_secs.max(1),
+    ));
+    loop {
+        interval.tick().await;
+        loop {
+            // here we log the errors and retry on the next tick
+            match tier_cold_ciphertexts(&pool, &cold, &args.tiering).await {
+                Ok(count) if count < args.tiering.tiering_batch_size as u64 => break,
+                Ok(_) => (),
+                Err(err) => {
+                    error!(target: "tiering", { error = err }, "Error in ciphertext tiering");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Moves a batch of ciphertexts not used since `tiering_cold_after_secs` to
+/// the object store, returns the number of tiered ciphertexts.
+///
+/// The ciphertexts that are inputs of pending computations, or pending a
+/// squash or an upload by the sns-executor, are kept. The ciphertexts are
+/// uploaded before the transaction that removes them, which skips the ones
+/// used in the meantime.
+pub async fn tier_cold_ciphertexts(
+    pool: &PgPool,
+    cold: &ColdStore,
+    args: &TieringArgs,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let rows = sqlx::query!(
+        "
+        SELECT c.tenant_id, c.handle, c.ciphertext_version, c.ciphertext, c.ciphertext_type,
+            c.input_blob_hash, c.input_blob_index, c.created_at, c.last_used_at
+        FROM ciphertexts c
+        WHERE c.last_used_at < NOW() - make_interval(secs => $1)
+        AND c.ciphertext128 IS NULL
+        AND NOT EXISTS (
+            SELECT 1 FROM computations p
+            WHERE p.tenant_id = c.tenant_id
+            AND c.handle = ANY(p.dependencies)
+            AND NOT p.is_completed
+            AND NOT p.is_error
+        )
+        AND NOT EXISTS (
+            SELECT 1 FROM pbs_computations p
+            WHERE p.tenant_id = c.tenant_id
+            AND p.handle = c.handle
+            AND NOT p.is_completed
+        )
+        AND NOT EXISTS (
+            SELECT 1 FROM ciphertext_digest d
+            WHERE d.tenant_id = c.tenant_id
+            AND d.handle = c.handle
+            AND (d.ciphertext IS NULL OR d.ciphertext128 IS NULL)
+        )
+        ORDER BY c.last_used_at
+        LIMIT $2
+        ",
+        args.tiering_cold_after_secs as f64,
+        args.tiering_batch_size,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut uploaded = Vec::with_capacity(rows.len());
+    for row in rows {
+        let digest = Keccak256::digest(&row.ciphertext).to_vec();
+        cold.store
+            .put(&cold.bucket, &hex::encode(&digest), row.ciphertext.clone())
+            .await?;
+        uploaded.push((row, digest));
+    }
+
+    let mut count = 0;
+    let mut trx = pool.begin().await?;
+    for (row, digest) in uploaded {
+        // not if used, or needed by a new computation, since the selection
+        let deleted = sqlx::query!(
+            "
+            DELETE FROM ciphertexts c
+            WHERE c.tenant_id = $1 AND c.handle = $2 AND c.ciphertext_version = $3
+            AND c.last_used_at = $4
+            AND c.ciphertext128 IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM computations p
+                WHERE p.tenant_id = c.tenant_id
+                AND c.handle = ANY(p.dependencies)
+                AND NOT p.is_completed
+                AND NOT p.is_error
+            )
+            ",
+            row.tenant_id,
+            row.handle,
+            row.ciphertext_version,
+            row.last_used_at,
+        )
+        .execute(trx.as_mut())
+        .await?;
+        if deleted.rows_affected() == 0 {
+            continue;
+        }
+
+        sqlx::query!(
+            "
+            INSERT INTO ciphertexts_cold(tenant_id, handle, ciphertext_version, ciphertext_type,
+                input_blob_hash, input_blob_index, created_at, digest)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (tenant_id, handle, ciphertext_version) DO NOTHING
+            ",
+            row.tenant_id,
+            row.handle,
+            row.ciphertext_version,
+            row.ciphertext_type,
+            row.input_blob_hash,
+            row.input_blob_index,
+            row.created_at,
+            digest,
+        )
+        .execute(trx.as_mut())
+        .await?;
+        count += 1;
+    }
+    trx.commit().await?;
+
+    if count > 0 {
+        info!(target: "tiering", { count }, "Tiered ciphertexts");
+    }
+    Ok(count)
+}
+
+/// Records the use of the ciphertexts, which delays their tiering
+pub async fn touch_ciphertexts(
+    pool: &PgPool,
+    tenant_ids: &[i32],
+    handles: &[Vec<u8>],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        UPDATE ciphertexts
+        SET last_used_at = NOW()
+        WHERE tenant_id = ANY($1::INT[])
+        AND handle = ANY($2::BYTEA[])
+        AND last_used_at < NOW() - make_interval(secs => $3)
+        ",
+        tenant_ids,
+        handles,
+        TOUCH_INTERVAL_SECS,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Moves the tiered ciphertexts of the handles back to the database, returns
+/// the number of rehydrated ciphertexts.
+pub async fn rehydrate_ciphertexts(
+    pool: &PgPool,
+    cold: &ColdStore,
+    tenant_id: i32,
+    handles: &[Vec<u8>],
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let rows = sqlx::query_as!(
+        ColdCiphertext,
+        "
+        SELECT tenant_id, handle, ciphertext_version, ciphertext_type,
+            input_blob_hash, input_blob_index, created_at, digest
+        FROM ciphertexts_cold
+        WHERE tenant_id = $1
+        AND handle = ANY($2::BYTEA[])
+        ",
+        tenant_id,
+        handles,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rehydrate(pool, cold, rows).await
+}
+
+/// Rehydrates the tiered ciphertexts that are inputs of pending computations,
+/// or of pending squashes of the sns-executor, e.g. for a decryption allowed
+/// after the tiering, up to `limit`, so that they can be scheduled.
+pub async fn rehydrate_pending_dependencies(
+    pool: &PgPool,
+    cold: &ColdStore,
+    limit: i64,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let rows = sqlx::query_as!(
+        ColdCiphertext,
+        "
+        SELECT cc.tenant_id, cc.handle, cc.ciphertext_version, cc.ciphertext_type,
+            cc.input_blob_hash, cc.input_blob_index, cc.created_at, cc.digest
+        FROM ciphertexts_cold cc
+        WHERE EXISTS (
+            SELECT 1 FROM computations c
+            WHERE c.tenant_id = cc.tenant_id
+            AND cc.handle = ANY(c.dependencies)
+            AND NOT c.is_completed
+            AND NOT c.is_error
+        )
+        OR EXISTS (
+            SELECT 1 FROM pbs_computations p
+            WHERE p.tenant_id = cc.tenant_id
+            AND p.handle = cc.handle
+            AND NOT p.is_completed
+        )
+        LIMIT $1
+        ",
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rehydrate(pool, cold, rows).await
+}
+
+async fn rehydrate(
+    pool: &PgPool,
+    cold: &ColdStore,
+    rows: Vec<ColdCiphertext>,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let count = rows.len() as u64;
+    for row in rows {
+        let key = hex::encode(&row.digest);
+        let ciphertext = cold
+            .store
+            .get(&cold.bucket, &key)
+            .await?
+            .ok_or_else(|| format!("tiered ciphertext {}/{} not found", cold.bucket, key))?;
+        if Keccak256::digest(&ciphertext).as_slice() != row.digest.as_slice() {
+            return Err(format!("tiered ciphertext {}/{} is corrupted", cold.bucket, key).into());
+        }
+
+        let mut trx = pool.begin().await?;
+        sqlx::query!(
+            "
+            INSERT INTO ciphertexts(tenant_id, handle, ciphertext, ciphertext_version,
+                ciphertext_type, input_blob_hash, input_blob_index, created_at, rehydrated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+            ON CONFLICT (tenant_id, handle, ciphertext_version) DO NOTHING
+            ",
+            row.tenant_id,
+            row.handle,
+            ciphertext,
+            row.ciphertext_version,
+            row.ciphertext_type,
+            row.input_blob_hash,
+            row.input_blob_index,
+            row.created_at,
+        )
+        .execute(trx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM ciphertexts_cold
+            WHERE tenant_id = $1 AND handle = $2 AND ciphertext_version = $3
+            ",
+            row.tenant_id,
+            row.handle,
+            row.ciphertext_version,
+        )
+        .execute(trx.as_mut())
+        .await?;
+        trx.commit().await?;
+    }
+
+    if count > 0 {
+        info!(target: "tiering", { count }, "Rehydrated ciphertexts");
+    }
+    Ok(count)
+}
@@ -26,6 +26,12 @@ pub enum CoprocessorError {
     Eip712SigningFailure {
         error: String,
     },
+    CiphertextRehydrationFailure {
+        error: String,
+    },
+    DebugDecryptionFailure {
+        error: String,
+    },
     DuplicateResultHandleInInputsUploaded {
         hex_handle: String,
     },
@@ -138,6 +144,12 @@ impl std::fmt::Display for CoprocessorError {
             Self::Eip712SigningFailure { error } => {
                 write!(f, "Error when signing EIP712 hash: {}", error,)
             }
+            Self::CiphertextRehydrationFailure { error } => {
+                write!(f, "Error when rehydrating tiered ciphertexts: {}", error)
+            }
+            Self::DebugDecryptionFailure { error } => {
+                write!(f, "Error in debug decryption: {}", error)
+            }
             Self::CiphertextComputationDependencyLoopDetected {
                 uncomputable_output_handle,
                 uncomputable_handle_dependency,
@@ -0,0 +1,33 @@
+CREATE TABLE IF NOT EXISTS delegations (
+    tenant_id INT NOT NULL,
+    delegator TEXT NOT NULL,
+    delegatee TEXT NOT NULL,
+    contract_address TEXT NOT NULL,
+    is_revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+
+    txn_is_sent BOOLEAN NOT NULL DEFAULT FALSE,
+    txn_retry_count INT NOT NULL DEFAULT 0,
+    txn_transport_retry_count INT NOT NULL DEFAULT 0,
+    txn_last_error TEXT DEFAULT NULL,
+    txn_last_error_at TIMESTAMP DEFAULT NULL,
+    PRIMARY KEY (tenant_id, delegator, delegatee, contract_address)
+);
+
+CREATE INDEX IF NOT EXISTS idx_delegations_txn_is_sent ON delegations(txn_is_sent, txn_retry_count);
+
+-- Notify delegations, fired for both inserts and upserts (revocations)
+CREATE OR REPLACE FUNCTION notify_event_delegation()
+    RETURNS trigger AS $$
+BEGIN
+    NOTIFY event_delegation;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER on_insert_notify_event_delegation
+    AFTER INSERT
+    ON delegations
+    FOR EACH STATEMENT
+    EXECUTE FUNCTION notify_event_delegation();
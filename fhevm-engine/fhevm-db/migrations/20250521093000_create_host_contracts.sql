@@ -0,0 +1,39 @@
+-- Registry of the host chain contracts followed by the fhevm-listener
+CREATE TABLE IF NOT EXISTS host_contracts (
+    chain_id BIGINT NOT NULL,
+    contract_name TEXT NOT NULL, -- ACL or FHEVMExecutor
+    contract_address TEXT NOT NULL,
+    implementation_address TEXT DEFAULT NULL, -- last seen proxy implementation
+    owner_address TEXT DEFAULT NULL, -- last seen owner
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_id, contract_name)
+);
+
+-- Alert trail of the changes of the host chain contracts
+CREATE TABLE IF NOT EXISTS host_contract_events (
+    chain_id BIGINT NOT NULL,
+    contract_name TEXT NOT NULL,
+    contract_address TEXT NOT NULL,
+    event_name TEXT NOT NULL, -- Upgraded, OwnershipTransferred, ..., UnknownEvent
+    event_value TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash BYTEA NOT NULL,
+    transaction_hash BYTEA DEFAULT NULL,
+    log_index BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_id, block_hash, log_index)
+);
+
+CREATE OR REPLACE FUNCTION notify_event_host_contract()
+    RETURNS trigger AS $$
+BEGIN
+    NOTIFY event_host_contract;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER on_insert_notify_event_host_contract
+    AFTER INSERT
+    ON host_contract_events
+    FOR EACH STATEMENT
+    EXECUTE FUNCTION notify_event_host_contract();
@@ -0,0 +1,23 @@
+-- Host chain logs the fhevm-listener could not decode or propagate
+CREATE TABLE IF NOT EXISTS listener_dead_letters (
+    id BIGSERIAL PRIMARY KEY,
+    chain_id BIGINT NOT NULL,
+    tenant_id INT NOT NULL,
+    contract_address TEXT NOT NULL,
+    topics BYTEA[] NOT NULL,
+    data BYTEA NOT NULL,
+    block_number BIGINT DEFAULT NULL,
+    block_hash BYTEA DEFAULT NULL,
+    transaction_hash BYTEA DEFAULT NULL,
+    log_index BIGINT DEFAULT NULL,
+    error_kind TEXT NOT NULL, -- decode or database
+    error TEXT NOT NULL,
+    replay_count INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    replayed_at TIMESTAMP DEFAULT NULL -- set once the log has been successfully replayed
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_listener_dead_letters_log
+    ON listener_dead_letters (chain_id, block_hash, log_index);
+CREATE INDEX IF NOT EXISTS idx_listener_dead_letters_pending
+    ON listener_dead_letters (chain_id, tenant_id) WHERE replayed_at IS NULL;
@@ -0,0 +1,36 @@
+-- Gateway events consumed by the gw-listener besides VerifyProofRequest
+
+CREATE TABLE IF NOT EXISTS gw_decryption_requests (
+    request_id BYTEA NOT NULL, -- uint256, big-endian
+    is_user_decryption BOOLEAN NOT NULL,
+    handles BYTEA[] NOT NULL,
+    sns_ciphertext_digests BYTEA[] NOT NULL,
+    user_address TEXT DEFAULT NULL, -- user decryption only
+    public_key BYTEA DEFAULT NULL, -- user decryption only
+    block_number BIGINT DEFAULT NULL,
+    transaction_hash BYTEA DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (is_user_decryption, request_id)
+);
+
+CREATE TABLE IF NOT EXISTS gw_ciphertext_commits (
+    handle BYTEA PRIMARY KEY NOT NULL,
+    ciphertext_digest BYTEA NOT NULL,
+    sns_ciphertext_digest BYTEA NOT NULL,
+    coprocessor_tx_sender_addresses TEXT[] NOT NULL,
+    block_number BIGINT DEFAULT NULL,
+    transaction_hash BYTEA DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS gw_acl_confirmations (
+    handle BYTEA NOT NULL,
+    -- same values as allowed_handles.event_type: 0 AllowAccount, 1 AllowPublicDecrypt
+    event_type SMALLINT NOT NULL,
+    account_address TEXT NOT NULL, -- empty for AllowPublicDecrypt
+    block_number BIGINT DEFAULT NULL,
+    transaction_hash BYTEA DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (handle, event_type, account_address)
+);
@@ -0,0 +1,25 @@
+-- Gateway event a verify proof request comes from, to detect re-emitted and orphaned requests
+ALTER TABLE verify_proofs
+    ADD COLUMN IF NOT EXISTS block_number BIGINT DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS block_hash BYTEA DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS log_index BIGINT DEFAULT NULL,
+    -- set when a gateway reorg removes the request event, the request is neither verified nor sent until re-emitted
+    ADD COLUMN IF NOT EXISTS orphaned BOOLEAN NOT NULL DEFAULT false;
+
+-- Previous payloads of requests re-emitted with a different payload for the same zk_proof_id
+CREATE TABLE IF NOT EXISTS verify_proof_conflicts (
+    id BIGSERIAL PRIMARY KEY,
+    zk_proof_id BIGINT NOT NULL,
+    chain_id INTEGER NOT NULL,
+    contract_address TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    input BYTEA,
+    block_hash BYTEA,
+    log_index BIGINT,
+    verified BOOLEAN,
+    new_block_hash BYTEA,
+    new_log_index BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_verify_proof_conflicts_zk_proof_id ON verify_proof_conflicts(zk_proof_id);
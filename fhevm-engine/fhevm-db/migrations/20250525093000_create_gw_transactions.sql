@@ -0,0 +1,22 @@
+-- Journal of the transactions sent to the Gateway by the transaction-sender
+
+CREATE TABLE IF NOT EXISTS gw_transactions (
+    id BIGSERIAL PRIMARY KEY,
+    signer_address TEXT NOT NULL,
+    nonce BIGINT NOT NULL,
+    tx_hash BYTEA NOT NULL UNIQUE,
+    operation TEXT NOT NULL,
+    handles BYTEA[] NOT NULL DEFAULT '{}',
+    -- the transaction request as JSON, to send it again if dropped
+    tx_request TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'mined', 'reverted', 'replaced', 'dropped')),
+    block_number BIGINT DEFAULT NULL,
+    error TEXT DEFAULT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_gw_transactions_pending
+    ON gw_transactions (signer_address, nonce)
+    WHERE status = 'pending';
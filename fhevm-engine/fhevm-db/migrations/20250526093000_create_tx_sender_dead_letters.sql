@@ -0,0 +1,33 @@
+-- Errors of every failed attempt of the transaction-sender to send an item to the Gateway
+
+CREATE TABLE IF NOT EXISTS tx_sender_errors (
+    id BIGSERIAL PRIMARY KEY,
+    operation TEXT NOT NULL, -- verify_proof, add_ciphertext, allow_handle or delegation
+    item_key TEXT NOT NULL, -- see transaction_sender::DeadLetterItem
+    error TEXT NOT NULL,
+    is_transport BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_tx_sender_errors_item
+    ON tx_sender_errors (operation, item_key, created_at);
+
+-- Items that exhausted their retries, kept for operator review and re-submission
+CREATE TABLE IF NOT EXISTS tx_sender_dead_letters (
+    id BIGSERIAL PRIMARY KEY,
+    operation TEXT NOT NULL,
+    item_key TEXT NOT NULL,
+    retry_count INT NOT NULL,
+    last_error TEXT NOT NULL,
+    -- the removed verify_proofs row as JSON when verify_proof_remove_after_max_retries is set, to restore it on
+    -- re-submission
+    payload TEXT DEFAULT NULL,
+    resubmit_count INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(), -- reset when a re-submitted item exhausts its retries again
+    resubmitted_at TIMESTAMPTZ DEFAULT NULL, -- set once re-submitted, reset when it exhausts its retries again
+    UNIQUE (operation, item_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tx_sender_dead_letters_pending
+    ON tx_sender_dead_letters (operation, created_at)
+    WHERE resubmitted_at IS NULL;
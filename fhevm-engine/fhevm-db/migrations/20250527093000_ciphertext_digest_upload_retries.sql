@@ -0,0 +1,12 @@
+-- Uploads of the sns-executor are driven from ciphertext_digest: a row with a NULL digest is a pending upload of the
+-- corresponding ciphertext, retried with an exponential backoff until it succeeds
+
+ALTER TABLE ciphertext_digest
+    ADD COLUMN upload_retry_count INT NOT NULL DEFAULT 0,
+    ADD COLUMN upload_last_error TEXT DEFAULT NULL,
+    -- also a lease, pushed forward when an upload is started so that it is not retried concurrently
+    ADD COLUMN upload_next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS idx_ciphertext_digest_pending_uploads
+    ON ciphertext_digest (upload_next_attempt_at)
+    WHERE ciphertext IS NULL OR ciphertext128 IS NULL;
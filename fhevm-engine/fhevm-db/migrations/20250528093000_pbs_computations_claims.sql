@@ -0,0 +1,4 @@
+-- SnS workers claim the pbs_computations with a lease instead of holding row locks while squashing, the tasks not
+-- completed when it expires are claimed again
+ALTER TABLE pbs_computations
+    ADD COLUMN claimed_until TIMESTAMPTZ DEFAULT NULL;
@@ -0,0 +1,14 @@
+-- Stored ciphertexts found missing or not matching their digests by the sns-executor audit
+CREATE TABLE IF NOT EXISTS ciphertext_audit_findings (
+    id BIGSERIAL PRIMARY KEY,
+    tenant_id INT NOT NULL,
+    handle BYTEA NOT NULL,
+    -- missing_ct64, corrupted_ct64, missing_ct128, corrupted_ct128 or commit_mismatch
+    kind TEXT NOT NULL,
+    detail TEXT NOT NULL,
+    repaired BOOLEAN NOT NULL DEFAULT FALSE, -- re-uploaded from the database copy
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ciphertext_audit_findings_handle
+    ON ciphertext_audit_findings (tenant_id, handle);
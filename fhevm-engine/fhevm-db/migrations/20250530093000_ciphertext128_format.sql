@@ -0,0 +1,7 @@
+-- Encoding of the ct128 computed by the sns-executor: 0 = raw safe serialization
+ALTER TABLE ciphertexts
+    ADD COLUMN IF NOT EXISTS ciphertext128_format SMALLINT NOT NULL DEFAULT 0;
+
+-- Encoding of the uploaded ct128 object, readers of the ct128 bucket must reject unknown ones
+ALTER TABLE ciphertext_digest
+    ADD COLUMN IF NOT EXISTS ciphertext128_format SMALLINT NOT NULL DEFAULT 0;
@@ -0,0 +1,13 @@
+-- Handles allowed for decryption by one AllowedForDecryption event, whose ct128 can be uploaded as one list object
+CREATE SEQUENCE IF NOT EXISTS pbs_computations_decryption_batch_seq;
+
+ALTER TABLE pbs_computations
+    ADD COLUMN IF NOT EXISTS decryption_batch BIGINT DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_pbs_computations_decryption_batch
+    ON pbs_computations (tenant_id, decryption_batch)
+    WHERE decryption_batch IS NOT NULL;
+
+-- Position of the handle in the ct128 list object of its decryption batch, NULL if the ct128 is uploaded alone
+ALTER TABLE ciphertext_digest
+    ADD COLUMN IF NOT EXISTS ciphertext128_list_index INT DEFAULT NULL;
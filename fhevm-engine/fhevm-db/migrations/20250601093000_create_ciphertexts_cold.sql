@@ -0,0 +1,20 @@
+-- Ciphertexts tiered to the object store, keyed there by the hex-encoded keccak256 digest of the ciphertext
+CREATE TABLE IF NOT EXISTS ciphertexts_cold (
+    tenant_id INT NOT NULL,
+    handle BYTEA NOT NULL,
+    ciphertext_version SMALLINT NOT NULL,
+    ciphertext_type SMALLINT NOT NULL,
+    input_blob_hash BYTEA,
+    input_blob_index INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP,
+    digest BYTEA NOT NULL,
+    tiered_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (tenant_id, handle, ciphertext_version)
+);
+
+-- Objects shared by identical ciphertexts are kept until their last row is collected
+CREATE INDEX IF NOT EXISTS idx_ciphertexts_cold_digest ON ciphertexts_cold (digest);
+
+-- Last time the ciphertext was moved back from the object store, NULL if never tiered
+ALTER TABLE ciphertexts
+    ADD COLUMN IF NOT EXISTS rehydrated_at TIMESTAMP DEFAULT NULL;
@@ -0,0 +1,7 @@
+-- The ct128 of the handles of a decryption batch are only uploaded as part of the list object of the batch, whose
+-- digest is ciphertext128_list, at ciphertext128_list_index. ciphertext128 is still the digest of the ct128 of the handle
+-- committed to the Gateway, to be checked once extracted from the list
+ALTER TABLE ciphertext_digest
+    ADD COLUMN IF NOT EXISTS ciphertext128_list BYTEA DEFAULT NULL,
+    -- attempts waiting for the other handles of the batch to be squashed, apart from the upload failures
+    ADD COLUMN IF NOT EXISTS decryption_batch_waits INT NOT NULL DEFAULT 0;
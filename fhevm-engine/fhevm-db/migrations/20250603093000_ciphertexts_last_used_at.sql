@@ -0,0 +1,8 @@
+-- Last time the ciphertext was read by GetCiphertexts or used as an input of a computation, the tiering moves the
+-- ciphertexts not used for a while to the object store
+ALTER TABLE ciphertexts
+    ADD COLUMN IF NOT EXISTS last_used_at TIMESTAMP NOT NULL DEFAULT NOW();
+
+UPDATE ciphertexts SET last_used_at = COALESCE(rehydrated_at, created_at, NOW());
+
+CREATE INDEX IF NOT EXISTS idx_ciphertexts_last_used_at ON ciphertexts (last_used_at);
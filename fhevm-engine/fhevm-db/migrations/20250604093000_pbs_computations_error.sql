@@ -0,0 +1,10 @@
+-- Last error of the squash of the task, e.g. a ct64 that fails to decompress, the task is claimed again once its lease
+-- expires
+ALTER TABLE pbs_computations
+    ADD COLUMN IF NOT EXISTS error TEXT;
+
+-- Failed squashes of the task, which is failed and no longer claimed once they reach the worker's
+-- --work-items-max-attempts
+ALTER TABLE pbs_computations
+    ADD COLUMN IF NOT EXISTS attempts INT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS is_failed BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- The rows sent by the transaction, as dead letter item keys of their operation, updated when the transaction is
+-- reconciled after a restart
+ALTER TABLE gw_transactions
+    ADD COLUMN IF NOT EXISTS item_operation TEXT DEFAULT NULL,
+    ADD COLUMN IF NOT EXISTS item_keys TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,15 @@
+-- Notifies the listeners following the registry of an address change, with
+-- the chain id as payload
+CREATE OR REPLACE FUNCTION notify_host_contracts_changed()
+    RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('host_contracts_changed', NEW.chain_id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER on_change_notify_host_contracts
+    AFTER INSERT OR UPDATE OF contract_address
+    ON host_contracts
+    FOR EACH ROW
+    EXECUTE FUNCTION notify_host_contracts_changed();
@@ -0,0 +1,3 @@
+-- Hash of the last block, to detect on restart a reorg that happened while the gw-listener was stopped
+ALTER TABLE gw_listener_last_block
+    ADD COLUMN IF NOT EXISTS last_block_hash BYTEA DEFAULT NULL;
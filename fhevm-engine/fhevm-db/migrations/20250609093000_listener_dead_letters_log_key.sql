@@ -0,0 +1,9 @@
+-- Dead letters are keyed by a digest of their log, computed by the fhevm-listener, as the logs without block hash or
+-- log index must neither be merged nor stored twice. The rows stored before keep a key of their own.
+ALTER TABLE listener_dead_letters ADD COLUMN IF NOT EXISTS log_key BYTEA;
+UPDATE listener_dead_letters SET log_key = int8send(id) WHERE log_key IS NULL;
+ALTER TABLE listener_dead_letters ALTER COLUMN log_key SET NOT NULL;
+
+DROP INDEX IF EXISTS idx_listener_dead_letters_log;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_listener_dead_letters_log
+    ON listener_dead_letters (chain_id, log_key);
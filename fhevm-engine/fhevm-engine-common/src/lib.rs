@@ -1,4 +1,6 @@
 pub mod keys;
+pub mod object_store;
+pub mod signer;
 pub mod telemetry;
 pub mod tenant_keys;
 pub mod tfhe_ops;
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use tracing::info;
+
+#[derive(Debug)]
+pub enum ObjectStoreError {
+    FailedUpload(String),
+    FailedDownload(String),
+    FailedDelete(String),
+}
+
+impl std::error::Error for ObjectStoreError {}
+
+impl std::fmt::Display for ObjectStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FailedUpload(err) => write!(f, "Failed upload: {err}"),
+            Self::FailedDownload(err) => write!(f, "Failed download: {err}"),
+            Self::FailedDelete(err) => write!(f, "Failed delete: {err}"),
+        }
+    }
+}
+
+/// Where the ciphertexts are uploaded
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// S3 buckets, configured from the AWS environment
+    #[default]
+    S3,
+    /// One sub-directory per bucket in the given directory
+    LocalDir(PathBuf),
+    /// Process memory, lost on exit
+    InMemory,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    /// Parses `s3`, `memory` or `local:<dir>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "s3" => Ok(Self::S3),
+            "memory" => Ok(Self::InMemory),
+            _ => match s.strip_prefix("local:") {
+                Some(dir) if !dir.is_empty() => Ok(Self::LocalDir(PathBuf::from(dir))),
+                _ => Err(format!(
+                    "invalid storage backend {s}, expected s3, memory or local:<dir>"
+                )),
+            },
+        }
+    }
+}
+
+/// Object store holding the ciphertexts, addressed by bucket and key
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, bucket: &str, key: &str, data: Vec<u8>) -> Result<(), ObjectStoreError>;
+
+    /// Returns None if the object does not exist
+    async fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, ObjectStoreError>;
+
+    /// Succeeds if the object does not exist
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), ObjectStoreError>;
+}
+
+/// Creates the object store of the backend
+pub async fn connect(backend: &StorageBackend) -> Result<Arc<dyn ObjectStore>, ObjectStoreError> {
+    info!("Using storage backend: {:?}", backend);
+    Ok(match backend {
+        StorageBackend::S3 => Arc::new(S3Store::from_env().await),
+        StorageBackend::LocalDir(root) => Arc::new(LocalDirStore::new(root.clone())),
+        StorageBackend::InMemory => Arc::new(InMemoryStore::default()),
+    })
+}
+
+pub struct S3Store {
+    client: aws_sdk_s3::Client,
+}
+
+impl S3Store {
+    /// Client construction is expensive due to connection thread pool
+    /// initialization, and should be done once at application start-up.
+    pub async fn from_env() -> Self {
+        let sdk_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+        Self::new(aws_sdk_s3::Client::new(&sdk_config))
+    }
+
+    pub fn new(client: aws_sdk_s3::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for S3Store {
+    async fn put(&self, bucket: &str, key: &str, data: Vec<u8>) -> Result<(), ObjectStoreError> {
+        self.client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(data.into())
+            .send()
+            .await
+            .map_err(|err| ObjectStoreError::FailedUpload(err.to_string()))?;
+        Ok(())
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, ObjectStoreError> {
+        let object = match self
+            .client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(object) => object,
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Ok(None)
+            }
+            Err(err) => return Err(ObjectStoreError::FailedDownload(err.to_string())),
+        };
+        let body = object
+            .body
+            .collect()
+            .await
+            .map_err(|err| ObjectStoreError::FailedDownload(err.to_string()))?;
+        Ok(Some(body.into_bytes().to_vec()))
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), ObjectStoreError> {
+        self.client
+            .delete_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| ObjectStoreError::FailedDelete(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Stores the objects in `<root>/<bucket>/<key>`
+pub struct LocalDirStore {
+    root: PathBuf,
+}
+
+impl LocalDirStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn path(&self, bucket: &str, key: &str) -> PathBuf {
+        self.root.join(bucket).join(key)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalDirStore {
+    async fn put(&self, bucket: &str, key: &str, data: Vec<u8>) -> Result<(), ObjectStoreError> {
+        let path = self.path(bucket, key);
+        let tmp_path = path.with_extension("tmp");
+        let upload = async {
+            tokio::fs::create_dir_all(self.root.join(bucket)).await?;
+            // Write then rename so that readers never see a partial object
+            tokio::fs::write(&tmp_path, data).await?;
+            tokio::fs::rename(&tmp_path, &path).await
+        };
+        upload
+            .await
+            .map_err(|err| ObjectStoreError::FailedUpload(format!("{}: {}", path.display(), err)))
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, ObjectStoreError> {
+        let path = self.path(bucket, key);
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(ObjectStoreError::FailedDownload(format!(
+                "{}: {}",
+                path.display(),
+                err
+            ))),
+        }
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), ObjectStoreError> {
+        let path = self.path(bucket, key);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(ObjectStoreError::FailedDelete(format!(
+                "{}: {}",
+                path.display(),
+                err
+            ))),
+        }
+    }
+}
+
+/// Objects keyed by bucket and key
+type Objects = HashMap<(String, String), Vec<u8>>;
+
+/// Keeps the objects in memory, for tests and local runs
+#[derive(Default, Clone)]
+pub struct InMemoryStore {
+    objects: Arc<Mutex<Objects>>,
+}
+
+impl InMemoryStore {
+    /// Keys of the objects in the bucket
+    pub fn keys(&self, bucket: &str) -> Vec<String> {
+        let objects = self.objects.lock().expect("in-memory store lock");
+        let mut keys: Vec<String> = objects
+            .keys()
+            .filter(|(b, _)| b == bucket)
+            .map(|(_, key)| key.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+#[async_trait]
+impl ObjectStore for InMemoryStore {
+    async fn put(&self, bucket: &str, key: &str, data: Vec<u8>) -> Result<(), ObjectStoreError> {
+        self.objects
+            .lock()
+            .expect("in-memory store lock")
+            .insert((bucket.to_owned(), key.to_owned()), data);
+        Ok(())
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, ObjectStoreError> {
+        Ok(self
+            .objects
+            .lock()
+            .expect("in-memory store lock")
+            .get(&(bucket.to_owned(), key.to_owned()))
+            .cloned())
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), ObjectStoreError> {
+        self.objects
+            .lock()
+            .expect("in-memory store lock")
+            .remove(&(bucket.to_owned(), key.to_owned()));
+        Ok(())
+    }
+}
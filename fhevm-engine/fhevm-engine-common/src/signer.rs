@@ -0,0 +1,240 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use alloy::{
+    consensus::SignableTransaction,
+    network::TxSigner,
+    primitives::{Address, ChainId, PrimitiveSignature, B256},
+    signers::{local::PrivateKeySigner, Error, Signer},
+};
+use async_trait::async_trait;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+
+use self::proto::{
+    remote_signer_client::RemoteSignerClient, remote_signer_server, GetAddressRequest,
+    GetAddressResponse, SignHashRequest, SignHashResponse,
+};
+
+pub mod proto {
+    tonic::include_proto!("fhevm.signer");
+}
+
+const REMOTE_SIGNER_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REMOTE_SIGNER_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where the signing key is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignerSource {
+    /// File containing the private key in plain text 0x1234.. format.
+    File(String),
+    /// Environment variable containing the private key in plain text 0x1234.. format.
+    Env(String),
+    /// `RemoteSigner` gRPC service keeping the key.
+    Remote(String),
+}
+
+/// Parses `env:<variable>`, `remote:<url>`, `file:<path>` or `<path>`.
+impl FromStr for SignerSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = if let Some(variable) = s.strip_prefix("env:") {
+            Self::Env(variable.to_owned())
+        } else if let Some(url) = s.strip_prefix("remote:") {
+            Self::Remote(url.to_owned())
+        } else {
+            Self::File(s.strip_prefix("file:").unwrap_or(s).to_owned())
+        };
+        match &source {
+            Self::File(value) | Self::Env(value) | Self::Remote(value) if value.is_empty() => {
+                Err(format!("invalid signer source {}", s))
+            }
+            _ => Ok(source),
+        }
+    }
+}
+
+impl fmt::Display for SignerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "file:{}", path),
+            Self::Env(variable) => write!(f, "env:{}", variable),
+            Self::Remote(url) => write!(f, "remote:{}", url),
+        }
+    }
+}
+
+/// Signer of EIP-712 messages and transactions, with a local key or a remote one.
+#[derive(Clone)]
+pub enum KeySigner {
+    Local(PrivateKeySigner),
+    Remote(RemoteSigner),
+}
+
+impl KeySigner {
+    pub async fn new(source: &SignerSource) -> anyhow::Result<Self> {
+        match source {
+            SignerSource::File(path) => {
+                let key = tokio::fs::read_to_string(path).await?;
+                Ok(Self::Local(PrivateKeySigner::from_str(key.trim())?))
+            }
+            SignerSource::Env(variable) => {
+                let key = std::env::var(variable)
+                    .map_err(|e| anyhow::anyhow!("cannot read {}: {}", variable, e))?;
+                Ok(Self::Local(PrivateKeySigner::from_str(key.trim())?))
+            }
+            SignerSource::Remote(url) => {
+                Ok(Self::Remote(RemoteSigner::connect(url.clone()).await?))
+            }
+        }
+    }
+
+    /// Sets the chain id transactions are signed for, as `PrivateKeySigner::with_chain_id` does.
+    pub fn with_chain_id(self, chain_id: Option<ChainId>) -> Self {
+        match self {
+            Self::Local(signer) => Self::Local(signer.with_chain_id(chain_id)),
+            Self::Remote(signer) => Self::Remote(RemoteSigner { chain_id, ..signer }),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        match self {
+            Self::Local(signer) => signer.address(),
+            Self::Remote(signer) => signer.address,
+        }
+    }
+
+    pub async fn sign_hash(&self, hash: &B256) -> alloy::signers::Result<PrimitiveSignature> {
+        match self {
+            Self::Local(signer) => signer.sign_hash(hash).await,
+            Self::Remote(signer) => signer.sign_hash(hash).await,
+        }
+    }
+}
+
+impl From<PrivateKeySigner> for KeySigner {
+    fn from(signer: PrivateKeySigner) -> Self {
+        Self::Local(signer)
+    }
+}
+
+#[async_trait]
+impl TxSigner<PrimitiveSignature> for KeySigner {
+    fn address(&self) -> Address {
+        KeySigner::address(self)
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<PrimitiveSignature>,
+    ) -> alloy::signers::Result<PrimitiveSignature> {
+        match self {
+            Self::Local(signer) => signer.sign_transaction(tx).await,
+            Self::Remote(signer) => {
+                if let Some(chain_id) = signer.chain_id {
+                    if !tx.set_chain_id_checked(chain_id) {
+                        return Err(Error::TransactionChainIdMismatch {
+                            signer: chain_id,
+                            // we can only end up here if the tx has a chain id
+                            tx: tx.chain_id().unwrap(),
+                        });
+                    }
+                }
+                signer.sign_hash(&tx.signature_hash()).await
+            }
+        }
+    }
+}
+
+/// Client of a `RemoteSigner` gRPC service.
+#[derive(Clone)]
+pub struct RemoteSigner {
+    client: RemoteSignerClient<Channel>,
+    address: Address,
+    chain_id: Option<ChainId>,
+}
+
+impl RemoteSigner {
+    /// Connects to the service, over TLS with the native root certificates for an https url.
+    pub async fn connect(url: String) -> anyhow::Result<Self> {
+        let mut endpoint = Endpoint::from_shared(url)?
+            .connect_timeout(REMOTE_SIGNER_CONNECT_TIMEOUT)
+            .timeout(REMOTE_SIGNER_REQUEST_TIMEOUT);
+        if endpoint.uri().scheme_str() == Some("https") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new().with_native_roots())?;
+        }
+        let mut client = RemoteSignerClient::new(endpoint.connect().await?);
+        let address = client.get_address(GetAddressRequest {}).await?.into_inner();
+        let address = Address::try_from(address.address.as_slice())?;
+        Ok(Self {
+            client,
+            address,
+            chain_id: None,
+        })
+    }
+
+    /// Signs with the service, checking that the signature is from the address it announced.
+    async fn sign_hash(&self, hash: &B256) -> alloy::signers::Result<PrimitiveSignature> {
+        let response = self
+            .client
+            .clone()
+            .sign_hash(SignHashRequest {
+                hash: hash.to_vec(),
+            })
+            .await
+            .map_err(Error::other)?
+            .into_inner();
+        let signature =
+            PrimitiveSignature::try_from(response.signature.as_slice()).map_err(Error::other)?;
+        let signer = signature.recover_address_from_prehash(hash)?;
+        if signer != self.address {
+            return Err(Error::other(format!(
+                "remote signature is from {}, expected {}",
+                signer, self.address
+            )));
+        }
+        Ok(signature)
+    }
+}
+
+/// `RemoteSigner` gRPC service with a local key, a stand-in for a KMS or an HSM in tests and development.
+pub struct LocalRemoteSigner {
+    signer: PrivateKeySigner,
+}
+
+impl LocalRemoteSigner {
+    pub fn new(signer: PrivateKeySigner) -> Self {
+        Self { signer }
+    }
+
+    pub fn into_service(self) -> remote_signer_server::RemoteSignerServer<Self> {
+        remote_signer_server::RemoteSignerServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl remote_signer_server::RemoteSigner for LocalRemoteSigner {
+    async fn get_address(
+        &self,
+        _request: tonic::Request<GetAddressRequest>,
+    ) -> Result<tonic::Response<GetAddressResponse>, tonic::Status> {
+        Ok(tonic::Response::new(GetAddressResponse {
+            address: self.signer.address().to_vec(),
+        }))
+    }
+
+    async fn sign_hash(
+        &self,
+        request: tonic::Request<SignHashRequest>,
+    ) -> Result<tonic::Response<SignHashResponse>, tonic::Status> {
+        let hash = B256::try_from(request.into_inner().hash.as_slice())
+            .map_err(|_| tonic::Status::invalid_argument("hash must be 32 bytes"))?;
+        let signature = self
+            .signer
+            .sign_hash(&hash)
+            .await
+            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        Ok(tonic::Response::new(SignHashResponse {
+            signature: signature.as_bytes().to_vec(),
+        }))
+    }
+}
@@ -0,0 +1,146 @@
+use alloy::providers::{Provider, ProviderBuilder, WsConnect};
+use alloy::rpc::types::Log;
+use alloy_sol_types::SolEventInterface;
+
+use crate::contracts::{AclContract, TfheContract};
+use crate::database::dead_letter::EventError;
+use crate::database::host_contracts::{ContractChange, HostContract};
+use crate::database::tfhe_event_propagate::Database;
+
+use super::{cli_contract_addresses, registry_contract_addresses, Args};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct DeadLettersArgs {
+    #[command(subcommand)]
+    pub action: DeadLettersAction,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum DeadLettersAction {
+    /// Prints the dead letters not yet replayed
+    List {
+        #[arg(long, default_value = "100")]
+        limit: i64,
+    },
+    /// Decodes again the dead letters with the current ABIs and propagates
+    /// them, the failing ones are kept with their new error
+    Replay {
+        #[arg(long, default_value = "100")]
+        limit: i64,
+    },
+}
+
+pub async fn dead_letters(args: &Args, dead_letters_args: &DeadLettersArgs) {
+    let Some(coprocessor_api_key) = args.coprocessor_api_key else {
+        panic!("A Coprocessor API key is required to access the database");
+    };
+    let provider = ProviderBuilder::new()
+        .on_ws(WsConnect::new(&args.url))
+        .await
+        .expect("Cannot connect to host chain");
+    let chain_id = provider
+        .get_chain_id()
+        .await
+        .expect("Cannot retrieve chain id");
+    let mut db =
+        Database::new(&args.database_url, &coprocessor_api_key, chain_id).await;
+    match dead_letters_args.action {
+        DeadLettersAction::List { limit } => {
+            let dead_letters = db
+                .pending_dead_letters(limit)
+                .await
+                .expect("Cannot read dead letters");
+            for dead_letter in &dead_letters {
+                println!(
+                    "#{} block {:?} log {:?} contract {} replayed {} times, {}: {}",
+                    dead_letter.id,
+                    dead_letter.block_number,
+                    dead_letter.log_index,
+                    dead_letter.contract_address,
+                    dead_letter.replay_count,
+                    dead_letter.error_kind,
+                    dead_letter.error
+                );
+            }
+            eprintln!("{} dead letters pending", dead_letters.len());
+        }
+        DeadLettersAction::Replay { limit } => {
+            let (replayed, failed) = replay_dead_letters(args, &mut db, limit)
+                .await
+                .expect("Cannot replay dead letters");
+            eprintln!(
+                "{replayed} dead letters replayed, {failed} still failing"
+            );
+        }
+    }
+}
+
+/// Propagates again the pending dead letters, returns the number of
+/// replayed and still failing ones
+pub async fn replay_dead_letters(
+    args: &Args,
+    db: &mut Database,
+    limit: i64,
+) -> Result<(u64, u64), sqlx::Error> {
+    let contract_addresses =
+        registry_contract_addresses(db, cli_contract_addresses(args)).await;
+    let mut replayed = 0;
+    let mut failed = 0;
+    for dead_letter in db.pending_dead_letters(limit).await? {
+        let res = match dead_letter.to_log() {
+            Some(log) => {
+                let emitter = contract_addresses.contract_at(&log.address());
+                propagate_log(db, &log, emitter).await
+            }
+            None => Err(EventError::Decode("invalid stored log".to_owned())),
+        };
+        match res {
+            Ok(()) => {
+                replayed += 1;
+                db.mark_dead_letter_replayed(dead_letter.id, None).await?;
+            }
+            Err(err) => {
+                failed += 1;
+                eprintln!(
+                    "Dead letter #{} still failing: {err}",
+                    dead_letter.id
+                );
+                db.mark_dead_letter_replayed(dead_letter.id, Some(&err))
+                    .await?;
+            }
+        }
+    }
+    Ok((replayed, failed))
+}
+
+async fn propagate_log(
+    db: &mut Database,
+    log: &Log,
+    emitter: Option<HostContract>,
+) -> Result<(), EventError> {
+    if let Ok(event) =
+        TfheContract::TfheContractEvents::decode_log(&log.inner, true)
+    {
+        if let Some(change) = ContractChange::from_tfhe_event(&event.data) {
+            let contract = emitter.unwrap_or(HostContract::Tfhe);
+            db.insert_host_contract_event(contract, &change, log)
+                .await?;
+        } else {
+            db.insert_tfhe_event(&event).await?;
+        }
+        return Ok(());
+    }
+    if let Ok(event) =
+        AclContract::AclContractEvents::decode_log(&log.inner, true)
+    {
+        if let Some(change) = ContractChange::from_acl_event(&event.data) {
+            let contract = emitter.unwrap_or(HostContract::Acl);
+            db.insert_host_contract_event(contract, &change, log)
+                .await?;
+        } else {
+            db.handle_acl_event(&event).await?;
+        }
+        return Ok(());
+    }
+    Err(EventError::decode(log))
+}
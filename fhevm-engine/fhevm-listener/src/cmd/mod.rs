@@ -1,9 +1,18 @@
+pub mod config;
+pub mod dead_letter;
+pub mod replay;
+
 use alloy_provider::fillers::{BlobGasFiller, ChainIdFiller, FillProvider, GasFiller, JoinFill, NonceFiller};
 use futures_util::stream::StreamExt;
 use sqlx::types::Uuid;
-use std::collections::VecDeque;
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::task::JoinSet;
 
 use alloy::primitives::Address;
 use alloy::providers::{Provider, ProviderBuilder, RootProvider, WsConnect};
@@ -15,7 +24,18 @@ use alloy_sol_types::SolEventInterface;
 use clap::Parser;
 
 use crate::contracts::{AclContract, TfheContract};
+use crate::database::dead_letter::EventError;
+use crate::database::host_contracts::{
+    ContractChange, HostContract, HostContractAddresses, RegistryWatch,
+};
 use crate::database::tfhe_event_propagate::{ChainId, Database};
+use crate::metrics::{run_metrics_server, ChainMetrics};
+use config::Config;
+use dead_letter::DeadLettersArgs;
+use replay::ReplayArgs;
+
+/// Delay before restarting a chain watcher that stopped on a panic
+const CHAIN_RESTART_DELAY: Duration = Duration::from_secs(5);
 
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
@@ -55,6 +75,27 @@ pub struct Args {
 
     #[arg(long, default_value = "5", help = "Initial block time, refined on each block")]
     pub initial_block_time: u64,
+
+    #[arg(long, default_value = "false", help = "Follow contract addresses changes from the host_contracts registry")]
+    pub follow_address_registry: bool,
+
+    #[arg(long, default_value = None, help = "JSON config file to listen several chains in one process, see cmd::config::Config")]
+    pub config_file: Option<PathBuf>,
+
+    #[arg(long, default_value = None, help = "Prometheus metrics server address")]
+    pub metrics_addr: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Re-scans a block range, reports rows missing or mismatched in the
+    /// database and optionally repairs them
+    Replay(ReplayArgs),
+    /// Lists or replays the logs that could not be decoded or inserted
+    DeadLetters(DeadLettersArgs),
 }
 
 type RProvider = FillProvider<
@@ -82,6 +123,11 @@ struct InfiniteLogIter {
     current_event: Option<Log>,
     last_block_event_count: u64,
     last_block_recheck_planned: u64,
+    wakeup: Option<Arc<AtomicBool>>, // raised to get a wakeup on a block timeout
+}
+enum LogOrWakeup {
+    Log(Log),
+    Wakeup, // no log before the block timeout while the wakeup flag is raised
 }
 enum LogOrBlockTimeout {
     Log(Option<Log>),
@@ -90,13 +136,7 @@ enum LogOrBlockTimeout {
 
 impl InfiniteLogIter {
     fn new(args: &Args) -> Self {
-        let mut contract_addresses = vec![];
-        if let Some(acl_contract_address) = &args.acl_contract_address {
-            contract_addresses.push(Address::from_str(acl_contract_address).unwrap());
-        };
-        if let Some(tfhe_contract_address) = &args.tfhe_contract_address {
-            contract_addresses.push(Address::from_str(tfhe_contract_address).unwrap());
-        };
+        let contract_addresses = cli_contract_addresses(args).to_vec();
         Self {
             url: args.url.clone(),
             block_time: args.catchup_margin,
@@ -113,6 +153,7 @@ impl InfiniteLogIter {
             current_event: None,
             last_block_event_count: 0,
             last_block_recheck_planned: 0,
+            wakeup: None,
         }
     }
 
@@ -263,7 +304,7 @@ impl InfiniteLogIter {
         }
     }
 
-    async fn next(&mut self) -> Option<Log> {
+    async fn next(&mut self) -> Option<LogOrWakeup> {
         let mut not_initialized = true;
         self.prev_event = self.current_event.take();
         while self.current_event.is_none() {
@@ -308,6 +349,11 @@ impl InfiniteLogIter {
                 }
                 LogOrBlockTimeout::BlockTimeout => {
                     self.recheck_prev_block().await;
+                    if self.wakeup.as_ref().is_some_and(|wakeup| wakeup.load(Ordering::SeqCst)) {
+                        // as if not called, the next log is compared to the last one
+                        self.current_event = self.prev_event.take();
+                        return Some(LogOrWakeup::Wakeup);
+                    }
                     continue;
                 }
             }
@@ -320,7 +366,21 @@ impl InfiniteLogIter {
             self.last_valid_block = Some(block_number.max(self.last_valid_block.unwrap_or_default()) - 1);
         }
         self.last_block_event_count += 1;
-        return self.current_event.clone();
+        return self.current_event.clone().map(LogOrWakeup::Log);
+    }
+
+    /// Changes the listened contracts, the stream is recreated from the last valid block
+    fn set_contract_addresses(&mut self, contract_addresses: Vec<Address>) {
+        if self.contract_addresses == contract_addresses {
+            return;
+        }
+        eprintln!(
+            "Contracts change from {:?} to {:?}",
+            &self.contract_addresses, &contract_addresses
+        );
+        self.contract_addresses = contract_addresses;
+        self.stream = None;
+        self.catchup_logs.clear();
     }
 
     fn is_first_of_block(&self) -> bool {
@@ -343,7 +403,182 @@ impl InfiniteLogIter {
     }
 }
 
+fn cli_contract_addresses(args: &Args) -> HostContractAddresses {
+    let parse = |address: &Option<String>| {
+        address.as_ref().map(|a| Address::from_str(a).unwrap())
+    };
+    HostContractAddresses {
+        acl: parse(&args.acl_contract_address),
+        tfhe: parse(&args.tfhe_contract_address),
+    }
+}
+
+async fn registry_contract_addresses(
+    db: &mut Database,
+    fallback: HostContractAddresses,
+) -> HostContractAddresses {
+    match db.read_host_contract_addresses().await {
+        Ok(registry) => HostContractAddresses {
+            acl: registry.acl.or(fallback.acl),
+            tfhe: registry.tfhe.or(fallback.tfhe),
+        },
+        Err(err) => {
+            eprintln!("Cannot read contract addresses registry: {err}");
+            fallback
+        }
+    }
+}
+
+/// Re-reads the registry if a change was notified, the next logs come from the
+/// new contract addresses
+async fn follow_registry(
+    registry_watch: &Option<RegistryWatch>,
+    db: &mut Option<Database>,
+    contract_addresses: &mut HostContractAddresses,
+    log_iter: &mut InfiniteLogIter,
+) {
+    if !registry_watch.as_ref().is_some_and(|watch| watch.take_change()) {
+        return;
+    }
+    let Some(ref mut db) = db else {
+        return;
+    };
+    let registry = registry_contract_addresses(db, *contract_addresses).await;
+    if registry != *contract_addresses {
+        eprintln!("ALERT: contract addresses changed in registry {registry:?}");
+        *contract_addresses = registry;
+        log_iter.set_contract_addresses(contract_addresses.to_vec());
+    }
+}
+
+async fn report_host_contract_change(
+    db: &mut Option<Database>,
+    contract: HostContract,
+    change: &ContractChange,
+    log: &Log,
+) {
+    let name = contract.name();
+    if change.is_upgrade() {
+        eprintln!(
+            "ALERT: {name} contract {} upgraded to implementation {}, events ABI may have changed",
+            log.address(),
+            change.value
+        );
+    } else if change.is_unknown() {
+        eprintln!(
+            "ALERT: {name} contract {} emitted an unknown event {} in block {:?}, events ABI may have changed",
+            log.address(),
+            change.value,
+            log.block_number
+        );
+    } else {
+        eprintln!("{name} contract {} {}: {}", log.address(), change.event_name, change.value);
+    }
+    if let Some(db) = db {
+        if let Err(err) = db.insert_host_contract_event(contract, change, log).await {
+            eprintln!("Error inserting {name} contract change: {err}");
+        }
+    }
+}
+
+async fn store_dead_letter(
+    db: &mut Database,
+    metrics: &ChainMetrics,
+    log: &Log,
+    error: EventError,
+) {
+    eprintln!(
+        "Storing log {:?} of block {:?} as dead letter: {error}",
+        log.log_index, log.block_number
+    );
+    metrics.dead_letter(error.kind());
+    if let Err(err) = db.insert_dead_letter(log, &error).await {
+        eprintln!("ALERT: cannot store dead letter, event is lost: {err}");
+    }
+}
+
 pub async fn main(args: Args) {
+    match &args.command {
+        Some(Command::Replay(replay_args)) => {
+            let report = replay::replay(&args, replay_args).await;
+            if !report.is_consistent() && !replay_args.repair {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::DeadLetters(dead_letters_args)) => {
+            dead_letter::dead_letters(&args, dead_letters_args).await;
+            return;
+        }
+        None => (),
+    }
+    if let Some(metrics_addr) = &args.metrics_addr {
+        tokio::spawn(run_metrics_server(metrics_addr.clone()));
+    }
+    let Some(config_file) = &args.config_file else {
+        run_chain(args, None).await;
+        return;
+    };
+    let config = match Config::from_file(config_file) {
+        Ok(config) => config,
+        Err(err) => panic!("{err}"),
+    };
+    let database_url =
+        config.database_url.clone().unwrap_or(args.database_url.clone());
+    if database_url.is_empty() {
+        panic!("A database url is required to listen several chains");
+    }
+    let pool_size = config
+        .database_pool_size
+        .unwrap_or(2 + 2 * config.chains.len() as u32);
+    let pool = Database::new_pool(&database_url, pool_size).await;
+    let mut chains = JoinSet::new();
+    let mut chains_args = HashMap::new();
+    for chain in &config.chains {
+        let chain_args = match chain.to_args(&args, &database_url) {
+            Ok(chain_args) => chain_args,
+            Err(err) => panic!("{err}"),
+        };
+        eprintln!("Starting chain watcher on {}", chain_args.url);
+        let task =
+            chains.spawn(run_chain(chain_args.clone(), Some(pool.clone())));
+        chains_args.insert(task.id(), chain_args);
+    }
+    // a failing chain watcher does not stop the others, it is restarted
+    while let Some(res) = chains.join_next_with_id().await {
+        let id = match &res {
+            Ok((id, ())) => *id,
+            Err(err) => err.id(),
+        };
+        let Some(mut chain_args) = chains_args.remove(&id) else {
+            continue;
+        };
+        let err = match res {
+            // reached its end block
+            Ok(_) => continue,
+            Err(err) => err,
+        };
+        eprintln!(
+            "ALERT: chain watcher on {} stopped: {err}, restarting in {} secs",
+            chain_args.url,
+            CHAIN_RESTART_DELAY.as_secs()
+        );
+        // resumes from the last valid block
+        chain_args.start_at_block = None;
+        let pool = pool.clone();
+        let task = chains.spawn({
+            let chain_args = chain_args.clone();
+            async move {
+                tokio::time::sleep(CHAIN_RESTART_DELAY).await;
+                run_chain(chain_args, Some(pool)).await
+            }
+        });
+        chains_args.insert(task.id(), chain_args);
+    }
+}
+
+/// Listens one chain, using the given shared pool if any
+pub async fn run_chain(args: Args, pool: Option<PgPool>) {
     if let Some(acl_contract_address) = &args.acl_contract_address {
         if let Err(err) = Address::from_str(acl_contract_address) {
             panic!("Invalid acl contract address: {err}");
@@ -361,12 +596,25 @@ pub async fn main(args: Args) {
 
     let mut db = if !args.database_url.is_empty() {
         if let Some(coprocessor_api_key) = args.coprocessor_api_key {
-            let mut db = Database::new(
-                &args.database_url,
-                &coprocessor_api_key,
-                chain_id,
-            )
-            .await;
+            let mut db = match pool {
+                Some(pool) => {
+                    Database::new_with_pool(
+                        &args.database_url,
+                        pool,
+                        &coprocessor_api_key,
+                        chain_id,
+                    )
+                    .await
+                }
+                None => {
+                    Database::new(
+                        &args.database_url,
+                        &coprocessor_api_key,
+                        chain_id,
+                    )
+                    .await
+                }
+            };
             if log_iter.start_at_block.is_none() {
                 log_iter.start_at_block = db
                     .read_last_valid_block()
@@ -381,10 +629,58 @@ pub async fn main(args: Args) {
         None
     };
 
+    let mut contract_addresses = cli_contract_addresses(&args);
+    let mut registry_watch = None;
+    if let Some(ref mut db) = db {
+        if args.follow_address_registry {
+            // watched first so that no change after the first read is missed
+            match db.watch_host_contract_addresses().await {
+                Ok(watch) => {
+                    // to switch even if the old contracts emit no more logs
+                    log_iter.wakeup = Some(watch.flag());
+                    registry_watch = Some(watch);
+                }
+                Err(err) => panic!("Cannot watch contract addresses registry: {err}"),
+            }
+        }
+        let overwrite = !args.follow_address_registry;
+        for (contract, address) in [
+            (HostContract::Acl, contract_addresses.acl),
+            (HostContract::Tfhe, contract_addresses.tfhe),
+        ] {
+            let Some(address) = address else {
+                continue;
+            };
+            if let Err(err) = db.register_host_contract(contract, &address, overwrite).await {
+                eprintln!("Cannot register {} contract: {err}", contract.name());
+            }
+        }
+        if args.follow_address_registry {
+            contract_addresses = registry_contract_addresses(db, contract_addresses).await;
+            log_iter.set_contract_addresses(contract_addresses.to_vec());
+        }
+    } else if args.follow_address_registry {
+        panic!("Following the address registry requires a database");
+    }
+
     log_iter.new_log_stream(true).await;
 
+    let metrics = ChainMetrics::new(chain_id);
     let mut block_error_event_fthe = 0;
-    while let Some(log) = log_iter.next().await {
+    while let Some(next) = log_iter.next().await {
+        let log = match next {
+            LogOrWakeup::Log(log) => log,
+            LogOrWakeup::Wakeup => {
+                follow_registry(
+                    &registry_watch,
+                    &mut db,
+                    &mut contract_addresses,
+                    &mut log_iter,
+                )
+                .await;
+                continue;
+            }
+        };
         if log_iter.is_first_of_block() {
             log_iter.reestimated_block_time();
             if let Some(block_number) = log.block_number {
@@ -403,38 +699,87 @@ pub async fn main(args: Args) {
                     block_error_event_fthe = 0;
                 }
                 eprintln!("\n--------------------");
-                eprintln!("Block {block_number}");
+                eprintln!("Chain {chain_id} Block {block_number}");
+                metrics.block(block_number);
             }
+            // the current log is still processed below
+            follow_registry(
+                &registry_watch,
+                &mut db,
+                &mut contract_addresses,
+                &mut log_iter,
+            )
+            .await;
         };
         if block_error_event_fthe > 0 {
             eprintln!("Errors in block {block_error_event_fthe}");
         }
-        if !args.ignore_tfhe_events {
-            if let Ok(event) =
-                TfheContract::TfheContractEvents::decode_log(&log.inner, true)
-            {
+        let emitter = contract_addresses.contract_at(&log.address());
+        if let Ok(event) =
+            TfheContract::TfheContractEvents::decode_log(&log.inner, true)
+        {
+            // upgrade and ownership events are common to both contracts
+            if let Some(change) = ContractChange::from_tfhe_event(&event.data) {
+                let contract = emitter.unwrap_or(HostContract::Tfhe);
+                metrics.event("host_contract");
+                report_host_contract_change(&mut db, contract, &change, &log)
+                    .await;
+                continue;
+            }
+            if !args.ignore_tfhe_events {
                 // TODO: filter on contract address if known
                 println!("TFHE {event:#?}");
+                metrics.event("tfhe");
                 if let Some(ref mut db) = db {
                     let res = db.insert_tfhe_event(&event).await;
                     if let Err(err) = res {
                         block_error_event_fthe += 1;
+                        metrics.event_error();
                         eprintln!("Error inserting tfhe event: {err}");
+                        store_dead_letter(db, &metrics, &log, err.into()).await;
                     }
                 }
-                continue;
             }
+            continue;
         }
-        if !args.ignore_acl_events {
-            if let Ok(event) =
-                AclContract::AclContractEvents::decode_log(&log.inner, true)
-            {
+        if let Ok(event) =
+            AclContract::AclContractEvents::decode_log(&log.inner, true)
+        {
+            if let Some(change) = ContractChange::from_acl_event(&event.data) {
+                let contract = emitter.unwrap_or(HostContract::Acl);
+                metrics.event("host_contract");
+                report_host_contract_change(&mut db, contract, &change, &log)
+                    .await;
+                continue;
+            }
+            if !args.ignore_acl_events {
                 println!("ACL {event:#?}");
+                metrics.event("acl");
                 if let Some(ref mut db) = db {
-                    let _ = db.handle_acl_event(&event).await;
+                    if let Err(err) = db.handle_acl_event(&event).await {
+                        metrics.event_error();
+                        eprintln!("Error handling acl event: {err}");
+                        store_dead_letter(db, &metrics, &log, err.into()).await;
+                    }
                 }
-                continue;
             }
+            continue;
+        }
+        metrics.event("unknown");
+        let Some(contract) = emitter else {
+            // e.g. any contract when listening to all logs
+            eprintln!(
+                "Ignoring undecodable event of unknown contract {} in block {:?}",
+                log.address(),
+                log.block_number
+            );
+            continue;
+        };
+        let change = ContractChange::unknown_event(&log);
+        report_host_contract_change(&mut db, contract, &change, &log).await;
+        // kept to be replayed once the ABI is fixed
+        if let Some(ref mut db) = db {
+            store_dead_letter(db, &metrics, &log, EventError::decode(&log)).await;
         }
     }
 }
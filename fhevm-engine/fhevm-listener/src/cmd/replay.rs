@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use alloy::providers::{Provider, ProviderBuilder, WsConnect};
+use alloy::rpc::types::{Filter, Log};
+use alloy_sol_types::SolEventInterface;
+use fhevm_engine_common::utils::compact_hex;
+use sqlx::Error as SqlxError;
+
+use crate::contracts::{AclContract, TfheContract};
+use crate::database::tfhe_event_propagate::{
+    acl_event_allowed_handles, tfhe_event_computation, Database,
+};
+
+use super::{cli_contract_addresses, Args};
+
+const GET_LOGS_RETRIES: u32 = 10;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReplayArgs {
+    #[arg(long, help = "First block to replay")]
+    pub from_block: u64,
+
+    #[arg(long, default_value = None, help = "Last block to replay (inclusive), defaults to the last block")]
+    pub to_block: Option<u64>,
+
+    #[arg(long, default_value = "500", help = "Number of blocks per log request")]
+    pub blocks_per_request: u64,
+
+    #[arg(long, default_value = "false", help = "Insert missing rows and overwrite mismatched ones")]
+    pub repair: bool,
+}
+
+/// Differences between the events of the replayed blocks and the database
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub tfhe_events: u64,
+    pub acl_events: u64,
+    pub missing_computations: u64,
+    pub mismatched_computations: u64,
+    pub missing_allowed_handles: u64,
+    pub mismatched_allowed_handles: u64,
+    pub missing_pbs_computations: u64,
+    pub repaired: u64,
+}
+
+impl ReplayReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing_computations == 0
+            && self.mismatched_computations == 0
+            && self.missing_allowed_handles == 0
+            && self.mismatched_allowed_handles == 0
+            && self.missing_pbs_computations == 0
+    }
+}
+
+/// Re-scans a block range and compares what the listener would insert with
+/// the computations, allowed_handles and pbs_computations tables
+pub async fn replay(args: &Args, replay_args: &ReplayArgs) -> ReplayReport {
+    let Some(coprocessor_api_key) = args.coprocessor_api_key else {
+        panic!("A Coprocessor API key is required to access the database");
+    };
+    let provider = ProviderBuilder::new()
+        .on_ws(WsConnect::new(&args.url))
+        .await
+        .expect("Cannot connect to host chain");
+    let chain_id = provider
+        .get_chain_id()
+        .await
+        .expect("Cannot retrieve chain id");
+    let mut db =
+        Database::new(&args.database_url, &coprocessor_api_key, chain_id)
+            .await;
+    let to_block = match replay_args.to_block {
+        Some(to_block) => to_block,
+        None => provider
+            .get_block_number()
+            .await
+            .expect("Cannot retrieve last block"),
+    };
+    let contract_addresses = cli_contract_addresses(args).to_vec();
+    let blocks_per_request = replay_args.blocks_per_request.max(1);
+    eprintln!(
+        "Replaying chain {chain_id} from block {} to {to_block}, repair: {}",
+        replay_args.from_block, replay_args.repair
+    );
+
+    let mut report = ReplayReport::default();
+    let mut from_block = replay_args.from_block;
+    while from_block <= to_block {
+        let end_block = (from_block + blocks_per_request - 1).min(to_block);
+        let mut filter = Filter::new().from_block(from_block).to_block(end_block); // inclusive
+        if !contract_addresses.is_empty() {
+            filter = filter.address(contract_addresses.clone());
+        }
+        let mut retry = GET_LOGS_RETRIES;
+        let logs = loop {
+            match provider.get_logs(&filter).await {
+                Ok(logs) => break logs,
+                Err(err) if retry > 0 => {
+                    eprintln!("Cannot get logs of blocks {from_block} to {end_block}: {err}, retrying");
+                    retry -= 1;
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                Err(err) => {
+                    panic!("Cannot get logs of blocks {from_block} to {end_block}: {err}")
+                }
+            }
+        };
+        for log in &logs {
+            if let Err(err) =
+                replay_log(args, replay_args.repair, &mut db, log, &mut report).await
+            {
+                panic!("Database error while replaying {log:?}: {err}");
+            }
+        }
+        eprintln!("Replayed blocks {from_block} to {end_block}: {report:?}");
+        from_block = end_block + 1;
+    }
+    if report.is_consistent() {
+        eprintln!("No difference found");
+    } else {
+        eprintln!("Differences found: {report:?}");
+    }
+    report
+}
+
+async fn replay_log(
+    args: &Args,
+    repair: bool,
+    db: &mut Database,
+    log: &Log,
+    report: &mut ReplayReport,
+) -> Result<(), SqlxError> {
+    let block = log.block_number.unwrap_or_default();
+    if let Ok(event) =
+        TfheContract::TfheContractEvents::decode_log(&log.inner, true)
+    {
+        if args.ignore_tfhe_events {
+            return Ok(());
+        }
+        let Some(expected) = tfhe_event_computation(&event) else {
+            return Ok(());
+        };
+        report.tfhe_events += 1;
+        let handle = compact_hex(&expected.output_handle);
+        match db.find_computation(&expected.output_handle).await? {
+            None => {
+                println!("Block {block}: missing computation {handle}");
+                report.missing_computations += 1;
+                if repair {
+                    let tenant_id = db.tenant_id;
+                    db.insert_computation(tenant_id, &expected).await?;
+                    report.repaired += 1;
+                }
+            }
+            Some(stored) if stored != expected => {
+                println!(
+                    "Block {block}: mismatched computation {handle}, stored {stored:?}, expected {expected:?}"
+                );
+                report.mismatched_computations += 1;
+                if repair {
+                    db.update_computation(&expected).await?;
+                    report.repaired += 1;
+                }
+            }
+            Some(_) => (),
+        }
+        return Ok(());
+    }
+    if let Ok(event) =
+        AclContract::AclContractEvents::decode_log(&log.inner, true)
+    {
+        if args.ignore_acl_events {
+            return Ok(());
+        }
+        let allowed_handles = acl_event_allowed_handles(&event.data);
+        if allowed_handles.is_empty() {
+            return Ok(());
+        }
+        report.acl_events += 1;
+        for expected in &allowed_handles {
+            let handle = compact_hex(&expected.handle);
+            match db.find_allowed_handle_event_type(expected).await? {
+                None => {
+                    println!(
+                        "Block {block}: missing allowed handle {handle} for {:?}",
+                        expected.account_address
+                    );
+                    report.missing_allowed_handles += 1;
+                    if repair {
+                        db.insert_allowed_handle(
+                            expected.handle.clone(),
+                            expected.account_address.clone(),
+                            expected.event_type,
+                        )
+                        .await?;
+                        report.repaired += 1;
+                    }
+                }
+                Some(event_type) if event_type != expected.event_type as i16 => {
+                    println!(
+                        "Block {block}: mismatched allowed handle {handle}, stored event type {event_type}, expected {:?}",
+                        expected.event_type
+                    );
+                    report.mismatched_allowed_handles += 1;
+                    if repair {
+                        db.update_allowed_handle_event_type(expected).await?;
+                        report.repaired += 1;
+                    }
+                }
+                Some(_) => (),
+            }
+            if !db.has_pbs_computation(&expected.handle).await? {
+                println!("Block {block}: missing pbs computation {handle}");
+                report.missing_pbs_computations += 1;
+                if repair {
+                    db.insert_pbs_computations(
+                        &vec![expected.handle.clone()],
+                        None,
+                    )
+                    .await?;
+                    report.repaired += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
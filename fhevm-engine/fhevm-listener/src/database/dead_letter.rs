@@ -0,0 +1,269 @@
+use std::fmt;
+
+use alloy_primitives::{keccak256, Address, Bytes, Log as PrimitiveLog, B256};
+use alloy_rpc_types::Log;
+use alloy_sol_types::SolEventInterface;
+use sqlx::Error as SqlxError;
+
+use crate::contracts::{AclContract, TfheContract};
+use crate::database::tfhe_event_propagate::{retry_on_sqlx_error, Database};
+
+/// Why a log could not be propagated
+#[derive(Debug)]
+pub enum EventError {
+    /// The log matches none of the contracts ABIs
+    Decode(String),
+    /// The database rejected the event with a non-retryable error
+    Database(SqlxError),
+}
+
+impl EventError {
+    /// The decoding errors of a log against all known ABIs
+    pub fn decode(log: &Log) -> Self {
+        let tfhe_err =
+            TfheContract::TfheContractEvents::decode_log(&log.inner, true)
+                .err();
+        let acl_err =
+            AclContract::AclContractEvents::decode_log(&log.inner, true).err();
+        EventError::Decode(format!(
+            "tfhe: {}, acl: {}",
+            tfhe_err.map_or("ok".to_owned(), |e| e.to_string()),
+            acl_err.map_or("ok".to_owned(), |e| e.to_string()),
+        ))
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EventError::Decode(_) => "decode",
+            EventError::Database(_) => "database",
+        }
+    }
+}
+
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventError::Decode(err) => write!(f, "decode error: {err}"),
+            EventError::Database(err) => write!(f, "database error: {err}"),
+        }
+    }
+}
+
+impl From<SqlxError> for EventError {
+    fn from(err: SqlxError) -> Self {
+        EventError::Database(err)
+    }
+}
+
+/// A stored log that could not be propagated
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub contract_address: String,
+    pub topics: Vec<Vec<u8>>,
+    pub data: Vec<u8>,
+    pub block_number: Option<i64>,
+    pub block_hash: Option<Vec<u8>>,
+    pub transaction_hash: Option<Vec<u8>>,
+    pub log_index: Option<i64>,
+    pub error_kind: String,
+    pub error: String,
+    pub replay_count: i32,
+}
+
+impl DeadLetter {
+    /// Rebuilds the original log, None if the stored data is invalid
+    pub fn to_log(&self) -> Option<Log> {
+        let address: Address = self.contract_address.parse().ok()?;
+        let topics = self
+            .topics
+            .iter()
+            .map(|t| B256::try_from(t.as_slice()).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let inner = PrimitiveLog::new(
+            address,
+            topics,
+            Bytes::copy_from_slice(&self.data),
+        )?;
+        let to_b256 = |bytes: &Option<Vec<u8>>| {
+            bytes
+                .as_ref()
+                .and_then(|b| B256::try_from(b.as_slice()).ok())
+        };
+        Some(Log {
+            inner,
+            block_hash: to_b256(&self.block_hash),
+            block_number: self.block_number.map(|n| n as u64),
+            block_timestamp: None,
+            transaction_hash: to_b256(&self.transaction_hash),
+            transaction_index: None,
+            log_index: self.log_index.map(|n| n as u64),
+            removed: false,
+        })
+    }
+}
+
+/// Identifies a log, including the ones without block hash or log index,
+/// which must neither be merged nor stored twice
+pub fn log_key(log: &Log) -> Vec<u8> {
+    let mut bytes = log.address().to_vec();
+    for hash in [log.block_hash, log.transaction_hash] {
+        match hash {
+            Some(hash) => {
+                bytes.push(1);
+                bytes.extend_from_slice(hash.as_slice());
+            }
+            None => bytes.push(0),
+        }
+    }
+    match log.log_index {
+        Some(log_index) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&log_index.to_be_bytes());
+        }
+        None => bytes.push(0),
+    }
+    bytes.push(log.topics().len() as u8);
+    for topic in log.topics() {
+        bytes.extend_from_slice(topic.as_slice());
+    }
+    bytes.extend_from_slice(&log.data().data);
+    keccak256(bytes).to_vec()
+}
+
+impl Database {
+    /// Stores a log that could not be propagated, with its raw content to
+    /// replay it later
+    pub async fn insert_dead_letter(
+        &mut self,
+        log: &Log,
+        error: &EventError,
+    ) -> Result<(), SqlxError> {
+        let topics =
+            log.topics().iter().map(|t| t.to_vec()).collect::<Vec<_>>();
+        let data = log.data().data.to_vec();
+        let block_hash = log.block_hash.map(|h| h.to_vec());
+        let transaction_hash = log.transaction_hash.map(|h| h.to_vec());
+        let log_key = log_key(log);
+        let error_message = error.to_string();
+        let chain_id = self.chain_id as i64;
+        let tenant_id = self.tenant_id;
+        let query = || {
+            sqlx::query!(
+                r#"
+                INSERT INTO listener_dead_letters (
+                    chain_id,
+                    tenant_id,
+                    contract_address,
+                    topics,
+                    data,
+                    block_number,
+                    block_hash,
+                    transaction_hash,
+                    log_index,
+                    log_key,
+                    error_kind,
+                    error
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                ON CONFLICT (chain_id, log_key) DO UPDATE
+                SET error_kind = EXCLUDED.error_kind,
+                    error = EXCLUDED.error
+                "#,
+                chain_id,
+                tenant_id,
+                log.address().to_string(),
+                &topics,
+                data,
+                log.block_number.map(|n| n as i64),
+                block_hash,
+                transaction_hash,
+                log.log_index.map(|n| n as i64),
+                &log_key,
+                error.kind(),
+                error_message,
+            )
+        };
+        loop {
+            match query().execute(&self.pool).await {
+                Ok(_) => return Ok(()),
+                Err(err) if retry_on_sqlx_error(&err) => {
+                    eprintln!(
+                        "\tDatabase I/O error: {}, will retry indefinitely",
+                        err
+                    );
+                    self.reconnect().await;
+                }
+                Err(sqlx_err) => {
+                    return Err(sqlx_err);
+                }
+            }
+        }
+    }
+
+    /// Lists the dead letters not yet replayed, oldest first
+    pub async fn pending_dead_letters(
+        &mut self,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>, SqlxError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, contract_address, topics, data, block_number, block_hash,
+                   transaction_hash, log_index, error_kind, error, replay_count
+            FROM listener_dead_letters
+            WHERE chain_id = $1 AND tenant_id = $2 AND replayed_at IS NULL
+            ORDER BY id
+            LIMIT $3
+            "#,
+            self.chain_id as i64,
+            self.tenant_id,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| DeadLetter {
+                id: row.id,
+                contract_address: row.contract_address,
+                topics: row.topics,
+                data: row.data,
+                block_number: row.block_number,
+                block_hash: row.block_hash,
+                transaction_hash: row.transaction_hash,
+                log_index: row.log_index,
+                error_kind: row.error_kind,
+                error: row.error,
+                replay_count: row.replay_count,
+            })
+            .collect())
+    }
+
+    /// Records the result of a replay attempt
+    pub async fn mark_dead_letter_replayed(
+        &mut self,
+        id: i64,
+        error: Option<&EventError>,
+    ) -> Result<(), SqlxError> {
+        let (error_kind, error) = match error {
+            Some(error) => (Some(error.kind()), Some(error.to_string())),
+            None => (None, None),
+        };
+        sqlx::query!(
+            r#"
+            UPDATE listener_dead_letters
+            SET replay_count = replay_count + 1,
+                replayed_at = CASE WHEN $2::TEXT IS NULL THEN NOW() ELSE NULL END,
+                error_kind = COALESCE($2, error_kind),
+                error = COALESCE($3, error)
+            WHERE id = $1
+            "#,
+            id,
+            error_kind,
+            error,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use alloy_primitives::Address;
+use sqlx::postgres::PgListener;
+use sqlx::Error as SqlxError;
+use tokio::task::JoinHandle;
+
+use crate::contracts::AclContract::AclContractEvents;
+use crate::contracts::TfheContract::TfheContractEvents;
+use crate::database::tfhe_event_propagate::{retry_on_sqlx_error, Database};
+
+pub const UNKNOWN_EVENT: &str = "UnknownEvent";
+
+/// Notified on an address change in the registry, with the chain id as payload
+pub const HOST_CONTRACTS_CHANNEL: &str = "host_contracts_changed";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostContract {
+    Acl,
+    Tfhe,
+}
+
+impl HostContract {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HostContract::Acl => "ACL",
+            HostContract::Tfhe => "FHEVMExecutor",
+        }
+    }
+}
+
+/// Addresses of the host contracts, as given on the command line or read
+/// from the host_contracts registry
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HostContractAddresses {
+    pub acl: Option<Address>,
+    pub tfhe: Option<Address>,
+}
+
+impl HostContractAddresses {
+    pub fn to_vec(&self) -> Vec<Address> {
+        self.acl.iter().chain(self.tfhe.iter()).copied().collect()
+    }
+
+    /// The contract emitting from the given address, if known
+    pub fn contract_at(&self, address: &Address) -> Option<HostContract> {
+        if self.acl.as_ref() == Some(address) {
+            Some(HostContract::Acl)
+        } else if self.tfhe.as_ref() == Some(address) {
+            Some(HostContract::Tfhe)
+        } else {
+            None
+        }
+    }
+}
+
+/// A change of a host contract, reported by its proxy and ownership events
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractChange {
+    pub event_name: &'static str,
+    pub value: String,
+}
+
+impl ContractChange {
+    pub fn from_acl_event(event: &AclContractEvents) -> Option<Self> {
+        use AclContractEvents as E;
+        let (event_name, value) = match event {
+            E::Upgraded(e) => ("Upgraded", e.implementation.to_string()),
+            E::OwnershipTransferStarted(e) => {
+                ("OwnershipTransferStarted", e.newOwner.to_string())
+            }
+            E::OwnershipTransferred(e) => {
+                ("OwnershipTransferred", e.newOwner.to_string())
+            }
+            E::Initialized(e) => ("Initialized", e.version.to_string()),
+            _ => return None,
+        };
+        Some(Self { event_name, value })
+    }
+
+    pub fn from_tfhe_event(event: &TfheContractEvents) -> Option<Self> {
+        use TfheContractEvents as E;
+        let (event_name, value) = match event {
+            E::Upgraded(e) => ("Upgraded", e.implementation.to_string()),
+            E::OwnershipTransferStarted(e) => {
+                ("OwnershipTransferStarted", e.newOwner.to_string())
+            }
+            E::OwnershipTransferred(e) => {
+                ("OwnershipTransferred", e.newOwner.to_string())
+            }
+            E::Initialized(e) => ("Initialized", e.version.to_string()),
+            _ => return None,
+        };
+        Some(Self { event_name, value })
+    }
+
+    /// An event emitted by a host contract that matches none of the known
+    /// ABIs, e.g. after an upgrade changing the events
+    pub fn unknown_event(log: &alloy_rpc_types::Log) -> Self {
+        let value = match log.topics().first() {
+            Some(topic0) => topic0.to_string(),
+            None => "no topic".to_string(),
+        };
+        Self {
+            event_name: UNKNOWN_EVENT,
+            value,
+        }
+    }
+
+    pub fn is_upgrade(&self) -> bool {
+        self.event_name == "Upgraded"
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.event_name == UNKNOWN_EVENT
+    }
+}
+
+impl Database {
+    /// Records a host contract change in the alert trail and updates the
+    /// registry accordingly
+    pub async fn insert_host_contract_event(
+        &mut self,
+        contract: HostContract,
+        change: &ContractChange,
+        log: &alloy_rpc_types::Log,
+    ) -> Result<(), SqlxError> {
+        let (Some(block_number), Some(block_hash), Some(log_index)) =
+            (log.block_number, log.block_hash, log.log_index)
+        else {
+            eprintln!("Contract change without block information {change:?}");
+            return Ok(());
+        };
+        let chain_id = self.chain_id as i64;
+        let contract_address = log.address().to_string();
+        let transaction_hash = log.transaction_hash.map(|h| h.to_vec());
+        let query = || {
+            sqlx::query!(
+                r#"
+                INSERT INTO host_contract_events (
+                    chain_id,
+                    contract_name,
+                    contract_address,
+                    event_name,
+                    event_value,
+                    block_number,
+                    block_hash,
+                    transaction_hash,
+                    log_index
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (chain_id, block_hash, log_index) DO NOTHING
+                "#,
+                chain_id,
+                contract.name(),
+                contract_address,
+                change.event_name,
+                change.value,
+                block_number as i64,
+                block_hash.to_vec(),
+                transaction_hash,
+                log_index as i64,
+            )
+        };
+        loop {
+            match query().execute(&self.pool).await {
+                Ok(_) => break,
+                Err(err) if retry_on_sqlx_error(&err) => {
+                    eprintln!(
+                        "\tDatabase I/O error: {}, will retry indefinitely",
+                        err
+                    );
+                    self.reconnect().await;
+                }
+                Err(sqlx_err) => {
+                    return Err(sqlx_err);
+                }
+            }
+        }
+        let (implementation, owner) = match change.event_name {
+            "Upgraded" => (Some(change.value.clone()), None),
+            "OwnershipTransferred" => (None, Some(change.value.clone())),
+            _ => return Ok(()),
+        };
+        sqlx::query!(
+            r#"
+            INSERT INTO host_contracts (
+                chain_id,
+                contract_name,
+                contract_address,
+                implementation_address,
+                owner_address
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (chain_id, contract_name) DO UPDATE
+            SET implementation_address = COALESCE(EXCLUDED.implementation_address, host_contracts.implementation_address),
+                owner_address = COALESCE(EXCLUDED.owner_address, host_contracts.owner_address),
+                updated_at = NOW()
+            "#,
+            chain_id,
+            contract.name(),
+            contract_address,
+            implementation,
+            owner,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Writes the contract address in the registry.
+    /// If `overwrite` is false, an already registered address is kept.
+    pub async fn register_host_contract(
+        &mut self,
+        contract: HostContract,
+        address: &Address,
+        overwrite: bool,
+    ) -> Result<(), SqlxError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO host_contracts (chain_id, contract_name, contract_address)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (chain_id, contract_name) DO UPDATE
+            SET contract_address = EXCLUDED.contract_address,
+                updated_at = NOW()
+            WHERE $4 AND host_contracts.contract_address <> EXCLUDED.contract_address
+            "#,
+            self.chain_id as i64,
+            contract.name(),
+            address.to_string(),
+            overwrite,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Reads the contract addresses from the registry
+    pub async fn read_host_contract_addresses(
+        &mut self,
+    ) -> Result<HostContractAddresses, SqlxError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT contract_name, contract_address
+            FROM host_contracts
+            WHERE chain_id = $1
+            "#,
+            self.chain_id as i64,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut addresses = HostContractAddresses::default();
+        for row in rows {
+            let Ok(address) = Address::from_str(&row.contract_address) else {
+                eprintln!(
+                    "Invalid {} address in registry: {}",
+                    row.contract_name, row.contract_address
+                );
+                continue;
+            };
+            if row.contract_name == HostContract::Acl.name() {
+                addresses.acl = Some(address);
+            } else if row.contract_name == HostContract::Tfhe.name() {
+                addresses.tfhe = Some(address);
+            }
+        }
+        Ok(addresses)
+    }
+    /// Watches the registry changes of this chain in the background, until
+    /// the returned watch is dropped.
+    pub async fn watch_host_contract_addresses(
+        &self,
+    ) -> Result<RegistryWatch, SqlxError> {
+        let mut listener = PgListener::connect(&self.url).await?;
+        listener.listen(HOST_CONTRACTS_CHANNEL).await?;
+        let changed = Arc::new(AtomicBool::new(false));
+        let flag = changed.clone();
+        let chain_id = self.chain_id.to_string();
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => {
+                        if notification.payload() == chain_id {
+                            flag.store(true, Ordering::SeqCst);
+                        }
+                    }
+                    // reconnected on next receive
+                    Ok(None) => flag.store(true, Ordering::SeqCst),
+                    Err(err) => {
+                        eprintln!(
+                            "Cannot watch contract addresses registry: {err}"
+                        );
+                        flag.store(true, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+        Ok(RegistryWatch { changed, task })
+    }
+}
+
+/// Background watch of the registry changes of a chain, holding its own
+/// database connection, both stopped when dropped, e.g. on a chain restart
+pub struct RegistryWatch {
+    changed: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+}
+
+impl RegistryWatch {
+    /// Raised on each change, and when notifications may have been lost,
+    /// until reset by `take_change`
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.changed.clone()
+    }
+
+    /// Resets the flag, returns whether it was raised
+    pub fn take_change(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for RegistryWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
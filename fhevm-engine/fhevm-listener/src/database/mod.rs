@@ -1 +1,4 @@
+pub mod dead_letter;
+pub mod host_contracts;
+pub mod replay;
 pub mod tfhe_event_propagate;
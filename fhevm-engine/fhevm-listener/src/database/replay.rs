@@ -0,0 +1,120 @@
+use sqlx::Error as SqlxError;
+
+use crate::database::tfhe_event_propagate::{
+    AllowedHandle, Computation, Database, FheOperation,
+};
+
+impl Database {
+    /// Reads the computation stored for the given output handle
+    pub async fn find_computation(
+        &mut self,
+        output_handle: &[u8],
+    ) -> Result<Option<Computation>, SqlxError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT output_handle, dependencies, fhe_operation, is_scalar
+            FROM computations
+            WHERE tenant_id = $1 AND output_handle = $2
+            "#,
+            self.tenant_id,
+            output_handle,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| Computation {
+            output_handle: row.output_handle,
+            dependencies: row.dependencies,
+            fhe_operation: row.fhe_operation as FheOperation,
+            is_scalar: row.is_scalar,
+        }))
+    }
+
+    /// Overwrites a mismatched computation, it will be computed again
+    pub async fn update_computation(
+        &mut self,
+        computation: &Computation,
+    ) -> Result<(), SqlxError> {
+        sqlx::query!(
+            r#"
+            UPDATE computations
+            SET dependencies = $3,
+                fhe_operation = $4,
+                is_scalar = $5,
+                is_completed = false,
+                is_error = false,
+                error_message = NULL,
+                completed_at = NULL
+            WHERE tenant_id = $1 AND output_handle = $2
+            "#,
+            self.tenant_id,
+            computation.output_handle,
+            &computation.dependencies,
+            computation.fhe_operation as i16,
+            computation.is_scalar,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Reads the event type stored for the given allowed handle
+    pub async fn find_allowed_handle_event_type(
+        &mut self,
+        allowed: &AllowedHandle,
+    ) -> Result<Option<i16>, SqlxError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT event_type
+            FROM allowed_handles
+            WHERE tenant_id = $1 AND handle = $2 AND account_address = $3
+            "#,
+            self.tenant_id,
+            allowed.handle,
+            allowed.account_address,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Overwrites a mismatched allowed handle, it will be propagated again
+    pub async fn update_allowed_handle_event_type(
+        &mut self,
+        allowed: &AllowedHandle,
+    ) -> Result<(), SqlxError> {
+        sqlx::query!(
+            r#"
+            UPDATE allowed_handles
+            SET event_type = $4,
+                txn_is_sent = false,
+                txn_retry_count = 0,
+                txn_transport_retry_count = 0
+            WHERE tenant_id = $1 AND handle = $2 AND account_address = $3
+            "#,
+            self.tenant_id,
+            allowed.handle,
+            allowed.account_address,
+            allowed.event_type as i16,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn has_pbs_computation(
+        &mut self,
+        handle: &[u8],
+    ) -> Result<bool, SqlxError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT 1 AS found
+            FROM pbs_computations
+            WHERE tenant_id = $1 AND handle = $2
+            "#,
+            self.tenant_id,
+            handle,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.is_some())
+    }
+}
@@ -17,7 +17,7 @@ use crate::contracts::TfheContract;
 use crate::contracts::TfheContract::TfheContractEvents;
 
 type CoprocessorApiKey = Uuid;
-type FheOperation = i32;
+pub type FheOperation = i32;
 pub type Handle = FixedBytes<32>;
 pub type TenantId = i32;
 pub type ChainId = u64;
@@ -25,6 +25,8 @@ pub type ToType = u8;
 pub type ScalarByte = FixedBytes<1>;
 pub type ClearConst = Uint<256, 4>;
 
+const DEFAULT_POOL_SIZE: u32 = 8;
+
 pub fn retry_on_sqlx_error(err: &SqlxError) -> bool {
     match err {
         SqlxError::Io(_)
@@ -39,10 +41,11 @@ pub fn retry_on_sqlx_error(err: &SqlxError) -> bool {
 
 // A pool of connection with some cached information and automatic reconnection
 pub struct Database {
-    url: String,
-    pool: sqlx::Pool<Postgres>,
-    tenant_id: TenantId,
-    chain_id: ChainId,
+    pub(crate) url: String,
+    pub(crate) pool: sqlx::Pool<Postgres>,
+    owns_pool: bool, // a shared pool is not recreated on reconnect
+    pub(crate) tenant_id: TenantId,
+    pub(crate) chain_id: ChainId,
 }
 
 impl Database {
@@ -51,7 +54,25 @@ impl Database {
         coprocessor_api_key: &CoprocessorApiKey,
         chain_id: ChainId,
     ) -> Self {
-        let pool = Self::new_pool(url).await;
+        let pool = Self::new_pool(url, DEFAULT_POOL_SIZE).await;
+        let tenant_id =
+            Self::find_tenant_id_or_panic(&pool, coprocessor_api_key).await;
+        Database {
+            url: url.into(),
+            tenant_id,
+            chain_id,
+            pool,
+            owns_pool: true,
+        }
+    }
+
+    /// Creates a database using a pool shared with other chains
+    pub async fn new_with_pool(
+        url: &str,
+        pool: PgPool,
+        coprocessor_api_key: &CoprocessorApiKey,
+        chain_id: ChainId,
+    ) -> Self {
         let tenant_id =
             Self::find_tenant_id_or_panic(&pool, coprocessor_api_key).await;
         Database {
@@ -59,10 +80,11 @@ impl Database {
             tenant_id,
             chain_id,
             pool,
+            owns_pool: false,
         }
     }
 
-    async fn new_pool(url: &str) -> PgPool {
+    pub async fn new_pool(url: &str, max_connections: u32) -> PgPool {
         let options: PgConnectOptions = url.parse().expect("bad url");
         let options = options.options([
             ("statement_timeout", "10000"), // 5 seconds
@@ -71,7 +93,7 @@ impl Database {
             PgPoolOptions::new()
                 .min_connections(2)
                 .max_lifetime(Duration::from_secs(10 * 60))
-                .max_connections(8)
+                .max_connections(max_connections)
                 .connect_with(options.clone())
         };
         let mut pool = connect().await;
@@ -85,10 +107,15 @@ impl Database {
         pool.expect("unreachable")
     }
 
-    async fn reconnect(&mut self) {
+    pub(crate) async fn reconnect(&mut self) {
+        if !self.owns_pool {
+            // the shared pool reconnects its connections on its own
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            return;
+        }
         self.pool.close().await;
         tokio::time::sleep(Duration::from_millis(100)).await;
-        self.pool = Self::new_pool(&self.url).await;
+        self.pool = Self::new_pool(&self.url, DEFAULT_POOL_SIZE).await;
     }
 
     pub async fn find_tenant_id_or_panic(
@@ -120,61 +147,11 @@ impl Database {
         }
     }
 
-    async fn insert_computation_bytes(
-        &mut self,
-        tenant_id: TenantId,
-        result: &Handle,
-        dependencies_handles: &[&Handle],
-        dependencies_bytes: &[Vec<u8>], /* always added after
-                                         * dependencies_handles */
-        fhe_operation: FheOperation,
-        scalar_byte: &FixedBytes<1>,
-    ) -> Result<(), SqlxError> {
-        let dependencies_handles = dependencies_handles
-            .iter()
-            .map(|d| d.to_vec())
-            .collect::<Vec<_>>();
-        let dependencies = [&dependencies_handles, dependencies_bytes].concat();
-        self.insert_computation_inner(
-            tenant_id,
-            result,
-            dependencies,
-            fhe_operation,
-            scalar_byte,
-        )
-        .await
-    }
-
-    async fn insert_computation(
-        &mut self,
-        tenant_id: TenantId,
-        result: &Handle,
-        dependencies: &[&Handle],
-        fhe_operation: FheOperation,
-        scalar_byte: &FixedBytes<1>,
-    ) -> Result<(), SqlxError> {
-        let dependencies =
-            dependencies.iter().map(|d| d.to_vec()).collect::<Vec<_>>();
-        self.insert_computation_inner(
-            tenant_id,
-            result,
-            dependencies,
-            fhe_operation,
-            scalar_byte,
-        )
-        .await
-    }
-
-    async fn insert_computation_inner(
+    pub(crate) async fn insert_computation(
         &mut self,
         tenant_id: TenantId,
-        result: &Handle,
-        dependencies: Vec<Vec<u8>>,
-        fhe_operation: FheOperation,
-        scalar_byte: &FixedBytes<1>,
+        computation: &Computation,
     ) -> Result<(), SqlxError> {
-        let is_scalar = !scalar_byte.is_zero();
-        let output_handle = result.to_vec();
         let query = || {
             sqlx::query!(
                 r#"
@@ -189,10 +166,10 @@ impl Database {
             ON CONFLICT (tenant_id, output_handle) DO NOTHING
             "#,
                 tenant_id as i32,
-                output_handle,
-                &dependencies,
-                fhe_operation as i16,
-                is_scalar
+                computation.output_handle,
+                &computation.dependencies,
+                computation.fhe_operation as i16,
+                computation.is_scalar
             )
         };
         // retry mecanism
@@ -213,79 +190,14 @@ impl Database {
         }
     }
 
-    #[rustfmt::skip]
     pub async fn insert_tfhe_event(
         &mut self,
-        event: &Log<TfheContractEvents>
+        event: &Log<TfheContractEvents>,
     ) -> Result<(), SqlxError> {
-        use TfheContract as C;
-        use TfheContractEvents as E;
-        const HAS_SCALAR : FixedBytes::<1> = FixedBytes([1]); // if any dependency is a scalar.
-        const NO_SCALAR : FixedBytes::<1> = FixedBytes([0]); // if all dependencies are handles.
-        // ciphertext type
-        let ty = |to_type: &ToType| vec![*to_type];
-        let as_bytes = |x: &ClearConst| x.to_be_bytes_vec();
-        let tenant_id = self.tenant_id;
-        let fhe_operation = event_to_op_int(event);
-        match &event.data {
-            E::Cast(C::Cast {ct, toType, result, ..})
-            => self.insert_computation_bytes(tenant_id, result, &[ct], &[ty(toType)], fhe_operation, &HAS_SCALAR).await,
-
-            E::FheAdd(C::FheAdd {lhs, rhs, scalarByte, result, ..})
-            | E::FheBitAnd(C::FheBitAnd {lhs, rhs, scalarByte, result, ..})
-            | E::FheBitOr(C::FheBitOr {lhs, rhs, scalarByte, result, ..})
-            | E::FheBitXor(C::FheBitXor {lhs, rhs, scalarByte, result, ..} )
-            | E::FheDiv(C::FheDiv {lhs, rhs, scalarByte, result, ..})
-            | E::FheMax(C::FheMax {lhs, rhs, scalarByte, result, ..})
-            | E::FheMin(C::FheMin {lhs, rhs, scalarByte, result, ..})
-            | E::FheMul(C::FheMul {lhs, rhs, scalarByte, result, ..})
-            | E::FheRem(C::FheRem {lhs, rhs, scalarByte, result, ..})
-            | E::FheRotl(C::FheRotl {lhs, rhs, scalarByte, result, ..})
-            | E::FheRotr(C::FheRotr {lhs, rhs, scalarByte, result, ..})
-            | E::FheShl(C::FheShl {lhs, rhs, scalarByte, result, ..})
-            | E::FheShr(C::FheShr {lhs, rhs, scalarByte, result, ..})
-            | E::FheSub(C::FheSub {lhs, rhs, scalarByte, result, ..})
-            => self.insert_computation(tenant_id, result, &[lhs, rhs], fhe_operation, scalarByte).await,
-
-            E::FheIfThenElse(C::FheIfThenElse {control, ifTrue, ifFalse, result, ..})
-            => self.insert_computation(tenant_id, result, &[control, ifTrue, ifFalse], fhe_operation, &NO_SCALAR).await,
-
-            | E::FheEq(C::FheEq {lhs, rhs, scalarByte, result, ..})
-            | E::FheGe(C::FheGe {lhs, rhs, scalarByte, result, ..})
-            | E::FheGt(C::FheGt {lhs, rhs, scalarByte, result, ..})
-            | E::FheLe(C::FheLe {lhs, rhs, scalarByte, result, ..})
-            | E::FheLt(C::FheLt {lhs, rhs, scalarByte, result, ..})
-            | E::FheNe(C::FheNe {lhs, rhs, scalarByte, result, ..})
-            => self.insert_computation(tenant_id, result, &[lhs, rhs], fhe_operation, scalarByte).await,
-
-
-            E::FheNeg(C::FheNeg {ct, result, ..})
-            | E::FheNot(C::FheNot {ct, result, ..})
-            => self.insert_computation(tenant_id, result, &[ct], fhe_operation, &NO_SCALAR).await,
-
-            | E::FheEqBytes(C::FheEqBytes {lhs, rhs, scalarByte, result, ..})
-            | E::FheNeBytes(C::FheNeBytes {lhs, rhs, scalarByte, result, ..})
-            => self.insert_computation_bytes(tenant_id, result, &[lhs], &[rhs.to_vec()], fhe_operation, scalarByte).await,
-
-            | E::FheRand(C::FheRand {randType, seed, result, ..})
-            => self.insert_computation_bytes(tenant_id, result, &[], &[seed.to_vec(), ty(randType)], fhe_operation, &HAS_SCALAR).await,
-
-            | E::FheRandBounded(C::FheRandBounded {upperBound, randType, seed, result, ..})
-            => self.insert_computation_bytes(tenant_id, result, &[], &[seed.to_vec(), as_bytes(upperBound), ty(randType)], fhe_operation, &HAS_SCALAR).await,
-
-            | E::TrivialEncrypt(C::TrivialEncrypt {pt, toType, result, ..})
-            => self.insert_computation_bytes(tenant_id, result, &[], &[as_bytes(pt), ty(toType)], fhe_operation, &HAS_SCALAR).await,
-
-            | E::TrivialEncryptBytes(C::TrivialEncryptBytes {pt, toType, result, ..})
-            => self.insert_computation_bytes(tenant_id, result, &[], &[pt.to_vec(), ty(toType)], fhe_operation, &HAS_SCALAR).await,
-
-            | E::Initialized(_)
-            | E::OwnershipTransferStarted(_)
-            | E::OwnershipTransferred(_)
-            | E::Upgraded(_)
-            | E::VerifyCiphertext(_)
-            => Ok(()),
-        }
+        let Some(computation) = tfhe_event_computation(event) else {
+            return Ok(());
+        };
+        self.insert_computation(self.tenant_id, &computation).await
     }
 
     pub async fn mark_prev_block_as_valid(
@@ -351,92 +263,124 @@ impl Database {
         let data = &event.data;
 
         match data {
-            AclContractEvents::Allowed(allowed) => {
-                let handle = allowed.handle.to_vec();
-
-                self.insert_allowed_handle(
-                    handle.clone(),
-                    allowed.account.to_string(),
-                    AllowEvents::AllowedAccount,
-                )
-                .await?;
-
-                self.insert_pbs_computations(&vec![handle]).await?;
-            }
-            AclContractEvents::AllowedForDecryption(allowed_for_decryption) => {
-                let handles = allowed_for_decryption
-                    .handlesList
-                    .iter()
-                    .map(|h| h.to_vec())
-                    .collect::<Vec<_>>();
-
-                for handle in handles.clone() {
-                    println!(
-                        "Allowed for public decryption: {}",
-                        compact_hex(&handle),
-                    );
+            AclContractEvents::Allowed(_)
+            | AclContractEvents::AllowedForDecryption(_) => {
+                let allowed_handles = acl_event_allowed_handles(data);
+                for allowed in &allowed_handles {
+                    if let AllowEvents::AllowedForDecryption =
+                        allowed.event_type
+                    {
+                        println!(
+                            "Allowed for public decryption: {}",
+                            compact_hex(&allowed.handle),
+                        );
+                    }
 
                     self.insert_allowed_handle(
-                        handle,
-                        "".to_string(),
-                        AllowEvents::AllowedForDecryption,
+                        allowed.handle.clone(),
+                        allowed.account_address.clone(),
+                        allowed.event_type,
                     )
                     .await?;
                 }
 
-                self.insert_pbs_computations(&handles).await?;
-            }
-            AclContractEvents::Initialized(initialized) => {
-                println!("unhandled Acl::Initialized event {:?}", initialized);
+                let handles = allowed_handles
+                    .into_iter()
+                    .map(|allowed| allowed.handle)
+                    .collect::<Vec<_>>();
+                // The handles allowed for decryption together can be squashed
+                // into one ct128 list by the SnS worker
+                let decryption_batch = match data {
+                    AclContractEvents::AllowedForDecryption(_)
+                        if handles.len() > 1 =>
+                    {
+                        Some(self.next_decryption_batch().await?)
+                    }
+                    _ => None,
+                };
+                self.insert_pbs_computations(&handles, decryption_batch)
+                    .await?;
             }
             AclContractEvents::NewDelegation(new_delegation) => {
-                println!(
-                    "unhandled Acl::NewDelegation event {:?}",
-                    new_delegation
-                );
-            }
-            AclContractEvents::OwnershipTransferStarted(
-                ownership_transfer_started,
-            ) => {
-                println!(
-                    "unhandled Acl::OwnershipTransferStarted event {:?}",
-                    ownership_transfer_started
-                );
-            }
-            AclContractEvents::OwnershipTransferred(ownership_transferred) => {
-                println!(
-                    "unhandled Acl::OwnershipTransferred event {:?}",
-                    ownership_transferred
-                );
+                for contract_address in &new_delegation.contractAddresses {
+                    self.insert_delegation(
+                        new_delegation.caller.to_string(),
+                        new_delegation.delegatee.to_string(),
+                        contract_address.to_string(),
+                        false,
+                    )
+                    .await?;
+                }
             }
             AclContractEvents::RevokedDelegation(revoked_delegation) => {
-                println!(
-                    "unhandled Acl::RevokedDelegation event {:?}",
-                    revoked_delegation
-                );
+                for contract_address in &revoked_delegation.contractAddresses {
+                    self.insert_delegation(
+                        revoked_delegation.caller.to_string(),
+                        revoked_delegation.delegatee.to_string(),
+                        contract_address.to_string(),
+                        true,
+                    )
+                    .await?;
+                }
             }
-            AclContractEvents::Upgraded(upgraded) => {
-                println!("unhandled Acl::Upgraded event {:?}", upgraded);
+            AclContractEvents::Initialized(_)
+            | AclContractEvents::OwnershipTransferStarted(_)
+            | AclContractEvents::OwnershipTransferred(_)
+            | AclContractEvents::Upgraded(_) => {
+                // recorded with block information by insert_host_contract_event
             }
         }
 
         Ok(())
     }
 
+    /// Allocates the id grouping the handles of an AllowedForDecryption event
+    pub async fn next_decryption_batch(&mut self) -> Result<i64, SqlxError> {
+        loop {
+            match sqlx::query_scalar!(
+                r#"SELECT nextval('pbs_computations_decryption_batch_seq') AS "id!""#
+            )
+            .fetch_one(&self.pool)
+            .await
+            {
+                Ok(id) => return Ok(id),
+                Err(err) if retry_on_sqlx_error(&err) => {
+                    eprintln!(
+                        "\tDatabase I/O error: {}, will retry indefinitely",
+                        err
+                    );
+                    self.reconnect().await;
+                }
+                Err(sqlx_err) => {
+                    return Err(sqlx_err);
+                }
+            }
+        }
+    }
+
     /// Adds handles to the pbs_computations table and alerts the SnS worker
     /// about new of PBS work.
+    ///
+    /// The handles of a decryption batch not squashed yet join the batch.
     pub async fn insert_pbs_computations(
         &mut self,
         handles: &Vec<Vec<u8>>,
+        decryption_batch: Option<i64>,
     ) -> Result<(), SqlxError> {
         let tenant_id = self.tenant_id;
         for handle in handles {
             let query = || {
                 sqlx::query!(
-                    "INSERT INTO pbs_computations(tenant_id, handle) VALUES($1, $2) 
-                         ON CONFLICT DO NOTHING;",
+                    "INSERT INTO pbs_computations(tenant_id, handle, decryption_batch)
+                         VALUES($1, $2, $3)
+                         ON CONFLICT (tenant_id, handle) DO UPDATE
+                         SET decryption_batch = EXCLUDED.decryption_batch
+                         WHERE EXCLUDED.decryption_batch IS NOT NULL
+                         AND pbs_computations.decryption_batch IS NULL
+                         AND NOT pbs_computations.is_completed;",
                     tenant_id,
                     handle,
+                    decryption_batch,
                 )
             };
 
@@ -498,6 +442,204 @@ impl Database {
 
         Ok(())
     }
+
+    /// Add or update the delegation in the delegations table.
+    /// A revocation overwrites the delegation state and is propagated again.
+    pub async fn insert_delegation(
+        &mut self,
+        delegator: String,
+        delegatee: String,
+        contract_address: String,
+        is_revoked: bool,
+    ) -> Result<(), SqlxError> {
+        let tenant_id = self.tenant_id;
+
+        let query = || {
+            sqlx::query!(
+                "INSERT INTO delegations(tenant_id, delegator, delegatee, contract_address, is_revoked)
+                     VALUES($1, $2, $3, $4, $5)
+                     ON CONFLICT (tenant_id, delegator, delegatee, contract_address) DO UPDATE
+                     SET is_revoked = EXCLUDED.is_revoked,
+                         updated_at = NOW(),
+                         txn_is_sent = false,
+                         txn_retry_count = 0,
+                         txn_transport_retry_count = 0,
+                         txn_last_error = NULL,
+                         txn_last_error_at = NULL
+                     WHERE delegations.is_revoked IS DISTINCT FROM EXCLUDED.is_revoked;",
+                tenant_id,
+                delegator,
+                delegatee,
+                contract_address,
+                is_revoked,
+            )
+        };
+
+        loop {
+            match query().execute(&self.pool).await {
+                Ok(_) => break,
+                Err(err) if retry_on_sqlx_error(&err) => {
+                    eprintln!(
+                        "\tDatabase I/O error: {}, will retry indefinitely",
+                        err
+                    );
+                    self.reconnect().await;
+                }
+                Err(sqlx_err) => {
+                    return Err(sqlx_err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An allowed_handles row inserted for an ACL event
+#[derive(Clone, Debug)]
+pub struct AllowedHandle {
+    pub handle: Vec<u8>,
+    pub account_address: String,
+    pub event_type: AllowEvents,
+}
+
+/// The allowed handles of an ACL event, empty if it does not allow handles
+pub fn acl_event_allowed_handles(
+    event: &AclContractEvents,
+) -> Vec<AllowedHandle> {
+    match event {
+        AclContractEvents::Allowed(allowed) => vec![AllowedHandle {
+            handle: allowed.handle.to_vec(),
+            account_address: allowed.account.to_string(),
+            event_type: AllowEvents::AllowedAccount,
+        }],
+        AclContractEvents::AllowedForDecryption(allowed_for_decryption) => {
+            allowed_for_decryption
+                .handlesList
+                .iter()
+                .map(|h| AllowedHandle {
+                    handle: h.to_vec(),
+                    account_address: "".to_string(),
+                    event_type: AllowEvents::AllowedForDecryption,
+                })
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+/// The computations row inserted for a TFHE event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Computation {
+    pub output_handle: Vec<u8>,
+    pub dependencies: Vec<Vec<u8>>,
+    pub fhe_operation: FheOperation,
+    pub is_scalar: bool,
+}
+
+fn computation_bytes(
+    result: &Handle,
+    dependencies_handles: &[&Handle],
+    dependencies_bytes: &[Vec<u8>], /* always added after
+                                     * dependencies_handles */
+    fhe_operation: FheOperation,
+    scalar_byte: &FixedBytes<1>,
+) -> Computation {
+    let dependencies_handles = dependencies_handles
+        .iter()
+        .map(|d| d.to_vec())
+        .collect::<Vec<_>>();
+    let dependencies = [&dependencies_handles, dependencies_bytes].concat();
+    Computation {
+        output_handle: result.to_vec(),
+        dependencies,
+        fhe_operation,
+        is_scalar: !scalar_byte.is_zero(),
+    }
+}
+
+fn computation(
+    result: &Handle,
+    dependencies: &[&Handle],
+    fhe_operation: FheOperation,
+    scalar_byte: &FixedBytes<1>,
+) -> Computation {
+    computation_bytes(result, dependencies, &[], fhe_operation, scalar_byte)
+}
+
+/// The computation described by a TFHE event, None if it is not an operation
+#[rustfmt::skip]
+pub fn tfhe_event_computation(
+    event: &Log<TfheContractEvents>
+) -> Option<Computation> {
+    use TfheContract as C;
+    use TfheContractEvents as E;
+    const HAS_SCALAR : FixedBytes::<1> = FixedBytes([1]); // if any dependency is a scalar.
+    const NO_SCALAR : FixedBytes::<1> = FixedBytes([0]); // if all dependencies are handles.
+    // ciphertext type
+    let ty = |to_type: &ToType| vec![*to_type];
+    let as_bytes = |x: &ClearConst| x.to_be_bytes_vec();
+    let fhe_operation = event_to_op_int(event);
+    let computation = match &event.data {
+        E::Cast(C::Cast {ct, toType, result, ..})
+        => computation_bytes(result, &[ct], &[ty(toType)], fhe_operation, &HAS_SCALAR),
+
+        E::FheAdd(C::FheAdd {lhs, rhs, scalarByte, result, ..})
+        | E::FheBitAnd(C::FheBitAnd {lhs, rhs, scalarByte, result, ..})
+        | E::FheBitOr(C::FheBitOr {lhs, rhs, scalarByte, result, ..})
+        | E::FheBitXor(C::FheBitXor {lhs, rhs, scalarByte, result, ..} )
+        | E::FheDiv(C::FheDiv {lhs, rhs, scalarByte, result, ..})
+        | E::FheMax(C::FheMax {lhs, rhs, scalarByte, result, ..})
+        | E::FheMin(C::FheMin {lhs, rhs, scalarByte, result, ..})
+        | E::FheMul(C::FheMul {lhs, rhs, scalarByte, result, ..})
+        | E::FheRem(C::FheRem {lhs, rhs, scalarByte, result, ..})
+        | E::FheRotl(C::FheRotl {lhs, rhs, scalarByte, result, ..})
+        | E::FheRotr(C::FheRotr {lhs, rhs, scalarByte, result, ..})
+        | E::FheShl(C::FheShl {lhs, rhs, scalarByte, result, ..})
+        | E::FheShr(C::FheShr {lhs, rhs, scalarByte, result, ..})
+        | E::FheSub(C::FheSub {lhs, rhs, scalarByte, result, ..})
+        => computation(result, &[lhs, rhs], fhe_operation, scalarByte),
+
+        E::FheIfThenElse(C::FheIfThenElse {control, ifTrue, ifFalse, result, ..})
+        => computation(result, &[control, ifTrue, ifFalse], fhe_operation, &NO_SCALAR),
+
+        | E::FheEq(C::FheEq {lhs, rhs, scalarByte, result, ..})
+        | E::FheGe(C::FheGe {lhs, rhs, scalarByte, result, ..})
+        | E::FheGt(C::FheGt {lhs, rhs, scalarByte, result, ..})
+        | E::FheLe(C::FheLe {lhs, rhs, scalarByte, result, ..})
+        | E::FheLt(C::FheLt {lhs, rhs, scalarByte, result, ..})
+        | E::FheNe(C::FheNe {lhs, rhs, scalarByte, result, ..})
+        => computation(result, &[lhs, rhs], fhe_operation, scalarByte),
+
+
+        E::FheNeg(C::FheNeg {ct, result, ..})
+        | E::FheNot(C::FheNot {ct, result, ..})
+        => computation(result, &[ct], fhe_operation, &NO_SCALAR),
+
+        | E::FheEqBytes(C::FheEqBytes {lhs, rhs, scalarByte, result, ..})
+        | E::FheNeBytes(C::FheNeBytes {lhs, rhs, scalarByte, result, ..})
+        => computation_bytes(result, &[lhs], &[rhs.to_vec()], fhe_operation, scalarByte),
+
+        | E::FheRand(C::FheRand {randType, seed, result, ..})
+        => computation_bytes(result, &[], &[seed.to_vec(), ty(randType)], fhe_operation, &HAS_SCALAR),
+
+        | E::FheRandBounded(C::FheRandBounded {upperBound, randType, seed, result, ..})
+        => computation_bytes(result, &[], &[seed.to_vec(), as_bytes(upperBound), ty(randType)], fhe_operation, &HAS_SCALAR),
+
+        | E::TrivialEncrypt(C::TrivialEncrypt {pt, toType, result, ..})
+        => computation_bytes(result, &[], &[as_bytes(pt), ty(toType)], fhe_operation, &HAS_SCALAR),
+
+        | E::TrivialEncryptBytes(C::TrivialEncryptBytes {pt, toType, result, ..})
+        => computation_bytes(result, &[], &[pt.to_vec(), ty(toType)], fhe_operation, &HAS_SCALAR),
+
+        | E::Initialized(_)
+        | E::OwnershipTransferStarted(_)
+        | E::OwnershipTransferred(_)
+        | E::Upgraded(_)
+        | E::VerifyCiphertext(_)
+        => return None,
+    };
+    Some(computation)
 }
 
 fn event_to_op_int(op: &TfheContractEvents) -> FheOperation {
@@ -1,3 +1,4 @@
 pub mod cmd;
 pub mod contracts;
 pub mod database;
+pub mod metrics;
@@ -0,0 +1,98 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_int_counter_vec, register_int_gauge_vec, IntCounterVec,
+    IntGaugeVec,
+};
+
+use crate::database::tfhe_event_propagate::ChainId;
+
+lazy_static! {
+    static ref EVENTS_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "fhevm_listener_events",
+        "events received per chain and kind",
+        &["chain_id", "kind"]
+    )
+    .unwrap();
+    static ref EVENT_ERRORS_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "fhevm_listener_event_errors",
+        "events that could not be propagated per chain",
+        &["chain_id"]
+    )
+    .unwrap();
+    static ref DEAD_LETTERS_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "fhevm_listener_dead_letters",
+        "logs stored as dead letters per chain and error kind",
+        &["chain_id", "kind"]
+    )
+    .unwrap();
+    static ref LAST_BLOCK_GAUGE: IntGaugeVec = register_int_gauge_vec!(
+        "fhevm_listener_last_block",
+        "last block seen per chain",
+        &["chain_id"]
+    )
+    .unwrap();
+}
+
+/// Metrics of one chain watcher
+#[derive(Clone)]
+pub struct ChainMetrics {
+    chain_id: String,
+}
+
+impl ChainMetrics {
+    pub fn new(chain_id: ChainId) -> Self {
+        Self {
+            chain_id: chain_id.to_string(),
+        }
+    }
+
+    pub fn event(&self, kind: &str) {
+        EVENTS_COUNTER
+            .with_label_values(&[self.chain_id.as_str(), kind])
+            .inc();
+    }
+
+    pub fn event_error(&self) {
+        EVENT_ERRORS_COUNTER
+            .with_label_values(&[self.chain_id.as_str()])
+            .inc();
+    }
+
+    pub fn dead_letter(&self, kind: &str) {
+        DEAD_LETTERS_COUNTER
+            .with_label_values(&[self.chain_id.as_str(), kind])
+            .inc();
+    }
+
+    pub fn block(&self, block_number: u64) {
+        LAST_BLOCK_GAUGE
+            .with_label_values(&[self.chain_id.as_str()])
+            .set(block_number as i64);
+    }
+}
+
+async fn metrics() -> impl actix_web::Responder {
+    let encoder = prometheus::TextEncoder::new();
+    let metric_families = prometheus::gather();
+    encoder
+        .encode_to_string(&metric_families)
+        .expect("can't encode metrics")
+}
+
+async fn healthcheck() -> impl actix_web::Responder {
+    "OK"
+}
+
+pub async fn run_metrics_server(metrics_addr: String) -> std::io::Result<()> {
+    eprintln!("Metrics server listening at {metrics_addr}");
+    actix_web::HttpServer::new(|| {
+        actix_web::App::new()
+            .route("/metrics", actix_web::web::to(metrics))
+            .route("/health", actix_web::web::to(healthcheck))
+    })
+    .bind(&metrics_addr)
+    .expect("can't bind to metrics server address")
+    .workers(1)
+    .run()
+    .await
+}
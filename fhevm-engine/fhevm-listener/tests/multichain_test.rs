@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use prometheus::proto::{MetricFamily, MetricType};
+use sqlx::types::Uuid;
+
+use fhevm_listener::cmd::config::Config;
+use fhevm_listener::cmd::Args;
+use fhevm_listener::metrics::ChainMetrics;
+
+fn write_config(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn test_config_chains_inherit_args() {
+    let path = write_config(
+        "fhevm_listener_chains.json",
+        r#"{
+            "database_url": "postgresql://postgres:postgres@db:5432/coprocessor",
+            "chains": [
+                {
+                    "url": "ws://chain-a:8746",
+                    "coprocessor_api_key": "00000000-0000-0000-0000-000000000000",
+                    "acl_contract_address": "0x339EcE85B9E11a3A3AA557582784a15d7F82AAf2"
+                },
+                {
+                    "url": "ws://chain-b:8746",
+                    "coprocessor_api_key": "00000000-0000-0000-0000-000000000001",
+                    "catchup_margin": 10,
+                    "follow_address_registry": true
+                }
+            ]
+        }"#,
+    );
+    let config = Config::from_file(&path).unwrap();
+    assert_eq!(config.chains.len(), 2);
+    assert_eq!(config.database_pool_size, None);
+
+    let base = Args::parse_from([
+        "fhevm_listener",
+        "--catchup-margin",
+        "3",
+        "--ignore-acl-events",
+    ]);
+    let database_url = config.database_url.clone().unwrap();
+    let chain_a = config.chains[0].to_args(&base, &database_url).unwrap();
+    assert_eq!(chain_a.url, "ws://chain-a:8746");
+    assert_eq!(chain_a.database_url, database_url);
+    assert_eq!(chain_a.coprocessor_api_key, Some(Uuid::nil()));
+    assert_eq!(
+        chain_a.acl_contract_address.as_deref(),
+        Some("0x339EcE85B9E11a3A3AA557582784a15d7F82AAf2")
+    );
+    assert_eq!(chain_a.tfhe_contract_address, None);
+    assert_eq!(chain_a.catchup_margin, 3);
+    assert!(chain_a.ignore_acl_events);
+    assert!(!chain_a.follow_address_registry);
+
+    let chain_b = config.chains[1].to_args(&base, &database_url).unwrap();
+    assert_eq!(chain_b.url, "ws://chain-b:8746");
+    assert_eq!(chain_b.catchup_margin, 10);
+    assert!(chain_b.follow_address_registry);
+    assert_ne!(chain_a.coprocessor_api_key, chain_b.coprocessor_api_key);
+}
+
+#[test]
+fn test_config_invalid_chains() {
+    let no_chain =
+        write_config("fhevm_listener_no_chain.json", r#"{ "chains": [] }"#);
+    assert!(Config::from_file(&no_chain)
+        .unwrap_err()
+        .contains("No chain"));
+
+    let no_url = write_config(
+        "fhevm_listener_no_url.json",
+        r#"{ "chains": [{ "coprocessor_api_key": "00000000-0000-0000-0000-000000000000" }] }"#,
+    );
+    assert!(Config::from_file(&no_url).unwrap_err().contains("Invalid"));
+
+    let bad_key = write_config(
+        "fhevm_listener_bad_key.json",
+        r#"{ "chains": [{ "url": "ws://chain-a:8746", "coprocessor_api_key": "key" }] }"#,
+    );
+    let config = Config::from_file(&bad_key).unwrap();
+    let base = Args::parse_from(["fhevm_listener"]);
+    let err = config.chains[0].to_args(&base, "").unwrap_err();
+    assert!(err.contains("ws://chain-a:8746"));
+}
+
+fn metric_value(family: &str, labels: &[(&str, &str)]) -> Option<f64> {
+    let families: Vec<MetricFamily> = prometheus::gather();
+    let family = families.iter().find(|f| f.name() == family)?;
+    family
+        .get_metric()
+        .iter()
+        .find(|metric| {
+            labels.iter().all(|(name, value)| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|l| l.name() == *name && l.value() == *value)
+            })
+        })
+        .map(|metric| {
+            if family.get_field_type() == MetricType::COUNTER {
+                metric.get_counter().value()
+            } else {
+                metric.get_gauge().value()
+            }
+        })
+}
+
+#[test]
+fn test_metrics_are_labelled_per_chain() {
+    let chain_a = ChainMetrics::new(12401);
+    let chain_b = ChainMetrics::new(12402);
+    chain_a.event("tfhe");
+    chain_a.event("tfhe");
+    chain_b.event("tfhe");
+    chain_b.event("acl");
+    chain_b.event_error();
+    chain_b.dead_letter("decode");
+    chain_a.block(100);
+    chain_b.block(7);
+
+    let events = "fhevm_listener_events";
+    let value = |chain_id, kind| {
+        metric_value(events, &[("chain_id", chain_id), ("kind", kind)])
+    };
+    assert_eq!(value("12401", "tfhe"), Some(2.0));
+    assert_eq!(value("12402", "tfhe"), Some(1.0));
+    assert_eq!(value("12402", "acl"), Some(1.0));
+    assert_eq!(value("12401", "acl"), None);
+    assert_eq!(
+        metric_value("fhevm_listener_event_errors", &[("chain_id", "12402")]),
+        Some(1.0)
+    );
+    assert_eq!(
+        metric_value("fhevm_listener_event_errors", &[("chain_id", "12401")]),
+        None
+    );
+    assert_eq!(
+        metric_value(
+            "fhevm_listener_dead_letters",
+            &[("chain_id", "12402"), ("kind", "decode")]
+        ),
+        Some(1.0)
+    );
+    assert_eq!(
+        metric_value("fhevm_listener_last_block", &[("chain_id", "12401")]),
+        Some(100.0)
+    );
+    assert_eq!(
+        metric_value("fhevm_listener_last_block", &[("chain_id", "12402")]),
+        Some(7.0)
+    );
+}
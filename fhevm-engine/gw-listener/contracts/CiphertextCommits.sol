@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: BSD-3-Clause-Clear
+pragma solidity ^0.8.28;
+
+/// @dev This contract is a mock of the CiphertextCommits contract from the Gateway.
+/// source: github.com/zama-ai/fhevm-gateway/blob/main/contracts/CiphertextCommits.sol
+contract CiphertextCommits {
+    event AddCiphertextMaterial(
+        bytes32 indexed ctHandle,
+        bytes32 ciphertextDigest,
+        bytes32 snsCiphertextDigest,
+        address[] coprocessorTxSenderAddresses
+    );
+
+    function addCiphertextMaterial(
+        bytes32 ctHandle,
+        uint256 /* keyId */,
+        bytes32 ciphertextDigest,
+        bytes32 snsCiphertextDigest
+    ) public {
+        address[] memory coprocessorTxSenderAddresses = new address[](1);
+        coprocessorTxSenderAddresses[0] = msg.sender;
+        emit AddCiphertextMaterial(ctHandle, ciphertextDigest, snsCiphertextDigest, coprocessorTxSenderAddresses);
+    }
+}
@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: BSD-3-Clause-Clear
+pragma solidity ^0.8.28;
+
+/// @dev This contract is a mock of the Decryption contract from the Gateway.
+/// source: github.com/zama-ai/fhevm-gateway/blob/main/contracts/Decryption.sol
+contract Decryption {
+    struct SnsCiphertextMaterial {
+        bytes32 ctHandle;
+        uint256 keyId;
+        bytes32 snsCiphertextDigest;
+        address[] coprocessorTxSenderAddresses;
+    }
+
+    event PublicDecryptionRequest(uint256 indexed publicDecryptionId, SnsCiphertextMaterial[] snsCtMaterials);
+
+    event UserDecryptionRequest(
+        uint256 indexed userDecryptionId,
+        SnsCiphertextMaterial[] snsCtMaterials,
+        address userAddress,
+        bytes publicKey
+    );
+
+    uint256 publicDecryptionCounter = 0;
+    uint256 userDecryptionCounter = 0;
+
+    function publicDecryptionRequest(bytes32[] calldata ctHandles) public {
+        uint256 publicDecryptionId = publicDecryptionCounter;
+        publicDecryptionCounter += 1;
+        emit PublicDecryptionRequest(publicDecryptionId, snsCiphertextMaterials(ctHandles));
+    }
+
+    function userDecryptionRequest(
+        bytes32[] calldata ctHandles,
+        address userAddress,
+        bytes calldata publicKey
+    ) public {
+        uint256 userDecryptionId = userDecryptionCounter;
+        userDecryptionCounter += 1;
+        emit UserDecryptionRequest(userDecryptionId, snsCiphertextMaterials(ctHandles), userAddress, publicKey);
+    }
+
+    function snsCiphertextMaterials(
+        bytes32[] calldata ctHandles
+    ) internal pure returns (SnsCiphertextMaterial[] memory materials) {
+        materials = new SnsCiphertextMaterial[](ctHandles.length);
+        for (uint256 i = 0; i < ctHandles.length; i++) {
+            materials[i] = SnsCiphertextMaterial(ctHandles[i], 0, keccak256(abi.encode(ctHandles[i])), new address[](0));
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// SPDX-License-Identifier: BSD-3-Clause-Clear
+pragma solidity ^0.8.28;
+
+/// @dev This contract is a mock of the MultichainAcl contract from the Gateway.
+/// source: github.com/zama-ai/fhevm-gateway/blob/main/contracts/MultichainAcl.sol
+contract MultichainAcl {
+    event AllowAccount(bytes32 indexed ctHandle, address accountAddress);
+    event AllowPublicDecrypt(bytes32 indexed ctHandle);
+
+    function allowAccount(bytes32 ctHandle, address accountAddress) public {
+        emit AllowAccount(ctHandle, accountAddress);
+    }
+
+    function allowPublicDecrypt(bytes32 ctHandle) public {
+        emit AllowPublicDecrypt(ctHandle);
+    }
+}
@@ -1,7 +1,13 @@
+use std::sync::Arc;
+
 use alloy::providers::{ProviderBuilder, WsConnect};
 use alloy::{primitives::Address, transports::http::reqwest::Url};
 use clap::Parser;
 use gw_listener::gw_listener::GatewayListener;
+use gw_listener::handlers::{
+    ciphertext_commits::CiphertextCommitsHandler, decryption::DecryptionHandler,
+    multichain_acl::MultichainAclHandler,
+};
 use gw_listener::ConfigSettings;
 use tokio::signal::unix::{signal, SignalKind};
 use tokio_util::sync::CancellationToken;
@@ -18,12 +24,41 @@ struct Conf {
     #[arg(long, default_value = "verify_proof_requests")]
     verify_proof_req_database_channel: String,
 
+    #[arg(long, default_value = "gw_decryption_requests")]
+    decryption_req_database_channel: String,
+
+    #[arg(long, default_value = "gw_ciphertext_commits")]
+    ciphertext_commit_database_channel: String,
+
+    #[arg(long, default_value = "gw_acl_confirmations")]
+    acl_confirmation_database_channel: String,
+
     #[arg(long)]
     gw_url: Url,
 
+    /// Maximum number of blocks per historical logs query when catching up
+    #[arg(long, default_value = "100")]
+    catchup_blocks_per_request: u64,
+
+    /// Number of blocks handled again on start when the last block was reorged while stopped
+    #[arg(long, default_value = "64")]
+    reorg_rewind_blocks: u64,
+
     #[arg(short, long)]
     input_verification_address: Address,
 
+    /// Listen to decryption requests if set
+    #[arg(long)]
+    decryption_address: Option<Address>,
+
+    /// Listen to ciphertext commit confirmations if set
+    #[arg(long)]
+    ciphertext_commits_address: Option<Address>,
+
+    /// Listen to ACL confirmations if set
+    #[arg(long)]
+    multichain_acl_address: Option<Address>,
+
     #[arg(long, default_value = "1")]
     error_sleep_initial_secs: u16,
 
@@ -60,19 +95,43 @@ async fn main() -> anyhow::Result<()> {
         .await?;
 
     let cancel_token = CancellationToken::new();
-    let gw_listener = GatewayListener::new(
+    let settings = ConfigSettings {
+        database_url,
+        database_pool_size: conf.database_pool_size,
+        verify_proof_req_db_channel: conf.verify_proof_req_database_channel,
+        decryption_req_db_channel: conf.decryption_req_database_channel,
+        ciphertext_commit_db_channel: conf.ciphertext_commit_database_channel,
+        acl_confirmation_db_channel: conf.acl_confirmation_database_channel,
+        gw_url: conf.gw_url,
+        catchup_blocks_per_request: conf.catchup_blocks_per_request,
+        reorg_rewind_blocks: conf.reorg_rewind_blocks,
+        error_sleep_initial_secs: conf.error_sleep_initial_secs,
+        error_sleep_max_secs: conf.error_sleep_max_secs,
+    };
+    let mut gw_listener = GatewayListener::new(
         conf.input_verification_address,
-        ConfigSettings {
-            database_url,
-            database_pool_size: conf.database_pool_size,
-            verify_proof_req_db_channel: conf.verify_proof_req_database_channel,
-            gw_url: conf.gw_url,
-            error_sleep_initial_secs: conf.error_sleep_initial_secs,
-            error_sleep_max_secs: conf.error_sleep_max_secs,
-        },
+        settings.clone(),
         cancel_token.clone(),
         provider,
     );
+    if let Some(address) = conf.decryption_address {
+        gw_listener = gw_listener.with_handler(Arc::new(DecryptionHandler::new(
+            address,
+            settings.decryption_req_db_channel.clone(),
+        )));
+    }
+    if let Some(address) = conf.ciphertext_commits_address {
+        gw_listener = gw_listener.with_handler(Arc::new(CiphertextCommitsHandler::new(
+            address,
+            settings.ciphertext_commit_db_channel.clone(),
+        )));
+    }
+    if let Some(address) = conf.multichain_acl_address {
+        gw_listener = gw_listener.with_handler(Arc::new(MultichainAclHandler::new(
+            address,
+            settings.acl_confirmation_db_channel.clone(),
+        )));
+    }
 
     // Run gw_listener thread
     install_signal_handlers(cancel_token)?;
@@ -1,23 +1,21 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use alloy::{
-    eips::BlockNumberOrTag, network::Ethereum, primitives::Address, providers::Provider, sol,
+    network::Ethereum,
+    primitives::{Address, B256},
+    providers::Provider,
+    rpc::types::{BlockTransactionsKind, Filter, Log},
 };
 use futures_util::StreamExt;
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+use crate::handlers::{verify_proof::VerifyProofHandler, EventHandler};
 use crate::ConfigSettings;
 
-sol!(
-    #[sol(rpc)]
-    InputVerification,
-    "artifacts/InputVerification.sol/InputVerification.json"
-);
-
 pub struct GatewayListener<P: Provider<Ethereum> + Clone + 'static> {
-    input_verification_address: Address,
+    handlers: Vec<Arc<dyn EventHandler>>,
     conf: ConfigSettings,
     cancel_token: CancellationToken,
     provider: P,
@@ -30,18 +28,33 @@ impl<P: Provider<Ethereum> + Clone + 'static> GatewayListener<P> {
         cancel_token: CancellationToken,
         provider: P,
     ) -> Self {
-        GatewayListener {
+        let verify_proof = VerifyProofHandler::new(
             input_verification_address,
+            conf.verify_proof_req_db_channel.clone(),
+        );
+        GatewayListener {
+            handlers: vec![Arc::new(verify_proof)],
             conf,
             cancel_token,
             provider,
         }
     }
 
+    /// Also consumes the events of the given handler.
+    pub fn with_handler(mut self, handler: Arc<dyn EventHandler>) -> Self {
+        self.handlers.push(handler);
+        self
+    }
+
     pub async fn run(&self) -> anyhow::Result<()> {
+        let contracts: Vec<String> = self
+            .handlers
+            .iter()
+            .map(|h| format!("{}: {}", h.name(), h.contract_address()))
+            .collect();
         info!(
-            "Starting Gateway Listener with: {:?}, InputVerification: {}",
-            self.conf, self.input_verification_address
+            "Starting Gateway Listener with: {:?}, contracts: {:?}",
+            self.conf, contracts
         );
         let db_pool = PgPoolOptions::new()
             .max_connections(self.conf.database_pool_size)
@@ -74,16 +87,51 @@ impl<P: Provider<Ethereum> + Clone + 'static> GatewayListener<P> {
         db_pool: &Pool<Postgres>,
         sleep_duration: &mut u64,
     ) -> anyhow::Result<()> {
-        let input_verification =
-            InputVerification::new(self.input_verification_address, &self.provider);
-        let mut from_block = self.get_last_block_num(db_pool).await?;
-        let filter = input_verification
-            .VerifyProofRequest_filter()
-            .from_block(from_block)
-            .subscribe()
-            .await?;
-        info!("Subscribed to InputVerification.VerifyProofRequest events");
-        let mut stream = filter.into_stream().fuse();
+        let filter = Filter::new()
+            .address(
+                self.handlers
+                    .iter()
+                    .map(|h| h.contract_address())
+                    .collect::<Vec<_>>(),
+            )
+            .event_signature(
+                self.handlers
+                    .iter()
+                    .flat_map(|h| h.event_signatures())
+                    .collect::<Vec<_>>(),
+            );
+        // Catch up to the head before subscribing, so that the live events do not lag in the subscription
+        // buffer during a long catch-up.
+        let head = self.provider.get_block_number().await?;
+        let last_block = match self.get_last_block(db_pool).await? {
+            Some((last_block, last_block_hash)) => {
+                let last_block = self
+                    .rewind_reorged_last_block(db_pool, last_block, last_block_hash)
+                    .await?;
+                self.catchup(db_pool, &filter, last_block, head).await?
+            }
+            None => {
+                info!("No last block, starting from head: {}", head);
+                self.update_last_block_num(db_pool, head).await?;
+                head
+            }
+        };
+        let subscription = self.provider.subscribe_logs(&filter).await?;
+        let mut stream = subscription.into_stream().fuse();
+        // The blocks mined during the catch-up are handled with a final catch-up up to a head read after
+        // subscribing, the later ones are received from the subscription.
+        let head = self.provider.get_block_number().await?;
+        let mut last_block = self.catchup(db_pool, &filter, last_block, head).await?;
+        info!(
+            "Subscribed to {} events from block {}",
+            self.handlers
+                .iter()
+                .map(|h| h.name())
+                .collect::<Vec<_>>()
+                .join(", "),
+            last_block + 1
+        );
+        self.reset_sleep_duration(sleep_duration);
         loop {
             tokio::select! {
                 _ = self.cancel_token.cancelled() => {
@@ -94,50 +142,31 @@ impl<P: Provider<Ethereum> + Clone + 'static> GatewayListener<P> {
                         error!( "Event stream closed");
                         return Err(anyhow::anyhow!("Event stream closed"))
                     }
-                    let (request, log) = item.unwrap()?;
-                    info!( "Received event for ZK proof request ID: {}", request.zkProofId);
-                    match log.block_number {
-                        Some(event_block_num) => {
-                            match from_block {
-                                BlockNumberOrTag::Latest => {
-                                    info!( "Updating from block from latest to {}", event_block_num);
-                                    from_block = BlockNumberOrTag::Number(event_block_num);
-                                    self.update_last_block_num(db_pool, Some(event_block_num)).await?;
-                                }
-                                BlockNumberOrTag::Number(from_block_num) => {
-                                    if from_block_num < event_block_num {
-                                        info!( "Updating from block from {} to {}", from_block_num, event_block_num);
-                                        from_block = BlockNumberOrTag::Number(event_block_num);
-                                        self.update_last_block_num(db_pool, Some(event_block_num)).await?;
-                                    }
-                                }
-                                _ => unreachable!("Unexpected from block type"),
-                            }
-                        }
-                        None => {
-                            error!( "Received an event without a block number, updating from block to latest");
-                            from_block = BlockNumberOrTag::Latest;
-                            self.update_last_block_num(db_pool, None).await?;
+                    let log = item.unwrap();
+                    let Some(event_block_num) = log.block_number else {
+                        // Restarting catches up from the last persisted block instead of skipping events.
+                        return Err(anyhow::anyhow!("Received an event without a block number"));
+                    };
+                    if log.removed {
+                        // The block is orphaned, the events of the new chain are handled again.
+                        if event_block_num <= last_block {
+                            last_block = event_block_num.saturating_sub(1);
+                            self.update_last_block_num(db_pool, last_block).await?;
                         }
+                        self.handle_removed_log(db_pool, &log).await?;
+                        continue;
+                    }
+                    if event_block_num <= last_block {
+                        // Already persisted during catch-up.
+                        continue;
+                    }
+                    // Events are received in block order, so all previous blocks are persisted.
+                    if event_block_num - 1 > last_block {
+                        last_block = event_block_num - 1;
+                        self.update_last_block_num(db_pool, last_block).await?;
                     }
 
-                    // TODO: check if we can avoid the cast from u256 to i64
-                    sqlx::query!(
-                        "WITH ins AS (
-                            INSERT INTO verify_proofs (zk_proof_id, chain_id, contract_address, user_address, input)
-                            VALUES ($1, $2, $3, $4, $5)
-                            ON CONFLICT(zk_proof_id) DO NOTHING
-                        )
-                        SELECT pg_notify($6, '')",
-                        request.zkProofId.to::<i64>(),
-                        request.contractChainId.to::<i32>(),
-                        request.contractAddress.to_string(),
-                        request.userAddress.to_string(),
-                        Some(request.ciphertextWithZKProof.as_ref()),
-                        self.conf.verify_proof_req_db_channel
-                    )
-                    .execute(db_pool)
-                    .await?;
+                    self.handle_log(db_pool, &log).await?;
                 }
             }
             // Reset sleep duration on successful iteration.
@@ -146,6 +175,93 @@ impl<P: Provider<Ethereum> + Clone + 'static> GatewayListener<P> {
         Ok(())
     }
 
+    /// Handles the events after `last_block` up to `head` with bounded historical queries, advancing the last block
+    /// after each range. Returns the new last block.
+    async fn catchup(
+        &self,
+        db_pool: &Pool<Postgres>,
+        filter: &Filter,
+        mut last_block: u64,
+        head: u64,
+    ) -> anyhow::Result<u64> {
+        let blocks_per_request = self.conf.catchup_blocks_per_request.max(1);
+        while last_block < head {
+            if self.cancel_token.is_cancelled() {
+                break;
+            }
+            let to_block = head.min(last_block + blocks_per_request);
+            info!("Catching up blocks {} to {}", last_block + 1, to_block);
+            let range_filter = filter.clone().from_block(last_block + 1).to_block(to_block);
+            let logs = self.provider.get_logs(&range_filter).await?;
+            for log in &logs {
+                self.handle_log(db_pool, log).await?;
+            }
+            self.update_last_block_num(db_pool, to_block).await?;
+            last_block = to_block;
+        }
+        Ok(last_block)
+    }
+
+    /// Returns the block to catch up from: the last block, or an earlier one when the last block is no longer in the
+    /// chain because of a reorg that happened while the listener was stopped.
+    async fn rewind_reorged_last_block(
+        &self,
+        db_pool: &Pool<Postgres>,
+        last_block: u64,
+        last_block_hash: Option<B256>,
+    ) -> anyhow::Result<u64> {
+        let Some(last_block_hash) = last_block_hash else {
+            return Ok(last_block);
+        };
+        let block_hash = self.get_block_hash(last_block).await?;
+        if block_hash == Some(last_block_hash) {
+            return Ok(last_block);
+        }
+        let from_block = last_block.saturating_sub(self.conf.reorg_rewind_blocks);
+        warn!(
+            { action = "review" },
+            "Last block {} was {} and is now {:?}, reorged while stopped, handling again the events after block {}",
+            last_block,
+            last_block_hash,
+            block_hash,
+            from_block
+        );
+        for handler in &self.handlers {
+            handler.handle_reorg(db_pool, from_block).await?;
+        }
+        self.update_last_block_num(db_pool, from_block).await?;
+        Ok(from_block)
+    }
+
+    async fn get_block_hash(&self, block_num: u64) -> anyhow::Result<Option<B256>> {
+        let block = self
+            .provider
+            .get_block_by_number(block_num.into(), BlockTransactionsKind::Hashes)
+            .await?;
+        Ok(block.map(|block| block.header.hash))
+    }
+
+    async fn handle_log(&self, db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()> {
+        match self.handlers.iter().find(|h| h.handles(log)) {
+            Some(handler) => handler.handle(db_pool, log).await,
+            None => {
+                warn!(
+                    "No handler for event from {} with topic0 {:?}",
+                    log.address(),
+                    log.topic0()
+                );
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle_removed_log(&self, db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()> {
+        match self.handlers.iter().find(|h| h.handles(log)) {
+            Some(handler) => handler.handle_removed(db_pool, log).await,
+            None => Ok(()),
+        }
+    }
+
     fn reset_sleep_duration(&self, sleep_duration: &mut u64) {
         *sleep_duration = self.conf.error_sleep_initial_secs as u64;
     }
@@ -155,12 +271,12 @@ impl<P: Provider<Ethereum> + Clone + 'static> GatewayListener<P> {
         *sleep_duration = std::cmp::min(*sleep_duration * 2, self.conf.error_sleep_max_secs as u64);
     }
 
-    async fn get_last_block_num(
+    async fn get_last_block(
         &self,
         db_pool: &Pool<Postgres>,
-    ) -> anyhow::Result<BlockNumberOrTag> {
+    ) -> anyhow::Result<Option<(u64, Option<B256>)>> {
         let rows = sqlx::query!(
-            "SELECT last_block_num
+            "SELECT last_block_num, last_block_hash
             FROM gw_listener_last_block
             WHERE dummy_id = true"
         )
@@ -172,26 +288,29 @@ impl<P: Provider<Ethereum> + Clone + 'static> GatewayListener<P> {
             rows.len()
         );
 
-        Ok(rows.first().map_or(BlockNumberOrTag::Latest, |row| {
-            if let Some(n) = row.last_block_num {
-                BlockNumberOrTag::Number(n.try_into().expect("Got an invalid block number"))
-            } else {
-                BlockNumberOrTag::Latest
-            }
+        Ok(rows.first().and_then(|row| {
+            let last_block_num = row
+                .last_block_num
+                .map(|n| n.try_into().expect("Got an invalid block number"))?;
+            let last_block_hash = row.last_block_hash.as_deref().map(B256::from_slice);
+            Some((last_block_num, last_block_hash))
         }))
     }
 
     async fn update_last_block_num(
         &self,
         db_pool: &Pool<Postgres>,
-        block_num: Option<u64>,
+        block_num: u64,
     ) -> anyhow::Result<()> {
-        info!("Updating last block number to: {:?}", block_num);
+        info!("Updating last block number to: {}", block_num);
+        let block_hash = self.get_block_hash(block_num).await?;
         sqlx::query!(
-            "INSERT into gw_listener_last_block (dummy_id, last_block_num)
-            VALUES (true, $1)
-            ON CONFLICT (dummy_id) DO UPDATE SET last_block_num = EXCLUDED.last_block_num",
-            block_num.map::<i64, _>(|n| n.try_into().expect("Invalid block number for update"))
+            "INSERT into gw_listener_last_block (dummy_id, last_block_num, last_block_hash)
+            VALUES (true, $1, $2)
+            ON CONFLICT (dummy_id) DO UPDATE
+            SET last_block_num = EXCLUDED.last_block_num, last_block_hash = EXCLUDED.last_block_hash",
+            i64::try_from(block_num).expect("Invalid block number for update"),
+            block_hash.map(|h| h.to_vec())
         )
         .execute(db_pool)
         .await?;
@@ -0,0 +1,87 @@
+use alloy::{
+    primitives::{Address, B256},
+    rpc::types::Log,
+    sol,
+    sol_types::SolEvent,
+};
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use tracing::info;
+
+use super::EventHandler;
+
+sol!(
+    #[sol(rpc)]
+    CiphertextCommits,
+    "artifacts/CiphertextCommits.sol/CiphertextCommits.json"
+);
+
+/// Inserts CiphertextCommits.AddCiphertextMaterial confirmations into
+/// gw_ciphertext_commits.
+pub struct CiphertextCommitsHandler {
+    ciphertext_commits_address: Address,
+    db_channel: String,
+}
+
+impl CiphertextCommitsHandler {
+    pub fn new(ciphertext_commits_address: Address, db_channel: String) -> Self {
+        Self {
+            ciphertext_commits_address,
+            db_channel,
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for CiphertextCommitsHandler {
+    fn name(&self) -> &str {
+        "CiphertextCommits"
+    }
+
+    fn contract_address(&self) -> Address {
+        self.ciphertext_commits_address
+    }
+
+    fn event_signatures(&self) -> Vec<B256> {
+        vec![CiphertextCommits::AddCiphertextMaterial::SIGNATURE_HASH]
+    }
+
+    async fn handle(&self, db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()> {
+        let commit = log
+            .log_decode::<CiphertextCommits::AddCiphertextMaterial>()?
+            .inner
+            .data;
+        info!("Received ciphertext commit for handle: {}", commit.ctHandle);
+        let coprocessor_tx_sender_addresses: Vec<String> = commit
+            .coprocessorTxSenderAddresses
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+
+        // A later commit of the same handle carries the updated coprocessors consensus
+        sqlx::query!(
+            "WITH ins AS (
+                INSERT INTO gw_ciphertext_commits (handle, ciphertext_digest, sns_ciphertext_digest, coprocessor_tx_sender_addresses, block_number, transaction_hash)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT(handle) DO UPDATE SET
+                    ciphertext_digest = EXCLUDED.ciphertext_digest,
+                    sns_ciphertext_digest = EXCLUDED.sns_ciphertext_digest,
+                    coprocessor_tx_sender_addresses = EXCLUDED.coprocessor_tx_sender_addresses,
+                    block_number = EXCLUDED.block_number,
+                    transaction_hash = EXCLUDED.transaction_hash,
+                    updated_at = NOW()
+            )
+            SELECT pg_notify($7, '')",
+            commit.ctHandle.as_slice(),
+            commit.ciphertextDigest.as_slice(),
+            commit.snsCiphertextDigest.as_slice(),
+            &coprocessor_tx_sender_addresses,
+            log.block_number.map(|n| n as i64),
+            log.transaction_hash.map(|h| h.to_vec()),
+            self.db_channel
+        )
+        .execute(db_pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use alloy::{
+    primitives::{Address, B256},
+    rpc::types::Log,
+    sol,
+    sol_types::{SolEvent, SolEventInterface},
+};
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use tracing::info;
+
+use super::EventHandler;
+
+sol!(
+    #[sol(rpc)]
+    Decryption,
+    "artifacts/Decryption.sol/Decryption.json"
+);
+
+use Decryption::{DecryptionEvents, SnsCiphertextMaterial};
+
+/// Inserts Decryption public and user decryption requests into
+/// gw_decryption_requests.
+pub struct DecryptionHandler {
+    decryption_address: Address,
+    db_channel: String,
+}
+
+impl DecryptionHandler {
+    pub fn new(decryption_address: Address, db_channel: String) -> Self {
+        Self {
+            decryption_address,
+            db_channel,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn insert_request(
+        &self,
+        db_pool: &Pool<Postgres>,
+        log: &Log,
+        request_id: B256,
+        is_user_decryption: bool,
+        materials: &[SnsCiphertextMaterial],
+        user_address: Option<String>,
+        public_key: Option<&[u8]>,
+    ) -> anyhow::Result<()> {
+        let handles: Vec<Vec<u8>> = materials.iter().map(|m| m.ctHandle.to_vec()).collect();
+        let sns_ciphertext_digests: Vec<Vec<u8>> = materials
+            .iter()
+            .map(|m| m.snsCiphertextDigest.to_vec())
+            .collect();
+        sqlx::query!(
+            "WITH ins AS (
+                INSERT INTO gw_decryption_requests (request_id, is_user_decryption, handles, sns_ciphertext_digests, user_address, public_key, block_number, transaction_hash)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT(is_user_decryption, request_id) DO NOTHING
+            )
+            SELECT pg_notify($9, '')",
+            request_id.as_slice(),
+            is_user_decryption,
+            &handles,
+            &sns_ciphertext_digests,
+            user_address,
+            public_key,
+            log.block_number.map(|n| n as i64),
+            log.transaction_hash.map(|h| h.to_vec()),
+            self.db_channel
+        )
+        .execute(db_pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for DecryptionHandler {
+    fn name(&self) -> &str {
+        "Decryption"
+    }
+
+    fn contract_address(&self) -> Address {
+        self.decryption_address
+    }
+
+    fn event_signatures(&self) -> Vec<B256> {
+        vec![
+            Decryption::PublicDecryptionRequest::SIGNATURE_HASH,
+            Decryption::UserDecryptionRequest::SIGNATURE_HASH,
+        ]
+    }
+
+    async fn handle(&self, db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()> {
+        match DecryptionEvents::decode_log(&log.inner, true)?.data {
+            DecryptionEvents::PublicDecryptionRequest(request) => {
+                info!(
+                    "Received public decryption request ID: {}",
+                    request.publicDecryptionId
+                );
+                self.insert_request(
+                    db_pool,
+                    log,
+                    request.publicDecryptionId.into(),
+                    false,
+                    &request.snsCtMaterials,
+                    None,
+                    None,
+                )
+                .await
+            }
+            DecryptionEvents::UserDecryptionRequest(request) => {
+                info!(
+                    "Received user decryption request ID: {}",
+                    request.userDecryptionId
+                );
+                self.insert_request(
+                    db_pool,
+                    log,
+                    request.userDecryptionId.into(),
+                    true,
+                    &request.snsCtMaterials,
+                    Some(request.userAddress.to_string()),
+                    Some(request.publicKey.as_ref()),
+                )
+                .await
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use alloy::{
+    primitives::{Address, B256},
+    rpc::types::Log,
+};
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use tracing::warn;
+
+/// Consumes the events of one Gateway contract.
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn contract_address(&self) -> Address;
+
+    /// The topic0 of the handled events.
+    fn event_signatures(&self) -> Vec<B256>;
+
+    async fn handle(&self, db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()>;
+
+    /// Called when a gateway reorg removes a previously handled event.
+    async fn handle_removed(&self, _db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()> {
+        warn!(
+            "{} event removed by a reorg of block {:?}, ignoring",
+            self.name(),
+            log.block_number
+        );
+        Ok(())
+    }
+
+    /// Called on start when the blocks after `from_block` may have been reorged while the listener was stopped,
+    /// before their events are handled again.
+    async fn handle_reorg(&self, _db_pool: &Pool<Postgres>, from_block: u64) -> anyhow::Result<()> {
+        warn!(
+            "{} events after block {} may have been removed by a reorg, ignoring",
+            self.name(),
+            from_block
+        );
+        Ok(())
+    }
+
+    fn handles(&self, log: &Log) -> bool {
+        log.address() == self.contract_address()
+            && log
+                .topic0()
+                .is_some_and(|topic0| self.event_signatures().contains(topic0))
+    }
+}
+
+pub mod ciphertext_commits;
+pub mod decryption;
+pub mod multichain_acl;
+pub mod verify_proof;
@@ -0,0 +1,94 @@
+use alloy::{
+    primitives::{Address, B256},
+    rpc::types::Log,
+    sol,
+    sol_types::{SolEvent, SolEventInterface},
+};
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use tracing::info;
+
+use super::EventHandler;
+
+sol!(
+    #[sol(rpc)]
+    MultichainAcl,
+    "artifacts/MultichainAcl.sol/MultichainAcl.json"
+);
+
+use MultichainAcl::MultichainAclEvents;
+
+/// Same values as allowed_handles.event_type.
+const ALLOW_ACCOUNT_EVENT_TYPE: i16 = 0;
+const ALLOW_PUBLIC_DECRYPT_EVENT_TYPE: i16 = 1;
+
+/// Inserts MultichainAcl AllowAccount and AllowPublicDecrypt confirmations
+/// into gw_acl_confirmations.
+pub struct MultichainAclHandler {
+    multichain_acl_address: Address,
+    db_channel: String,
+}
+
+impl MultichainAclHandler {
+    pub fn new(multichain_acl_address: Address, db_channel: String) -> Self {
+        Self {
+            multichain_acl_address,
+            db_channel,
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for MultichainAclHandler {
+    fn name(&self) -> &str {
+        "MultichainAcl"
+    }
+
+    fn contract_address(&self) -> Address {
+        self.multichain_acl_address
+    }
+
+    fn event_signatures(&self) -> Vec<B256> {
+        vec![
+            MultichainAcl::AllowAccount::SIGNATURE_HASH,
+            MultichainAcl::AllowPublicDecrypt::SIGNATURE_HASH,
+        ]
+    }
+
+    async fn handle(&self, db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()> {
+        let (handle, event_type, account_address) =
+            match MultichainAclEvents::decode_log(&log.inner, true)?.data {
+                MultichainAclEvents::AllowAccount(event) => (
+                    event.ctHandle,
+                    ALLOW_ACCOUNT_EVENT_TYPE,
+                    event.accountAddress.to_string(),
+                ),
+                MultichainAclEvents::AllowPublicDecrypt(event) => (
+                    event.ctHandle,
+                    ALLOW_PUBLIC_DECRYPT_EVENT_TYPE,
+                    String::new(),
+                ),
+            };
+        info!(
+            "Received ACL confirmation for handle: {}, event type: {}",
+            handle, event_type
+        );
+        sqlx::query!(
+            "WITH ins AS (
+                INSERT INTO gw_acl_confirmations (handle, event_type, account_address, block_number, transaction_hash)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT(handle, event_type, account_address) DO NOTHING
+            )
+            SELECT pg_notify($6, '')",
+            handle.as_slice(),
+            event_type,
+            account_address,
+            log.block_number.map(|n| n as i64),
+            log.transaction_hash.map(|h| h.to_vec()),
+            self.db_channel
+        )
+        .execute(db_pool)
+        .await?;
+        Ok(())
+    }
+}
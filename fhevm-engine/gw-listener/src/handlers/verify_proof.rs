@@ -0,0 +1,217 @@
+use alloy::{
+    primitives::{Address, B256},
+    rpc::types::Log,
+    sol,
+    sol_types::SolEvent,
+};
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+use tracing::{info, warn};
+
+use super::EventHandler;
+
+sol!(
+    #[sol(rpc)]
+    InputVerification,
+    "artifacts/InputVerification.sol/InputVerification.json"
+);
+
+/// Inserts InputVerification.VerifyProofRequest events into verify_proofs.
+pub struct VerifyProofHandler {
+    input_verification_address: Address,
+    db_channel: String,
+}
+
+impl VerifyProofHandler {
+    pub fn new(input_verification_address: Address, db_channel: String) -> Self {
+        Self {
+            input_verification_address,
+            db_channel,
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for VerifyProofHandler {
+    fn name(&self) -> &str {
+        "InputVerification"
+    }
+
+    fn contract_address(&self) -> Address {
+        self.input_verification_address
+    }
+
+    fn event_signatures(&self) -> Vec<B256> {
+        vec![InputVerification::VerifyProofRequest::SIGNATURE_HASH]
+    }
+
+    async fn handle(&self, db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()> {
+        let request = log
+            .log_decode::<InputVerification::VerifyProofRequest>()?
+            .inner
+            .data;
+        info!(
+            "Received event for ZK proof request ID: {}",
+            request.zkProofId
+        );
+
+        // TODO: check if we can avoid the cast from u256 to i64
+        let zk_proof_id = request.zkProofId.to::<i64>();
+        let chain_id = request.contractChainId.to::<i32>();
+        let contract_address = request.contractAddress.to_string();
+        let user_address = request.userAddress.to_string();
+        let input = request.ciphertextWithZKProof.to_vec();
+        let block_number = log.block_number.map(|n| n as i64);
+        let block_hash = log.block_hash.map(|h| h.to_vec());
+        let log_index = log.log_index.map(|i| i as i64);
+
+        let mut txn = db_pool.begin().await?;
+        // Locking the row waits for an ongoing verification of the same request.
+        let existing = sqlx::query!(
+            "SELECT chain_id, contract_address, user_address, input, block_hash, log_index, verified, orphaned
+             FROM verify_proofs
+             WHERE zk_proof_id = $1
+             FOR UPDATE",
+            zk_proof_id
+        )
+        .fetch_optional(&mut *txn)
+        .await?;
+        match existing {
+            None => {
+                sqlx::query!(
+                    "INSERT INTO verify_proofs (zk_proof_id, chain_id, contract_address, user_address, input, block_number, block_hash, log_index)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                     ON CONFLICT(zk_proof_id) DO NOTHING",
+                    zk_proof_id,
+                    chain_id,
+                    contract_address,
+                    user_address,
+                    Some(input.as_slice()),
+                    block_number,
+                    block_hash,
+                    log_index
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+            Some(row)
+                if row.block_hash == block_hash && row.log_index == log_index && !row.orphaned =>
+            {
+                info!("ZK proof request ID: {} already received", zk_proof_id);
+                return Ok(());
+            }
+            Some(row)
+                if row.chain_id == chain_id
+                    && row.contract_address == contract_address
+                    && row.user_address == user_address
+                    && row.input.as_deref() == Some(input.as_slice()) =>
+            {
+                // Same payload, the verification result still holds.
+                info!(
+                    "ZK proof request ID: {} re-emitted in block {:?}",
+                    zk_proof_id, block_number
+                );
+                sqlx::query!(
+                    "UPDATE verify_proofs
+                     SET block_number = $2, block_hash = $3, log_index = $4, orphaned = false
+                     WHERE zk_proof_id = $1",
+                    zk_proof_id,
+                    block_number,
+                    block_hash,
+                    log_index
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+            Some(row) => {
+                warn!(
+                    "ZK proof request ID: {} re-emitted in block {:?} with a different payload, verifying it again",
+                    zk_proof_id, block_number
+                );
+                sqlx::query!(
+                    "INSERT INTO verify_proof_conflicts (zk_proof_id, chain_id, contract_address, user_address, input, block_hash, log_index, verified, new_block_hash, new_log_index)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                    zk_proof_id,
+                    row.chain_id,
+                    row.contract_address,
+                    row.user_address,
+                    row.input,
+                    row.block_hash,
+                    row.log_index,
+                    row.verified,
+                    block_hash,
+                    log_index
+                )
+                .execute(&mut *txn)
+                .await?;
+                sqlx::query!(
+                    "UPDATE verify_proofs
+                     SET chain_id = $2, contract_address = $3, user_address = $4, input = $5,
+                         block_number = $6, block_hash = $7, log_index = $8, orphaned = false,
+                         handles = NULL, verified = NULL, verified_at = NULL,
+                         retry_count = 0, last_error = NULL, last_retry_at = NULL
+                     WHERE zk_proof_id = $1",
+                    zk_proof_id,
+                    chain_id,
+                    contract_address,
+                    user_address,
+                    Some(input.as_slice()),
+                    block_number,
+                    block_hash,
+                    log_index
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+        }
+        sqlx::query!("SELECT pg_notify($1, '')", self.db_channel)
+            .execute(&mut *txn)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn handle_removed(&self, db_pool: &Pool<Postgres>, log: &Log) -> anyhow::Result<()> {
+        let request = log
+            .log_decode::<InputVerification::VerifyProofRequest>()?
+            .inner
+            .data;
+        let zk_proof_id = request.zkProofId.to::<i64>();
+        let res = sqlx::query!(
+            "UPDATE verify_proofs
+             SET orphaned = true
+             WHERE zk_proof_id = $1 AND block_hash = $2 AND log_index = $3",
+            zk_proof_id,
+            log.block_hash.map(|h| h.to_vec()),
+            log.log_index.map(|i| i as i64)
+        )
+        .execute(db_pool)
+        .await?;
+        if res.rows_affected() > 0 {
+            warn!(
+                "ZK proof request ID: {} orphaned by a reorg of block {:?}",
+                zk_proof_id, log.block_number
+            );
+        }
+        Ok(())
+    }
+
+    async fn handle_reorg(&self, db_pool: &Pool<Postgres>, from_block: u64) -> anyhow::Result<()> {
+        // The requests still in the chain are handled again as re-emitted with the same payload, which keeps
+        // their verification result and clears the flag.
+        let res = sqlx::query!(
+            "UPDATE verify_proofs
+             SET orphaned = true
+             WHERE block_number > $1 AND NOT orphaned",
+            from_block as i64
+        )
+        .execute(db_pool)
+        .await?;
+        warn!(
+            "{} ZK proof requests after block {} orphaned until handled again",
+            res.rows_affected(),
+            from_block
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,23 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use alloy::{
     network::EthereumWallet,
     node_bindings::{Anvil, AnvilInstance},
-    primitives::U256,
-    providers::{Provider, ProviderBuilder, WsConnect},
+    primitives::{FixedBytes, Log as PrimitiveLog, B256, U256},
+    providers::{ext::AnvilApi, Provider, ProviderBuilder, WsConnect},
+    rpc::types::Log,
     signers::local::PrivateKeySigner,
     sol,
+    sol_types::SolEvent,
+};
+use gw_listener::{
+    gw_listener::GatewayListener,
+    handlers::{
+        ciphertext_commits::CiphertextCommitsHandler, decryption::DecryptionHandler,
+        multichain_acl::MultichainAclHandler, verify_proof::VerifyProofHandler, EventHandler,
+    },
+    ConfigSettings,
 };
-use gw_listener::{gw_listener::GatewayListener, ConfigSettings};
 use serial_test::serial;
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use tokio::time::sleep;
@@ -21,6 +30,24 @@ sol!(
     "artifacts/InputVerification.sol/InputVerification.json"
 );
 
+sol!(
+    #[sol(rpc)]
+    Decryption,
+    "artifacts/Decryption.sol/Decryption.json"
+);
+
+sol!(
+    #[sol(rpc)]
+    CiphertextCommits,
+    "artifacts/CiphertextCommits.sol/CiphertextCommits.json"
+);
+
+sol!(
+    #[sol(rpc)]
+    MultichainAcl,
+    "artifacts/MultichainAcl.sol/MultichainAcl.json"
+);
+
 struct TestEnvironment {
     wallet: EthereumWallet,
     conf: ConfigSettings,
@@ -46,10 +73,17 @@ impl TestEnvironment {
             .await?;
 
         // Delete all proofs from the database.
-        sqlx::query!("TRUNCATE verify_proofs",)
+        sqlx::query!("TRUNCATE verify_proofs, verify_proof_conflicts",)
             .execute(&db_pool)
             .await?;
 
+        // Delete other gateway events.
+        sqlx::query!(
+            "TRUNCATE gw_decryption_requests, gw_ciphertext_commits, gw_acl_confirmations",
+        )
+        .execute(&db_pool)
+        .await?;
+
         // Delete last block.
         sqlx::query!("TRUNCATE gw_listener_last_block",)
             .execute(&db_pool)
@@ -122,3 +156,378 @@ async fn verify_proof_request_inserted_into_db() -> anyhow::Result<()> {
     run_handle.await??;
     Ok(())
 }
+
+#[tokio::test]
+#[serial(db)]
+async fn gateway_events_inserted_into_db() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider = ProviderBuilder::new()
+        .wallet(env.wallet)
+        .on_ws(WsConnect::new(env.anvil.ws_endpoint_url()))
+        .await?;
+    let input_verification = InputVerification::deploy(&provider).await?;
+    let decryption = Decryption::deploy(&provider).await?;
+    let multichain_acl = MultichainAcl::deploy(&provider).await?;
+    let gw_listener = GatewayListener::new(
+        *input_verification.address(),
+        env.conf.clone(),
+        env.cancel_token.clone(),
+        provider.clone(),
+    )
+    .with_handler(Arc::new(DecryptionHandler::new(
+        *decryption.address(),
+        env.conf.decryption_req_db_channel.clone(),
+    )))
+    .with_handler(Arc::new(MultichainAclHandler::new(
+        *multichain_acl.address(),
+        env.conf.acl_confirmation_db_channel.clone(),
+    )));
+
+    let run_handle = tokio::spawn(async move { gw_listener.run().await });
+
+    let handle = FixedBytes::<32>::from([42u8; 32]);
+    let user_address = PrivateKeySigner::random().address();
+    let txn_reqs = [
+        decryption
+            .userDecryptionRequest(vec![handle], user_address, vec![7u8; 64].into())
+            .into_transaction_request(),
+        multichain_acl
+            .allowAccount(handle, user_address)
+            .into_transaction_request(),
+        multichain_acl
+            .allowPublicDecrypt(handle)
+            .into_transaction_request(),
+    ];
+    for txn_req in txn_reqs {
+        let pending_txn = provider.send_transaction(txn_req).await?;
+        let receipt = pending_txn.get_receipt().await?;
+        assert!(receipt.status());
+    }
+
+    loop {
+        let requests = sqlx::query!(
+            "SELECT is_user_decryption, handles, user_address, public_key
+             FROM gw_decryption_requests",
+        )
+        .fetch_all(&env.db_pool)
+        .await?;
+        let confirmations = sqlx::query!(
+            "SELECT event_type, account_address
+             FROM gw_acl_confirmations
+             WHERE handle = $1
+             ORDER BY event_type",
+            handle.as_slice(),
+        )
+        .fetch_all(&env.db_pool)
+        .await?;
+        if !requests.is_empty() && confirmations.len() == 2 {
+            assert!(requests[0].is_user_decryption);
+            assert_eq!(requests[0].handles, vec![handle.to_vec()]);
+            assert_eq!(requests[0].user_address, Some(user_address.to_string()));
+            assert_eq!(requests[0].public_key, Some(vec![7u8; 64]));
+            assert_eq!(confirmations[0].event_type, 0);
+            assert_eq!(confirmations[0].account_address, user_address.to_string());
+            assert_eq!(confirmations[1].event_type, 1);
+            assert_eq!(confirmations[1].account_address, "");
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+
+    env.cancel_token.cancel();
+    run_handle.await??;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn ciphertext_commits_inserted_into_db() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let sender_address = env.wallet.default_signer().address();
+    let provider = ProviderBuilder::new()
+        .wallet(env.wallet)
+        .on_ws(WsConnect::new(env.anvil.ws_endpoint_url()))
+        .await?;
+    let input_verification = InputVerification::deploy(&provider).await?;
+    let ciphertext_commits = CiphertextCommits::deploy(&provider).await?;
+    let gw_listener = GatewayListener::new(
+        *input_verification.address(),
+        env.conf.clone(),
+        env.cancel_token.clone(),
+        provider.clone(),
+    )
+    .with_handler(Arc::new(CiphertextCommitsHandler::new(
+        *ciphertext_commits.address(),
+        env.conf.ciphertext_commit_db_channel.clone(),
+    )));
+
+    let run_handle = tokio::spawn(async move { gw_listener.run().await });
+
+    // A later commit of the same handle updates its row.
+    let handle = FixedBytes::<32>::from([42u8; 32]);
+    let mut receipt = None;
+    for digest in [[1u8; 32], [2u8; 32]] {
+        let txn_req = ciphertext_commits
+            .addCiphertextMaterial(handle, U256::from(1), digest.into(), [3u8; 32].into())
+            .into_transaction_request();
+        let pending_txn = provider.send_transaction(txn_req).await?;
+        let txn_receipt = pending_txn.get_receipt().await?;
+        assert!(txn_receipt.status());
+        receipt = Some(txn_receipt);
+    }
+    let receipt = receipt.unwrap();
+
+    loop {
+        let commits = sqlx::query!(
+            "SELECT ciphertext_digest, sns_ciphertext_digest, coprocessor_tx_sender_addresses, block_number, transaction_hash
+             FROM gw_ciphertext_commits
+             WHERE handle = $1",
+            handle.as_slice(),
+        )
+        .fetch_all(&env.db_pool)
+        .await?;
+        if commits.len() == 1 && commits[0].ciphertext_digest == vec![2u8; 32] {
+            assert_eq!(commits[0].sns_ciphertext_digest, vec![3u8; 32]);
+            assert_eq!(
+                commits[0].coprocessor_tx_sender_addresses,
+                vec![sender_address.to_string()]
+            );
+            assert_eq!(
+                commits[0].block_number,
+                receipt.block_number.map(|n| n as i64)
+            );
+            assert_eq!(
+                commits[0].transaction_hash,
+                Some(receipt.transaction_hash.to_vec())
+            );
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+
+    env.cancel_token.cancel();
+    run_handle.await??;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn verify_proof_request_caught_up_from_last_block() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider = ProviderBuilder::new()
+        .wallet(env.wallet)
+        .on_ws(WsConnect::new(env.anvil.ws_endpoint_url()))
+        .await?;
+    let input_verification = InputVerification::deploy(&provider).await?;
+
+    // Emit the request while the listener is not running.
+    let txn_req = input_verification
+        .verifyProofRequest(
+            U256::from(42),
+            PrivateKeySigner::random().address(),
+            PrivateKeySigner::random().address(),
+            (&[1u8; 64]).into(),
+        )
+        .into_transaction_request();
+    let pending_txn = provider.send_transaction(txn_req).await?;
+    let receipt = pending_txn.get_receipt().await?;
+    assert!(receipt.status());
+    let event_block = receipt.block_number.unwrap();
+
+    sqlx::query!("INSERT INTO gw_listener_last_block (dummy_id, last_block_num) VALUES (true, 0)")
+        .execute(&env.db_pool)
+        .await?;
+
+    // Use small ranges to go through several historical queries.
+    let mut conf = env.conf.clone();
+    conf.catchup_blocks_per_request = 2;
+    let gw_listener = GatewayListener::new(
+        *input_verification.address(),
+        conf,
+        env.cancel_token.clone(),
+        provider.clone(),
+    );
+    let run_handle = tokio::spawn(async move { gw_listener.run().await });
+
+    loop {
+        let count = sqlx::query_scalar!("SELECT COUNT(*) FROM verify_proofs")
+            .fetch_one(&env.db_pool)
+            .await?;
+        let last_block = sqlx::query_scalar!(
+            "SELECT last_block_num FROM gw_listener_last_block WHERE dummy_id = true"
+        )
+        .fetch_one(&env.db_pool)
+        .await?;
+        if count == Some(1) && last_block >= Some(event_block as i64) {
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+
+    env.cancel_token.cancel();
+    run_handle.await??;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn verify_proof_request_orphaned_by_reorg_while_stopped() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider = ProviderBuilder::new()
+        .wallet(env.wallet)
+        .on_ws(WsConnect::new(env.anvil.ws_endpoint_url()))
+        .await?;
+    let input_verification = InputVerification::deploy(&provider).await?;
+    let snapshot = provider.anvil_snapshot().await?;
+    sleep(Duration::from_secs(2)).await;
+
+    let new_gw_listener = |cancel_token: CancellationToken| {
+        GatewayListener::new(
+            *input_verification.address(),
+            env.conf.clone(),
+            cancel_token,
+            provider.clone(),
+        )
+    };
+    let last_block = || async {
+        sqlx::query_scalar!(
+            "SELECT last_block_num FROM gw_listener_last_block WHERE dummy_id = true"
+        )
+        .fetch_optional(&env.db_pool)
+        .await
+        .map(Option::flatten)
+    };
+    let gw_listener = new_gw_listener(env.cancel_token.clone());
+    let run_handle = tokio::spawn(async move { gw_listener.run().await });
+
+    let txn_req = input_verification
+        .verifyProofRequest(
+            U256::from(42),
+            PrivateKeySigner::random().address(),
+            PrivateKeySigner::random().address(),
+            (&[1u8; 64]).into(),
+        )
+        .into_transaction_request();
+    let receipt = provider
+        .send_transaction(txn_req)
+        .await?
+        .get_receipt()
+        .await?;
+    assert!(receipt.status());
+    let event_block = receipt.block_number.unwrap();
+    // Wait for the request and a newer last block.
+    loop {
+        let count = sqlx::query_scalar!("SELECT COUNT(*) FROM verify_proofs")
+            .fetch_one(&env.db_pool)
+            .await?;
+        if count == Some(1) && last_block().await? >= Some(event_block as i64 - 1) {
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    env.cancel_token.cancel();
+    run_handle.await??;
+
+    // While stopped, the blocks of the request are replaced by other ones.
+    assert!(provider.anvil_revert(snapshot).await?);
+    let stopped_last_block = last_block().await?.unwrap() as u64;
+    while provider.get_block_number().await? <= stopped_last_block {
+        sleep(Duration::from_millis(500)).await;
+    }
+
+    let cancel_token = CancellationToken::new();
+    let gw_listener = new_gw_listener(cancel_token.clone());
+    let run_handle = tokio::spawn(async move { gw_listener.run().await });
+    loop {
+        let orphaned = sqlx::query_scalar!("SELECT orphaned FROM verify_proofs")
+            .fetch_one(&env.db_pool)
+            .await?;
+        if orphaned {
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    cancel_token.cancel();
+    run_handle.await??;
+    Ok(())
+}
+
+fn verify_proof_request_log(input: &[u8], block_hash: B256) -> Log {
+    let event = InputVerification::VerifyProofRequest {
+        zkProofId: U256::from(7),
+        contractChainId: U256::from(42),
+        contractAddress: PrivateKeySigner::random().address(),
+        userAddress: PrivateKeySigner::random().address(),
+        ciphertextWithZKProof: input.to_vec().into(),
+    };
+    Log {
+        inner: PrimitiveLog {
+            address: PrivateKeySigner::random().address(),
+            data: event.encode_log_data(),
+        },
+        block_hash: Some(block_hash),
+        block_number: Some(1),
+        log_index: Some(0),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn verify_proof_request_reemitted_after_reorg() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let handler = VerifyProofHandler::new(
+        PrivateKeySigner::random().address(),
+        env.conf.verify_proof_req_db_channel.clone(),
+    );
+
+    let log = verify_proof_request_log(&[1u8; 64], B256::repeat_byte(1));
+    handler.handle(&env.db_pool, &log).await?;
+    sqlx::query!(
+        "UPDATE verify_proofs SET verified = true, handles = $1",
+        &[0u8; 32][..]
+    )
+    .execute(&env.db_pool)
+    .await?;
+
+    // Received again from the same block, nothing changes.
+    handler.handle(&env.db_pool, &log).await?;
+    let row = sqlx::query!("SELECT verified FROM verify_proofs WHERE zk_proof_id = 7")
+        .fetch_one(&env.db_pool)
+        .await?;
+    assert_eq!(row.verified, Some(true));
+
+    // The original block is orphaned.
+    let mut removed_log = log.clone();
+    removed_log.removed = true;
+    handler.handle_removed(&env.db_pool, &removed_log).await?;
+    let row = sqlx::query!("SELECT orphaned FROM verify_proofs WHERE zk_proof_id = 7")
+        .fetch_one(&env.db_pool)
+        .await?;
+    assert!(row.orphaned);
+
+    // Re-emitted in the new chain with a different payload, it must be verified again.
+    let new_log = verify_proof_request_log(&[2u8; 64], B256::repeat_byte(2));
+    handler.handle(&env.db_pool, &new_log).await?;
+    let row = sqlx::query!(
+        "SELECT verified, handles, input, block_hash, orphaned FROM verify_proofs WHERE zk_proof_id = 7"
+    )
+    .fetch_one(&env.db_pool)
+    .await?;
+    assert_eq!(row.verified, None);
+    assert_eq!(row.handles, None);
+    assert_eq!(row.input, Some(vec![2u8; 64]));
+    assert_eq!(row.block_hash, Some(B256::repeat_byte(2).to_vec()));
+    assert!(!row.orphaned);
+
+    let conflicts = sqlx::query!(
+        "SELECT input, verified, block_hash FROM verify_proof_conflicts WHERE zk_proof_id = 7"
+    )
+    .fetch_all(&env.db_pool)
+    .await?;
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].input, Some(vec![1u8; 64]));
+    assert_eq!(conflicts[0].verified, Some(true));
+    assert_eq!(conflicts[0].block_hash, Some(B256::repeat_byte(1).to_vec()));
+    Ok(())
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use fhevm_engine_common::utils::compact_hex;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::aws_upload::compute_digest;
+use crate::ct128_list::Ct128List;
+use crate::ObjectStore;
+use crate::{ExecutionError, S3Config};
+
+#[derive(Clone, Debug)]
+pub struct AuditConfig {
+    /// Fraction of the uploaded ciphertexts that are audited, all if 1
+    pub sample_rate: f64,
+    pub batch_size: u32,
+    /// Re-upload the ct64 objects from the database, the ct128 are not kept
+    /// after their upload and are reported as unrepairable
+    pub repair: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FindingKind {
+    MissingCt64,
+    CorruptedCt64,
+    MissingCt128,
+    CorruptedCt128,
+    /// The digest differs from the one committed on the Gateway
+    CommitMismatch,
+}
+
+impl FindingKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MissingCt64 => "missing_ct64",
+            Self::CorruptedCt64 => "corrupted_ct64",
+            Self::MissingCt128 => "missing_ct128",
+            Self::CorruptedCt128 => "corrupted_ct128",
+            Self::CommitMismatch => "commit_mismatch",
+        }
+    }
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    pub audited: u64,
+    pub findings: u64,
+    pub repaired: u64,
+}
+
+/// Scans or samples the uploaded ciphertexts, checks that the stored objects
+/// exist and match their digests in `ciphertext_digest` and that these digests
+/// match the ones committed on the Gateway.
+///
+/// The ct128 packed in the list of their decryption batch are checked in the
+/// list, at their recorded position.
+///
+/// Each mismatch is recorded in `ciphertext_audit_findings`. With `repair`,
+/// the ct64 objects whose content is still in the `ciphertexts` table are
+/// re-uploaded.
+pub async fn audit_ciphertexts(
+    pool: &PgPool,
+    store: &dyn ObjectStore,
+    conf: &S3Config,
+    audit: &AuditConfig,
+    token: CancellationToken,
+) -> Result<AuditReport, ExecutionError> {
+    info!(target: "audit", "Starting audit with {:?}", audit);
+
+    let mut report = AuditReport::default();
+    let mut cursor: (i32, Vec<u8>) = (i32::MIN, vec![]);
+
+    while !token.is_cancelled() {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                d.tenant_id,
+                d.handle,
+                d.ciphertext AS ct64_digest,
+                d.ciphertext128 AS ct128_digest,
+                d.ciphertext128_list AS ct128_list_digest,
+                d.ciphertext128_list_index AS ct128_list_index,
+                g.ciphertext_digest AS "committed_ct64_digest?",
+                g.sns_ciphertext_digest AS "committed_ct128_digest?"
+            FROM ciphertext_digest d
+            JOIN tenants t ON t.tenant_id = d.tenant_id
+            -- The Gateway commits are per handle, which belongs to the tenant
+            -- of the host chain encoded in its bytes 22..30
+            LEFT JOIN gw_ciphertext_commits g
+            ON g.handle = d.handle
+            AND substring(g.handle FROM 23 FOR 8) = int8send(t.chain_id::BIGINT)
+            WHERE (d.tenant_id, d.handle) > ($1, $2)
+            AND (d.ciphertext IS NOT NULL OR d.ciphertext128 IS NOT NULL)
+            AND random() < $3
+            ORDER BY d.tenant_id, d.handle
+            LIMIT $4
+            "#,
+            cursor.0,
+            cursor.1,
+            audit.sample_rate,
+            audit.batch_size as i64,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        // The next page starts after the last sampled row, the rows skipped
+        // in between were not sampled
+        let Some(last) = rows.last() else {
+            break;
+        };
+        cursor = (last.tenant_id, last.handle.clone());
+
+        // The lists of the decryption batches of the page, fetched once
+        let mut lists = HashMap::new();
+
+        for row in rows {
+            report.audited += 1;
+            let item = AuditedItem {
+                tenant_id: row.tenant_id,
+                handle: row.handle,
+            };
+
+            for (digest, committed, is_ct128) in [
+                (row.ct64_digest, row.committed_ct64_digest, false),
+                (row.ct128_digest, row.committed_ct128_digest, true),
+            ] {
+                let Some(digest) = digest else {
+                    continue;
+                };
+
+                if committed.as_ref().is_some_and(|c| *c != digest) {
+                    item.record(
+                        pool,
+                        FindingKind::CommitMismatch,
+                        &format!(
+                            "{} digest {} committed as {}",
+                            if is_ct128 { "ct128" } else { "ct64" },
+                            hex::encode(&digest),
+                            hex::encode(committed.unwrap_or_default())
+                        ),
+                        false,
+                        &mut report,
+                    )
+                    .await?;
+                }
+
+                let bucket = if is_ct128 {
+                    &conf.bucket_ct128
+                } else {
+                    &conf.bucket_ct64
+                };
+                let (kind, object) = match (is_ct128, &row.ct128_list_digest) {
+                    (true, Some(list_digest)) => {
+                        let index = row.ct128_list_index.unwrap_or_default();
+                        let key = hex::encode(list_digest);
+                        if !lists.contains_key(list_digest) {
+                            let list = ListObject::fetch(store, bucket, list_digest).await?;
+                            lists.insert(list_digest.clone(), list);
+                        }
+                        let kind = match &lists[list_digest] {
+                            ListObject::Missing => FindingKind::MissingCt128,
+                            ListObject::Corrupted => FindingKind::CorruptedCt128,
+                            ListObject::Valid(list) => match list.get(index as usize) {
+                                Some((handle, ct128))
+                                    if handle == item.handle && compute_digest(ct128) == digest =>
+                                {
+                                    continue
+                                }
+                                _ => FindingKind::CorruptedCt128,
+                            },
+                        };
+                        (kind, format!("{}/{} at {}", bucket, key, index))
+                    }
+                    _ => {
+                        let key = hex::encode(&digest);
+                        let kind = match store.get(bucket, &key).await? {
+                            None if is_ct128 => FindingKind::MissingCt128,
+                            None => FindingKind::MissingCt64,
+                            Some(object) if compute_digest(&object) == digest => continue,
+                            Some(_) if is_ct128 => FindingKind::CorruptedCt128,
+                            Some(_) => FindingKind::CorruptedCt64,
+                        };
+                        (kind, format!("{}/{}", bucket, key))
+                    }
+                };
+
+                // The ct128 is dropped from the database once uploaded
+                let repaired =
+                    audit.repair && !is_ct128 && item.repair(pool, store, bucket, &digest).await?;
+                item.record(
+                    pool,
+                    kind,
+                    &format!(
+                        "object {}{}",
+                        object,
+                        if is_ct128 { ", unrepairable" } else { "" }
+                    ),
+                    repaired,
+                    &mut report,
+                )
+                .await?;
+            }
+        }
+    }
+
+    info!(target: "audit", "Audit finished: {:?}", report);
+    Ok(report)
+}
+
+/// List object of the ct128 of a decryption batch
+enum ListObject {
+    Missing,
+    /// Does not match its digest or cannot be decoded
+    Corrupted,
+    Valid(Ct128List),
+}
+
+impl ListObject {
+    async fn fetch(
+        store: &dyn ObjectStore,
+        bucket: &str,
+        digest: &[u8],
+    ) -> Result<Self, ExecutionError> {
+        Ok(match store.get(bucket, &hex::encode(digest)).await? {
+            None => Self::Missing,
+            Some(object) if compute_digest(&object) != digest => Self::Corrupted,
+            Some(object) => Ct128List::decode(&object).map_or(Self::Corrupted, Self::Valid),
+        })
+    }
+}
+
+struct AuditedItem {
+    tenant_id: i32,
+    handle: Vec<u8>,
+}
+
+impl AuditedItem {
+    /// Re-uploads the ct64 object from the database copy of the ciphertext if
+    /// it still matches the digest.
+    async fn repair(
+        &self,
+        pool: &PgPool,
+        store: &dyn ObjectStore,
+        bucket: &str,
+        digest: &[u8],
+    ) -> Result<bool, ExecutionError> {
+        let ct = sqlx::query_scalar!(
+            "
+            SELECT ciphertext
+            FROM ciphertexts
+            WHERE tenant_id = $1 AND handle = $2
+            ORDER BY ciphertext_version DESC
+            LIMIT 1
+            ",
+            self.tenant_id,
+            self.handle,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        match ct {
+            Some(ct) if compute_digest(&ct) == digest => {
+                store.put(bucket, &hex::encode(digest), ct).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn record(
+        &self,
+        pool: &PgPool,
+        kind: FindingKind,
+        detail: &str,
+        repaired: bool,
+        report: &mut AuditReport,
+    ) -> Result<(), ExecutionError> {
+        warn!(target: "audit", {action = "review", tenant_id = self.tenant_id, repaired},
+            "Audit finding {} for handle {}: {}", kind, compact_hex(&self.handle), detail
+        );
+
+        sqlx::query!(
+            "
+            INSERT INTO ciphertext_audit_findings (tenant_id, handle, kind, detail, repaired)
+            VALUES ($1, $2, $3, $4, $5)
+            ",
+            self.tenant_id,
+            self.handle,
+            kind.as_str(),
+            detail,
+            repaired,
+        )
+        .execute(pool)
+        .await?;
+
+        report.findings += 1;
+        if repaired {
+            report.repaired += 1;
+        }
+        Ok(())
+    }
+}
@@ -1,13 +1,13 @@
-use crate::{Config, ExecutionError, HandleItem, S3Config};
-use aws_config::BehaviorVersion;
-use aws_sdk_s3::Client;
+use crate::ct128_list::Ct128List;
+use crate::ObjectStore;
+use crate::{Config, Ct128Format, ExecutionError, HandleItem, S3Config};
 use bytesize::ByteSize;
 use fhevm_engine_common::telemetry::{self};
 use fhevm_engine_common::utils::compact_hex;
 use sha3::{Digest, Keccak256};
 
 use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use tokio::sync::{mpsc, Semaphore};
@@ -20,17 +20,25 @@ use tracing::{debug, error, info, warn};
 pub const EVENT_CIPHERTEXTS_UPLOADED: &str = "event_ciphertexts_uploaded";
 pub const UPLOAD_TIMEOUT_DURATION: Duration = Duration::from_secs(10);
 
-/// Process the S3 uploads
-pub(crate) async fn process_s3_uploads(
+/// Time during which a started upload is not retried, long enough for the
+/// upload of a ct128 queued in the channel to complete or fail
+pub const UPLOAD_LEASE_DURATION: Duration = Duration::from_secs(60);
+
+/// Attempts after which a handle waiting for the other handles of its
+/// decryption batch to be squashed gives up packing its ct128 in a list
+pub const MAX_DECRYPTION_BATCH_WAITS: i32 = 5;
+
+/// Process the uploads to the object store
+///
+/// The uploads are received from the SnS worker through `tasks` and retried
+/// from the `ciphertext_digest` rows with a missing digest, so that a crash or
+/// an outage of the object store does not lose any ciphertext.
+pub(crate) async fn process_uploads(
     conf: &Config,
+    store: Arc<dyn ObjectStore>,
     mut tasks: mpsc::Receiver<HandleItem>,
     token: CancellationToken,
 ) -> Result<(), ExecutionError> {
-    // Client construction is expensive due to connection thread pool initialization, and should
-    // be done once at application start-up.
-    let sdk_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = Arc::new(aws_sdk_s3::Client::new(&sdk_config));
-
     let pool = Arc::new(
         PgPoolOptions::new()
             .max_connections(conf.db.max_connections)
@@ -43,46 +51,40 @@ pub(crate) async fn process_s3_uploads(
     let semaphore = Arc::new(Semaphore::new(max_concurrent_uploads));
     let mut upload_jobs: Vec<JoinHandle<()>> = Vec::new();
 
+    let reconciled = reconcile_uploads(&pool).await?;
+    info!(
+        "Reconciled uploads, missing ciphertext_digest rows: {}",
+        reconciled
+    );
+
+    // The first tick is immediate, retrying the pending uploads at startup
+    let mut retry_interval = tokio::time::interval(Duration::from_secs(
+        conf.retry_polling_interval.max(1).into(),
+    ));
+
     loop {
-        select! {
+        let tasks_to_upload = select! {
             task = tasks.recv() => {
-                let task = match task {
-                    Some(task) => task,
+                match task {
+                    Some(task) => vec![task],
                     None => return Ok(()),
-                };
-
+                }
+            },
+            _ = retry_interval.tick() => {
                 // Cleanup completed tasks
                 upload_jobs.retain(|h| !h.is_finished());
 
-                // Check if we have reached the max concurrent uploads
-                if upload_jobs.len() >= max_concurrent_uploads {
-                    warn!({target = "worker", action = "review"},
-                        "Max concurrent uploads reached: {}, waiting for a slot ...",
-                        max_concurrent_uploads
-                    );
-                } else {
-                    debug!(
-                        "Available upload slots: {}",
-                        max_concurrent_uploads - upload_jobs.len(),
-                    );
+                let available = max_concurrent_uploads.saturating_sub(upload_jobs.len());
+                if available == 0 {
+                    continue;
+                }
+                match claim_pending_uploads(&pool, available as i64, conf).await {
+                    Ok(tasks) => tasks,
+                    Err(err) => {
+                        error!("Failed to fetch pending uploads: {}", err);
+                        continue;
+                    }
                 }
-
-                // Acquire a permit for an upload
-                let permit = semaphore.clone().acquire_owned().await.expect("Failed to acquire semaphore permit");
-                let client = client.clone();
-                let pool = pool.clone();
-                let conf = conf.clone();
-
-                // Spawn a new task to upload the ciphertexts
-                let h = tokio::spawn(async move {
-                        if let Err(err) = upload_ciphertexts(task, &client, &pool, &conf).await {
-                            error!("Failed to upload ciphertexts: {}", err);
-                            // TODO: Implement retry-mechanism.
-                        }
-                        drop(permit);
-                });
-
-                upload_jobs.push(h);
             },
             _ = token.cancelled() => {
                 // Cleanup completed tasks
@@ -97,66 +99,278 @@ pub(crate) async fn process_s3_uploads(
 
                 return Ok(())
             },
+        };
+
+        for task in tasks_to_upload {
+            // Cleanup completed tasks
+            upload_jobs.retain(|h| !h.is_finished());
+
+            // Check if we have reached the max concurrent uploads
+            if upload_jobs.len() >= max_concurrent_uploads {
+                warn!({target = "worker", action = "review"},
+                    "Max concurrent uploads reached: {}, waiting for a slot ...",
+                    max_concurrent_uploads
+                );
+            } else {
+                debug!(
+                    "Available upload slots: {}",
+                    max_concurrent_uploads - upload_jobs.len(),
+                );
+            }
+
+            // Acquire a permit for an upload
+            let permit = semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("Failed to acquire semaphore permit");
+            let store = store.clone();
+            let pool = pool.clone();
+            let conf = conf.clone();
+
+            // Spawn a new task to upload the ciphertexts
+            let h = tokio::spawn(async move {
+                if let Err(err) = upload_ciphertexts(task, store.as_ref(), &pool, &conf).await {
+                    // Retried once the backoff or the lease expires
+                    error!("Failed to upload ciphertexts: {}", err);
+                }
+                drop(permit);
+            });
+
+            upload_jobs.push(h);
         }
     }
 }
 
-/// Uploads both 128-bit bootstrapped ciphertext and regular ciphertext to S3
-/// buckets. If successful, it stores their digests in the database.
+/// Inserts the missing `ciphertext_digest` rows of the ct128 computed but never
+/// uploaded, e.g. computed before a crash, so that they are retried.
+///
+/// Returns the number of inserted rows.
+pub(crate) async fn reconcile_uploads(pool: &PgPool) -> Result<u64, ExecutionError> {
+    let res = sqlx::query!(
+        "
+        INSERT INTO ciphertext_digest (tenant_id, handle)
+        SELECT DISTINCT c.tenant_id, c.handle
+        FROM ciphertexts c
+        WHERE c.ciphertext128 IS NOT NULL
+        AND NOT EXISTS (
+            SELECT 1 FROM ciphertext_digest d
+            WHERE d.tenant_id = c.tenant_id AND d.handle = c.handle
+        )
+        ON CONFLICT DO NOTHING
+        ",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Leases the pending uploads whose backoff expired and returns them with
+/// the ciphertexts to upload.
+///
+/// The ct128 is None if it has already been uploaded.
+pub(crate) async fn claim_pending_uploads(
+    pool: &PgPool,
+    limit: i64,
+    conf: &S3Config,
+) -> Result<Vec<HandleItem>, ExecutionError> {
+    let records = sqlx::query!(
+        r#"
+        WITH due AS (
+            SELECT tenant_id, handle
+            FROM ciphertext_digest
+            WHERE (ciphertext IS NULL OR ciphertext128 IS NULL)
+            AND upload_next_attempt_at <= NOW()
+            ORDER BY upload_next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        ),
+        claimed AS (
+            UPDATE ciphertext_digest d
+            SET upload_next_attempt_at = NOW() + make_interval(secs => $2)
+            FROM due
+            WHERE d.tenant_id = due.tenant_id AND d.handle = due.handle
+            RETURNING d.tenant_id, d.handle, d.upload_retry_count
+        )
+        SELECT DISTINCT ON (claimed.tenant_id, claimed.handle)
+            claimed.tenant_id AS "tenant_id!",
+            claimed.handle AS "handle!",
+            claimed.upload_retry_count AS "upload_retry_count!",
+            c.ciphertext AS "ciphertext?",
+            c.ciphertext128 AS "ciphertext128?",
+            c.ciphertext128_format AS "ciphertext128_format?"
+        FROM claimed
+        LEFT JOIN ciphertexts c
+        ON c.tenant_id = claimed.tenant_id AND c.handle = claimed.handle
+        ORDER BY claimed.tenant_id, claimed.handle, c.ciphertext_version DESC
+        "#,
+        limit,
+        UPLOAD_LEASE_DURATION.as_secs_f64(),
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if !records.is_empty() {
+        info!("Retrying pending uploads, count: {}", records.len());
+    }
+
+    let mut tasks = Vec::with_capacity(records.len());
+    for record in records {
+        let Some(ct64_compressed) = record.ciphertext else {
+            let handle_as_hex = compact_hex(&record.handle);
+            warn!({target = "worker", action = "review"},
+                "Ciphertext of pending upload not found, handle: {}", handle_as_hex
+            );
+            record_upload_failure(
+                pool,
+                record.tenant_id,
+                &record.handle,
+                "ciphertext not found",
+                conf,
+            )
+            .await?;
+            continue;
+        };
+        debug!(
+            "Retrying upload, handle: {}, retry_count: {}",
+            compact_hex(&record.handle),
+            record.upload_retry_count
+        );
+        if let Some(Err(err)) = record.ciphertext128_format.map(Ct128Format::try_from) {
+            record_upload_failure(
+                pool,
+                record.tenant_id,
+                &record.handle,
+                &err.to_string(),
+                conf,
+            )
+            .await?;
+            continue;
+        }
+        tasks.push(HandleItem {
+            tenant_id: record.tenant_id,
+            handle: record.handle.clone(),
+            ct64_compressed,
+            ct128_uncompressed: record.ciphertext128,
+            otel: telemetry::tracer_with_handle("retry_upload", record.handle),
+        });
+    }
+
+    Ok(tasks)
+}
+
+/// Uploads both 128-bit bootstrapped ciphertext and regular ciphertext to the
+/// object store, keyed by their digests. If successful, it stores their digests
+/// in the database.
 ///
 /// Guarantees:
 /// - If the upload of the 128-bit ciphertext fails, the function will not store
 ///   its digest in the database.
 /// - If the upload of the regular ciphertext fails, the function will not store
 ///   its digest in the database.
-async fn upload_ciphertexts(
+/// - The ciphertexts whose digest is already stored are not uploaded again.
+/// - If an upload fails, the next attempt is scheduled with an exponential
+///   backoff.
+/// - With `pack_decryption_batches`, the ct128 of the handles of a decryption
+///   batch are only uploaded as one list, once all of them are squashed. The
+///   digest of the ct128 of each handle is still recorded, to be committed to
+///   the Gateway.
+pub(crate) async fn upload_ciphertexts(
     task: HandleItem,
-    client: &Client,
+    store: &dyn ObjectStore,
     pool: &PgPool,
     conf: &S3Config,
 ) -> Result<(), ExecutionError> {
     let handle_as_hex: String = compact_hex(&task.handle);
     info!("Received uploading task, handle: {}", handle_as_hex);
 
-    let ct128_bytes = match task.ct128_uncompressed {
-        Some(ct) => ct,
-        None => {
-            return Err(ExecutionError::MissingCiphertext128(handle_as_hex));
+    let uploaded = sqlx::query!(
+        r#"
+        SELECT
+            ciphertext IS NOT NULL AS "ct64_uploaded!",
+            ciphertext128 IS NOT NULL AS "ct128_uploaded!",
+            decryption_batch_waits
+        FROM ciphertext_digest
+        WHERE tenant_id = $1 AND handle = $2
+        "#,
+        task.tenant_id,
+        task.handle,
+    )
+    .fetch_optional(pool)
+    .await?;
+    let (ct64_already_uploaded, ct128_already_uploaded, batch_waits) = uploaded
+        .map(|r| (r.ct64_uploaded, r.ct128_uploaded, r.decryption_batch_waits))
+        .unwrap_or_default();
+
+    if ct64_already_uploaded && ct128_already_uploaded {
+        debug!("Ciphertexts already uploaded, handle: {}", handle_as_hex);
+        return Ok(());
+    }
+
+    let list = if conf.pack_decryption_batches && !ct128_already_uploaded {
+        fetch_ct128_list(pool, task.tenant_id, &task.handle, batch_waits).await?
+    } else {
+        ListUpload::Alone
+    };
+
+    // The ct128 is uploaded alone only if not packed in the list of its batch
+    let upload_alone = !ct128_already_uploaded && matches!(list, ListUpload::Alone);
+    let ct128_bytes = match (task.ct128_uncompressed, upload_alone) {
+        (_, false) => vec![],
+        (Some(ct), true) => ct,
+        (None, true) => {
+            let err = ExecutionError::MissingCiphertext128(handle_as_hex);
+            record_upload_failure(pool, task.tenant_id, &task.handle, &err.to_string(), conf)
+                .await?;
+            return Err(err);
         }
     };
+    let list_bytes = match &list {
+        ListUpload::Ready(list) => list.encode()?,
+        _ => vec![],
+    };
 
     let s = task.otel.child_span("compute_digest");
     let ct128_digest = compute_digest(&ct128_bytes);
     let ct64_digest = compute_digest(&task.ct64_compressed);
+    let list_digest = compute_digest(&list_bytes);
     telemetry::end_span(s);
 
     info!(
-        "Start uploading task, handle: {}, tenant_id: {}, ct128_len: {}, ct64_compressed_len: {}",
+        "Start uploading task, handle: {}, tenant_id: {}, ct128_len: {}, ct64_compressed_len: {}, list_len: {}",
         handle_as_hex,
         task.tenant_id,
         ByteSize::b(ct128_bytes.len() as u64),
-        ByteSize::b(task.ct64_compressed.len() as u64)
+        ByteSize::b(task.ct64_compressed.len() as u64),
+        ByteSize::b(list_bytes.len() as u64)
     );
 
     let s = task.otel.child_span("s3_upload");
-    let (up1, up2) = join!(
-        tokio::time::timeout(
+    let (up1, up2, up3) = join!(
+        put_unless_uploaded(
+            store,
+            !upload_alone,
+            &conf.bucket_ct128,
+            &ct128_digest,
+            ct128_bytes,
             3 * UPLOAD_TIMEOUT_DURATION,
-            client
-                .put_object()
-                .bucket(conf.bucket_ct128.clone())
-                .key(hex::encode(&ct128_digest))
-                .body(ct128_bytes.into())
-                .send(),
         ),
-        tokio::time::timeout(
+        put_unless_uploaded(
+            store,
+            ct64_already_uploaded,
+            &conf.bucket_ct64,
+            &ct64_digest,
+            task.ct64_compressed,
             UPLOAD_TIMEOUT_DURATION,
-            client
-                .put_object()
-                .bucket(conf.bucket_ct64.clone())
-                .key(hex::encode(&ct64_digest))
-                .body(task.ct64_compressed.into())
-                .send(),
+        ),
+        put_unless_uploaded(
+            store,
+            !matches!(list, ListUpload::Ready(_)),
+            &conf.bucket_ct128,
+            &list_digest,
+            list_bytes,
+            3 * UPLOAD_TIMEOUT_DURATION,
         )
     );
 
@@ -166,29 +380,33 @@ async fn upload_ciphertexts(
 
     sqlx::query!(
         "INSERT INTO ciphertext_digest (tenant_id, handle)
-        VALUES ($1, $2)",
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING",
         task.tenant_id,
         task.handle,
     )
     .execute(trx.as_mut())
     .await?;
 
-    let mut ct128_uploaded = false;
+    let mut errors = vec![];
+
+    let mut ct128_uploaded = ct128_already_uploaded;
     // Insert digest for ct128 only if ct128 upload was successful
     match &up1 {
-        Ok(Ok(_)) => {
+        Ok(true) => {
             ct128_uploaded = true;
             sqlx::query!(
                 "UPDATE ciphertext_digest
-                 SET ciphertext128 = $1
+                 SET ciphertext128 = $1, ciphertext128_format = $3
                  WHERE handle = $2",
                 ct128_digest,
-                task.handle
+                task.handle,
+                Ct128Format::Raw.as_i16(),
             )
             .execute(trx.as_mut())
             .await?;
 
-            // Reset ciphertext128 as the ct128 has been successfully uploaded to S3
+            // Reset ciphertext128 as the ct128 has been successfully uploaded
             // NB: For reclaiming the disk-space in DB, we rely on auto vacuuming in
             // Postgres
             sqlx::query!(
@@ -200,24 +418,20 @@ async fn upload_ciphertexts(
             .execute(trx.as_mut())
             .await?;
         }
-        Ok(Err(err)) => {
-            error!(
-                "Failed to upload ct128, handle: {}, err: {}",
-                handle_as_hex, err
-            );
-        }
+        Ok(false) => {}
         Err(err) => {
             error!(
                 "Failed to upload ct128, handle: {}, err: {}",
                 handle_as_hex, err
             );
+            errors.push(format!("ct128: {}", err));
         }
     };
 
     // Insert digest for ct64 only if ct64 upload was successful
-    let mut ct64_uploaded = false;
+    let mut ct64_uploaded = ct64_already_uploaded;
     match &up2 {
-        Ok(Ok(_)) => {
+        Ok(true) => {
             ct64_uploaded = true;
             sqlx::query!(
                 "UPDATE ciphertext_digest
@@ -229,18 +443,36 @@ async fn upload_ciphertexts(
             .execute(trx.as_mut())
             .await?;
         }
-        Ok(Err(err)) => {
+        Ok(false) => {}
+        Err(err) => {
             error!(
                 "Failed to upload ct64, handle: {}, err: {}",
                 handle_as_hex, err
             );
+            errors.push(format!("ct64: {}", err));
         }
-        Err(err) => {
+    }
+
+    match (&list, &up3) {
+        (ListUpload::Ready(list), Ok(_)) => {
+            ct128_uploaded = true;
+            record_ct128_list(&mut trx, task.tenant_id, list, &list_digest).await?;
+        }
+        (ListUpload::Ready(_), Err(err)) => {
             error!(
-                "Failed to upload ct64, handle: {}, err: {}",
+                "Failed to upload ct128 list, handle: {}, err: {}",
                 handle_as_hex, err
             );
+            errors.push(format!("ct128 list: {}", err));
+        }
+        (ListUpload::Waiting(count), _) => {
+            info!(
+                "Waiting for the ct128 of {} handles of the decryption batch, handle: {}",
+                count, handle_as_hex
+            );
+            record_decryption_batch_wait(trx.as_mut(), task.tenant_id, &task.handle, conf).await?;
         }
+        (ListUpload::Alone, _) => {}
     }
 
     // If both uploads are successful, notify the Transaction Sender
@@ -251,13 +483,32 @@ async fn upload_ciphertexts(
             .await?;
 
         info!(
-            "Uploaded to S3, handle = {}, ct64_digest = {}, ct128_digest = {}",
+            "Uploaded, handle = {}, ct64_digest = {}, ct128_digest = {}",
             handle_as_hex,
             compact_hex(&ct64_digest),
-            compact_hex(&ct128_digest)
+            if ct128_already_uploaded {
+                "already uploaded".to_owned()
+            } else if upload_alone {
+                compact_hex(&ct128_digest)
+            } else {
+                format!("in list {}", compact_hex(&list_digest))
+            }
         );
+    }
+
+    if errors.is_empty() && ct128_uploaded && ct64_uploaded {
         telemetry::end_span_with_timestamp(s, upload_finish_time);
+    } else if errors.is_empty() && matches!(list, ListUpload::Waiting(_)) {
+        telemetry::end_span(s);
     } else {
+        record_upload_failure(
+            trx.as_mut(),
+            task.tenant_id,
+            &task.handle,
+            &errors.join(", "),
+            conf,
+        )
+        .await?;
         telemetry::end_span_with_err(
             s,
             format!(
@@ -272,7 +523,211 @@ async fn upload_ciphertexts(
     Ok(())
 }
 
-fn compute_digest(ct: &[u8]) -> Vec<u8> {
+/// Upload of the ct128 of a handle in the list of its decryption batch
+enum ListUpload {
+    /// Not in a decryption batch, or waited too long for the other handles of
+    /// the batch, the ct128 is uploaded alone
+    Alone,
+    /// Some handles of the decryption batch are not squashed yet
+    Waiting(usize),
+    /// All handles of the decryption batch not uploaded alone after waiting
+    Ready(Ct128List),
+}
+
+/// Gathers the ct128 of the handles of the decryption batch of the handle
+/// that are not uploaded yet.
+async fn fetch_ct128_list(
+    pool: &PgPool,
+    tenant_id: i32,
+    handle: &[u8],
+    batch_waits: i32,
+) -> Result<ListUpload, ExecutionError> {
+    // The handles uploaded alone after waiting for the batch are not part of
+    // the list
+    let records = sqlx::query!(
+        r#"
+        SELECT DISTINCT ON (p.handle)
+            p.handle,
+            c.ciphertext128 AS "ciphertext128?",
+            c.ciphertext128_format AS "ciphertext128_format?"
+        FROM pbs_computations p
+        LEFT JOIN ciphertexts c
+        ON c.tenant_id = p.tenant_id AND c.handle = p.handle
+        LEFT JOIN ciphertext_digest d
+        ON d.tenant_id = p.tenant_id AND d.handle = p.handle
+        WHERE p.tenant_id = $1
+        AND p.decryption_batch = (
+            SELECT decryption_batch FROM pbs_computations
+            WHERE tenant_id = $1 AND handle = $2
+        )
+        AND d.ciphertext128 IS NULL
+        ORDER BY p.handle, c.ciphertext_version DESC
+        "#,
+        tenant_id,
+        handle,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if records.len() < 2 {
+        return Ok(ListUpload::Alone);
+    }
+
+    let waiting = records.iter().filter(|r| r.ciphertext128.is_none()).count();
+    if waiting > 0 {
+        if batch_waits < MAX_DECRYPTION_BATCH_WAITS {
+            return Ok(ListUpload::Waiting(waiting));
+        }
+        warn!({target = "worker", action = "review"},
+            "Not packing ct128, {} handles of its decryption batch not squashed, handle: {}",
+            waiting, compact_hex(handle)
+        );
+        return Ok(ListUpload::Alone);
+    }
+
+    // The handles are sorted, bytea being compared bytewise
+    let mut list = Ct128List::default();
+    for record in records {
+        let (Some(ct128), Some(format)) = (record.ciphertext128, record.ciphertext128_format)
+        else {
+            continue;
+        };
+        Ct128Format::try_from(format)?;
+        list.push(record.handle, ct128);
+    }
+    Ok(ListUpload::Ready(list))
+}
+
+/// Records the digest of the uploaded list, the position of each handle in it
+/// and the digest of its ct128, and drops the ct128 from the database.
+async fn record_ct128_list(
+    trx: &mut Transaction<'_, Postgres>,
+    tenant_id: i32,
+    list: &Ct128List,
+    digest: &[u8],
+) -> Result<(), ExecutionError> {
+    for (index, (handle, ct128)) in list.handles.iter().zip(&list.ciphertexts).enumerate() {
+        sqlx::query!(
+            "INSERT INTO ciphertext_digest (tenant_id, handle)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING",
+            tenant_id,
+            handle,
+        )
+        .execute(trx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "UPDATE ciphertext_digest
+             SET ciphertext128 = $1,
+             ciphertext128_format = $2,
+             ciphertext128_list = $3,
+             ciphertext128_list_index = $4
+             WHERE tenant_id = $5 AND handle = $6",
+            compute_digest(ct128),
+            Ct128Format::Raw.as_i16(),
+            digest,
+            index as i32,
+            tenant_id,
+            handle,
+        )
+        .execute(trx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "UPDATE ciphertexts
+             SET ciphertext128 = NULL
+             WHERE tenant_id = $1 AND handle = $2",
+            tenant_id,
+            handle,
+        )
+        .execute(trx.as_mut())
+        .await?;
+    }
+
+    info!(
+        "Uploaded ct128 list of {} handles, digest: {}",
+        list.len(),
+        compact_hex(digest)
+    );
+    Ok(())
+}
+
+/// Schedules the next attempt of a handle waiting for the other handles of
+/// its decryption batch, without counting it as an upload failure.
+async fn record_decryption_batch_wait<'c, E: PgExecutor<'c>>(
+    executor: E,
+    tenant_id: i32,
+    handle: &[u8],
+    conf: &S3Config,
+) -> Result<(), ExecutionError> {
+    sqlx::query!(
+        "
+        UPDATE ciphertext_digest
+        SET
+        decryption_batch_waits = decryption_batch_waits + 1,
+        upload_next_attempt_at = NOW() + make_interval(secs => $1)
+        WHERE tenant_id = $2 AND handle = $3",
+        conf.retry_base_delay as f64,
+        tenant_id,
+        handle,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Uploads the ciphertext under its hex-encoded digest unless it has already
+/// been uploaded.
+///
+/// Returns whether it has been uploaded.
+async fn put_unless_uploaded(
+    store: &dyn ObjectStore,
+    already_uploaded: bool,
+    bucket: &str,
+    digest: &[u8],
+    ct: Vec<u8>,
+    timeout: Duration,
+) -> Result<bool, ExecutionError> {
+    if already_uploaded {
+        return Ok(false);
+    }
+    tokio::time::timeout(timeout, store.put(bucket, &hex::encode(digest), ct))
+        .await
+        .map_err(|_| ExecutionError::UploadTimeout)??;
+    Ok(true)
+}
+
+/// Schedules the next attempt of a failed upload with an exponential backoff.
+async fn record_upload_failure<'c, E: PgExecutor<'c>>(
+    executor: E,
+    tenant_id: i32,
+    handle: &[u8],
+    err: &str,
+    conf: &S3Config,
+) -> Result<(), ExecutionError> {
+    sqlx::query!(
+        "
+        UPDATE ciphertext_digest
+        SET
+        upload_retry_count = upload_retry_count + 1,
+        upload_last_error = $1,
+        upload_next_attempt_at = NOW() + make_interval(
+            secs => LEAST($2 * power(2.0::float8, LEAST(upload_retry_count, 30)), $3)
+        )
+        WHERE tenant_id = $4 AND handle = $5",
+        err,
+        conf.retry_base_delay as f64,
+        conf.retry_max_delay as f64,
+        tenant_id,
+        handle,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub(crate) fn compute_digest(ct: &[u8]) -> Vec<u8> {
     let mut hasher = Keccak256::new();
     hasher.update(ct);
     hasher.finalize().to_vec()
@@ -0,0 +1,103 @@
+use clap::Parser;
+use sns_executor::{
+    audit_ciphertexts, connect_object_store, AuditConfig, S3Config, StorageBackend,
+};
+use sqlx::postgres::PgPoolOptions;
+use tokio::signal::unix;
+use tokio_util::sync::CancellationToken;
+use tracing::Level;
+
+/// Checks that the uploaded ciphertexts exist and match their digests in the
+/// database and on the Gateway, records the mismatches in
+/// `ciphertext_audit_findings` and optionally repairs them.
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Postgres database url. If unspecified DATABASE_URL environment variable
+    /// is used
+    #[arg(long)]
+    database_url: Option<String>,
+
+    /// Where the ciphertexts are uploaded: s3, local:<dir> or memory
+    #[arg(long, default_value = "s3")]
+    storage_backend: StorageBackend,
+
+    #[arg(long, default_value = "ct128")]
+    bucket_name_ct128: String,
+
+    #[arg(long, default_value = "ct64")]
+    bucket_name_ct64: String,
+
+    /// Fraction of the uploaded ciphertexts that are audited, between 0 and 1
+    #[arg(long, default_value_t = 1.0)]
+    sample_rate: f64,
+
+    /// Number of ciphertexts fetched from the database at once
+    #[arg(long, default_value_t = 100)]
+    batch_size: u32,
+
+    /// Re-upload the missing or corrupted ct64 objects whose content is still
+    /// in the database, the ct128 ones are reported as unrepairable
+    #[arg(long)]
+    repair: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt()
+        .json()
+        .with_level(true)
+        .with_max_level(Level::INFO)
+        .init();
+
+    anyhow::ensure!(
+        args.sample_rate > 0.0 && args.sample_rate <= 1.0,
+        "sample rate must be in (0, 1]"
+    );
+
+    let database_url = args
+        .database_url
+        .clone()
+        .unwrap_or_else(|| std::env::var("DATABASE_URL").expect("DATABASE_URL is undefined"));
+    let pool = PgPoolOptions::new()
+        .max_connections(2)
+        .connect(&database_url)
+        .await?;
+
+    let conf = S3Config {
+        bucket_ct128: args.bucket_name_ct128,
+        bucket_ct64: args.bucket_name_ct64,
+        backend: args.storage_backend,
+        ..Default::default()
+    };
+    let store = connect_object_store(&conf.backend).await?;
+
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        let mut signal = unix::signal(unix::SignalKind::interrupt()).unwrap();
+        signal.recv().await;
+        cancel.cancel();
+    });
+
+    let audit = AuditConfig {
+        sample_rate: args.sample_rate,
+        batch_size: args.batch_size,
+        repair: args.repair,
+    };
+    let report = audit_ciphertexts(&pool, store.as_ref(), &conf, &audit, token).await?;
+
+    println!(
+        "audited {} ciphertexts, {} findings, {} repaired",
+        report.audited, report.findings, report.repaired
+    );
+    if report.findings > report.repaired {
+        anyhow::bail!(
+            "{} findings not repaired",
+            report.findings - report.repaired
+        );
+    }
+    Ok(())
+}
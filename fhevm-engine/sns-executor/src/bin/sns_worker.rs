@@ -22,7 +22,9 @@ fn construct_config() -> Config {
         .unwrap_or_else(|| std::env::var("DATABASE_URL").expect("DATABASE_URL is undefined"));
 
     Config {
-        tenant_api_key: args.tenant_api_key,
+        tenant_api_keys: args.tenant_api_keys,
+        key_cache_budget: args.key_cache_budget.as_u64(),
+        squash_threads: args.squash_threads,
         service_name: args.service_name,
         db: DBConfig {
             url: db_url,
@@ -31,11 +33,18 @@ fn construct_config() -> Config {
             batch_limit: args.work_items_batch_size,
             polling_interval: args.pg_polling_interval,
             max_connections: args.pg_pool_connections,
+            task_lease_duration: args.work_items_lease_duration,
+            max_task_attempts: args.work_items_max_attempts,
         },
         s3: S3Config {
             bucket_ct128: args.bucket_name_ct128,
             bucket_ct64: args.bucket_name_ct64,
             max_concurrent_uploads: args.max_concurrent_uploads,
+            backend: args.storage_backend,
+            retry_polling_interval: args.upload_retry_polling_interval,
+            retry_base_delay: args.upload_retry_base_delay,
+            retry_max_delay: args.upload_retry_max_delay,
+            pack_decryption_batches: args.pack_decryption_batches,
         },
     }
 }
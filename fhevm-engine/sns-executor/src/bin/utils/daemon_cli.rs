@@ -1,11 +1,19 @@
+use bytesize::ByteSize;
 use clap::{command, Parser};
+use sns_executor::StorageBackend;
 
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    /// Tenant API key
-    #[arg(long)]
-    pub tenant_api_key: String,
+    /// API keys of the tenants whose tasks are processed. If unspecified the
+    /// tasks of all tenants are processed
+    #[arg(long, alias = "tenant-api-key", num_args(1..))]
+    pub tenant_api_keys: Vec<String>,
+
+    /// Memory budget of the cached tenant keys, ~1.1 GB per tenant. The least
+    /// recently used keys are evicted when it is exceeded
+    #[arg(long, default_value = "4GiB")]
+    pub key_cache_budget: ByteSize,
 
     /// Work items batch size
     #[arg(long, default_value_t = 4)]
@@ -27,6 +35,22 @@ pub struct Args {
     #[arg(long, default_value_t = 10)]
     pub pg_pool_connections: u32,
 
+    /// Time in seconds after which a claimed work item that is not completed,
+    /// e.g. because the worker crashed, is claimed again. Must exceed the time
+    /// to squash a batch
+    #[arg(long, default_value_t = 300)]
+    pub work_items_lease_duration: u32,
+
+    /// Failed squashes after which a work item is marked failed, for review,
+    /// and no longer claimed
+    #[arg(long, default_value_t = 5)]
+    pub work_items_max_attempts: u32,
+
+    /// Threads squashing the work items of a batch in parallel. If 0 the
+    /// number of CPUs is used
+    #[arg(long, default_value_t = 0)]
+    pub squash_threads: usize,
+
     /// Postgres database url. If unspecified DATABASE_URL environment variable
     /// is used
     #[arg(long)]
@@ -53,6 +77,33 @@ pub struct Args {
     /// Maximum number of concurrent uploads to S3
     #[arg(long, default_value_t = 100)]
     pub max_concurrent_uploads: u32,
+
+    /// Where the ciphertexts are uploaded: s3, local:<dir> (one sub-directory
+    /// per bucket) or memory (lost on exit, for testing)
+    #[arg(long, default_value = "s3")]
+    pub storage_backend: StorageBackend,
+
+    /// Interval in seconds between the retries of the failed or interrupted
+    /// uploads
+    #[arg(long, default_value_t = 30)]
+    pub upload_retry_polling_interval: u32,
+
+    /// Backoff in seconds after the first failure of an upload, doubled on
+    /// every failure
+    #[arg(long, default_value_t = 5)]
+    pub upload_retry_base_delay: u32,
+
+    /// Maximum backoff in seconds between two attempts of an upload
+    #[arg(long, default_value_t = 3600)]
+    pub upload_retry_max_delay: u32,
+
+    /// Upload the ct128 of the handles allowed for decryption by the same
+    /// AllowedForDecryption event as one list instead of one object
+    /// per handle, once all of them are squashed. The readers of the ct128
+    /// bucket must look up the list of the handles recorded in
+    /// ciphertext_digest before it is enabled
+    #[arg(long)]
+    pub pack_decryption_batches: bool,
 }
 
 pub fn parse_args() -> Args {
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ExecutionError;
+
+/// Squashed noise ciphertexts of the handles of a decryption batch, i.e. of
+/// one AllowedForDecryption event, uploaded as one object instead of the ct128
+/// of each handle.
+///
+/// The position of each handle is recorded in
+/// `ciphertext_digest.ciphertext128_list_index`, the digest of the object in
+/// `ciphertext_digest.ciphertext128_list` and the one of its ct128 in
+/// `ciphertext_digest.ciphertext128`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ct128List {
+    /// Handles sorted in ascending order
+    pub handles: Vec<Vec<u8>>,
+    /// Safe serializations of the ct128, in the order of the handles
+    pub ciphertexts: Vec<Vec<u8>>,
+}
+
+impl Ct128List {
+    pub fn push(&mut self, handle: Vec<u8>, ct128: Vec<u8>) {
+        self.handles.push(handle);
+        self.ciphertexts.push(ct128);
+    }
+
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Returns the ct128 at the recorded position of the handle
+    pub fn get(&self, index: usize) -> Option<(&[u8], &[u8])> {
+        Some((
+            self.handles.get(index)?.as_slice(),
+            self.ciphertexts.get(index)?.as_slice(),
+        ))
+    }
+
+    /// Returns the ct128 of the handle
+    pub fn find(&self, handle: &[u8]) -> Option<&[u8]> {
+        let index = self
+            .handles
+            .binary_search_by(|h| h.as_slice().cmp(handle))
+            .ok()?;
+        Some(self.ciphertexts[index].as_slice())
+    }
+
+    /// Serializes the list into the uploaded object
+    pub fn encode(&self) -> Result<Vec<u8>, ExecutionError> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, ExecutionError> {
+        Ok(bincode::deserialize(data)?)
+    }
+}
@@ -1,13 +1,18 @@
-use crate::keyset::fetch_keyset;
-use crate::squash_noise::SquashNoiseCiphertext;
+use crate::aws_upload::UPLOAD_LEASE_DURATION;
+use crate::keyset::KeyCache;
+use crate::squash_noise::{Ct128Format, SquashNoiseCiphertext};
 use crate::HandleItem;
 use crate::KeySet;
 use crate::{Config, DBConfig, ExecutionError};
 use fhevm_engine_common::telemetry;
 use fhevm_engine_common::utils::compact_hex;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use sqlx::pool::PoolConnection;
 use sqlx::postgres::PgListener;
+use sqlx::types::time::OffsetDateTime;
 use sqlx::{Acquire, PgPool, Postgres, Transaction};
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::SystemTime;
 use tfhe::set_server_key;
@@ -32,7 +37,9 @@ pub(crate) async fn run_loop(
     tx: &Sender<HandleItem>,
     token: CancellationToken,
 ) -> Result<(), ExecutionError> {
-    let tenant_api_key = &conf.tenant_api_key;
+    let tenant_api_keys = &conf.tenant_api_keys;
+    let key_cache_budget = conf.key_cache_budget;
+    let squash_threads = conf.squash_threads;
     let conf = &conf.db;
 
     let t = telemetry::tracer("init_service");
@@ -51,12 +58,33 @@ pub(crate) async fn run_loop(
         .listen_all(conf.listen_channels.iter().map(|v| v.as_str()))
         .await?;
 
-    let s = t.child_span("fetch_keyset");
-    let keys: KeySet = fetch_keyset(&pool, tenant_api_key).await?;
+    let s = t.child_span("fetch_tenants");
+    let tenants = fetch_tenant_ids(&pool, tenant_api_keys).await?;
     telemetry::end_span(s);
 
     t.end();
 
+    match &tenants {
+        Some(tenants) => info!(target: "worker", "Processing tasks of tenants: {:?}", tenants),
+        None => info!(target: "worker", "Processing tasks of all tenants"),
+    }
+
+    // Keys are fetched on demand, the ones of the tenants that fail to load
+    // are retried after the polling interval
+    let mut keys = KeyCache::new(
+        key_cache_budget,
+        Duration::from_secs(conf.polling_interval.into()),
+    );
+
+    // 0 threads stands for the number of CPUs
+    let squash_pool = Arc::new(
+        ThreadPoolBuilder::new()
+            .num_threads(squash_threads)
+            .thread_name(|i| format!("sns-squash-{i}"))
+            .build()?,
+    );
+    info!(target: "worker", "Squashing on {} threads", squash_pool.current_num_threads());
+
     loop {
         let mut conn: PoolConnection<Postgres> =
             match acquire_connection(&pool, token.clone()).await {
@@ -69,10 +97,21 @@ pub(crate) async fn run_loop(
             };
 
         loop {
-            match fetch_and_execute_sns_tasks(&mut conn, tx, &keys, conf).await {
+            match fetch_and_execute_sns_tasks(
+                &pool,
+                &mut conn,
+                tx,
+                &mut keys,
+                &squash_pool,
+                &tenants,
+                conf,
+            )
+            .await
+            {
                 Ok(_) => {
                     // Check if more tasks are available
-                    let count = get_remaining_tasks(&mut conn).await?;
+                    let excluded = keys.unavailable_tenants();
+                    let count = get_remaining_tasks(&mut conn, &tenants, &excluded).await?;
                     if count > 0 {
                         if token.is_cancelled() {
                             return Ok(());
@@ -105,13 +144,98 @@ pub(crate) async fn run_loop(
     Ok(())
 }
 
+/// Resolves the API keys of the tenants to process into tenant ids, ignoring
+/// the duplicate keys.
+///
+/// Returns None if all tenants are processed.
+async fn fetch_tenant_ids(
+    pool: &PgPool,
+    tenant_api_keys: &[String],
+) -> Result<Option<Vec<i32>>, ExecutionError> {
+    if tenant_api_keys.is_empty() {
+        return Ok(None);
+    }
+
+    let mut api_keys: Vec<String> = tenant_api_keys
+        .iter()
+        .map(|key| key.trim().to_lowercase())
+        .collect();
+    api_keys.sort();
+    api_keys.dedup();
+
+    let tenants = sqlx::query!(
+        r#"
+        SELECT tenant_id, tenant_api_key::TEXT AS "tenant_api_key!"
+        FROM tenants
+        WHERE tenant_api_key::TEXT = ANY($1::TEXT[])
+        "#,
+        &api_keys,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let unknown: Vec<&String> = api_keys
+        .iter()
+        .filter(|key| !tenants.iter().any(|t| &t.tenant_api_key == *key))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(ExecutionError::UnknownTenants(format!(
+            "no tenant for the API keys {:?}",
+            unknown
+        )));
+    }
+
+    Ok(Some(tenants.into_iter().map(|t| t.tenant_id).collect()))
+}
+
 /// Fetch and process SnS tasks from the database.
+///
+/// The keys of the tenant with the oldest task are loaded first, skipping the
+/// tenants whose keys are unavailable, then the tasks of that tenant only are
+/// claimed. The tasks are claimed with a lease in a short transaction, so that
+/// the row locks are not held while squashing, and their results are
+/// committed in another one. The tasks not committed before the lease expires,
+/// e.g. if the worker crashes, are claimed again.
 async fn fetch_and_execute_sns_tasks(
+    pool: &PgPool,
     conn: &mut PoolConnection<Postgres>,
     tx: &Sender<HandleItem>,
-    keys: &KeySet,
+    keys: &mut KeyCache,
+    squash_pool: &Arc<ThreadPool>,
+    tenants: &Option<Vec<i32>>,
     conf: &DBConfig,
 ) -> Result<(), ExecutionError> {
+    let excluded = keys.unavailable_tenants();
+    let mut tenant_keys = None;
+    for tenant_id in get_pending_tenants(conn, tenants, &excluded).await? {
+        match keys.get(pool, tenant_id).await {
+            Some(k) => {
+                tenant_keys = Some((tenant_id, k));
+                break;
+            }
+            None => {
+                error!(target: "worker", { tenant_id }, "Skipping tasks of tenant, keys unavailable");
+            }
+        }
+    }
+    let Some((tenant_id, tenant_keys)) = tenant_keys else {
+        return Ok(());
+    };
+
+    let Some((tasks, claimed_until)) = claim_sns_tasks(
+        conn,
+        conf.batch_limit,
+        conf.task_lease_duration,
+        &Some(vec![tenant_id]),
+        &[],
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let (processed, failed) = process_tasks(tasks, tenant_keys, squash_pool.clone()).await?;
+
     let mut db_txn = match conn.begin().await {
         Ok(txn) => txn,
         Err(err) => {
@@ -119,16 +243,13 @@ async fn fetch_and_execute_sns_tasks(
             return Err(err.into());
         }
     };
+    record_task_errors(&mut db_txn, &failed, claimed_until, conf.max_task_attempts).await?;
+    let processed = update_computations_status(&mut db_txn, processed, claimed_until).await?;
+    update_ciphertext128(&mut db_txn, &processed).await?;
+    notify_ciphertext128_ready(&mut db_txn, &conf.notify_channel).await?;
+    db_txn.commit().await?;
 
-    if let Some(mut tasks) = query_sns_tasks(&mut db_txn, conf.batch_limit).await? {
-        process_tasks(&mut tasks, keys, tx)?;
-        update_computations_status(&mut db_txn, &tasks).await?;
-        update_ciphertext128(&mut db_txn, &tasks).await?;
-        notify_ciphertext128_ready(&mut db_txn, &conf.notify_channel).await?;
-        db_txn.commit().await?;
-    } else {
-        db_txn.rollback().await?;
-    }
+    send_to_upload(processed, tx);
 
     Ok(())
 }
@@ -149,34 +270,66 @@ async fn acquire_connection(pool: &PgPool, token: CancellationToken) -> ConnStat
     }
 }
 
-/// Queries the database for a fixed number of tasks.
-async fn query_sns_tasks(
-    db_txn: &mut Transaction<'_, Postgres>,
+/// Claims a fixed number of tasks of the tenants, all if None, except the
+/// excluded ones, for the lease duration in seconds.
+///
+/// Returns the tasks with the end of their lease, which identifies the claim
+/// when completing them.
+pub(crate) async fn claim_sns_tasks(
+    conn: &mut PoolConnection<Postgres>,
     limit: u32,
-) -> Result<Option<Vec<HandleItem>>, ExecutionError> {
+    lease_duration: u32,
+    tenants: &Option<Vec<i32>>,
+    excluded: &[i32],
+) -> Result<Option<(Vec<HandleItem>, OffsetDateTime)>, ExecutionError> {
     let start_time = SystemTime::now();
     let records = sqlx::query!(
-        " 
-        SELECT a.*, c.ciphertext
-        FROM pbs_computations a
-        JOIN ciphertexts c 
-        ON a.handle = c.handle          -- fetch handles inserted into the ciphertexts table
-        WHERE c.ciphertext IS NOT NULL  -- filter out tasks with no computed ciphertext64
-        AND a.is_completed = FALSE      -- filter out completed tasks
-        ORDER BY a.created_at           -- quickly find uncompleted tasks
-        FOR UPDATE SKIP LOCKED
-        LIMIT $1;
-        ",
-        limit as i64
+        r#"
+        WITH claimed AS (
+            UPDATE pbs_computations
+            SET claimed_until = NOW() + make_interval(secs => $4)
+            WHERE (tenant_id, handle) IN (
+                SELECT a.tenant_id, a.handle
+                FROM pbs_computations a
+                WHERE EXISTS (                  -- fetch handles with a computed ciphertext64
+                    SELECT 1 FROM ciphertexts c
+                    WHERE c.tenant_id = a.tenant_id
+                    AND c.handle = a.handle
+                    AND c.ciphertext IS NOT NULL
+                )
+                AND a.is_completed = FALSE      -- filter out completed tasks
+                AND a.is_failed = FALSE         -- filter out tasks that exhausted their attempts
+                AND (a.claimed_until IS NULL OR a.claimed_until < NOW()) -- filter out claimed tasks
+                AND ($2::INT[] IS NULL OR a.tenant_id = ANY($2))
+                AND NOT (a.tenant_id = ANY($3::INT[]))
+                ORDER BY a.created_at           -- quickly find uncompleted tasks
+                FOR UPDATE OF a SKIP LOCKED
+                LIMIT $1
+            )
+            RETURNING tenant_id, handle, claimed_until
+        )
+        SELECT DISTINCT ON (claimed.tenant_id, claimed.handle)
+            claimed.tenant_id AS "tenant_id!", claimed.handle AS "handle!",
+            claimed.claimed_until AS "claimed_until!", c.ciphertext
+        FROM claimed
+        JOIN ciphertexts c
+        ON c.tenant_id = claimed.tenant_id AND c.handle = claimed.handle
+        WHERE c.ciphertext IS NOT NULL
+        ORDER BY claimed.tenant_id, claimed.handle, c.ciphertext_version DESC;
+        "#,
+        limit as i64,
+        tenants.as_deref(),
+        excluded,
+        lease_duration as f64,
     )
-    .fetch_all(db_txn.as_mut())
+    .fetch_all(conn.as_mut())
     .await?;
 
     info!(target: "sns", { count = records.len()}, "Fetched SnS tasks");
 
-    if records.is_empty() {
+    let Some(claimed_until) = records.first().map(|record| record.claimed_until) else {
         return Ok(None);
-    }
+    };
 
     let t = telemetry::tracer_with_start_time("db_fetch_tasks", start_time);
     t.set_attribute("count", records.len().to_string());
@@ -193,12 +346,48 @@ async fn query_sns_tasks(
         })
         .collect();
 
-    Ok(Some(tasks))
+    Ok(Some((tasks, claimed_until)))
+}
+
+/// Returns the tenants with tasks to claim, all if None, except the excluded
+/// ones, the tenant with the oldest task first.
+async fn get_pending_tenants(
+    conn: &mut PoolConnection<Postgres>,
+    tenants: &Option<Vec<i32>>,
+    excluded: &[i32],
+) -> Result<Vec<i32>, ExecutionError> {
+    let tenant_ids = sqlx::query_scalar!(
+        "
+        SELECT a.tenant_id
+        FROM pbs_computations a
+        WHERE EXISTS (
+            SELECT 1 FROM ciphertexts c
+            WHERE c.tenant_id = a.tenant_id
+            AND c.handle = a.handle
+            AND c.ciphertext IS NOT NULL
+        )
+        AND a.is_completed = FALSE
+        AND a.is_failed = FALSE
+        AND (a.claimed_until IS NULL OR a.claimed_until < NOW())
+        AND ($1::INT[] IS NULL OR a.tenant_id = ANY($1))
+        AND NOT (a.tenant_id = ANY($2::INT[]))
+        GROUP BY a.tenant_id
+        ORDER BY MIN(a.created_at)
+        ",
+        tenants.as_deref(),
+        excluded,
+    )
+    .fetch_all(conn.as_mut())
+    .await?;
+
+    Ok(tenant_ids)
 }
 
 /// Returns the number of remaining tasks in the database.
 async fn get_remaining_tasks(
     conn: &mut sqlx::pool::PoolConnection<sqlx::Postgres>,
+    tenants: &Option<Vec<i32>>,
+    excluded: &[i32],
 ) -> Result<i64, ExecutionError> {
     let mut db_txn = match conn.begin().await {
         Ok(txn) => txn,
@@ -214,13 +403,22 @@ async fn get_remaining_tasks(
         FROM (
             SELECT 1
             FROM pbs_computations a
-            JOIN ciphertexts c 
-            ON a.handle = c.handle
-            WHERE c.ciphertext IS NOT NULL
+            WHERE EXISTS (
+                SELECT 1 FROM ciphertexts c
+                WHERE c.tenant_id = a.tenant_id
+                AND c.handle = a.handle
+                AND c.ciphertext IS NOT NULL
+            )
             AND a.is_completed = FALSE -- filter out completed tasks
+            AND a.is_failed = FALSE -- filter out tasks that exhausted their attempts
+            AND (a.claimed_until IS NULL OR a.claimed_until < NOW()) -- filter out claimed tasks
+            AND ($1::INT[] IS NULL OR a.tenant_id = ANY($1))
+            AND NOT (a.tenant_id = ANY($2::INT[]))
             FOR UPDATE OF a SKIP LOCKED -- don't count locked rows
         ) AS unlocked_rows;
         ",
+        tenants.as_deref(),
+        excluded,
     )
     .fetch_one(db_txn.as_mut())
     .await?;
@@ -228,47 +426,99 @@ async fn get_remaining_tasks(
     Ok(records_count.unwrap_or(0))
 }
 
-/// Processes the tasks by decompressing and transforming ciphertexts.
-fn process_tasks(
-    tasks: &mut [HandleItem],
-    keys: &KeySet,
-    tx: &Sender<HandleItem>,
-) -> Result<(), ExecutionError> {
-    set_server_key(keys.server_key.clone());
-
-    for task in tasks.iter_mut() {
-        let s = task.otel.child_span("decompress_ct64");
-        let ct = decompress_ct(&task.handle, &task.ct64_compressed)?;
-        telemetry::end_span(s);
-
-        let handle = compact_hex(&task.handle);
-        info!(target: "sns",  { handle }, "Converting ciphertext");
-
-        let span = task.otel.child_span("squash_noise");
-        match ct.squash_noise_and_serialize() {
-            Ok(squashed_noise_serialized) => {
-                telemetry::end_span(span);
-                info!(target: "sns", { handle }, "Ciphertext converted, length: {}", squashed_noise_serialized.len());
-
-                // Optional: Decrypt and log for debugging
-                #[cfg(feature = "test_decrypt_128")]
-                {
-                    if let Some(client_key) = &keys.client_key {
-                        let ct = ct
-                            .decrypt_squash_noise(client_key, &squashed_noise_serialized)
-                            .expect("Failed to decrypt");
-
-                        info!(target: "sns", { handle }, "Decrypted plaintext: {:?}", ct);
-                    }
-                }
+/// Processes the tasks by decompressing and transforming ciphertexts, in
+/// parallel on the squashing thread pool.
+///
+/// Returns the squashed tasks and the failed ones with their error.
+async fn process_tasks(
+    mut tasks: Vec<HandleItem>,
+    keys: Arc<KeySet>,
+    squash_pool: Arc<ThreadPool>,
+) -> Result<(Vec<HandleItem>, Vec<(HandleItem, String)>), ExecutionError> {
+    let (tasks, errors) = tokio::task::spawn_blocking(move || {
+        let errors: Vec<Option<String>> = squash_pool.install(|| {
+            tasks
+                .par_iter_mut()
+                .map(|task| {
+                    // The server key is thread-local, setting it is cheap as it
+                    // is reference-counted
+                    set_server_key(keys.server_key.clone());
+                    process_task(task, &keys).err()
+                })
+                .collect()
+        });
+        (tasks, errors)
+    })
+    .await?;
 
-                task.ct128_uncompressed = Some(squashed_noise_serialized);
-            }
-            Err(err) => {
-                telemetry::end_span_with_err(span, err.to_string());
-                error!(target: "sns", { handle }, "Failed to convert ct: {err}");
+    let mut squashed = vec![];
+    let mut failed = vec![];
+    for (task, error) in tasks.into_iter().zip(errors) {
+        match error {
+            Some(error) => failed.push((task, error)),
+            None => squashed.push(task),
+        }
+    }
+    Ok((squashed, failed))
+}
+
+/// Decompresses the ct64 of the task and squashes its noise into its ct128.
+fn process_task(
+    task: &mut HandleItem,
+    #[cfg_attr(not(feature = "test_decrypt_128"), allow(unused_variables))] keys: &KeySet,
+) -> Result<(), String> {
+    let handle = compact_hex(&task.handle);
+
+    let s = task.otel.child_span("decompress_ct64");
+    let ct = match decompress_ct(&task.handle, &task.ct64_compressed) {
+        Ok(ct) => {
+            telemetry::end_span(s);
+            ct
+        }
+        Err(err) => {
+            telemetry::end_span_with_err(s, err.to_string());
+            error!(target: "sns", { handle, action = "review" }, "Failed to decompress ct: {err}");
+            return Err(format!("failed to decompress ct64: {err}"));
+        }
+    };
+
+    info!(target: "sns",  { handle }, "Converting ciphertext");
+
+    let span = task.otel.child_span("squash_noise");
+    match ct.squash_noise_and_serialize() {
+        Ok(squashed_noise_serialized) => {
+            telemetry::end_span(span);
+            info!(target: "sns", { handle }, "Ciphertext converted, length: {}", squashed_noise_serialized.len());
+
+            // Optional: Decrypt and log for debugging
+            #[cfg(feature = "test_decrypt_128")]
+            {
+                if let Some(client_key) = &keys.client_key {
+                    let ct = ct
+                        .decrypt_squash_noise(client_key, &squashed_noise_serialized)
+                        .expect("Failed to decrypt");
+
+                    info!(target: "sns", { handle }, "Decrypted plaintext: {:?}", ct);
+                }
             }
-        };
+
+            task.ct128_uncompressed = Some(squashed_noise_serialized);
+            Ok(())
+        }
+        Err(err) => {
+            telemetry::end_span_with_err(span, err.to_string());
+            error!(target: "sns", { handle, action = "review" }, "Failed to convert ct: {err}");
+            Err(format!("failed to squash noise: {err}"))
+        }
+    }
+}
+
+/// Sends the committed ct128 to the upload worker.
+fn send_to_upload(tasks: Vec<HandleItem>, tx: &Sender<HandleItem>) {
+    for task in tasks {
+        if task.ct128_uncompressed.is_none() {
+            continue;
+        }
 
         // Start uploading the ciphertexts sooner than later
         //
@@ -289,20 +539,19 @@ fn process_tasks(
             telemetry::end_span_with_err(task.otel.child_span("send_task"), err.to_string());
         }
     }
-
-    Ok(())
 }
 
 /// Updates the database with the computed large ciphertexts.
 ///
 /// The ct128 is temporarily stored in PostgresDB to ensure reliability.
 /// After the AWS uploader successfully uploads the ct128 to S3, the ct128 blob
-/// is deleted from Postgres.
+/// is deleted from Postgres. Until then, the `ciphertext_digest` row inserted
+/// here without digests marks the upload as pending.
 ///
 /// The assumption for now is that the DB insertion is faster and more reliable
 /// than the S3 upload. Later on, the DB insertion of ct128 might be removed
 /// completely.
-async fn update_ciphertext128(
+pub(crate) async fn update_ciphertext128(
     db_txn: &mut Transaction<'_, Postgres>,
     tasks: &[HandleItem],
 ) -> Result<(), ExecutionError> {
@@ -312,10 +561,17 @@ async fn update_ciphertext128(
             let res = sqlx::query!(
                 "
                 UPDATE ciphertexts
-                SET ciphertext128 = $1
-                WHERE handle = $2;",
+                SET ciphertext128 = $1, ciphertext128_format = $3
+                WHERE tenant_id = $4
+                AND handle = $2
+                AND ciphertext_version = (
+                    SELECT MAX(ciphertext_version) FROM ciphertexts
+                    WHERE tenant_id = $4 AND handle = $2
+                );",
                 ciphertext128,
-                task.handle
+                task.handle,
+                Ct128Format::Raw.as_i16(),
+                task.tenant_id,
             )
             .execute(db_txn.as_mut())
             .await;
@@ -324,6 +580,20 @@ async fn update_ciphertext128(
                 Ok(_) => {
                     debug!(target: "worker", handle = ?task.handle, "Inserted ct128 in DB");
                     telemetry::end_span(s);
+
+                    // Record the pending upload, retried by the upload worker once
+                    // the lease expires if the task sent through the channel is lost
+                    sqlx::query!(
+                        "
+                        INSERT INTO ciphertext_digest (tenant_id, handle, upload_next_attempt_at)
+                        VALUES ($1, $2, NOW() + make_interval(secs => $3))
+                        ON CONFLICT DO NOTHING;",
+                        task.tenant_id,
+                        task.handle,
+                        UPLOAD_LEASE_DURATION.as_secs_f64(),
+                    )
+                    .execute(db_txn.as_mut())
+                    .await?;
                 }
                 Err(err) => {
                     error!(target: "worker", handle = ?task.handle, "Failed to insert ct128 in DB: {err}");
@@ -340,23 +610,75 @@ async fn update_ciphertext128(
     Ok(())
 }
 
-async fn update_computations_status(
+/// Completes the squashed tasks still claimed with the given lease.
+///
+/// Returns the completed tasks, the ones whose lease expired and were claimed
+/// again in the meantime are left to the new claim.
+pub(crate) async fn update_computations_status(
     db_txn: &mut Transaction<'_, Postgres>,
-    tasks: &[HandleItem],
-) -> Result<(), ExecutionError> {
+    tasks: Vec<HandleItem>,
+    claimed_until: OffsetDateTime,
+) -> Result<Vec<HandleItem>, ExecutionError> {
+    let mut completed = Vec::with_capacity(tasks.len());
     for task in tasks {
-        if task.ct128_uncompressed.is_some() {
-            sqlx::query!(
-                "
-                UPDATE pbs_computations
-                SET is_completed = TRUE, completed_at = NOW()
-                WHERE handle = $1;",
-                task.handle
-            )
-            .execute(db_txn.as_mut())
-            .await?;
-        } else {
+        if task.ct128_uncompressed.is_none() {
             error!(target: "worker", handle = ?task.handle, "Large ciphertext not computed for task");
+            continue;
+        }
+
+        let res = sqlx::query!(
+            "
+            UPDATE pbs_computations
+            SET is_completed = TRUE, completed_at = NOW(), error = NULL
+            WHERE tenant_id = $1 AND handle = $2
+            AND claimed_until = $3
+            AND is_completed = FALSE;",
+            task.tenant_id,
+            task.handle,
+            claimed_until,
+        )
+        .execute(db_txn.as_mut())
+        .await?;
+
+        if res.rows_affected() == 0 {
+            error!(target: "worker", handle = ?task.handle, "Task lease lost, dropping its result");
+            continue;
+        }
+        completed.push(task);
+    }
+    Ok(completed)
+}
+
+/// Records the error of the failed tasks still claimed with the given lease,
+/// they are claimed again once the lease expires, unless they failed
+/// `max_attempts` times.
+pub(crate) async fn record_task_errors(
+    db_txn: &mut Transaction<'_, Postgres>,
+    tasks: &[(HandleItem, String)],
+    claimed_until: OffsetDateTime,
+    max_attempts: u32,
+) -> Result<(), ExecutionError> {
+    for (task, error) in tasks {
+        let failed = sqlx::query_scalar!(
+            "
+            UPDATE pbs_computations
+            SET error = $3, attempts = attempts + 1, is_failed = attempts + 1 >= $5
+            WHERE tenant_id = $1 AND handle = $2
+            AND claimed_until = $4
+            RETURNING is_failed;",
+            task.tenant_id,
+            task.handle,
+            error,
+            claimed_until,
+            max_attempts as i32,
+        )
+        .fetch_optional(db_txn.as_mut())
+        .await?;
+
+        if failed == Some(true) {
+            let handle = compact_hex(&task.handle);
+            error!(target: "worker", { handle, tenant_id = task.tenant_id, action = "review" },
+                "Task failed after {max_attempts} attempts: {error}");
         }
     }
     Ok(())
@@ -1,28 +1,136 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytesize::ByteSize;
 use fhevm_engine_common::{
-    tenant_keys::read_keys_from_large_object, utils::safe_deserialize_sns_key,
+    tenant_keys::read_large_object_in_chunks, utils::safe_deserialize_sns_key,
 };
-use sqlx::{PgPool, Row};
-use tracing::info;
+use lru::LruCache;
+use sqlx::{postgres::types::Oid, PgPool, Row};
+use tracing::{error, info};
 
 use crate::{ExecutionError, KeySet};
 
 const SKS_KEY_WITH_NOISE_SQUASHING_SIZE: usize = 1_150 * 1_000_000; // ~1.1 GB
+const CHUNK_SIZE: i32 = 64 * 1024; // 64KiB
+
+/// LRU cache of the tenant keysets, evicting the least recently used ones
+/// when the serialized size of the cached keys exceeds the memory budget.
+///
+/// The keyset in use is kept even if it alone exceeds the budget.
+pub(crate) struct KeyCache<K = KeySet> {
+    keys: LruCache<i32, (Arc<K>, usize)>,
+    size: usize,
+    budget: usize,
+    /// Tenants whose keys failed to load, not retried until the instant
+    unavailable: HashMap<i32, Instant>,
+    retry_delay: Duration,
+}
+
+impl KeyCache<KeySet> {
+    /// Returns the keyset of the tenant, fetching it from the database if not
+    /// cached.
+    ///
+    /// Returns None if the keys are unavailable.
+    pub(crate) async fn get(&mut self, pool: &PgPool, tenant_id: i32) -> Option<Arc<KeySet>> {
+        self.get_or_fetch(tenant_id, || fetch_keyset(pool, tenant_id))
+            .await
+    }
+}
+
+impl<K> KeyCache<K> {
+    pub(crate) fn new(budget: u64, retry_delay: Duration) -> Self {
+        Self {
+            keys: LruCache::unbounded(),
+            size: 0,
+            budget: budget as usize,
+            unavailable: HashMap::new(),
+            retry_delay,
+        }
+    }
+
+    /// Returns the keys of the tenant, fetched with their size if not cached
+    /// and not marked unavailable by a previous failure.
+    pub(crate) async fn get_or_fetch<F, Fut>(&mut self, tenant_id: i32, fetch: F) -> Option<Arc<K>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(K, usize), ExecutionError>>,
+    {
+        if let Some((keys, _)) = self.keys.get(&tenant_id) {
+            return Some(keys.clone());
+        }
+
+        if self
+            .unavailable
+            .get(&tenant_id)
+            .is_some_and(|retry_at| Instant::now() < *retry_at)
+        {
+            return None;
+        }
+
+        match fetch().await {
+            Ok((keys, size)) => {
+                self.unavailable.remove(&tenant_id);
+                let keys = Arc::new(keys);
+                self.insert(tenant_id, keys.clone(), size);
+                Some(keys)
+            }
+            Err(err) => {
+                error!(target: "sns", {tenant_id, action = "review"}, "Failed to fetch keys: {err}");
+                self.unavailable
+                    .insert(tenant_id, Instant::now() + self.retry_delay);
+                None
+            }
+        }
+    }
 
-/// Retrieve the keyset from the database
+    /// Tenants whose keys are unavailable
+    pub(crate) fn unavailable_tenants(&mut self) -> Vec<i32> {
+        let now = Instant::now();
+        self.unavailable.retain(|_, retry_at| now < *retry_at);
+        self.unavailable.keys().copied().collect()
+    }
+
+    fn insert(&mut self, tenant_id: i32, keys: Arc<K>, size: usize) {
+        while self.size + size > self.budget {
+            let Some((evicted, (_, evicted_size))) = self.keys.pop_lru() else {
+                break;
+            };
+            self.size -= evicted_size;
+            info!(target: "sns", { tenant_id = evicted }, "Evicted keys, size: {}", ByteSize::b(evicted_size as u64));
+        }
+
+        self.size += size;
+        self.keys.put(tenant_id, (keys, size));
+        info!(target: "sns", { tenant_id }, "Cached keys, size: {}, total: {}, budget: {}",
+            ByteSize::b(size as u64), ByteSize::b(self.size as u64), ByteSize::b(self.budget as u64));
+    }
+
+    /// Tenants whose keys are cached, the most recently used first
+    #[cfg(test)]
+    pub(crate) fn cached_tenants(&self) -> Vec<i32> {
+        self.keys.iter().map(|(tenant_id, _)| *tenant_id).collect()
+    }
+}
+
+/// Retrieve the keyset of the tenant from the database with its serialized size
 pub(crate) async fn fetch_keyset(
     pool: &PgPool,
-    tenant_api_key: &String,
-) -> Result<KeySet, ExecutionError> {
-    let (client_key, server_key) = fetch_keys(pool, tenant_api_key).await?;
+    tenant_id: i32,
+) -> Result<(KeySet, usize), ExecutionError> {
+    let (client_key, server_key, size) = fetch_keys(pool, tenant_id).await?;
     let key_set = KeySet {
         client_key,
         server_key,
     };
 
-    Ok(key_set)
+    Ok((key_set, size))
 }
 
-/// Retrieve both the ClientKey and ServerKey from the tenants table
+/// Retrieve both the ClientKey and ServerKey from the tenants table, with
+/// their serialized size
 ///
 /// The ServerKey is stored in a large object (LOB) in the database.
 /// ServerKey must be generated with enable_noise_squashing option.
@@ -31,36 +139,35 @@ pub(crate) async fn fetch_keyset(
 /// for decrypting on testing.
 pub async fn fetch_keys(
     pool: &PgPool,
-    tenant_api_key: &String,
-) -> anyhow::Result<(Option<tfhe::ClientKey>, tfhe::ServerKey)> {
-    let blob = read_keys_from_large_object(
-        pool,
-        tenant_api_key,
-        "sns_pk",
-        SKS_KEY_WITH_NOISE_SQUASHING_SIZE,
-    )
-    .await?;
-    info!(target: "sns", "Retrieved sns_pk bytes length: {:?}", blob.len());
-
-    let server_key: tfhe::ServerKey = safe_deserialize_sns_key(&blob)?;
-
+    tenant_id: i32,
+) -> anyhow::Result<(Option<tfhe::ClientKey>, tfhe::ServerKey, usize)> {
     let keys = sqlx::query(
         "
-                SELECT cks_key FROM tenants
-                WHERE tenant_api_key = $1::uuid
+                SELECT sns_pk, cks_key FROM tenants
+                WHERE tenant_id = $1
             ",
     )
-    .bind(tenant_api_key)
+    .bind(tenant_id)
     .fetch_one(pool)
     .await?;
 
-    if let Ok(cks) = keys.try_get::<Vec<u8>, _>(0) {
+    let oid: Oid = keys.try_get(0)?;
+    let blob =
+        read_large_object_in_chunks(pool, oid, CHUNK_SIZE, SKS_KEY_WITH_NOISE_SQUASHING_SIZE)
+            .await?;
+    info!(target: "sns", { tenant_id }, "Retrieved sns_pk bytes length: {:?}", blob.len());
+
+    let server_key: tfhe::ServerKey = safe_deserialize_sns_key(&blob)?;
+    let mut size = blob.len();
+
+    if let Ok(cks) = keys.try_get::<Vec<u8>, _>(1) {
         if !cks.is_empty() {
-            info!(target: "sns", "Retrieved cks bytes length: {:?}", cks.len());
+            info!(target: "sns", { tenant_id }, "Retrieved cks bytes length: {:?}", cks.len());
             let client_key: tfhe::ClientKey = safe_deserialize_sns_key(&cks)?;
-            return Ok((Some(client_key), server_key));
+            size += cks.len();
+            return Ok((Some(client_key), server_key, size));
         }
     }
 
-    Ok((None, server_key))
+    Ok((None, server_key, size))
 }
@@ -1,4 +1,6 @@
+mod audit;
 mod aws_upload;
+mod ct128_list;
 mod executor;
 mod keyset;
 mod squash_noise;
@@ -6,6 +8,8 @@ mod squash_noise;
 #[cfg(test)]
 mod tests;
 
+use std::sync::Arc;
+
 use fhevm_engine_common::{telemetry::OtelTracer, types::FhevmError};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -13,6 +17,18 @@ use tokio::sync::mpsc::{self, Sender};
 use tokio_util::sync::CancellationToken;
 use tracing::info;
 
+pub use audit::{audit_ciphertexts, AuditConfig, AuditReport, FindingKind};
+pub use ct128_list::Ct128List;
+pub use fhevm_engine_common::object_store::{
+    connect as connect_object_store, InMemoryStore, LocalDirStore, ObjectStore, ObjectStoreError,
+    S3Store, StorageBackend,
+};
+#[cfg(feature = "test_decrypt_128")]
+pub use keyset::fetch_keys;
+pub use squash_noise::Ct128Format;
+#[cfg(feature = "test_decrypt_128")]
+pub use squash_noise::{decrypt_ct128, squash_noise};
+
 pub const UPLOAD_QUEUE_SIZE: usize = 20;
 pub const SAFE_SER_LIMIT: u64 = 1024 * 1024 * 66;
 
@@ -30,6 +46,10 @@ pub struct DBConfig {
     pub batch_limit: u32,
     pub polling_interval: u32,
     pub max_connections: u32,
+    /// Time in seconds during which the claimed tasks are not claimed again
+    pub task_lease_duration: u32,
+    /// Failed squashes after which a task is failed and no longer claimed
+    pub max_task_attempts: u32,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -37,11 +57,27 @@ pub struct S3Config {
     pub bucket_ct128: String,
     pub bucket_ct64: String,
     pub max_concurrent_uploads: u32,
+    pub backend: StorageBackend,
+    /// Interval in seconds between the retries of the pending uploads
+    pub retry_polling_interval: u32,
+    /// Backoff in seconds after the first failure of an upload, doubled on
+    /// every failure up to `retry_max_delay`
+    pub retry_base_delay: u32,
+    pub retry_max_delay: u32,
+    /// Upload the ct128 of the handles allowed for decryption by the same
+    /// event as one list, instead of one object per handle
+    pub pack_decryption_batches: bool,
 }
 
 #[derive(Clone)]
 pub struct Config {
-    pub tenant_api_key: String,
+    /// API keys of the tenants whose tasks are processed, all tenants if empty
+    pub tenant_api_keys: Vec<String>,
+    /// Memory budget in bytes of the cached keys, ~1.1 GB per tenant
+    pub key_cache_budget: u64,
+    /// Threads squashing the ciphertexts of a batch in parallel, the number of
+    /// CPUs if 0
+    pub squash_threads: usize,
     pub service_name: String,
     pub db: DBConfig,
     pub s3: S3Config,
@@ -52,8 +88,16 @@ impl std::fmt::Display for Config {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "db_url: {},  db_listen_channel: {:?}, db_notify_channel: {}, db_batch_limit: {}",
-            self.db.url, self.db.listen_channels, self.db.notify_channel, self.db.batch_limit
+            "db_url: {},  db_listen_channel: {:?}, db_notify_channel: {}, db_batch_limit: {}, tenants: {}",
+            self.db.url,
+            self.db.listen_channels,
+            self.db.notify_channel,
+            self.db.batch_limit,
+            if self.tenant_api_keys.is_empty() {
+                "all".to_owned()
+            } else {
+                self.tenant_api_keys.len().to_string()
+            }
         )
     }
 }
@@ -87,8 +131,8 @@ pub enum ExecutionError {
     #[error("Recv error")]
     RecvFailure,
 
-    #[error("Failed S3 upload: {0}")]
-    FailedUpload(String),
+    #[error(transparent)]
+    ObjectStore(#[from] ObjectStoreError),
 
     #[error("Upload timeout")]
     UploadTimeout,
@@ -98,6 +142,15 @@ pub enum ExecutionError {
 
     #[error("Deserialization error: {0}")]
     DeserializationError(String),
+
+    #[error("Unknown tenants: {0}")]
+    UnknownTenants(String),
+
+    #[error("Squashing task failed: {0}")]
+    SquashingTaskFailure(#[from] tokio::task::JoinError),
+
+    #[error("Squashing thread pool error: {0}")]
+    ThreadPoolError(#[from] rayon::ThreadPoolBuildError),
 }
 
 /// Runs the SnS worker loop
@@ -119,10 +172,21 @@ pub async fn process_s3_uploads(
     conf: &Config,
     rx: mpsc::Receiver<HandleItem>,
     token: CancellationToken,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let store = connect_object_store(&conf.s3.backend).await?;
+    process_uploads(conf, store, rx, token).await
+}
+
+/// Runs the uploader loop with the given object store
+pub async fn process_uploads(
+    conf: &Config,
+    store: Arc<dyn ObjectStore>,
+    rx: mpsc::Receiver<HandleItem>,
+    token: CancellationToken,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     info!(target: "sns", "Uploader started with {:?}", conf.s3);
 
-    aws_upload::process_s3_uploads(conf, rx, token).await?;
+    aws_upload::process_uploads(conf, store, rx, token).await?;
 
     info!(target: "sns", "Uploader stopped");
     Ok(())
@@ -10,6 +10,40 @@ use tfhe::Versionize;
 
 use fhevm_engine_common::types::SupportedFheCiphertexts;
 
+/// Encoding of the serialized ct128 stored in the database and uploaded,
+/// recorded in `ciphertexts.ciphertext128_format` and
+/// `ciphertext_digest.ciphertext128_format` so that readers reject an
+/// encoding they do not know.
+///
+/// The ct128 are not compressed, see the sns-executor README.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Ct128Format {
+    /// Safe serialization of the `SquashedNoiseFheUint`/`SquashedNoiseFheBool`
+    #[default]
+    Raw,
+}
+
+impl Ct128Format {
+    pub fn as_i16(&self) -> i16 {
+        match self {
+            Self::Raw => 0,
+        }
+    }
+}
+
+impl TryFrom<i16> for Ct128Format {
+    type Error = ExecutionError;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Raw),
+            _ => Err(ExecutionError::DeserializationError(format!(
+                "unknown ct128 format {value}"
+            ))),
+        }
+    }
+}
+
 macro_rules! squash_and_serialize_with_error {
     ($value:expr, $target_ty:ty) => {{
         let squashed: $target_ty = $value
@@ -85,23 +119,41 @@ impl SquashNoiseCiphertext for SupportedFheCiphertexts {
         key: &tfhe::ClientKey,
         data: &[u8],
     ) -> Result<u128, ExecutionError> {
-        use tfhe::{prelude::FheDecrypt, SquashedNoiseFheUint};
-        let res = match self {
-            SupportedFheCiphertexts::FheBool(_) => {
-                let v: tfhe::SquashedNoiseFheBool = safe_deserialize(data)?;
-                let clear: bool = v.decrypt(key);
-                clear as u128
-            }
-            _ => {
-                let v: SquashedNoiseFheUint = safe_deserialize(data).unwrap();
-                let clear: u128 = v.decrypt(key);
-                clear
-            }
-        };
-        Ok(res)
+        decrypt_ct128(key, self.type_num(), data)
     }
 }
 
+/// Squashes the noise of the ciphertext with the current server key and
+/// serializes it as the SnS worker does, for testing the readers of the ct128
+#[cfg(feature = "test_decrypt_128")]
+pub fn squash_noise(ct: &SupportedFheCiphertexts) -> Result<Vec<u8>, ExecutionError> {
+    ct.squash_noise_and_serialize()
+}
+
+/// Decrypts the squashed noise ciphertext of the given type with the tenant
+/// client key.
+#[cfg(feature = "test_decrypt_128")]
+pub fn decrypt_ct128(
+    key: &tfhe::ClientKey,
+    ct_type: i16,
+    data: &[u8],
+) -> Result<u128, ExecutionError> {
+    use tfhe::prelude::FheDecrypt;
+    let res = match ct_type {
+        0 => {
+            let v: tfhe::SquashedNoiseFheBool = safe_deserialize(data)?;
+            let clear: bool = v.decrypt(key);
+            clear as u128
+        }
+        _ => {
+            let v: SquashedNoiseFheUint = safe_deserialize(data)?;
+            let clear: u128 = v.decrypt(key);
+            clear
+        }
+    };
+    Ok(res)
+}
+
 pub fn safe_serialize<T: Serialize + Named + Versionize>(
     object: &T,
 ) -> Result<Vec<u8>, ExecutionError> {
@@ -1,14 +1,30 @@
-use crate::{keyset::fetch_keys, squash_noise::safe_deserialize, Config, DBConfig, HandleItem};
+use crate::{
+    audit_ciphertexts,
+    aws_upload::{
+        claim_pending_uploads, compute_digest, reconcile_uploads, upload_ciphertexts,
+        MAX_DECRYPTION_BATCH_WAITS,
+    },
+    executor::{
+        claim_sns_tasks, record_task_errors, update_ciphertext128, update_computations_status,
+    },
+    keyset::{fetch_keys, KeyCache},
+    squash_noise::safe_deserialize,
+    AuditConfig, Config, Ct128Format, Ct128List, DBConfig, ExecutionError, FindingKind, HandleItem,
+    InMemoryStore, LocalDirStore, ObjectStore, ObjectStoreError, S3Config, StorageBackend,
+};
 use anyhow::Ok;
+use fhevm_engine_common::telemetry;
 use serde::{Deserialize, Serialize};
 use std::{
     fs::File,
     io::{Read, Write},
+    sync::atomic::{AtomicBool, Ordering},
     time::Duration,
 };
 use test_harness::instance::DBInstance;
 use tfhe::{prelude::FheDecrypt, ClientKey, SquashedNoiseFheUint};
 use tokio::{sync::mpsc, time::sleep};
+use tokio_util::sync::CancellationToken;
 
 const LISTEN_CHANNEL: &str = "sns_worker_chan";
 const TENANT_API_KEY: &str = "a1503fb6-d79b-4e9e-826d-44cf262f3e05";
@@ -41,6 +57,1131 @@ async fn test_fhe_ciphertext128() {
     .expect("test_decryptable, first_fhe_computation = false");
 }
 
+#[tokio::test]
+#[ignore = "requires valid SnS keys in CI"]
+async fn test_fhe_ciphertext128_two_tenants() {
+    let _ = tracing_subscriber::fmt().json().with_level(true).try_init();
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(5)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+
+    // a second tenant sharing the keys of the first one
+    let (second_tenant_id, second_api_key): (i32, String) = sqlx::query_as(
+        "
+        INSERT INTO tenants (chain_id, key_id, verifying_contract_address, acl_contract_address,
+            pks_key, sks_key, public_params, cks_key, sns_pk)
+        SELECT chain_id + 1, key_id, verifying_contract_address, acl_contract_address,
+            pks_key, sks_key, public_params, cks_key, sns_pk
+        FROM tenants
+        WHERE tenant_api_key = $1::uuid
+        RETURNING tenant_id, tenant_api_key::TEXT
+        ",
+    )
+    .bind(TENANT_API_KEY)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let (client_key, _, _) = fetch_keys(&pool, tenant_id).await.unwrap();
+
+    // the duplicate API key is ignored, and the budget holds a single keyset
+    // so the tenants evict each other's keys
+    let _rx = start_worker(
+        &test_instance,
+        vec![
+            TENANT_API_KEY.to_string(),
+            second_api_key,
+            TENANT_API_KEY.to_uppercase(),
+        ],
+        1,
+    )
+    .await;
+
+    let tf: TestFile = read_test_file("ciphertext64.bin");
+    let mut second_handle = tf.handle.to_vec();
+    second_handle[0] ^= 0xff;
+    let tasks = [
+        (tenant_id, tf.handle.to_vec()),
+        (second_tenant_id, second_handle),
+    ];
+    for (tenant_id, handle) in &tasks {
+        clean_up(&pool, handle).await.unwrap();
+        test_harness::db_utils::insert_ciphertext64(&pool, *tenant_id, handle, &tf.ciphertext64)
+            .await
+            .unwrap();
+        test_harness::db_utils::insert_into_pbs_computations(&pool, *tenant_id, handle)
+            .await
+            .unwrap();
+    }
+    sqlx::query("SELECT pg_notify($1, '')")
+        .bind(LISTEN_CHANNEL)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    for (tenant_id, handle) in &tasks {
+        let clear = wait_for_decrypted_ct128(&pool, &client_key, *tenant_id, handle)
+            .await
+            .unwrap();
+        assert_eq!(clear, tf.decrypted as u128, "tenant {tenant_id}");
+    }
+
+    for (_, handle) in &tasks {
+        clean_up(&pool, handle).await.unwrap();
+    }
+    sqlx::query("DELETE FROM tenants WHERE tenant_id = $1")
+        .bind(second_tenant_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_task_lease() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let tenants = Some(vec![tenant_id]);
+    let handle = vec![0x6c; 32];
+    clean_up(&pool, &handle).await.unwrap();
+    test_harness::db_utils::insert_ciphertext64(&pool, tenant_id, &handle, &vec![1u8; 64])
+        .await
+        .unwrap();
+    test_harness::db_utils::insert_into_pbs_computations(&pool, tenant_id, &handle)
+        .await
+        .unwrap();
+
+    let mut conn = pool.acquire().await.unwrap();
+    let claim = |tasks: Option<(Vec<HandleItem>, _)>| {
+        tasks.and_then(|(tasks, claimed_until)| {
+            let task = tasks.into_iter().find(|t| t.handle == handle)?;
+            Some((task, claimed_until))
+        })
+    };
+
+    let (_, first_lease) = claim(
+        claim_sns_tasks(&mut conn, 10, 1, &tenants, &[])
+            .await
+            .unwrap(),
+    )
+    .expect("claimed task");
+
+    // not claimed again while leased
+    assert!(claim(
+        claim_sns_tasks(&mut conn, 10, 1, &tenants, &[])
+            .await
+            .unwrap()
+    )
+    .is_none());
+
+    // claimed again once the lease expires
+    sleep(Duration::from_millis(1500)).await;
+    let (mut task, second_lease) = claim(
+        claim_sns_tasks(&mut conn, 10, 300, &tenants, &[])
+            .await
+            .unwrap(),
+    )
+    .expect("task claimed again after the lease expired");
+    assert_ne!(first_lease, second_lease);
+
+    // the error and the result of the expired claim are dropped
+    let mut db_txn = pool.begin().await.unwrap();
+    record_task_errors(
+        &mut db_txn,
+        &[(task.clone(), "expired claim".to_owned())],
+        first_lease,
+        5,
+    )
+    .await
+    .unwrap();
+    record_task_errors(
+        &mut db_txn,
+        &[(task.clone(), "current claim".to_owned())],
+        second_lease,
+        5,
+    )
+    .await
+    .unwrap();
+    task.ct128_uncompressed = Some(vec![2u8; 128]);
+    let completed = update_computations_status(&mut db_txn, vec![task.clone()], first_lease)
+        .await
+        .unwrap();
+    assert!(completed.is_empty());
+    db_txn.commit().await.unwrap();
+
+    let (is_completed, error): (bool, Option<String>) = sqlx::query_as(
+        "SELECT is_completed, error FROM pbs_computations WHERE tenant_id = $1 AND handle = $2",
+    )
+    .bind(tenant_id)
+    .bind(&handle)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(!is_completed);
+    assert_eq!(error.as_deref(), Some("current claim"));
+
+    // completed once by the current claim
+    let mut db_txn = pool.begin().await.unwrap();
+    let completed = update_computations_status(&mut db_txn, vec![task.clone()], second_lease)
+        .await
+        .unwrap();
+    assert_eq!(completed.len(), 1);
+    let completed = update_computations_status(&mut db_txn, vec![task], second_lease)
+        .await
+        .unwrap();
+    assert!(completed.is_empty());
+    db_txn.commit().await.unwrap();
+
+    let (is_completed, error): (bool, Option<String>) = sqlx::query_as(
+        "SELECT is_completed, error FROM pbs_computations WHERE tenant_id = $1 AND handle = $2",
+    )
+    .bind(tenant_id)
+    .bind(&handle)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(is_completed);
+    assert_eq!(error, None);
+
+    // a completed task is not claimed again
+    assert!(claim(
+        claim_sns_tasks(&mut conn, 10, 300, &tenants, &[])
+            .await
+            .unwrap()
+    )
+    .is_none());
+
+    clean_up(&pool, &handle).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_task_max_attempts() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let tenants = Some(vec![tenant_id]);
+    let handle = vec![0x6e; 32];
+    clean_up(&pool, &handle).await.unwrap();
+    test_harness::db_utils::insert_ciphertext64(&pool, tenant_id, &handle, &vec![1u8; 64])
+        .await
+        .unwrap();
+    test_harness::db_utils::insert_into_pbs_computations(&pool, tenant_id, &handle)
+        .await
+        .unwrap();
+
+    // claimed again after each failure, until the last attempt
+    let mut conn = pool.acquire().await.unwrap();
+    let max_attempts = 3;
+    for attempt in 1..=max_attempts {
+        let (tasks, claimed_until) = claim_sns_tasks(&mut conn, 10, 0, &tenants, &[])
+            .await
+            .unwrap()
+            .expect("claimed task");
+        let task = tasks
+            .into_iter()
+            .find(|t| t.handle == handle)
+            .expect("task claimed again after a failure");
+        let mut db_txn = pool.begin().await.unwrap();
+        record_task_errors(
+            &mut db_txn,
+            &[(task, format!("failure {attempt}"))],
+            claimed_until,
+            max_attempts,
+        )
+        .await
+        .unwrap();
+        db_txn.commit().await.unwrap();
+    }
+
+    let (attempts, is_failed, error): (i32, bool, Option<String>) = sqlx::query_as(
+        "SELECT attempts, is_failed, error FROM pbs_computations
+         WHERE tenant_id = $1 AND handle = $2",
+    )
+    .bind(tenant_id)
+    .bind(&handle)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(attempts, max_attempts as i32);
+    assert!(is_failed);
+    assert_eq!(error.as_deref(), Some("failure 3"));
+
+    // a failed task is no longer claimed
+    let tasks = claim_sns_tasks(&mut conn, 10, 0, &tenants, &[])
+        .await
+        .unwrap();
+    assert!(tasks.is_none_or(|(tasks, _)| tasks.iter().all(|t| t.handle != handle)));
+
+    clean_up(&pool, &handle).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_claim_colliding_handles() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let other_tenant_id: i32 = sqlx::query_scalar(
+        "
+        INSERT INTO tenants (chain_id, key_id, verifying_contract_address, acl_contract_address,
+            pks_key, sks_key, public_params, cks_key, sns_pk)
+        SELECT chain_id + 2, key_id, verifying_contract_address, acl_contract_address,
+            pks_key, sks_key, public_params, cks_key, sns_pk
+        FROM tenants
+        WHERE tenant_id = $1
+        RETURNING tenant_id
+        ",
+    )
+    .bind(tenant_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let tenants = Some(vec![tenant_id, other_tenant_id]);
+
+    // the same handle in both tenants, computed twice in the first one
+    let handle = vec![0x6d; 32];
+    clean_up(&pool, &handle).await.unwrap();
+    test_harness::db_utils::insert_ciphertext64(&pool, tenant_id, &handle, &vec![1u8; 64])
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO ciphertexts(tenant_id, handle, ciphertext, ciphertext_version, ciphertext_type)
+         VALUES ($1, $2, $3, 1, 0)",
+    )
+    .bind(tenant_id)
+    .bind(&handle)
+    .bind(vec![2u8; 64])
+    .execute(&pool)
+    .await
+    .unwrap();
+    test_harness::db_utils::insert_ciphertext64(&pool, other_tenant_id, &handle, &vec![4u8; 64])
+        .await
+        .unwrap();
+    for tenant_id in [tenant_id, other_tenant_id] {
+        test_harness::db_utils::insert_into_pbs_computations(&pool, tenant_id, &handle)
+            .await
+            .unwrap();
+    }
+
+    // each task is claimed once, with the latest ct64 of its tenant
+    let mut conn = pool.acquire().await.unwrap();
+    let (tasks, _) = claim_sns_tasks(&mut conn, 10, 300, &tenants, &[])
+        .await
+        .unwrap()
+        .expect("claimed tasks");
+    let mut tasks: Vec<HandleItem> = tasks.into_iter().filter(|t| t.handle == handle).collect();
+    tasks.sort_by_key(|t| t.tenant_id);
+    let claimed: Vec<(i32, Vec<u8>)> = tasks
+        .iter()
+        .map(|t| (t.tenant_id, t.ct64_compressed.clone()))
+        .collect();
+    assert_eq!(
+        claimed,
+        vec![(tenant_id, vec![2u8; 64]), (other_tenant_id, vec![4u8; 64])]
+    );
+
+    // the ct128 is stored in the latest ciphertext of its tenant only
+    tasks[0].ct128_uncompressed = Some(vec![3u8; 128]);
+    let mut db_txn = pool.begin().await.unwrap();
+    update_ciphertext128(&mut db_txn, &tasks[..1])
+        .await
+        .unwrap();
+    db_txn.commit().await.unwrap();
+    let ct128s: Vec<(i32, i16, Option<Vec<u8>>)> = sqlx::query_as(
+        "SELECT tenant_id, ciphertext_version, ciphertext128 FROM ciphertexts
+         WHERE handle = $1
+         ORDER BY tenant_id, ciphertext_version",
+    )
+    .bind(&handle)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        ct128s,
+        vec![
+            (tenant_id, 0, None),
+            (tenant_id, 1, Some(vec![3u8; 128])),
+            (other_tenant_id, 0, None),
+        ]
+    );
+
+    clean_up(&pool, &handle).await.unwrap();
+    sqlx::query("DELETE FROM ciphertext_digest WHERE handle = $1")
+        .bind(&handle)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM tenants WHERE tenant_id = $1")
+        .bind(other_tenant_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_key_cache_eviction() {
+    let mut cache = KeyCache::<Vec<u8>>::new(10, Duration::from_secs(60));
+    let fetch = |size: usize| move || async move { Result::Ok((vec![0u8; size], size)) };
+
+    assert!(cache.get_or_fetch(1, fetch(4)).await.is_some());
+    assert!(cache.get_or_fetch(2, fetch(4)).await.is_some());
+    assert_eq!(cache.cached_tenants(), vec![2, 1]);
+
+    // a cached keyset is not fetched again, and becomes the most recent one
+    let keys = cache
+        .get_or_fetch(1, || async { panic!("cached keys fetched") })
+        .await
+        .unwrap();
+    assert_eq!(keys.len(), 4);
+    assert_eq!(cache.cached_tenants(), vec![1, 2]);
+
+    // over the budget, the least recently used keys are evicted
+    assert!(cache.get_or_fetch(3, fetch(4)).await.is_some());
+    assert_eq!(cache.cached_tenants(), vec![3, 1]);
+
+    // a keyset larger than the budget evicts all others but is kept
+    assert!(cache.get_or_fetch(4, fetch(20)).await.is_some());
+    assert_eq!(cache.cached_tenants(), vec![4]);
+    assert!(cache.get_or_fetch(5, fetch(1)).await.is_some());
+    assert_eq!(cache.cached_tenants(), vec![5]);
+}
+
+#[tokio::test]
+async fn test_key_cache_unavailable_tenant() {
+    let retry_delay = Duration::from_millis(200);
+    let mut cache = KeyCache::<Vec<u8>>::new(10, retry_delay);
+    let fetches = std::sync::atomic::AtomicUsize::new(0);
+    let fetch = |available: bool| {
+        let fetches = &fetches;
+        move || async move {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            if available {
+                Result::Ok((vec![1u8], 1))
+            } else {
+                Err(ExecutionError::ConversionError(anyhow::anyhow!(
+                    "keys not found"
+                )))
+            }
+        }
+    };
+
+    assert!(cache.get_or_fetch(1, fetch(false)).await.is_none());
+    assert_eq!(cache.unavailable_tenants(), vec![1]);
+
+    // skipped without fetching until the retry delay elapses
+    assert!(cache.get_or_fetch(1, fetch(true)).await.is_none());
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    assert!(cache.get_or_fetch(2, fetch(true)).await.is_some());
+    assert_eq!(cache.unavailable_tenants(), vec![1]);
+
+    sleep(retry_delay).await;
+    assert!(cache.unavailable_tenants().is_empty());
+    assert!(cache.get_or_fetch(1, fetch(true)).await.is_some());
+    assert_eq!(fetches.load(Ordering::SeqCst), 3);
+    assert_eq!(cache.cached_tenants(), vec![1, 2]);
+}
+
+#[tokio::test]
+async fn test_storage_backends() {
+    assert_eq!("s3".parse(), std::result::Result::Ok(StorageBackend::S3));
+    assert_eq!(
+        "memory".parse(),
+        std::result::Result::Ok(StorageBackend::InMemory)
+    );
+    assert_eq!(
+        "local:/tmp/cts".parse(),
+        std::result::Result::Ok(StorageBackend::LocalDir("/tmp/cts".into()))
+    );
+    assert!("local:".parse::<StorageBackend>().is_err());
+    assert!("gcs".parse::<StorageBackend>().is_err());
+
+    let root = std::env::temp_dir().join(format!("sns-storage-{}", std::process::id()));
+    let stores: Vec<Box<dyn ObjectStore>> = vec![
+        Box::new(InMemoryStore::default()),
+        Box::new(LocalDirStore::new(root.clone())),
+    ];
+    for store in stores {
+        store.put("ct128", "00aa", vec![1, 2, 3]).await.unwrap();
+        store.put("ct128", "00aa", vec![4, 5]).await.unwrap();
+        assert_eq!(store.get("ct128", "00aa").await.unwrap(), Some(vec![4, 5]));
+        assert_eq!(store.get("ct64", "00aa").await.unwrap(), None);
+        assert_eq!(store.get("ct128", "00bb").await.unwrap(), None);
+        store.delete("ct128", "00aa").await.unwrap();
+        store.delete("ct128", "00aa").await.unwrap();
+        assert_eq!(store.get("ct128", "00aa").await.unwrap(), None);
+    }
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn test_upload_to_in_memory_store() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let conf = test_s3_config();
+    let store = InMemoryStore::default();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let handle = vec![0x5a; 32];
+    let (ct64, ct128) = (vec![1u8; 64], vec![2u8; 128]);
+    clean_up(&pool, &handle).await.unwrap();
+    sqlx::query("DELETE FROM ciphertext_digest WHERE handle = $1")
+        .bind(&handle)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let task = HandleItem {
+        tenant_id,
+        handle: handle.clone(),
+        ct64_compressed: ct64.clone(),
+        ct128_uncompressed: Some(ct128.clone()),
+        otel: telemetry::tracer("test_upload"),
+    };
+    upload_ciphertexts(task, &store, &pool, &conf)
+        .await
+        .unwrap();
+
+    let (ct64_digest, ct128_digest) = (compute_digest(&ct64), compute_digest(&ct128));
+    assert_eq!(store.keys("ct64"), vec![hex::encode(&ct64_digest)]);
+    assert_eq!(store.keys("ct128"), vec![hex::encode(&ct128_digest)]);
+    assert_eq!(
+        store
+            .get("ct128", &hex::encode(&ct128_digest))
+            .await
+            .unwrap(),
+        Some(ct128)
+    );
+
+    let (digest64, digest128, format128): (Option<Vec<u8>>, Option<Vec<u8>>, i16) = sqlx::query_as(
+        "SELECT ciphertext, ciphertext128, ciphertext128_format FROM ciphertext_digest
+             WHERE tenant_id = $1 AND handle = $2",
+    )
+    .bind(tenant_id)
+    .bind(&handle)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(digest64, Some(ct64_digest));
+    assert_eq!(digest128, Some(ct128_digest));
+    assert_eq!(format128, Ct128Format::Raw.as_i16());
+}
+
+#[test]
+fn test_ct128_formats() {
+    assert_eq!(Ct128Format::try_from(0).unwrap(), Ct128Format::Raw);
+    assert_eq!(Ct128Format::Raw.as_i16(), 0);
+    // unknown formats are rejected by the readers
+    assert!(Ct128Format::try_from(1).is_err());
+}
+
+#[test]
+fn test_ct128_list_encoding() {
+    let mut list = Ct128List::default();
+    list.push(vec![1; 32], vec![10; 64]);
+    list.push(vec![2; 32], vec![20; 64]);
+
+    let decoded = Ct128List::decode(&list.encode().unwrap()).unwrap();
+    assert_eq!(decoded, list);
+    assert_eq!(decoded.get(1), Some((&[2u8; 32][..], &[20u8; 64][..])));
+    assert_eq!(decoded.get(2), None);
+    assert_eq!(decoded.find(&[1; 32]), Some(&[10u8; 64][..]));
+    assert_eq!(decoded.find(&[3; 32]), None);
+}
+
+#[tokio::test]
+async fn test_upload_decryption_batch() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let conf = S3Config {
+        pack_decryption_batches: true,
+        ..test_s3_config()
+    };
+    let store = InMemoryStore::default();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let handles = [vec![0x5d; 32], vec![0x5e; 32]];
+    let ct64s = [vec![8u8; 64], vec![9u8; 64]];
+    let ct128s = [vec![10u8; 128], vec![11u8; 128]];
+
+    let batch: i64 = sqlx::query_scalar("SELECT nextval('pbs_computations_decryption_batch_seq')")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    for (handle, ct64) in handles.iter().zip(&ct64s) {
+        clean_up(&pool, handle).await.unwrap();
+        for table in ["ciphertext_digest", "ciphertext_audit_findings"] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE handle = $1"))
+                .bind(handle)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO pbs_computations (tenant_id, handle, decryption_batch, is_completed)
+             VALUES ($1, $2, $3, TRUE)",
+        )
+        .bind(tenant_id)
+        .bind(handle)
+        .bind(batch)
+        .execute(&pool)
+        .await
+        .unwrap();
+        test_harness::db_utils::insert_ciphertext64(&pool, tenant_id, handle, ct64)
+            .await
+            .unwrap();
+    }
+
+    let squash = |i: usize| {
+        let pool = pool.clone();
+        let (handle, ct128) = (handles[i].clone(), ct128s[i].clone());
+        async move {
+            sqlx::query(
+                "UPDATE ciphertexts SET ciphertext128 = $1, ciphertext128_format = $2
+                 WHERE handle = $3",
+            )
+            .bind(&ct128)
+            .bind(Ct128Format::Raw.as_i16())
+            .bind(&handle)
+            .execute(&pool)
+            .await
+            .unwrap();
+            ct128
+        }
+    };
+    let task = |i: usize, ct128: Vec<u8>| HandleItem {
+        tenant_id,
+        handle: handles[i].clone(),
+        ct64_compressed: ct64s[i].clone(),
+        ct128_uncompressed: Some(ct128),
+        otel: telemetry::tracer("test_upload_batch"),
+    };
+
+    // The first handle waits for the second one to be squashed, without it
+    // being an upload failure, and only its ct64 is uploaded
+    let ct128_0 = squash(0).await;
+    upload_ciphertexts(task(0, ct128_0), &store, &pool, &conf)
+        .await
+        .unwrap();
+    assert_eq!(store.keys("ct128"), Vec::<String>::new());
+    assert_eq!(store.keys("ct64").len(), 1);
+    let (digest128, retry_count, last_error, waits): (Option<Vec<u8>>, i32, Option<String>, i32) =
+        sqlx::query_as(
+            "SELECT ciphertext128, upload_retry_count, upload_last_error, decryption_batch_waits
+             FROM ciphertext_digest WHERE handle = $1",
+        )
+        .bind(&handles[0])
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(digest128, None);
+    assert_eq!(retry_count, 0);
+    assert_eq!(last_error, None);
+    assert_eq!(waits, 1);
+
+    // The last squashed handle uploads the list of both, and no ct128 alone
+    let ct128_1 = squash(1).await;
+    upload_ciphertexts(task(1, ct128_1), &store, &pool, &conf)
+        .await
+        .unwrap();
+    let keys = store.keys("ct128");
+    assert_eq!(keys.len(), 1);
+    let object = store.get("ct128", &keys[0]).await.unwrap().unwrap();
+    let list_digest = compute_digest(&object);
+    let list = Ct128List::decode(&object).unwrap();
+    assert_eq!(list.handles, handles.to_vec());
+    assert_eq!(list.ciphertexts, ct128s.to_vec());
+
+    for (index, (handle, ct128)) in handles.iter().zip(&ct128s).enumerate() {
+        let (digest128, format, list, list_index): Ct128DigestState = sqlx::query_as(
+            "SELECT ciphertext128, ciphertext128_format, ciphertext128_list,
+                ciphertext128_list_index
+             FROM ciphertext_digest WHERE handle = $1",
+        )
+        .bind(handle)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        // the digest committed to the Gateway is the one of the raw ct128 in
+        // the list
+        assert_eq!(digest128, Some(compute_digest(ct128)));
+        assert_eq!(format, Ct128Format::Raw.as_i16());
+        assert_eq!(list, Some(list_digest.clone()));
+        assert_eq!(list_index, Some(index as i32));
+
+        let stored_ct128: Option<Vec<u8>> =
+            sqlx::query_scalar("SELECT ciphertext128 FROM ciphertexts WHERE handle = $1")
+                .bind(handle)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(stored_ct128, None);
+    }
+
+    // Both handles are uploaded, the audit finds their ct128 in the list
+    for handle in &handles {
+        assert!(claim_test_upload(&pool, handle).await.is_none());
+    }
+    let audit = AuditConfig {
+        sample_rate: 1.0,
+        batch_size: 10,
+        repair: false,
+    };
+    audit_ciphertexts(&pool, &store, &conf, &audit, CancellationToken::new())
+        .await
+        .unwrap();
+    assert_eq!(audit_findings(&pool, &handles[1]).await, vec![]);
+
+    // A corrupted list is reported for each of its handles
+    store.put("ct128", &keys[0], vec![0]).await.unwrap();
+    audit_ciphertexts(&pool, &store, &conf, &audit, CancellationToken::new())
+        .await
+        .unwrap();
+    for handle in &handles {
+        assert_eq!(
+            audit_findings(&pool, handle).await,
+            vec![(FindingKind::CorruptedCt128.to_string(), false)]
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_upload_decryption_batch_gives_up_waiting() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let conf = S3Config {
+        pack_decryption_batches: true,
+        ..test_s3_config()
+    };
+    let store = InMemoryStore::default();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let handles = [vec![0x5f; 32], vec![0x60; 32]];
+    let (ct64, ct128) = (vec![12u8; 64], vec![13u8; 128]);
+
+    let batch: i64 = sqlx::query_scalar("SELECT nextval('pbs_computations_decryption_batch_seq')")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    for handle in &handles {
+        clean_up(&pool, handle).await.unwrap();
+        sqlx::query("DELETE FROM ciphertext_digest WHERE handle = $1")
+            .bind(handle)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO pbs_computations (tenant_id, handle, decryption_batch, is_completed)
+             VALUES ($1, $2, $3, TRUE)",
+        )
+        .bind(tenant_id)
+        .bind(handle)
+        .bind(batch)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    // Only the first handle is ever squashed
+    test_harness::db_utils::insert_ciphertext64(&pool, tenant_id, &handles[0], &ct64)
+        .await
+        .unwrap();
+    sqlx::query(
+        "UPDATE ciphertexts SET ciphertext128 = $1, ciphertext128_format = $2 WHERE handle = $3",
+    )
+    .bind(&ct128)
+    .bind(Ct128Format::Raw.as_i16())
+    .bind(&handles[0])
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let task = || HandleItem {
+        tenant_id,
+        handle: handles[0].clone(),
+        ct64_compressed: ct64.clone(),
+        ct128_uncompressed: Some(ct128.clone()),
+        otel: telemetry::tracer("test_upload_batch_wait"),
+    };
+    for _ in 0..MAX_DECRYPTION_BATCH_WAITS {
+        upload_ciphertexts(task(), &store, &pool, &conf)
+            .await
+            .unwrap();
+        assert_eq!(store.keys("ct128"), Vec::<String>::new());
+    }
+
+    // The ct128 is uploaded alone once the handle gives up waiting
+    upload_ciphertexts(task(), &store, &pool, &conf)
+        .await
+        .unwrap();
+    assert_eq!(
+        store.keys("ct128"),
+        vec![hex::encode(compute_digest(&ct128))]
+    );
+    let (digest128, _, list, list_index): Ct128DigestState = sqlx::query_as(
+        "SELECT ciphertext128, ciphertext128_format, ciphertext128_list, ciphertext128_list_index
+         FROM ciphertext_digest WHERE handle = $1",
+    )
+    .bind(&handles[0])
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(digest128, Some(compute_digest(&ct128)));
+    assert_eq!((list, list_index), (None, None));
+}
+
+/// Fails the uploads of ct128 while `failing` is set
+#[derive(Default)]
+struct FailingStore {
+    inner: InMemoryStore,
+    failing: AtomicBool,
+}
+
+#[async_trait::async_trait]
+impl ObjectStore for FailingStore {
+    async fn put(&self, bucket: &str, key: &str, data: Vec<u8>) -> Result<(), ObjectStoreError> {
+        if bucket == "ct128" && self.failing.load(Ordering::SeqCst) {
+            return Err(ObjectStoreError::FailedUpload("unavailable".to_owned()));
+        }
+        self.inner.put(bucket, key, data).await
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, ObjectStoreError> {
+        self.inner.get(bucket, key).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), ObjectStoreError> {
+        self.inner.delete(bucket, key).await
+    }
+}
+
+#[tokio::test]
+async fn test_upload_retry_and_reconciliation() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let conf = test_s3_config();
+    let store = FailingStore::default();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let handle = vec![0x5b; 32];
+    let (ct64, ct128) = (vec![3u8; 64], vec![4u8; 128]);
+    clean_up(&pool, &handle).await.unwrap();
+    sqlx::query("DELETE FROM ciphertext_digest WHERE handle = $1")
+        .bind(&handle)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // A ct128 computed but not uploaded before a crash
+    test_harness::db_utils::insert_ciphertext64(&pool, tenant_id, &handle, &ct64)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE ciphertexts SET ciphertext128 = $1 WHERE handle = $2")
+        .bind(&ct128)
+        .bind(&handle)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert!(reconcile_uploads(&pool).await.unwrap() >= 1);
+    assert_eq!(reconcile_uploads(&pool).await.unwrap(), 0);
+
+    // The ct128 upload fails, the ct64 one succeeds
+    store.failing.store(true, Ordering::SeqCst);
+    let task = claim_test_upload(&pool, &handle)
+        .await
+        .expect("pending upload");
+    assert_eq!(task.ct128_uncompressed, Some(ct128.clone()));
+    upload_ciphertexts(task, &store, &pool, &conf)
+        .await
+        .unwrap();
+
+    let (digest64, digest128, retry_count, last_error, is_due): UploadState = sqlx::query_as(
+        "SELECT ciphertext, ciphertext128, upload_retry_count, upload_last_error,
+            upload_next_attempt_at <= NOW()
+         FROM ciphertext_digest
+         WHERE tenant_id = $1 AND handle = $2",
+    )
+    .bind(tenant_id)
+    .bind(&handle)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(digest64, Some(compute_digest(&ct64)));
+    assert_eq!(digest128, None);
+    assert_eq!(retry_count, 1);
+    assert!(last_error.unwrap().contains("ct128"));
+    assert!(!is_due);
+    assert!(
+        claim_test_upload(&pool, &handle).await.is_none(),
+        "retried during the backoff"
+    );
+
+    // Once the backoff expired, the ct128 is retried
+    sqlx::query("UPDATE ciphertext_digest SET upload_next_attempt_at = NOW() WHERE handle = $1")
+        .bind(&handle)
+        .execute(&pool)
+        .await
+        .unwrap();
+    store.failing.store(false, Ordering::SeqCst);
+    let task = claim_test_upload(&pool, &handle)
+        .await
+        .expect("pending upload");
+    upload_ciphertexts(task, &store, &pool, &conf)
+        .await
+        .unwrap();
+
+    let digest128: Option<Vec<u8>> =
+        sqlx::query_scalar("SELECT ciphertext128 FROM ciphertext_digest WHERE handle = $1")
+            .bind(&handle)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(digest128, Some(compute_digest(&ct128)));
+    assert_eq!(
+        store.inner.keys("ct128"),
+        vec![hex::encode(compute_digest(&ct128))]
+    );
+    let stored_ct128: Option<Vec<u8>> =
+        sqlx::query_scalar("SELECT ciphertext128 FROM ciphertexts WHERE handle = $1")
+            .bind(&handle)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored_ct128, None);
+    assert!(claim_test_upload(&pool, &handle).await.is_none());
+}
+
+#[tokio::test]
+async fn test_audit_finds_and_repairs_objects() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let conf = test_s3_config();
+    let store = InMemoryStore::default();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let handle = vec![0x5c; 32];
+    let (ct64, ct128) = (vec![5u8; 64], vec![6u8; 128]);
+    clean_up(&pool, &handle).await.unwrap();
+    for table in ["ciphertext_digest", "ciphertext_audit_findings"] {
+        sqlx::query(&format!("DELETE FROM {table} WHERE handle = $1"))
+            .bind(&handle)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    test_harness::db_utils::insert_ciphertext64(&pool, tenant_id, &handle, &ct64)
+        .await
+        .unwrap();
+    let task = HandleItem {
+        tenant_id,
+        handle: handle.clone(),
+        ct64_compressed: ct64.clone(),
+        ct128_uncompressed: Some(ct128.clone()),
+        otel: telemetry::tracer("test_audit"),
+    };
+    upload_ciphertexts(task, &store, &pool, &conf)
+        .await
+        .unwrap();
+
+    let audit = AuditConfig {
+        sample_rate: 1.0,
+        batch_size: 10,
+        repair: true,
+    };
+    let report = audit_ciphertexts(&pool, &store, &conf, &audit, CancellationToken::new())
+        .await
+        .unwrap();
+    assert!(report.audited >= 1);
+    assert_eq!(audit_findings(&pool, &handle).await, vec![]);
+
+    // Both objects are overwritten, only the ct64 is still in the database
+    let (ct64_key, ct128_key) = (
+        hex::encode(compute_digest(&ct64)),
+        hex::encode(compute_digest(&ct128)),
+    );
+    store.put("ct64", &ct64_key, vec![0]).await.unwrap();
+    store.put("ct128", &ct128_key, vec![0]).await.unwrap();
+    let report = audit_ciphertexts(&pool, &store, &conf, &audit, CancellationToken::new())
+        .await
+        .unwrap();
+    assert!(report.findings >= 2 && report.repaired >= 1);
+    assert_eq!(store.get("ct64", &ct64_key).await.unwrap(), Some(ct64));
+    assert_eq!(
+        audit_findings(&pool, &handle).await,
+        vec![
+            (FindingKind::CorruptedCt128.to_string(), false),
+            (FindingKind::CorruptedCt64.to_string(), true)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_audit_commits_of_tenant_chain() {
+    let test_instance = test_harness::instance::setup_test_db()
+        .await
+        .expect("valid db instance");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(2)
+        .connect(test_instance.db_url())
+        .await
+        .unwrap();
+    let conf = test_s3_config();
+    let store = InMemoryStore::default();
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let chain_id: i32 = sqlx::query_scalar("SELECT chain_id FROM tenants WHERE tenant_id = $1")
+        .bind(tenant_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    // Handles of the chain of the tenant and of another chain
+    let mut handle = vec![0x5b; 32];
+    handle[22..30].copy_from_slice(&(chain_id as u64).to_be_bytes());
+    let mut other_chain_handle = vec![0x5a; 32];
+    other_chain_handle[22..30].copy_from_slice(&(chain_id as u64 + 1).to_be_bytes());
+
+    for (i, handle) in [&handle, &other_chain_handle].into_iter().enumerate() {
+        let (ct64, ct128) = (vec![i as u8; 64], vec![i as u8 + 2; 128]);
+        clean_up(&pool, handle).await.unwrap();
+        for table in [
+            "ciphertext_digest",
+            "ciphertext_audit_findings",
+            "gw_ciphertext_commits",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE handle = $1"))
+                .bind(handle)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        test_harness::db_utils::insert_ciphertext64(&pool, tenant_id, handle, &ct64)
+            .await
+            .unwrap();
+        let task = HandleItem {
+            tenant_id,
+            handle: handle.clone(),
+            ct64_compressed: ct64,
+            ct128_uncompressed: Some(ct128),
+            otel: telemetry::tracer("test_audit_commits"),
+        };
+        upload_ciphertexts(task, &store, &pool, &conf)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO gw_ciphertext_commits (handle, ciphertext_digest, sns_ciphertext_digest,
+                coprocessor_tx_sender_addresses)
+             VALUES ($1, $2, $2, '{}')",
+        )
+        .bind(handle)
+        .bind(vec![0xffu8; 32])
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let audit = AuditConfig {
+        sample_rate: 1.0,
+        batch_size: 10,
+        repair: false,
+    };
+    audit_ciphertexts(&pool, &store, &conf, &audit, CancellationToken::new())
+        .await
+        .unwrap();
+
+    // Only the commits of the handle of the chain of the tenant are compared
+    assert_eq!(
+        audit_findings(&pool, &handle).await,
+        vec![
+            (FindingKind::CommitMismatch.to_string(), false),
+            (FindingKind::CommitMismatch.to_string(), false)
+        ]
+    );
+    assert_eq!(audit_findings(&pool, &other_chain_handle).await, vec![]);
+}
+
+/// ct128 digest and format, and digest of the list and index in it
+type Ct128DigestState = (Option<Vec<u8>>, i16, Option<Vec<u8>>, Option<i32>);
+
+/// Digests, retry count, last error and whether the next upload attempt is due
+type UploadState = (Option<Vec<u8>>, Option<Vec<u8>>, i32, Option<String>, bool);
+
+/// The kinds of the audit findings of the handle, with whether they were repaired
+async fn audit_findings(pool: &sqlx::PgPool, handle: &Vec<u8>) -> Vec<(String, bool)> {
+    sqlx::query_as(
+        "SELECT kind, repaired FROM ciphertext_audit_findings WHERE handle = $1 ORDER BY kind",
+    )
+    .bind(handle)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+async fn claim_test_upload(pool: &sqlx::PgPool, handle: &Vec<u8>) -> Option<HandleItem> {
+    claim_pending_uploads(pool, 100, &test_s3_config())
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|task| &task.handle == handle)
+}
+
+fn test_s3_config() -> S3Config {
+    S3Config {
+        bucket_ct128: "ct128".to_owned(),
+        bucket_ct64: "ct64".to_owned(),
+        max_concurrent_uploads: 1,
+        backend: StorageBackend::InMemory,
+        retry_polling_interval: 1,
+        retry_base_delay: 60,
+        retry_max_delay: 600,
+        pack_decryption_batches: false,
+    }
+}
+
 async fn test_decryptable(
     pool: &sqlx::PgPool,
     client_key: &Option<ClientKey>,
@@ -62,7 +1203,23 @@ async fn test_decryptable(
     }
 
     let tenant_id = get_tenant_id_from_db(pool, TENANT_API_KEY).await;
+    let clear = wait_for_decrypted_ct128(pool, client_key, tenant_id, handle).await?;
+
+    assert!(
+        clear == expected_result as u128,
+        "Decrypted value does not match expected value",
+    );
 
+    anyhow::Result::<()>::Ok(())
+}
+
+/// Waits for the ct128 of the handle and decrypts it
+async fn wait_for_decrypted_ct128(
+    pool: &sqlx::PgPool,
+    client_key: &Option<ClientKey>,
+    tenant_id: i32,
+    handle: &Vec<u8>,
+) -> anyhow::Result<u128> {
     // wait until ciphertext.large_ct is not NULL
     let data = test_harness::db_utils::wait_for_ciphertext(pool, tenant_id, handle, 10).await?;
     let v: SquashedNoiseFheUint = safe_deserialize(&data).unwrap();
@@ -70,12 +1227,7 @@ async fn test_decryptable(
 
     println!("Decrypted value: {clear}");
 
-    assert!(
-        clear == expected_result as u128,
-        "Decrypted value does not match expected value",
-    );
-
-    anyhow::Result::<()>::Ok(())
+    Ok(clear)
 }
 
 async fn setup() -> anyhow::Result<(
@@ -84,13 +1236,39 @@ async fn setup() -> anyhow::Result<(
     tokio::sync::mpsc::Receiver<HandleItem>,
     DBInstance,
 )> {
-    tracing_subscriber::fmt().json().with_level(true).init();
+    let _ = tracing_subscriber::fmt().json().with_level(true).try_init();
     let test_instance = test_harness::instance::setup_test_db()
         .await
         .expect("valid db instance");
 
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(5)
+        .connect(test_instance.db_url())
+        .await?;
+
+    let tenant_id = get_tenant_id_from_db(&pool, TENANT_API_KEY).await;
+    let (client_key, _, _) = fetch_keys(&pool, tenant_id).await?;
+
+    let upload_rx = start_worker(
+        &test_instance,
+        vec![TENANT_API_KEY.to_string()],
+        4 * 1024 * 1024 * 1024,
+    )
+    .await;
+
+    Ok((pool, client_key, upload_rx, test_instance))
+}
+
+/// Starts a worker processing the tasks of the tenants
+async fn start_worker(
+    test_instance: &DBInstance,
+    tenant_api_keys: Vec<String>,
+    key_cache_budget: u64,
+) -> tokio::sync::mpsc::Receiver<HandleItem> {
     let conf = Config {
-        tenant_api_key: TENANT_API_KEY.to_string(),
+        tenant_api_keys,
+        key_cache_budget,
+        squash_threads: 0,
         db: DBConfig {
             url: test_instance.db_url().to_owned(),
             listen_channels: vec![LISTEN_CHANNEL.to_string()],
@@ -98,21 +1276,16 @@ async fn setup() -> anyhow::Result<(
             batch_limit: 10,
             polling_interval: 60000,
             max_connections: 5,
+            task_lease_duration: 300,
+            max_task_attempts: 5,
         },
         s3: crate::S3Config::default(),
         service_name: "test-sns-worker".to_owned(),
     };
 
-    let pool = sqlx::postgres::PgPoolOptions::new()
-        .max_connections(conf.db.max_connections)
-        .connect(&conf.db.url)
-        .await?;
-
     let (upload_tx, upload_rx) = mpsc::channel::<HandleItem>(10);
 
     let token = test_instance.parent_token.child_token();
-    let (client_key, _) = fetch_keys(&pool, &TENANT_API_KEY.to_owned()).await?;
-
     tokio::spawn(async move {
         crate::compute_128bit_ct(&conf, upload_tx, token)
             .await
@@ -123,7 +1296,7 @@ async fn setup() -> anyhow::Result<(
     // TODO: Replace this with notification from the worker when it's in ready-state
     sleep(Duration::from_secs(5)).await;
 
-    Ok((pool, client_key, upload_rx, test_instance))
+    upload_rx
 }
 
 #[derive(Serialize, Deserialize)]
@@ -36,4 +36,18 @@ contract CiphertextCommits {
             new address[](0)
         );
     }
+
+    /// @dev Same as OpenZeppelin's Multicall: calls this contract with each data, keeping msg.sender.
+    function multicall(bytes[] calldata data) external returns (bytes[] memory results) {
+        results = new bytes[](data.length);
+        for (uint256 i = 0; i < data.length; i++) {
+            (bool success, bytes memory result) = address(this).delegatecall(data[i]);
+            if (!success) {
+                assembly {
+                    revert(add(result, 32), mload(result))
+                }
+            }
+            results[i] = result;
+        }
+    }
 }
@@ -6,9 +6,17 @@ pragma solidity ^0.8.24;
 /// @notice This contract is a mock of the MultichainAcl contract from L2.
 contract MultichainAcl {
     error CoprocessorAlreadyAllowed(address coprocessor, bytes32 ctHandle);
+    error CoprocessorAlreadyDelegated(address coprocessor, uint256 chainId, DelegationAccounts delegationAccounts);
+
+    struct DelegationAccounts {
+        address delegatorAddress;
+        address delegatedAddress;
+    }
 
     event AllowAccount(bytes32 indexed ctHandle, address accountAddress);
     event AllowPublicDecrypt(bytes32 indexed ctHandle);
+    event DelegateAccount(uint256 indexed chainId, DelegationAccounts delegationAccounts, address[] contractAddresses);
+    event RevokeDelegation(uint256 indexed chainId, DelegationAccounts delegationAccounts, address[] contractAddresses);
 
     bool alreadyAllowedRevert;
 
@@ -29,4 +37,40 @@ contract MultichainAcl {
         }
         emit AllowPublicDecrypt(ctHandle);
     }
+
+    function delegateAccount(
+        uint256 chainId,
+        DelegationAccounts calldata delegationAccounts,
+        address[] calldata contractAddresses
+    ) public {
+        if (alreadyAllowedRevert) {
+            revert CoprocessorAlreadyDelegated(msg.sender, chainId, delegationAccounts);
+        }
+        emit DelegateAccount(chainId, delegationAccounts, contractAddresses);
+    }
+
+    function revokeDelegation(
+        uint256 chainId,
+        DelegationAccounts calldata delegationAccounts,
+        address[] calldata contractAddresses
+    ) public {
+        if (alreadyAllowedRevert) {
+            revert CoprocessorAlreadyDelegated(msg.sender, chainId, delegationAccounts);
+        }
+        emit RevokeDelegation(chainId, delegationAccounts, contractAddresses);
+    }
+
+    /// @dev Same as OpenZeppelin's Multicall: calls this contract with each data, keeping msg.sender.
+    function multicall(bytes[] calldata data) external returns (bytes[] memory results) {
+        results = new bytes[](data.length);
+        for (uint256 i = 0; i < data.length; i++) {
+            (bool success, bytes memory result) = address(this).delegatecall(data[i]);
+            if (!success) {
+                assembly {
+                    revert(add(result, 32), mload(result))
+                }
+            }
+            results[i] = result;
+        }
+    }
 }
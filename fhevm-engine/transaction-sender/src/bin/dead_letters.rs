@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use alloy::providers::RootProvider;
+use clap::{Parser, Subcommand};
+use sqlx::postgres::PgPoolOptions;
+use transaction_sender::{
+    ConfigSettings, DeadLetter, DeadLetterQueue, OperationRegistry, OperationsConfig,
+};
+
+/// Lists, inspects and re-submits the items the transaction-sender gave up on after exhausting their retries.
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, long_about = None)]
+struct Conf {
+    #[arg(short, long)]
+    database_url: Option<String>,
+
+    #[arg(long, default_value = "verify_proof_responses")]
+    verify_proof_resp_database_channel: String,
+
+    #[arg(long, default_value = "add_ciphertexts")]
+    add_ciphertexts_database_channel: String,
+
+    #[arg(long, default_value = "event_allowed_handle")]
+    allow_handle_database_channel: String,
+
+    #[arg(long, default_value = "event_delegation")]
+    delegation_database_channel: String,
+
+    /// The operations configuration of the transaction-sender, to notify the operations on their configured channels
+    #[arg(long)]
+    operations_config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Action {
+    /// Prints the dead letters not yet re-submitted, oldest first
+    List {
+        /// One of verify_proof, add_ciphertext, allow_handle or delegation
+        #[arg(long)]
+        operation: Option<String>,
+
+        #[arg(long, default_value = "100")]
+        limit: i64,
+    },
+    /// Prints a dead letter with the errors of all the attempts to send it
+    Inspect { id: i64 },
+    /// Resets the retry counts of the dead letters, restoring removed proofs, and notifies the transaction-sender
+    Resubmit {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+}
+
+fn print_dead_letter(dead_letter: &DeadLetter) {
+    println!(
+        "#{} {} retries {} age {}s resubmitted {} times{}: {}",
+        dead_letter.id,
+        dead_letter.item,
+        dead_letter.retry_count,
+        dead_letter.age_secs,
+        dead_letter.resubmit_count,
+        if dead_letter.removed {
+            " (removed)"
+        } else {
+            ""
+        },
+        dead_letter.last_error
+    );
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let conf = Conf::parse();
+    let database_url = conf
+        .database_url
+        .clone()
+        .unwrap_or_else(|| std::env::var("DATABASE_URL").expect("DATABASE_URL is undefined"));
+    let db_pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&database_url)
+        .await?;
+    let dead_letters = DeadLetterQueue::new(db_pool);
+    match conf.action {
+        Action::List { operation, limit } => {
+            let list = dead_letters.list(operation.as_deref(), limit).await?;
+            for dead_letter in &list {
+                print_dead_letter(dead_letter);
+            }
+            eprintln!("{} dead letters pending", list.len());
+        }
+        Action::Inspect { id } => {
+            let Some(dead_letter) = dead_letters.get(id).await? else {
+                anyhow::bail!("no dead letter with id {}", id);
+            };
+            print_dead_letter(&dead_letter);
+            println!("created at {}", dead_letter.created_at);
+            if let Some(resubmitted_at) = &dead_letter.resubmitted_at {
+                println!("resubmitted at {}", resubmitted_at);
+            }
+            for error in dead_letters.errors(&dead_letter.item).await? {
+                println!(
+                    "{} {}{}",
+                    error.created_at,
+                    if error.is_transport {
+                        "transport: "
+                    } else {
+                        ""
+                    },
+                    error.error
+                );
+            }
+        }
+        Action::Resubmit { ids } => {
+            let settings = ConfigSettings {
+                database_url,
+                verify_proof_resp_db_channel: conf.verify_proof_resp_database_channel,
+                add_ciphertexts_db_channel: conf.add_ciphertexts_database_channel,
+                allow_handle_db_channel: conf.allow_handle_database_channel,
+                delegation_db_channel: conf.delegation_database_channel,
+                ..Default::default()
+            };
+            let operations_config = match &conf.operations_config {
+                Some(path) => OperationsConfig::from_file(path)?,
+                None => OperationsConfig::default(),
+            };
+            let db_channels = OperationRegistry::<RootProvider>::default()
+                .with_config(operations_config)
+                .db_channels(&settings);
+            let mut failed = 0;
+            for id in ids {
+                match dead_letters.resubmit(id, &db_channels).await {
+                    Ok(()) => eprintln!("#{} resubmitted", id),
+                    Err(e) => {
+                        failed += 1;
+                        eprintln!("#{} not resubmitted: {}", id, e);
+                    }
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("{} dead letters not resubmitted", failed);
+            }
+        }
+    }
+    Ok(())
+}
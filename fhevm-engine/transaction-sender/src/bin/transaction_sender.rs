@@ -1,17 +1,19 @@
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr};
 
 use alloy::{
     network::EthereumWallet,
     primitives::Address,
-    providers::{ProviderBuilder, WsConnect},
+    providers::{Provider, ProviderBuilder, WsConnect},
     signers::local::PrivateKeySigner,
     transports::http::reqwest::Url,
 };
 use clap::Parser;
+use fhevm_engine_common::signer::{KeySigner, SignerSource};
 use tokio::signal::unix::{signal, SignalKind};
 use tokio_util::sync::CancellationToken;
 use transaction_sender::{
-    ConfigSettings, FillersWithoutNonceManagement, NonceManagedProvider, TransactionSender,
+    run_metrics_server, ConfigSettings, FeeStrategy, FillersWithoutNonceManagement,
+    NonceManagedProvider, OperationRegistry, OperationsConfig, SignerPool, TransactionSender,
 };
 
 #[derive(Parser, Debug, Clone)]
@@ -29,8 +31,24 @@ struct Conf {
     #[arg(short, long)]
     gateway_url: Url,
 
-    #[arg(short, long)]
-    private_key: String,
+    /// Private key in plain text 0x1234.. format, prefer --signer to keep it off the command line
+    #[arg(
+        short,
+        long,
+        required_unless_present = "signer",
+        conflicts_with = "signer"
+    )]
+    private_key: Option<String>,
+
+    /// Coprocessor signer and transaction sender: a private key file path (optionally prefixed with file:),
+    /// env:<variable> or remote:<url> of a RemoteSigner service
+    #[arg(long)]
+    signer: Option<SignerSource>,
+
+    /// Additional transaction senders, each with its own nonce sequence, in the same format as --signer. The
+    /// coprocessor signer stays the one of --signer or --private-key
+    #[arg(long, value_delimiter = ',')]
+    additional_signers: Vec<SignerSource>,
 
     #[arg(short, long)]
     database_url: Option<String>,
@@ -50,6 +68,9 @@ struct Conf {
     #[arg(long, default_value = "event_allowed_handle")]
     allow_handle_database_channel: String,
 
+    #[arg(long, default_value = "event_delegation")]
+    delegation_database_channel: String,
+
     #[arg(long, default_value = "128")]
     verify_proof_resp_batch_limit: u32,
 
@@ -71,6 +92,22 @@ struct Conf {
     #[arg(long, default_value = "15")]
     add_ciphertexts_max_retries: u32,
 
+    /// Send the selected ciphertexts in a single multicall transaction. Requires a CiphertextCommits contract exposing
+    /// OpenZeppelin's `multicall(bytes[])`, which only the test mocks are known to do
+    #[arg(long)]
+    add_ciphertexts_multicall: bool,
+
+    /// Send the selected ACL entries in a single multicall transaction. Requires a MultichainAcl contract exposing
+    /// OpenZeppelin's `multicall(bytes[])`, which only the test mocks are known to do
+    #[arg(long)]
+    allow_handle_multicall: bool,
+
+    #[arg(long, default_value = "10")]
+    delegation_batch_limit: u32,
+
+    #[arg(long, default_value = "10")]
+    delegation_max_retries: u32,
+
     #[arg(long, default_value = "1")]
     error_sleep_initial_secs: u16,
 
@@ -83,8 +120,46 @@ struct Conf {
     #[arg(long, default_value = "0")]
     required_txn_confirmations: u16,
 
+    /// One of provider, fixed:<max fee>:<max priority fee>, percentile:<reward percentile>[:<blocks>],
+    /// capped:<max fee>:<max priority fee>, fees in wei
+    #[arg(long, default_value = "provider")]
+    fee_strategy: FeeStrategy,
+
+    /// Percentage by which fees are bumped when replacing a stuck transaction
+    #[arg(long, default_value = "20")]
+    fee_bump_percent: u16,
+
+    /// Fee bumps of a stuck transaction, after which its nonce is cancelled by a 0-value transfer to the signer
+    #[arg(long, default_value = "3")]
+    max_fee_bumps: u16,
+
     #[arg(long, default_value = "30")]
     review_after_transport_retries: u16,
+
+    #[arg(long, default_value = "60")]
+    balance_check_interval_secs: u16,
+
+    /// Signers with a balance below it are alerted on and skipped while others are not
+    #[arg(long, default_value = "100000000000000000")]
+    low_balance_threshold_wei: u128,
+
+    /// Interval of the dead letters and operations metrics updates
+    #[arg(long, default_value = "60")]
+    dead_letter_metrics_interval_secs: u16,
+
+    /// Age after which the recorded errors of the items still not sent are removed, at the dead letter metrics
+    /// interval
+    #[arg(long, default_value = "604800")]
+    errors_retention_secs: u32,
+
+    /// Prometheus metrics server address
+    #[arg(long)]
+    metrics_addr: Option<String>,
+
+    /// JSON file enabling and configuring the operations, see transaction_sender::OperationsConfig. All the
+    /// operations are enabled with the command-line settings if not set
+    #[arg(long)]
+    operations_config: Option<PathBuf>,
 }
 
 fn install_signal_handlers(cancel_token: CancellationToken) -> anyhow::Result<()> {
@@ -104,27 +179,52 @@ fn install_signal_handlers(cancel_token: CancellationToken) -> anyhow::Result<()
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt().json().with_level(true).init();
     let conf = Conf::parse();
-    let signer = PrivateKeySigner::from_str(conf.private_key.trim())?;
-    let wallet = EthereumWallet::new(signer.clone());
+    let signer = match (&conf.private_key, &conf.signer) {
+        (Some(private_key), _) => KeySigner::from(PrivateKeySigner::from_str(private_key.trim())?),
+        (None, Some(source)) => KeySigner::new(source).await?,
+        (None, None) => anyhow::bail!("--signer or --private-key is required"),
+    };
     let database_url = conf
         .database_url
         .clone()
         .unwrap_or_else(|| std::env::var("DATABASE_URL").expect("DATABASE_URL is undefined"));
     let cancel_token = CancellationToken::new();
-    let provider = NonceManagedProvider::new(
-        ProviderBuilder::default()
-            .filler(FillersWithoutNonceManagement::default())
-            .wallet(wallet.clone())
-            .on_ws(WsConnect::new(conf.gateway_url))
-            .await?,
-        Some(wallet.default_signer().address()),
-    );
-    let sender = TransactionSender::new(
+    // Sign transactions for the gateway chain only, remote signers included.
+    let gw_chain_id = ProviderBuilder::new()
+        .on_ws(WsConnect::new(conf.gateway_url.clone()))
+        .await?
+        .get_chain_id()
+        .await?;
+    let signer = signer.with_chain_id(Some(gw_chain_id));
+    let mut wallets = vec![EthereumWallet::new(signer.clone())];
+    for source in &conf.additional_signers {
+        wallets.push(EthereumWallet::new(
+            KeySigner::new(source)
+                .await?
+                .with_chain_id(Some(gw_chain_id)),
+        ));
+    }
+    let mut providers = vec![];
+    for wallet in wallets {
+        providers.push(NonceManagedProvider::new(
+            ProviderBuilder::default()
+                .filler(FillersWithoutNonceManagement::default())
+                .wallet(wallet.clone())
+                .on_ws(WsConnect::new(conf.gateway_url.clone()))
+                .await?,
+            Some(wallet.default_signer().address()),
+        ));
+    }
+    let operations_config = match &conf.operations_config {
+        Some(path) => OperationsConfig::from_file(path)?,
+        None => OperationsConfig::default(),
+    };
+    let sender = TransactionSender::new_with_registry(
         conf.input_verification_address,
         conf.ciphertext_commits_address,
         conf.multichain_acl_address,
         signer,
-        provider,
+        SignerPool::new(providers),
         cancel_token.clone(),
         ConfigSettings {
             database_url,
@@ -132,6 +232,7 @@ async fn main() -> anyhow::Result<()> {
             verify_proof_resp_db_channel: conf.verify_proof_resp_database_channel,
             add_ciphertexts_db_channel: conf.add_ciphertexts_database_channel,
             allow_handle_db_channel: conf.allow_handle_database_channel,
+            delegation_db_channel: conf.delegation_database_channel,
             verify_proof_resp_batch_limit: conf.verify_proof_resp_batch_limit,
             verify_proof_resp_max_retries: conf.verify_proof_resp_max_retries,
             verify_proof_remove_after_max_retries: conf.verify_proof_remove_after_max_retries,
@@ -140,15 +241,30 @@ async fn main() -> anyhow::Result<()> {
             error_sleep_initial_secs: conf.error_sleep_initial_secs,
             error_sleep_max_secs: conf.error_sleep_max_secs,
             add_ciphertexts_max_retries: conf.add_ciphertexts_max_retries,
+            add_ciphertexts_multicall: conf.add_ciphertexts_multicall,
             allow_handle_batch_limit: conf.allow_handle_batch_limit,
             allow_handle_max_retries: conf.allow_handle_max_retries,
+            allow_handle_multicall: conf.allow_handle_multicall,
+            delegation_batch_limit: conf.delegation_batch_limit,
+            delegation_max_retries: conf.delegation_max_retries,
             txn_receipt_timeout_secs: conf.txn_receipt_timeout_secs,
             required_txn_confirmations: conf.required_txn_confirmations,
+            fee_strategy: conf.fee_strategy,
+            fee_bump_percent: conf.fee_bump_percent,
+            max_fee_bumps: conf.max_fee_bumps,
             review_after_transport_retries: conf.review_after_transport_retries,
+            balance_check_interval_secs: conf.balance_check_interval_secs,
+            low_balance_threshold_wei: conf.low_balance_threshold_wei,
+            dead_letter_metrics_interval_secs: conf.dead_letter_metrics_interval_secs,
+            errors_retention_secs: conf.errors_retention_secs,
         },
         None,
+        OperationRegistry::default().with_config(operations_config),
     )
     .await?;
+    if let Some(metrics_addr) = conf.metrics_addr {
+        tokio::spawn(run_metrics_server(metrics_addr));
+    }
     install_signal_handlers(cancel_token)?;
     sender.run().await
 }
@@ -0,0 +1,525 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail};
+use fhevm_engine_common::utils::compact_hex;
+use sqlx::{types::time::OffsetDateTime, Pool, Postgres};
+use tracing::error;
+
+use crate::{metrics, ConfigSettings, REVIEW};
+
+pub const VERIFY_PROOF: &str = "verify_proof";
+pub const ADD_CIPHERTEXT: &str = "add_ciphertext";
+pub const ALLOW_HANDLE: &str = "allow_handle";
+pub const DELEGATION: &str = "delegation";
+
+pub const OPERATIONS: [&str; 4] = [VERIFY_PROOF, ADD_CIPHERTEXT, ALLOW_HANDLE, DELEGATION];
+
+/// Number of errors kept per item, the oldest ones are pruned.
+pub const MAX_ERRORS_PER_ITEM: i64 = 100;
+
+/// An item sent to the Gateway, identified by the primary key of its row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeadLetterItem {
+    VerifyProof {
+        zk_proof_id: i64,
+    },
+    AddCiphertext {
+        handle: Vec<u8>,
+    },
+    AllowHandle {
+        tenant_id: i32,
+        handle: Vec<u8>,
+        account_address: String,
+    },
+    Delegation {
+        tenant_id: i32,
+        delegator: String,
+        delegatee: String,
+        contract_address: String,
+    },
+}
+
+impl DeadLetterItem {
+    pub fn operation(&self) -> &'static str {
+        match self {
+            Self::VerifyProof { .. } => VERIFY_PROOF,
+            Self::AddCiphertext { .. } => ADD_CIPHERTEXT,
+            Self::AllowHandle { .. } => ALLOW_HANDLE,
+            Self::Delegation { .. } => DELEGATION,
+        }
+    }
+
+    /// The primary key fields separated by ':', handles in hex.
+    pub fn key(&self) -> String {
+        match self {
+            Self::VerifyProof { zk_proof_id } => zk_proof_id.to_string(),
+            Self::AddCiphertext { handle } => hex::encode(handle),
+            Self::AllowHandle {
+                tenant_id,
+                handle,
+                account_address,
+            } => format!("{}:{}:{}", tenant_id, hex::encode(handle), account_address),
+            Self::Delegation {
+                tenant_id,
+                delegator,
+                delegatee,
+                contract_address,
+            } => format!(
+                "{}:{}:{}:{}",
+                tenant_id, delegator, delegatee, contract_address
+            ),
+        }
+    }
+
+    pub fn parse(operation: &str, key: &str) -> anyhow::Result<Self> {
+        let invalid = || anyhow!("invalid {} key {}", operation, key);
+        match operation {
+            VERIFY_PROOF => Ok(Self::VerifyProof {
+                zk_proof_id: key.parse().map_err(|_| invalid())?,
+            }),
+            ADD_CIPHERTEXT => Ok(Self::AddCiphertext {
+                handle: hex::decode(key).map_err(|_| invalid())?,
+            }),
+            ALLOW_HANDLE => {
+                let mut fields = key.splitn(3, ':');
+                let (Some(tenant_id), Some(handle), Some(account_address)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(invalid());
+                };
+                Ok(Self::AllowHandle {
+                    tenant_id: tenant_id.parse().map_err(|_| invalid())?,
+                    handle: hex::decode(handle).map_err(|_| invalid())?,
+                    account_address: account_address.to_owned(),
+                })
+            }
+            DELEGATION => {
+                let fields: Vec<&str> = key.split(':').collect();
+                let [tenant_id, delegator, delegatee, contract_address] = fields[..] else {
+                    return Err(invalid());
+                };
+                Ok(Self::Delegation {
+                    tenant_id: tenant_id.parse().map_err(|_| invalid())?,
+                    delegator: delegator.to_owned(),
+                    delegatee: delegatee.to_owned(),
+                    contract_address: contract_address.to_owned(),
+                })
+            }
+            _ => bail!("unknown operation {}", operation),
+        }
+    }
+
+    pub(crate) fn max_retries(&self, conf: &ConfigSettings) -> u32 {
+        match self {
+            Self::VerifyProof { .. } => conf.verify_proof_resp_max_retries,
+            Self::AddCiphertext { .. } => conf.add_ciphertexts_max_retries,
+            Self::AllowHandle { .. } => conf.allow_handle_max_retries,
+            Self::Delegation { .. } => conf.delegation_max_retries,
+        }
+    }
+}
+
+impl std::fmt::Display for DeadLetterItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AddCiphertext { handle } => {
+                write!(f, "{} {}", self.operation(), compact_hex(handle))
+            }
+            _ => write!(f, "{} {}", self.operation(), self.key()),
+        }
+    }
+}
+
+/// An item that exhausted its retries.
+#[derive(Clone, Debug)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub item: DeadLetterItem,
+    pub retry_count: i32,
+    pub last_error: String,
+    /// The source row was removed, it is restored on re-submission.
+    pub removed: bool,
+    pub resubmit_count: i32,
+    pub created_at: OffsetDateTime,
+    pub age_secs: i64,
+    pub resubmitted_at: Option<OffsetDateTime>,
+}
+
+struct DeadLetterRow {
+    id: i64,
+    operation: String,
+    item_key: String,
+    retry_count: i32,
+    last_error: String,
+    removed: bool,
+    resubmit_count: i32,
+    created_at: OffsetDateTime,
+    age_secs: i64,
+    resubmitted_at: Option<OffsetDateTime>,
+}
+
+impl TryFrom<DeadLetterRow> for DeadLetter {
+    type Error = anyhow::Error;
+
+    fn try_from(row: DeadLetterRow) -> anyhow::Result<Self> {
+        Ok(Self {
+            id: row.id,
+            item: DeadLetterItem::parse(&row.operation, &row.item_key)?,
+            retry_count: row.retry_count,
+            last_error: row.last_error,
+            removed: row.removed,
+            resubmit_count: row.resubmit_count,
+            created_at: row.created_at,
+            age_secs: row.age_secs,
+            resubmitted_at: row.resubmitted_at,
+        })
+    }
+}
+
+/// A failed attempt to send an item.
+#[derive(Clone, Debug)]
+pub struct ErrorRecord {
+    pub error: String,
+    pub is_transport: bool,
+    pub created_at: OffsetDateTime,
+}
+
+/// Postgres-backed history of the errors of the items sent to the Gateway and dead letters of the ones that
+/// exhausted their retries.
+#[derive(Clone)]
+pub struct DeadLetterQueue {
+    db_pool: Pool<Postgres>,
+}
+
+impl DeadLetterQueue {
+    pub fn new(db_pool: Pool<Postgres>) -> Self {
+        Self { db_pool }
+    }
+
+    /// Records an error of the item, keeping only its last `MAX_ERRORS_PER_ITEM` errors.
+    pub async fn record_error(
+        &self,
+        item: &DeadLetterItem,
+        error: &str,
+        is_transport: bool,
+    ) -> Result<(), sqlx::Error> {
+        let key = item.key();
+        let mut txn = self.db_pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO tx_sender_errors (operation, item_key, error, is_transport)
+            VALUES ($1, $2, $3, $4)",
+            item.operation(),
+            key,
+            error,
+            is_transport,
+        )
+        .execute(&mut *txn)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM tx_sender_errors
+            WHERE operation = $1 AND item_key = $2
+            AND id <= (
+                SELECT id FROM tx_sender_errors
+                WHERE operation = $1 AND item_key = $2
+                ORDER BY id DESC
+                OFFSET $3
+                LIMIT 1
+            )",
+            item.operation(),
+            key,
+            MAX_ERRORS_PER_ITEM,
+        )
+        .execute(&mut *txn)
+        .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Removes the errors of an item that has been sent.
+    pub async fn clear_errors(&self, item: &DeadLetterItem) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM tx_sender_errors WHERE operation = $1 AND item_key = $2",
+            item.operation(),
+            item.key(),
+        )
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes the errors older than `retention_secs`, e.g. of the items that have been abandoned.
+    pub async fn prune_errors(&self, retention_secs: u32) -> Result<u64, sqlx::Error> {
+        let pruned = sqlx::query!(
+            "DELETE FROM tx_sender_errors WHERE created_at < NOW() - make_interval(secs => $1)",
+            retention_secs as f64,
+        )
+        .execute(&self.db_pool)
+        .await?
+        .rows_affected();
+        Ok(pruned)
+    }
+
+    /// Adds an item whose row is kept in its table, with a retry count that excludes it from processing.
+    pub async fn add(
+        &self,
+        item: &DeadLetterItem,
+        retry_count: i32,
+        last_error: &str,
+    ) -> Result<(), sqlx::Error> {
+        error!(
+            action = REVIEW,
+            "Max ({}) retries reached for {}, moved to dead letters: {}",
+            retry_count,
+            item,
+            last_error
+        );
+        sqlx::query!(
+            "INSERT INTO tx_sender_dead_letters (operation, item_key, retry_count, last_error)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (operation, item_key) DO UPDATE
+            SET retry_count = EXCLUDED.retry_count,
+                last_error = EXCLUDED.last_error,
+                payload = NULL,
+                created_at = NOW(),
+                resubmitted_at = NULL",
+            item.operation(),
+            item.key(),
+            retry_count,
+            last_error,
+        )
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes the proofs that exhausted their retries, keeping them as dead letters to restore them on
+    /// re-submission.
+    pub async fn remove_exhausted_proofs(&self, max_retries: u32) -> Result<u64, sqlx::Error> {
+        let removed = sqlx::query!(
+            "WITH removed AS (
+                DELETE FROM verify_proofs WHERE retry_count >= $1 RETURNING *
+            )
+            INSERT INTO tx_sender_dead_letters (operation, item_key, retry_count, last_error, payload)
+            SELECT $2, zk_proof_id::TEXT, retry_count, COALESCE(last_error, ''), row_to_json(removed)::TEXT
+            FROM removed
+            ON CONFLICT (operation, item_key) DO UPDATE
+            SET retry_count = EXCLUDED.retry_count,
+                last_error = EXCLUDED.last_error,
+                payload = EXCLUDED.payload,
+                created_at = NOW(),
+                resubmitted_at = NULL",
+            max_retries as i64,
+            VERIFY_PROOF,
+        )
+        .execute(&self.db_pool)
+        .await?
+        .rows_affected();
+        if removed > 0 {
+            error!(
+                action = REVIEW,
+                "Max ({}) retries reached for {} proofs, removed and moved to dead letters",
+                max_retries,
+                removed
+            );
+        }
+        Ok(removed)
+    }
+
+    /// Lists the dead letters not yet re-submitted, oldest first.
+    pub async fn list(
+        &self,
+        operation: Option<&str>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<DeadLetter>> {
+        let rows = sqlx::query_as!(
+            DeadLetterRow,
+            r#"SELECT id, operation, item_key, retry_count, last_error, payload IS NOT NULL AS "removed!",
+                resubmit_count, created_at, EXTRACT(EPOCH FROM NOW() - created_at)::BIGINT AS "age_secs!",
+                resubmitted_at
+            FROM tx_sender_dead_letters
+            WHERE resubmitted_at IS NULL AND ($1::TEXT IS NULL OR operation = $1)
+            ORDER BY created_at, id
+            LIMIT $2"#,
+            operation,
+            limit,
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        rows.into_iter().map(DeadLetter::try_from).collect()
+    }
+
+    pub async fn get(&self, id: i64) -> anyhow::Result<Option<DeadLetter>> {
+        let row = sqlx::query_as!(
+            DeadLetterRow,
+            r#"SELECT id, operation, item_key, retry_count, last_error, payload IS NOT NULL AS "removed!",
+                resubmit_count, created_at, EXTRACT(EPOCH FROM NOW() - created_at)::BIGINT AS "age_secs!",
+                resubmitted_at
+            FROM tx_sender_dead_letters
+            WHERE id = $1"#,
+            id,
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+        row.map(DeadLetter::try_from).transpose()
+    }
+
+    /// Returns the last errors of the attempts to send the item, oldest first.
+    pub async fn errors(&self, item: &DeadLetterItem) -> Result<Vec<ErrorRecord>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT error, is_transport, created_at
+            FROM tx_sender_errors
+            WHERE operation = $1 AND item_key = $2
+            ORDER BY created_at, id",
+            item.operation(),
+            item.key(),
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ErrorRecord {
+                error: row.error,
+                is_transport: row.is_transport,
+                created_at: row.created_at,
+            })
+            .collect())
+    }
+
+    /// Resets the retry counts of the item, restoring its row if it was removed, and notifies its operation on its
+    /// channel in `db_channels`, see `OperationRegistry::db_channels`.
+    pub async fn resubmit(
+        &self,
+        id: i64,
+        db_channels: &BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let mut txn = self.db_pool.begin().await?;
+        let Some(row) = sqlx::query!(
+            "SELECT operation, item_key, payload
+            FROM tx_sender_dead_letters
+            WHERE id = $1 AND resubmitted_at IS NULL
+            FOR UPDATE",
+            id,
+        )
+        .fetch_optional(&mut *txn)
+        .await?
+        else {
+            bail!("no pending dead letter with id {}", id);
+        };
+        let item = DeadLetterItem::parse(&row.operation, &row.item_key)?;
+        let Some(db_channel) = db_channels.get(item.operation()) else {
+            bail!("no channel for operation {}", item.operation());
+        };
+
+        let updated = match &item {
+            DeadLetterItem::VerifyProof { zk_proof_id } => {
+                if let Some(payload) = &row.payload {
+                    sqlx::query!(
+                        "INSERT INTO verify_proofs
+                        SELECT (json_populate_record(NULL::verify_proofs, $1::TEXT::JSON)).*
+                        ON CONFLICT (zk_proof_id) DO NOTHING",
+                        payload,
+                    )
+                    .execute(&mut *txn)
+                    .await?;
+                }
+                sqlx::query!(
+                    "UPDATE verify_proofs
+                    SET retry_count = 0, last_error = NULL
+                    WHERE zk_proof_id = $1",
+                    zk_proof_id,
+                )
+                .execute(&mut *txn)
+                .await?
+            }
+            DeadLetterItem::AddCiphertext { handle } => {
+                sqlx::query!(
+                    "UPDATE ciphertext_digest
+                    SET txn_retry_count = 0, txn_transport_retry_count = 0
+                    WHERE handle = $1 AND txn_is_sent = false",
+                    handle,
+                )
+                .execute(&mut *txn)
+                .await?
+            }
+            DeadLetterItem::AllowHandle {
+                tenant_id,
+                handle,
+                account_address,
+            } => {
+                sqlx::query!(
+                    "UPDATE allowed_handles
+                    SET txn_retry_count = 0, txn_transport_retry_count = 0
+                    WHERE handle = $1
+                    AND account_address = $2
+                    AND tenant_id = $3
+                    AND txn_is_sent = false",
+                    handle,
+                    account_address,
+                    tenant_id,
+                )
+                .execute(&mut *txn)
+                .await?
+            }
+            DeadLetterItem::Delegation {
+                tenant_id,
+                delegator,
+                delegatee,
+                contract_address,
+            } => {
+                sqlx::query!(
+                    "UPDATE delegations
+                    SET txn_retry_count = 0, txn_transport_retry_count = 0
+                    WHERE tenant_id = $1
+                    AND delegator = $2
+                    AND delegatee = $3
+                    AND contract_address = $4
+                    AND txn_is_sent = false",
+                    tenant_id,
+                    delegator,
+                    delegatee,
+                    contract_address,
+                )
+                .execute(&mut *txn)
+                .await?
+            }
+        };
+        if updated.rows_affected() == 0 {
+            bail!("{} is not pending anymore", item);
+        }
+
+        sqlx::query!(
+            "UPDATE tx_sender_dead_letters
+            SET resubmitted_at = NOW(), resubmit_count = resubmit_count + 1
+            WHERE id = $1",
+            id,
+        )
+        .execute(&mut *txn)
+        .await?;
+        sqlx::query!("SELECT pg_notify($1, '')", db_channel)
+            .execute(&mut *txn)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Updates the dead letters count and age metrics of each operation.
+    pub async fn refresh_metrics(&self) -> Result<(), sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"SELECT operation, COUNT(*) AS "count!",
+                EXTRACT(EPOCH FROM NOW() - MIN(created_at))::BIGINT AS "oldest_age_secs!"
+            FROM tx_sender_dead_letters
+            WHERE resubmitted_at IS NULL
+            GROUP BY operation"#,
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        for operation in OPERATIONS {
+            let (count, oldest_age_secs) = rows
+                .iter()
+                .find(|row| row.operation == operation)
+                .map_or((0, 0), |row| (row.count, row.oldest_age_secs));
+            metrics::set_dead_letters(operation, count, oldest_age_secs);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fmt, str::FromStr};
+
+use alloy::{
+    eips::BlockNumberOrTag, network::Ethereum, providers::Provider, rpc::types::TransactionRequest,
+    transports::TransportResult,
+};
+
+/// How EIP-1559 fees are set on gateway transactions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FeeStrategy {
+    /// Fees estimated by the provider.
+    #[default]
+    Provider,
+
+    /// Fixed fees, in wei.
+    Fixed {
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    },
+
+    /// Priority fee at the given percentile of the rewards of the last `blocks` blocks, max fee of twice the next base
+    /// fee plus the priority fee.
+    Percentile { reward_percentile: f64, blocks: u64 },
+
+    /// Fees estimated by the provider, capped to the given values in wei. Fee bumps are capped too.
+    Capped {
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    },
+}
+
+impl FeeStrategy {
+    /// Sets the fees of the transaction, if not already set.
+    pub async fn fill<P: Provider<Ethereum>>(
+        &self,
+        provider: &P,
+        tx: &mut TransactionRequest,
+    ) -> TransportResult<()> {
+        if tx.max_fee_per_gas.is_some() && tx.max_priority_fee_per_gas.is_some() {
+            return Ok(());
+        }
+        let (max_fee_per_gas, max_priority_fee_per_gas) = match self {
+            Self::Provider => {
+                let estimation = provider.estimate_eip1559_fees(None).await?;
+                (
+                    estimation.max_fee_per_gas,
+                    estimation.max_priority_fee_per_gas,
+                )
+            }
+            Self::Fixed {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => (*max_fee_per_gas, *max_priority_fee_per_gas),
+            Self::Percentile {
+                reward_percentile,
+                blocks,
+            } => {
+                let history = provider
+                    .get_fee_history(*blocks, BlockNumberOrTag::Latest, &[*reward_percentile])
+                    .await?;
+                let rewards: Vec<u128> = history
+                    .reward
+                    .as_deref()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|block_rewards| block_rewards.first().copied())
+                    .collect();
+                let priority_fee = if rewards.is_empty() {
+                    0
+                } else {
+                    rewards.iter().sum::<u128>() / rewards.len() as u128
+                };
+                let base_fee = history.next_block_base_fee().unwrap_or_default();
+                (2 * base_fee + priority_fee, priority_fee)
+            }
+            Self::Capped {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => {
+                let estimation = provider.estimate_eip1559_fees(None).await?;
+                (
+                    estimation.max_fee_per_gas.min(*max_fee_per_gas),
+                    estimation
+                        .max_priority_fee_per_gas
+                        .min(*max_priority_fee_per_gas),
+                )
+            }
+        };
+        tx.max_fee_per_gas = Some(max_fee_per_gas);
+        tx.max_priority_fee_per_gas = Some(max_priority_fee_per_gas.min(max_fee_per_gas));
+        Ok(())
+    }
+
+    /// Returns the transaction with fees bumped by `bump_percent`, to replace it at the same nonce, or None if fees
+    /// cannot be bumped anymore.
+    pub fn bump(&self, tx: &TransactionRequest, bump_percent: u16) -> Option<TransactionRequest> {
+        let bumped = |fee: u128| fee + (fee * bump_percent as u128 / 100).max(1);
+        let mut max_fee_per_gas = bumped(tx.max_fee_per_gas?);
+        let mut max_priority_fee_per_gas = bumped(tx.max_priority_fee_per_gas?);
+        if let Self::Capped {
+            max_fee_per_gas: max_fee_cap,
+            max_priority_fee_per_gas: priority_fee_cap,
+        } = self
+        {
+            max_fee_per_gas = max_fee_per_gas.min(*max_fee_cap);
+            max_priority_fee_per_gas = max_priority_fee_per_gas.min(*priority_fee_cap);
+        }
+        max_priority_fee_per_gas = max_priority_fee_per_gas.min(max_fee_per_gas);
+        // Nodes refuse replacements that do not increase both fees.
+        if Some(max_fee_per_gas) <= tx.max_fee_per_gas
+            || Some(max_priority_fee_per_gas) <= tx.max_priority_fee_per_gas
+        {
+            return None;
+        }
+        let mut tx = tx.clone();
+        tx.max_fee_per_gas = Some(max_fee_per_gas);
+        tx.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+        Some(tx)
+    }
+}
+
+/// Parses `provider`, `fixed:<max fee>:<max priority fee>`, `percentile:<reward percentile>[:<blocks>]` or
+/// `capped:<max fee>:<max priority fee>`, fees in wei.
+impl FromStr for FeeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let fee = |value: &str| {
+            value
+                .parse::<u128>()
+                .map_err(|e| format!("invalid fee {}: {}", value, e))
+        };
+        match parts.as_slice() {
+            ["provider"] => Ok(Self::Provider),
+            ["fixed", max_fee, priority_fee] => Ok(Self::Fixed {
+                max_fee_per_gas: fee(max_fee)?,
+                max_priority_fee_per_gas: fee(priority_fee)?,
+            }),
+            ["percentile", percentile] | ["percentile", percentile, _] => {
+                let reward_percentile = percentile
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|p| (0.0..=100.0).contains(p))
+                    .ok_or(format!("invalid reward percentile {}", percentile))?;
+                let blocks = match parts.get(2) {
+                    Some(blocks) => blocks
+                        .parse::<u64>()
+                        .map_err(|e| format!("invalid blocks count {}: {}", blocks, e))?,
+                    None => 10,
+                };
+                Ok(Self::Percentile {
+                    reward_percentile,
+                    blocks,
+                })
+            }
+            ["capped", max_fee, priority_fee] => Ok(Self::Capped {
+                max_fee_per_gas: fee(max_fee)?,
+                max_priority_fee_per_gas: fee(priority_fee)?,
+            }),
+            _ => Err(format!("invalid fee strategy {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for FeeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Provider => write!(f, "provider"),
+            Self::Fixed {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => write!(f, "fixed:{}:{}", max_fee_per_gas, max_priority_fee_per_gas),
+            Self::Percentile {
+                reward_percentile,
+                blocks,
+            } => write!(f, "percentile:{}:{}", reward_percentile, blocks),
+            Self::Capped {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => write!(f, "capped:{}:{}", max_fee_per_gas, max_priority_fee_per_gas),
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use alloy::{
+    primitives::{Address, TxHash},
+    rpc::types::TransactionRequest,
+};
+use sqlx::{Pool, Postgres};
+use tracing::warn;
+
+use crate::{
+    dead_letter::{DeadLetterItem, DeadLetterQueue},
+    ConfigSettings,
+};
+
+/// Status of a transaction in the journal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionStatus {
+    /// Sent, receipt not yet received.
+    Pending,
+    /// Mined with a successful receipt.
+    Mined,
+    /// Mined with a failed receipt.
+    Reverted,
+    /// Replaced by another transaction at the same nonce.
+    Replaced,
+    /// Not known to the Gateway anymore and its nonce is used by another transaction.
+    Dropped,
+}
+
+impl TransactionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Mined => "mined",
+            Self::Reverted => "reverted",
+            Self::Replaced => "replaced",
+            Self::Dropped => "dropped",
+        }
+    }
+}
+
+/// A pending transaction of the journal.
+#[derive(Clone, Debug)]
+pub struct JournalEntry {
+    pub id: i64,
+    pub nonce: u64,
+    pub tx_hash: TxHash,
+    pub operation: String,
+    pub tx_request: Option<TransactionRequest>,
+    /// The rows sent by the transaction.
+    pub items: Vec<DeadLetterItem>,
+}
+
+/// Postgres-backed journal of the transactions sent to the Gateway, used to resume receipt tracking after a restart
+/// and as an audit trail.
+#[derive(Clone)]
+pub struct TransactionJournal {
+    db_pool: Pool<Postgres>,
+}
+
+impl TransactionJournal {
+    pub fn new(db_pool: Pool<Postgres>) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn record_sent(
+        &self,
+        signer_address: Address,
+        operation: &str,
+        handles: &[Vec<u8>],
+        items: &[DeadLetterItem],
+        tx_hash: TxHash,
+        tx: &TransactionRequest,
+    ) -> Result<(), sqlx::Error> {
+        let tx_request = serialize_request(tx)?;
+        let item_keys: Vec<String> = items.iter().map(DeadLetterItem::key).collect();
+        sqlx::query!(
+            "INSERT INTO gw_transactions (signer_address, nonce, tx_hash, operation, handles, tx_request,
+                item_operation, item_keys)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (tx_hash) DO NOTHING",
+            signer_address.to_string(),
+            tx.nonce.unwrap_or_default() as i64,
+            tx_hash.as_slice(),
+            operation,
+            handles,
+            tx_request,
+            items.first().map(DeadLetterItem::operation),
+            &item_keys,
+        )
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks the stuck transaction as replaced and records its replacement, with the same operation and handles.
+    pub async fn record_replacement(
+        &self,
+        stuck_tx_hash: TxHash,
+        tx_hash: TxHash,
+        tx: &TransactionRequest,
+    ) -> Result<(), sqlx::Error> {
+        let tx_request = serialize_request(tx)?;
+        let mut txn = self.db_pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO gw_transactions (signer_address, nonce, tx_hash, operation, handles, tx_request,
+                item_operation, item_keys)
+            SELECT signer_address, nonce, $2, operation, handles, $3, item_operation, item_keys
+            FROM gw_transactions
+            WHERE tx_hash = $1
+            ON CONFLICT (tx_hash) DO NOTHING",
+            stuck_tx_hash.as_slice(),
+            tx_hash.as_slice(),
+            tx_request,
+        )
+        .execute(&mut *txn)
+        .await?;
+        sqlx::query!(
+            "UPDATE gw_transactions
+            SET status = 'replaced', updated_at = NOW()
+            WHERE tx_hash = $1 AND status = 'pending'",
+            stuck_tx_hash.as_slice(),
+        )
+        .execute(&mut *txn)
+        .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    pub async fn set_status(
+        &self,
+        tx_hash: TxHash,
+        status: TransactionStatus,
+        block_number: Option<u64>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE gw_transactions
+            SET status = $2, block_number = COALESCE($3, block_number), error = COALESCE($4, error), updated_at = NOW()
+            WHERE tx_hash = $1",
+            tx_hash.as_slice(),
+            status.as_str(),
+            block_number.map(|n| n as i64),
+            error,
+        )
+        .execute(&self.db_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the pending transactions of the signer, ordered by nonce and sending order.
+    pub async fn pending(&self, signer_address: Address) -> Result<Vec<JournalEntry>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT id, nonce, tx_hash, operation, tx_request, item_operation, item_keys
+            FROM gw_transactions
+            WHERE signer_address = $1 AND status = 'pending'
+            ORDER BY nonce, id",
+            signer_address.to_string(),
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| JournalEntry {
+                id: row.id,
+                nonce: row.nonce as u64,
+                tx_hash: TxHash::from_slice(&row.tx_hash),
+                operation: row.operation,
+                tx_request: serde_json::from_str(&row.tx_request).ok(),
+                items: row
+                    .item_operation
+                    .map(|operation| {
+                        row.item_keys
+                            .iter()
+                            .filter_map(|key| match DeadLetterItem::parse(&operation, key) {
+                                Ok(item) => Some(item),
+                                Err(e) => {
+                                    warn!("Ignoring journaled item: {}", e);
+                                    None
+                                }
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Marks the items of a transaction mined while the sender was down as sent.
+    pub async fn mark_items_sent(&self, items: &[DeadLetterItem]) -> Result<(), sqlx::Error> {
+        let dead_letters = DeadLetterQueue::new(self.db_pool.clone());
+        for item in items {
+            match item {
+                DeadLetterItem::VerifyProof { zk_proof_id } => {
+                    sqlx::query!(
+                        "DELETE FROM verify_proofs WHERE zk_proof_id = $1",
+                        zk_proof_id
+                    )
+                    .execute(&self.db_pool)
+                    .await?;
+                }
+                DeadLetterItem::AddCiphertext { handle } => {
+                    sqlx::query!(
+                        "UPDATE ciphertext_digest
+                        SET txn_is_sent = true
+                        WHERE handle = $1",
+                        handle,
+                    )
+                    .execute(&self.db_pool)
+                    .await?;
+                }
+                DeadLetterItem::AllowHandle {
+                    tenant_id,
+                    handle,
+                    account_address,
+                } => {
+                    sqlx::query!(
+                        "UPDATE allowed_handles
+                        SET txn_is_sent = true
+                        WHERE handle = $1
+                        AND account_address = $2
+                        AND tenant_id = $3",
+                        handle,
+                        account_address,
+                        tenant_id,
+                    )
+                    .execute(&self.db_pool)
+                    .await?;
+                }
+                DeadLetterItem::Delegation {
+                    tenant_id,
+                    delegator,
+                    delegatee,
+                    contract_address,
+                } => {
+                    sqlx::query!(
+                        "UPDATE delegations
+                        SET txn_is_sent = true
+                        WHERE tenant_id = $1
+                        AND delegator = $2
+                        AND delegatee = $3
+                        AND contract_address = $4",
+                        tenant_id,
+                        delegator,
+                        delegatee,
+                        contract_address,
+                    )
+                    .execute(&self.db_pool)
+                    .await?;
+                }
+            }
+            dead_letters.clear_errors(item).await?;
+        }
+        Ok(())
+    }
+
+    /// Counts a failed attempt for the items of a transaction that reverted or failed while the sender was down, the
+    /// ones that exhaust their retries are moved to the dead letters.
+    pub async fn count_failed_items(
+        &self,
+        items: &[DeadLetterItem],
+        error: &str,
+        conf: &ConfigSettings,
+    ) -> Result<(), sqlx::Error> {
+        let dead_letters = DeadLetterQueue::new(self.db_pool.clone());
+        for item in items {
+            let retry_count = match item {
+                DeadLetterItem::VerifyProof { zk_proof_id } => {
+                    sqlx::query_scalar!(
+                        "UPDATE verify_proofs
+                        SET retry_count = retry_count + 1, last_error = $2, last_retry_at = NOW()
+                        WHERE zk_proof_id = $1
+                        RETURNING retry_count",
+                        zk_proof_id,
+                        error,
+                    )
+                    .fetch_optional(&self.db_pool)
+                    .await?
+                }
+                DeadLetterItem::AddCiphertext { handle } => {
+                    sqlx::query_scalar!(
+                        "UPDATE ciphertext_digest
+                        SET txn_retry_count = txn_retry_count + 1, txn_last_error = $2, txn_last_error_at = NOW()
+                        WHERE handle = $1 AND txn_is_sent = false
+                        RETURNING txn_retry_count",
+                        handle,
+                        error,
+                    )
+                    .fetch_optional(&self.db_pool)
+                    .await?
+                }
+                DeadLetterItem::AllowHandle {
+                    tenant_id,
+                    handle,
+                    account_address,
+                } => {
+                    sqlx::query_scalar!(
+                        "UPDATE allowed_handles
+                        SET txn_retry_count = txn_retry_count + 1, txn_last_error = $4, txn_last_error_at = NOW()
+                        WHERE handle = $1
+                        AND account_address = $2
+                        AND tenant_id = $3
+                        AND txn_is_sent = false
+                        RETURNING txn_retry_count",
+                        handle,
+                        account_address,
+                        tenant_id,
+                        error,
+                    )
+                    .fetch_optional(&self.db_pool)
+                    .await?
+                }
+                DeadLetterItem::Delegation {
+                    tenant_id,
+                    delegator,
+                    delegatee,
+                    contract_address,
+                } => {
+                    sqlx::query_scalar!(
+                        "UPDATE delegations
+                        SET txn_retry_count = txn_retry_count + 1, txn_last_error = $5, txn_last_error_at = NOW()
+                        WHERE tenant_id = $1
+                        AND delegator = $2
+                        AND delegatee = $3
+                        AND contract_address = $4
+                        AND txn_is_sent = false
+                        RETURNING txn_retry_count",
+                        tenant_id,
+                        delegator,
+                        delegatee,
+                        contract_address,
+                        error,
+                    )
+                    .fetch_optional(&self.db_pool)
+                    .await?
+                }
+            };
+            // Not pending anymore.
+            let Some(retry_count) = retry_count else {
+                continue;
+            };
+            dead_letters.record_error(item, error, false).await?;
+            let exhausted = retry_count as u32 >= item.max_retries(conf);
+            // The exhausted proofs are moved to the dead letters when removed.
+            let removed_later = matches!(item, DeadLetterItem::VerifyProof { .. })
+                && conf.verify_proof_remove_after_max_retries;
+            if exhausted && !removed_later {
+                dead_letters.add(item, retry_count, error).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serializes the request to re-send it on reconciliation, failing rather than journaling a transaction that could
+/// not be re-sent.
+fn serialize_request(tx: &TransactionRequest) -> Result<String, sqlx::Error> {
+    serde_json::to_string(tx).map_err(|e| sqlx::Error::Encode(Box::new(e)))
+}
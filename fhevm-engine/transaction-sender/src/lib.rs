@@ -1,5 +1,10 @@
+mod dead_letter;
+mod fee_strategy;
+mod journal;
+mod metrics;
 mod nonce_managed_provider;
 mod ops;
+mod signer_pool;
 mod transaction_sender;
 
 #[derive(Clone, Debug)]
@@ -10,6 +15,7 @@ pub struct ConfigSettings {
     pub verify_proof_resp_db_channel: String,
     pub add_ciphertexts_db_channel: String,
     pub allow_handle_db_channel: String,
+    pub delegation_db_channel: String,
 
     pub verify_proof_resp_batch_limit: u32,
     pub verify_proof_resp_max_retries: u32,
@@ -17,9 +23,14 @@ pub struct ConfigSettings {
 
     pub add_ciphertexts_batch_limit: u32,
     pub add_ciphertexts_max_retries: u32,
+    pub add_ciphertexts_multicall: bool,
 
     pub allow_handle_batch_limit: u32,
     pub allow_handle_max_retries: u32,
+    pub allow_handle_multicall: bool,
+
+    pub delegation_batch_limit: u32,
+    pub delegation_max_retries: u32,
 
     pub db_polling_interval_secs: u16,
 
@@ -30,7 +41,17 @@ pub struct ConfigSettings {
 
     pub required_txn_confirmations: u16,
 
+    pub fee_strategy: FeeStrategy,
+    pub fee_bump_percent: u16,
+    pub max_fee_bumps: u16,
+
     pub review_after_transport_retries: u16,
+
+    pub balance_check_interval_secs: u16,
+    pub low_balance_threshold_wei: u128,
+
+    pub dead_letter_metrics_interval_secs: u16,
+    pub errors_retention_secs: u32,
 }
 
 impl Default for ConfigSettings {
@@ -42,6 +63,7 @@ impl Default for ConfigSettings {
             verify_proof_resp_db_channel: "verify_proof_responses".to_owned(),
             add_ciphertexts_db_channel: "add_ciphertexts".to_owned(),
             allow_handle_db_channel: "event_allowed_handle".to_owned(),
+            delegation_db_channel: "event_delegation".to_owned(),
             verify_proof_resp_batch_limit: 128,
             verify_proof_resp_max_retries: 3,
             verify_proof_remove_after_max_retries: true,
@@ -50,17 +72,39 @@ impl Default for ConfigSettings {
             error_sleep_max_secs: 16,
             add_ciphertexts_batch_limit: 10,
             add_ciphertexts_max_retries: 15,
+            add_ciphertexts_multicall: false,
             allow_handle_batch_limit: 10,
             allow_handle_max_retries: 10,
+            allow_handle_multicall: false,
+            delegation_batch_limit: 10,
+            delegation_max_retries: 10,
             txn_receipt_timeout_secs: 10,
             required_txn_confirmations: 0,
+            fee_strategy: FeeStrategy::Provider,
+            fee_bump_percent: 20,
+            max_fee_bumps: 3,
             review_after_transport_retries: 30,
+            balance_check_interval_secs: 60,
+            low_balance_threshold_wei: 100_000_000_000_000_000, // 0.1 ETH
+            dead_letter_metrics_interval_secs: 60,
+            errors_retention_secs: 7 * 24 * 60 * 60,
         }
     }
 }
 
+pub use dead_letter::{
+    DeadLetter, DeadLetterItem, DeadLetterQueue, ErrorRecord, MAX_ERRORS_PER_ITEM,
+};
+pub use fee_strategy::FeeStrategy;
+pub use journal::{TransactionJournal, TransactionStatus};
+pub use metrics::run_metrics_server;
 pub use nonce_managed_provider::FillersWithoutNonceManagement;
 pub use nonce_managed_provider::NonceManagedProvider;
-pub use transaction_sender::TransactionSender;
+pub use ops::registry::{
+    OperationBuilder, OperationConfig, OperationContext, OperationRegistry, OperationsConfig,
+};
+pub use ops::{OperationHealth, TransactionOperation};
+pub use signer_pool::SignerPool;
+pub use transaction_sender::{OperationStatus, TransactionSender};
 
 pub const REVIEW: &str = "review";
@@ -0,0 +1,97 @@
+use lazy_static::lazy_static;
+use prometheus::{register_gauge_vec, register_int_gauge_vec, GaugeVec, IntGaugeVec};
+use tracing::info;
+
+lazy_static! {
+    static ref DEAD_LETTERS_GAUGE: IntGaugeVec = register_int_gauge_vec!(
+        "transaction_sender_dead_letters",
+        "items that exhausted their retries and are not yet re-submitted, per operation",
+        &["operation"]
+    )
+    .unwrap();
+    static ref DEAD_LETTERS_OLDEST_AGE_GAUGE: IntGaugeVec = register_int_gauge_vec!(
+        "transaction_sender_dead_letters_oldest_age_seconds",
+        "age of the oldest dead letter not yet re-submitted, per operation",
+        &["operation"]
+    )
+    .unwrap();
+    static ref OPERATION_HEALTHY_GAUGE: IntGaugeVec = register_int_gauge_vec!(
+        "transaction_sender_operation_healthy",
+        "1 if the operation is healthy, 0 otherwise",
+        &["operation"]
+    )
+    .unwrap();
+    static ref OPERATION_QUEUE_DEPTH_GAUGE: IntGaugeVec = register_int_gauge_vec!(
+        "transaction_sender_operation_queue_depth",
+        "items waiting to be sent, per operation",
+        &["operation"]
+    )
+    .unwrap();
+    static ref SIGNER_BALANCE_GAUGE: GaugeVec = register_gauge_vec!(
+        "transaction_sender_signer_balance_wei",
+        "balance of the signer in wei, per signer",
+        &["signer"]
+    )
+    .unwrap();
+    static ref SIGNER_LOW_BALANCE_GAUGE: IntGaugeVec = register_int_gauge_vec!(
+        "transaction_sender_signer_low_balance",
+        "1 if the balance of the signer is below the low balance threshold, 0 otherwise",
+        &["signer"]
+    )
+    .unwrap();
+}
+
+pub(crate) fn set_dead_letters(operation: &str, count: i64, oldest_age_secs: i64) {
+    DEAD_LETTERS_GAUGE
+        .with_label_values(&[operation])
+        .set(count);
+    DEAD_LETTERS_OLDEST_AGE_GAUGE
+        .with_label_values(&[operation])
+        .set(oldest_age_secs);
+}
+
+pub(crate) fn set_operation_status(operation: &str, healthy: bool, queue_depth: Option<i64>) {
+    OPERATION_HEALTHY_GAUGE
+        .with_label_values(&[operation])
+        .set(healthy as i64);
+    if let Some(queue_depth) = queue_depth {
+        OPERATION_QUEUE_DEPTH_GAUGE
+            .with_label_values(&[operation])
+            .set(queue_depth);
+    }
+}
+
+pub(crate) fn set_signer_balance(signer: &str, balance_wei: f64, low_balance: bool) {
+    SIGNER_BALANCE_GAUGE
+        .with_label_values(&[signer])
+        .set(balance_wei);
+    SIGNER_LOW_BALANCE_GAUGE
+        .with_label_values(&[signer])
+        .set(low_balance as i64);
+}
+
+async fn metrics() -> impl actix_web::Responder {
+    let encoder = prometheus::TextEncoder::new();
+    let metric_families = prometheus::gather();
+    encoder
+        .encode_to_string(&metric_families)
+        .expect("can't encode metrics")
+}
+
+async fn healthcheck() -> impl actix_web::Responder {
+    "OK"
+}
+
+pub async fn run_metrics_server(metrics_addr: String) -> std::io::Result<()> {
+    info!("metrics server listening at {}", metrics_addr);
+    actix_web::HttpServer::new(|| {
+        actix_web::App::new()
+            .route("/metrics", actix_web::web::to(metrics))
+            .route("/health", actix_web::web::to(healthcheck))
+    })
+    .bind(&metrics_addr)
+    .expect("can't bind to metrics server address")
+    .workers(1)
+    .run()
+    .await
+}
@@ -1,18 +1,27 @@
-use std::{ops::Deref, sync::Arc};
+use std::{collections::HashMap, ops::Deref, sync::Arc, time::Duration};
 
 use alloy::{
     network::Ethereum,
-    primitives::Address,
+    primitives::{Address, TxHash, U256},
     providers::{
-        fillers::{
-            BlobGasFiller, CachedNonceManager, ChainIdFiller, GasFiller, JoinFill, NonceManager,
-        },
-        PendingTransactionBuilder,
+        fillers::{BlobGasFiller, ChainIdFiller, GasFiller, JoinFill},
+        PendingTransactionBuilder, PendingTransactionError, WatchTxError,
     },
-    rpc::types::TransactionRequest,
+    rpc::types::{TransactionReceipt, TransactionRequest},
     transports::TransportResult,
 };
 use futures_util::lock::Mutex;
+use tracing::{error, info, warn};
+
+use crate::{
+    dead_letter::DeadLetterItem,
+    fee_strategy::FeeStrategy,
+    journal::{TransactionJournal, TransactionStatus},
+    ConfigSettings, REVIEW,
+};
+
+/// Gas of a 0-value transfer, cancelling a stuck transaction.
+const CANCEL_GAS_LIMIT: u64 = 21_000;
 
 pub type FillersWithoutNonceManagement =
     JoinFill<GasFiller, JoinFill<BlobGasFiller, ChainIdFiller>>;
@@ -20,43 +29,530 @@ pub type FillersWithoutNonceManagement =
 /// A wrapper around an `alloy` provider that sends transactions with the correct nonce.
 /// Note that the given provider by the user must not have nonce management enabled, as this
 /// is done by the `NonceManagedProvider` itself. Users can use the default `FillersWithoutNonceManagement` to create a provider.
+/// With a journal, sent transactions are recorded in the DB and reconciled against the Gateway on startup.
 #[derive(Clone)]
 pub struct NonceManagedProvider<P>
 where
     P: alloy::providers::Provider<Ethereum> + Clone + 'static,
 {
     provider: P,
-    nonce_manager: Arc<Mutex<CachedNonceManager>>,
+    // Next nonce to use, None if it must be read from the Gateway.
+    next_nonce: Arc<Mutex<Option<u64>>>,
     signer_address: Option<Address>,
+    fee_strategy: FeeStrategy,
+    // Sent transactions with their nonce and fees, to replace them if stuck.
+    sent_transactions: Arc<Mutex<HashMap<TxHash, TransactionRequest>>>,
+    journal: Option<TransactionJournal>,
 }
 
 impl<P: alloy::providers::Provider<Ethereum> + Clone + 'static> NonceManagedProvider<P> {
     pub fn new(provider: P, signer_address: Option<Address>) -> Self {
         Self {
             provider,
-            nonce_manager: Default::default(),
+            next_nonce: Default::default(),
             signer_address,
+            fee_strategy: FeeStrategy::default(),
+            sent_transactions: Default::default(),
+            journal: None,
         }
     }
 
+    pub fn with_journal(mut self, journal: TransactionJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn with_fee_strategy(mut self, fee_strategy: FeeStrategy) -> Self {
+        self.fee_strategy = fee_strategy;
+        self
+    }
+
+    /// Sends the transaction of the given operation, for the given handles and rows, with the next nonce.
     pub async fn send_transaction(
         &self,
+        operation: &str,
+        handles: &[Vec<u8>],
+        items: &[DeadLetterItem],
         tx: impl Into<TransactionRequest>,
     ) -> TransportResult<PendingTransactionBuilder<Ethereum>> {
         let mut tx = tx.into();
-        if let Some(signer_address) = self.signer_address {
-            let nonce_manager = self.nonce_manager.lock().await;
-            let nonce = nonce_manager
-                .get_next_nonce(&self.provider, signer_address)
-                .await?;
-            tx.nonce = Some(nonce);
+        self.fee_strategy.fill(&self.provider, &mut tx).await?;
+        let Some(signer_address) = self.signer_address else {
+            return self.provider.send_transaction(tx).await;
+        };
+        // The nonce is reserved, so that concurrent transactions are sent without waiting for each other.
+        let nonce = {
+            let mut next_nonce = self.next_nonce.lock().await;
+            let nonce = match *next_nonce {
+                Some(nonce) => nonce,
+                None => {
+                    self.provider
+                        .get_transaction_count(signer_address)
+                        .pending()
+                        .await?
+                }
+            };
+            *next_nonce = Some(nonce + 1);
+            nonce
+        };
+        tx.nonce = Some(nonce);
+        let pending = match self.provider.send_transaction(tx.clone()).await {
+            Ok(pending) => pending,
+            Err(e) => {
+                // Read the nonce from the Gateway again, in case the transaction reached it anyway. If it did not and
+                // later nonces were reserved meanwhile, the next transaction fills the gap and the ones colliding
+                // with the later nonces fail and read it again.
+                *self.next_nonce.lock().await = None;
+                return Err(e);
+            }
+        };
+        if let Some(journal) = &self.journal {
+            if let Err(e) = journal
+                .record_sent(
+                    signer_address,
+                    operation,
+                    handles,
+                    items,
+                    *pending.tx_hash(),
+                    &tx,
+                )
+                .await
+            {
+                error!(
+                    operation,
+                    "Failed to record transaction {} in the journal: {}",
+                    pending.tx_hash(),
+                    e
+                );
+            }
+        }
+        self.sent_transactions
+            .lock()
+            .await
+            .insert(*pending.tx_hash(), tx);
+        Ok(pending)
+    }
+
+    /// Returns the nonce of a transaction sent with `send_transaction` whose receipt is not awaited yet.
+    pub async fn sent_nonce(&self, tx_hash: &TxHash) -> Option<u64> {
+        self.sent_transactions
+            .lock()
+            .await
+            .get(tx_hash)
+            .and_then(|tx| tx.nonce)
+    }
+
+    /// Waits for the receipt of a transaction sent with `send_transaction`. Each time `txn_receipt_timeout_secs`
+    /// expires, the transaction is replaced at the same nonce with fees bumped by `fee_bump_percent`, up to
+    /// `max_fee_bumps` times. If it is still stuck, it is cancelled, see `cancel`, and the timeout is returned.
+    pub async fn get_receipt(
+        &self,
+        operation: &str,
+        pending: PendingTransactionBuilder<Ethereum>,
+        conf: &ConfigSettings,
+    ) -> Result<TransactionReceipt, PendingTransactionError> {
+        let mut tx = self
+            .sent_transactions
+            .lock()
+            .await
+            .remove(pending.tx_hash());
+        let mut tx_hashes = vec![*pending.tx_hash()];
+        let mut pending = pending;
+        let mut bumps = 0;
+        loop {
+            let res = pending
+                .with_timeout(Some(Duration::from_secs(
+                    conf.txn_receipt_timeout_secs as u64,
+                )))
+                .with_required_confirmations(conf.required_txn_confirmations as u64)
+                .get_receipt()
+                .await;
+            if let Ok(receipt) = &res {
+                let status = if receipt.status() {
+                    TransactionStatus::Mined
+                } else {
+                    TransactionStatus::Reverted
+                };
+                self.record_status(receipt.transaction_hash, status, receipt.block_number, None)
+                    .await;
+            }
+            let stuck_tx = match res {
+                Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout)) => {
+                    // A replaced transaction can still be mined instead of its replacement.
+                    if let Some(receipt) = self.find_receipt(&tx_hashes).await? {
+                        return Ok(receipt);
+                    }
+                    if bumps >= conf.max_fee_bumps {
+                        if let Some(stuck_tx) = &tx {
+                            if let Some(receipt) =
+                                self.cancel(operation, stuck_tx, &tx_hashes, conf).await?
+                            {
+                                return Ok(receipt);
+                            }
+                        }
+                        return Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout));
+                    }
+                    tx.take()
+                }
+                res => return res,
+            };
+            let Some(bumped_tx) = stuck_tx
+                .as_ref()
+                .and_then(|stuck_tx| self.fee_strategy.bump(stuck_tx, conf.fee_bump_percent))
+            else {
+                warn!(
+                    operation,
+                    "Transaction {} is stuck and its fees cannot be bumped",
+                    tx_hashes.last().unwrap()
+                );
+                return Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout));
+            };
+            bumps += 1;
+            warn!(
+                operation,
+                "Transaction {} is stuck, replacing it at nonce {:?} with max fee per gas {:?} and max priority fee per gas {:?}, bump {}/{}",
+                tx_hashes.last().unwrap(),
+                bumped_tx.nonce,
+                bumped_tx.max_fee_per_gas,
+                bumped_tx.max_priority_fee_per_gas,
+                bumps,
+                conf.max_fee_bumps
+            );
+            pending = match self.provider.send_transaction(bumped_tx.clone()).await {
+                Ok(pending) => pending,
+                Err(e) => {
+                    // Most likely, the stuck transaction was mined in the meantime.
+                    if let Some(receipt) = self.find_receipt(&tx_hashes).await? {
+                        return Ok(receipt);
+                    }
+                    return Err(PendingTransactionError::TransportError(e));
+                }
+            };
+            info!(
+                operation,
+                "Transaction {} replaced by {}",
+                tx_hashes.last().unwrap(),
+                pending.tx_hash()
+            );
+            if let Some(journal) = &self.journal {
+                if let Err(e) = journal
+                    .record_replacement(*tx_hashes.last().unwrap(), *pending.tx_hash(), &bumped_tx)
+                    .await
+                {
+                    error!(
+                        operation,
+                        "Failed to record replacement {} in the journal: {}",
+                        pending.tx_hash(),
+                        e
+                    );
+                }
+            }
+            tx_hashes.push(*pending.tx_hash());
+            tx = Some(bumped_tx);
         }
-        let res = self.provider.send_transaction(tx).await;
-        if res.is_err() {
-            // Reset the nonce manager if the transaction sending failed.
-            *self.nonce_manager.lock().await = Default::default();
+    }
+
+    /// Replaces a transaction still stuck after `max_fee_bumps` by a 0-value transfer to the signer itself, with
+    /// bumped fees, so that the next transactions are not blocked behind its nonce. Its rows are sent again at another
+    /// nonce by their operation.
+    ///
+    /// Returns the receipt of the stuck transaction if it was mined instead.
+    async fn cancel(
+        &self,
+        operation: &str,
+        stuck_tx: &TransactionRequest,
+        tx_hashes: &[TxHash],
+        conf: &ConfigSettings,
+    ) -> Result<Option<TransactionReceipt>, PendingTransactionError> {
+        let stuck_tx_hash = *tx_hashes.last().unwrap();
+        let (Some(signer_address), Some(nonce)) = (self.signer_address, stuck_tx.nonce) else {
+            return Ok(None);
+        };
+        let Some(bumped_tx) = self.fee_strategy.bump(stuck_tx, conf.fee_bump_percent) else {
+            warn!(
+                operation,
+                "Transaction {} is stuck and cannot be cancelled, its fees cannot be bumped",
+                stuck_tx_hash
+            );
+            return Ok(None);
+        };
+        let cancel_tx = TransactionRequest {
+            from: Some(signer_address),
+            to: Some(signer_address.into()),
+            value: Some(U256::ZERO),
+            nonce: Some(nonce),
+            gas: Some(CANCEL_GAS_LIMIT),
+            chain_id: stuck_tx.chain_id,
+            max_fee_per_gas: bumped_tx.max_fee_per_gas,
+            max_priority_fee_per_gas: bumped_tx.max_priority_fee_per_gas,
+            ..Default::default()
+        };
+        let pending = match self.provider.send_transaction(cancel_tx).await {
+            Ok(pending) => pending,
+            Err(e) => {
+                // Most likely, the stuck transaction was mined in the meantime.
+                if let Some(receipt) = self.find_receipt(tx_hashes).await? {
+                    return Ok(Some(receipt));
+                }
+                error!(
+                    operation,
+                    action = REVIEW,
+                    "Cancelling stuck transaction {} at nonce {} failed: {}",
+                    stuck_tx_hash,
+                    nonce,
+                    e
+                );
+                return Ok(None);
+            }
+        };
+        let cancel_tx_hash = *pending.tx_hash();
+        warn!(
+            operation,
+            "Transaction {} is still stuck after {} fee bumps, cancelled by {} at nonce {}",
+            stuck_tx_hash,
+            conf.max_fee_bumps,
+            cancel_tx_hash,
+            nonce
+        );
+        match pending
+            .with_timeout(Some(Duration::from_secs(
+                conf.txn_receipt_timeout_secs as u64,
+            )))
+            .get_receipt()
+            .await
+        {
+            Ok(_) => {
+                let error = format!("cancelled by {}", cancel_tx_hash);
+                for tx_hash in tx_hashes {
+                    self.record_status(*tx_hash, TransactionStatus::Dropped, None, Some(&error))
+                        .await;
+                }
+                Ok(None)
+            }
+            Err(e) => {
+                if let Some(receipt) = self.find_receipt(tx_hashes).await? {
+                    return Ok(Some(receipt));
+                }
+                error!(
+                    operation,
+                    action = REVIEW,
+                    "Cancellation {} of stuck transaction {} at nonce {} is not mined: {}",
+                    cancel_tx_hash,
+                    stuck_tx_hash,
+                    nonce,
+                    e
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    async fn find_receipt(
+        &self,
+        tx_hashes: &[TxHash],
+    ) -> TransportResult<Option<TransactionReceipt>> {
+        for tx_hash in tx_hashes {
+            if let Some(receipt) = self.provider.get_transaction_receipt(*tx_hash).await? {
+                let status = if receipt.status() {
+                    TransactionStatus::Mined
+                } else {
+                    TransactionStatus::Reverted
+                };
+                self.record_status(*tx_hash, status, receipt.block_number, None)
+                    .await;
+                for other_tx_hash in tx_hashes.iter().filter(|h| *h != tx_hash) {
+                    self.record_status(*other_tx_hash, TransactionStatus::Replaced, None, None)
+                        .await;
+                }
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn record_status(
+        &self,
+        tx_hash: TxHash,
+        status: TransactionStatus,
+        block_number: Option<u64>,
+        error: Option<&str>,
+    ) {
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        if let Err(e) = journal
+            .set_status(tx_hash, status, block_number, error)
+            .await
+        {
+            error!(
+                "Failed to set status {} of transaction {} in the journal: {}",
+                status.as_str(),
+                tx_hash,
+                e
+            );
+        }
+    }
+
+    /// Reconciles the pending transactions of the journal with the Gateway, typically after a restart:
+    /// - mined ones are marked as mined or reverted, and their rows as sent or failed
+    /// - ones whose nonce was used by another transaction are marked as dropped, their rows are sent again by their
+    ///   operation, or replaced if a transaction at the same nonce was sent later
+    /// - ones unknown to the Gateway are sent again, and the receipts of the still pending ones are tracked in the
+    ///   background to update their rows.
+    ///
+    /// The next nonce is then read from the Gateway.
+    pub async fn reconcile(&self, conf: &ConfigSettings) -> anyhow::Result<()> {
+        let (Some(journal), Some(signer_address)) = (&self.journal, self.signer_address) else {
+            return Ok(());
+        };
+        let mut next_nonce = self.next_nonce.lock().await;
+        let entries = journal.pending(signer_address).await?;
+        info!("Reconciling {} pending transactions", entries.len());
+        let confirmed_nonce = self
+            .provider
+            .get_transaction_count(signer_address)
+            .latest()
+            .await?;
+        let mut to_track = vec![];
+        for (i, entry) in entries.iter().enumerate() {
+            if let Some(receipt) = self.find_receipt(&[entry.tx_hash]).await? {
+                info!(
+                    operation = entry.operation,
+                    "Transaction {} was mined in block {:?}", entry.tx_hash, receipt.block_number
+                );
+                self.settle_items(&entry.items, Ok(&receipt), conf).await;
+                continue;
+            }
+            let replaced = entries[i + 1..].iter().any(|e| e.nonce == entry.nonce);
+            if replaced {
+                self.record_status(entry.tx_hash, TransactionStatus::Replaced, None, None)
+                    .await;
+                continue;
+            }
+            if entry.nonce < confirmed_nonce {
+                warn!(
+                    operation = entry.operation,
+                    "Transaction {} was dropped, nonce {} is used", entry.tx_hash, entry.nonce
+                );
+                self.record_status(entry.tx_hash, TransactionStatus::Dropped, None, None)
+                    .await;
+                continue;
+            }
+            if self
+                .provider
+                .get_transaction_by_hash(entry.tx_hash)
+                .await?
+                .is_none()
+            {
+                let Some(tx) = entry.tx_request.clone() else {
+                    self.record_status(
+                        entry.tx_hash,
+                        TransactionStatus::Dropped,
+                        None,
+                        Some("unknown to the Gateway, no request to send again"),
+                    )
+                    .await;
+                    continue;
+                };
+                let pending = match self.provider.send_transaction(tx.clone()).await {
+                    Ok(pending) => pending,
+                    Err(e) => {
+                        warn!(
+                            operation = entry.operation,
+                            "Sending again transaction {} failed: {}", entry.tx_hash, e
+                        );
+                        self.record_status(
+                            entry.tx_hash,
+                            TransactionStatus::Dropped,
+                            None,
+                            Some(&e.to_string()),
+                        )
+                        .await;
+                        continue;
+                    }
+                };
+                info!(
+                    operation = entry.operation,
+                    "Transaction {} sent again as {} at nonce {}",
+                    entry.tx_hash,
+                    pending.tx_hash(),
+                    entry.nonce
+                );
+                // The hash differs if fields filled by the provider, e.g. the gas limit, changed.
+                if *pending.tx_hash() != entry.tx_hash {
+                    journal
+                        .record_replacement(entry.tx_hash, *pending.tx_hash(), &tx)
+                        .await?;
+                    to_track.push((
+                        *pending.tx_hash(),
+                        entry.operation.clone(),
+                        Some(tx),
+                        entry.items.clone(),
+                    ));
+                    continue;
+                }
+            }
+            to_track.push((
+                entry.tx_hash,
+                entry.operation.clone(),
+                entry.tx_request.clone(),
+                entry.items.clone(),
+            ));
+        }
+        *next_nonce = None;
+        drop(next_nonce);
+
+        for (tx_hash, operation, tx, items) in to_track {
+            if let Some(tx) = tx {
+                self.sent_transactions.lock().await.insert(tx_hash, tx);
+            }
+            let provider = self.clone();
+            let conf = conf.clone();
+            tokio::spawn(async move {
+                let pending =
+                    PendingTransactionBuilder::new(provider.provider.root().clone(), tx_hash);
+                match provider.get_receipt(&operation, pending, &conf).await {
+                    Ok(receipt) => provider.settle_items(&items, Ok(&receipt), &conf).await,
+                    Err(e) => {
+                        warn!(
+                            operation,
+                            "Getting receipt of journaled transaction {} failed: {}", tx_hash, e
+                        );
+                        provider
+                            .settle_items(&items, Err(&e.to_string()), &conf)
+                            .await;
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Marks the rows of a reconciled transaction as sent if it succeeded, else counts a failed attempt.
+    async fn settle_items(
+        &self,
+        items: &[DeadLetterItem],
+        receipt: Result<&TransactionReceipt, &str>,
+        conf: &ConfigSettings,
+    ) {
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let res = match receipt {
+            Ok(receipt) if receipt.status() => journal.mark_items_sent(items).await,
+            Ok(_) => {
+                journal
+                    .count_failed_items(items, "receipt status = false", conf)
+                    .await
+            }
+            Err(error) => journal.count_failed_items(items, error, conf).await,
+        };
+        if let Err(e) = res {
+            error!(
+                "Failed to update the rows of a reconciled transaction: {}",
+                e
+            );
         }
-        res
     }
 
     pub async fn get_chain_id(&self) -> TransportResult<u64> {
@@ -67,6 +563,10 @@ impl<P: alloy::providers::Provider<Ethereum> + Clone + 'static> NonceManagedProv
         self.provider.get_transaction_count(address).await
     }
 
+    pub fn signer_address(&self) -> Option<Address> {
+        self.signer_address
+    }
+
     pub fn inner(&self) -> &P {
         &self.provider
     }
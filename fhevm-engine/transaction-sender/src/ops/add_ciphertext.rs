@@ -1,8 +1,10 @@
-use std::time::Duration;
-
-use crate::{nonce_managed_provider::NonceManagedProvider, REVIEW};
+use crate::{
+    dead_letter::{DeadLetterItem, DeadLetterQueue},
+    signer_pool::SignerPool,
+    REVIEW,
+};
 
-use super::common::try_into_array;
+use super::common::{send_multicall, try_into_array, MulticallOutcome, MULTICALL_PENDING};
 use super::TransactionOperation;
 use alloy::{
     network::{Ethereum, TransactionBuilder},
@@ -29,10 +31,11 @@ sol!(
 #[derive(Clone)]
 pub struct AddCiphertextOperation<P: Provider<Ethereum> + Clone + 'static> {
     ciphertext_commits_address: Address,
-    provider: NonceManagedProvider<P>,
+    providers: SignerPool<P>,
     conf: crate::ConfigSettings,
     gas: Option<u64>,
     db_pool: Pool<Postgres>,
+    dead_letters: DeadLetterQueue,
 }
 
 impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
@@ -48,7 +51,18 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
         info!("Processing transaction, handle: {}", h);
 
         let txn_req = txn_request.into();
-        let transaction = match self.provider.send_transaction(txn_req.clone()).await {
+        let provider = self.providers.next_provider();
+        let transaction = match provider
+            .send_transaction(
+                self.channel(),
+                &[handle.to_vec()],
+                &[DeadLetterItem::AddCiphertext {
+                    handle: handle.to_vec(),
+                }],
+                txn_req.clone(),
+            )
+            .await
+        {
             Ok(txn) => txn,
             Err(e) if self.already_added_error(&e).is_some() => {
                 warn!(
@@ -87,12 +101,8 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
 
         // We assume that if we were able to send the transaction, we will be able to get a receipt, eventually. If there is a transport
         // error in-between, we rely on the retry logic to handle it.
-        let receipt = match transaction
-            .with_timeout(Some(Duration::from_secs(
-                self.conf.txn_receipt_timeout_secs as u64,
-            )))
-            .with_required_confirmations(self.conf.required_txn_confirmations as u64)
-            .get_receipt()
+        let receipt = match provider
+            .get_receipt(self.channel(), transaction, &self.conf)
             .await
         {
             Ok(receipt) => receipt,
@@ -151,6 +161,11 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
         )
         .execute(&self.db_pool)
         .await?;
+        self.dead_letters
+            .clear_errors(&DeadLetterItem::AddCiphertext {
+                handle: handle.to_vec(),
+            })
+            .await?;
         Ok(())
     }
 }
@@ -158,7 +173,7 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
 impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
     pub fn new(
         ciphertext_commits_address: Address,
-        provider: NonceManagedProvider<P>,
+        providers: SignerPool<P>,
         conf: crate::ConfigSettings,
         gas: Option<u64>,
         db_pool: Pool<Postgres>,
@@ -170,9 +185,10 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
         );
 
         Self {
+            dead_letters: DeadLetterQueue::new(db_pool.clone()),
             db_pool,
             ciphertext_commits_address,
-            provider,
+            providers,
             conf,
             gas,
         }
@@ -185,20 +201,9 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
         current_retry_count: i32,
     ) -> anyhow::Result<()> {
         let compact_hex_handle = compact_hex(handle);
-        if current_retry_count == (self.conf.add_ciphertexts_max_retries as i32) - 1 {
-            error!(
-                action = REVIEW,
-                "Max ({}) retries reached for adding ciphertext with handle {}",
-                self.conf.add_ciphertexts_max_retries,
-                compact_hex_handle
-            );
-        } else {
-            warn!(
-                "Updating retry count to {}, handle {}",
-                current_retry_count + 1,
-                compact_hex_handle
-            );
-        }
+        let item = DeadLetterItem::AddCiphertext {
+            handle: handle.to_vec(),
+        };
         sqlx::query!(
             "UPDATE ciphertext_digest
             SET
@@ -211,6 +216,18 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
         )
         .execute(&self.db_pool)
         .await?;
+        self.dead_letters.record_error(&item, err, false).await?;
+        if current_retry_count == (self.conf.add_ciphertexts_max_retries as i32) - 1 {
+            self.dead_letters
+                .add(&item, current_retry_count + 1, err)
+                .await?;
+        } else {
+            warn!(
+                "Updating retry count to {}, handle {}",
+                current_retry_count + 1,
+                compact_hex_handle
+            );
+        }
         Ok(())
     }
 
@@ -221,6 +238,22 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
         current_transport_retry_count: i32,
     ) -> anyhow::Result<()> {
         let compact_hex_handle = compact_hex(handle);
+        let item = DeadLetterItem::AddCiphertext {
+            handle: handle.to_vec(),
+        };
+        sqlx::query!(
+            "UPDATE ciphertext_digest
+            SET
+            txn_transport_retry_count = txn_transport_retry_count + 1,
+            txn_last_error = $1,
+            txn_last_error_at = NOW()
+            WHERE handle = $2",
+            err,
+            handle,
+        )
+        .execute(&self.db_pool)
+        .await?;
+        self.dead_letters.record_error(&item, err, true).await?;
         if current_transport_retry_count >= (self.conf.review_after_transport_retries as i32) - 1 {
             error!(
                 action = REVIEW,
@@ -235,18 +268,6 @@ impl<P: Provider<Ethereum> + Clone + 'static> AddCiphertextOperation<P> {
                 compact_hex_handle
             );
         }
-        sqlx::query!(
-            "UPDATE ciphertext_digest
-            SET
-            txn_transport_retry_count = txn_transport_retry_count + 1,
-            txn_last_error = $1,
-            txn_last_error_at = NOW()
-            WHERE handle = $2",
-            err,
-            handle,
-        )
-        .execute(&self.db_pool)
-        .await?;
         Ok(())
     }
 }
@@ -260,6 +281,21 @@ where
         &self.conf.add_ciphertexts_db_channel
     }
 
+    async fn queue_depth(&self) -> anyhow::Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!"
+            FROM ciphertext_digest
+            WHERE txn_is_sent = false
+            AND ciphertext IS NOT NULL
+            AND ciphertext128 IS NOT NULL
+            AND txn_retry_count < $1"#,
+            self.conf.add_ciphertexts_max_retries as i32,
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        Ok(count)
+    }
+
     async fn execute(&self) -> anyhow::Result<bool> {
         // The service responsible for populating the ciphertext_digest table must
         // ensure that ciphertext and ciphertext128 are non-null only after the
@@ -280,13 +316,16 @@ where
         .await?;
 
         let ciphertext_manager: CiphertextCommits::CiphertextCommitsInstance<(), &P> =
-            CiphertextCommits::new(self.ciphertext_commits_address, self.provider.inner());
+            CiphertextCommits::new(
+                self.ciphertext_commits_address,
+                self.providers.first().inner(),
+            );
 
         info!("Selected {} rows to process", rows.len());
 
         let maybe_has_more_work = rows.len() == self.conf.add_ciphertexts_batch_limit as usize;
 
-        let mut join_set = JoinSet::new();
+        let mut txn_requests = vec![];
         for row in rows.into_iter() {
             let tenant_info = match query_tenant_info(&self.db_pool, row.tenant_id).await {
                 Ok(res) => res,
@@ -346,14 +385,64 @@ where
                     .into_transaction_request(),
             };
 
+            txn_requests.push((
+                row.handle,
+                txn_request,
+                row.txn_retry_count,
+                row.txn_transport_retry_count,
+            ));
+        }
+
+        if self.conf.add_ciphertexts_multicall && txn_requests.len() > 1 {
+            let handles: Vec<Vec<u8>> = txn_requests.iter().map(|r| r.0.clone()).collect();
+            let items: Vec<DeadLetterItem> = handles
+                .iter()
+                .map(|handle| DeadLetterItem::AddCiphertext {
+                    handle: handle.clone(),
+                })
+                .collect();
+            let calls: Vec<TransactionRequest> = txn_requests.iter().map(|r| r.1.clone()).collect();
+            match send_multicall(
+                self.providers.next_provider(),
+                self.channel(),
+                self.ciphertext_commits_address,
+                &handles,
+                &items,
+                &calls,
+                self.gas,
+                &self.conf,
+            )
+            .await
+            {
+                MulticallOutcome::Sent => {
+                    for handle in &handles {
+                        self.set_txn_is_sent(handle).await?;
+                    }
+                    return Ok(maybe_has_more_work);
+                }
+                // Counted as an attempt, so a multicall that is never mined is not sent again forever, then wait for
+                // the next notification or polling, so the pending multicall is not sent again right away.
+                MulticallOutcome::Pending => {
+                    for (handle, _, txn_retry_count, _) in &txn_requests {
+                        self.increment_txn_retry_count(handle, MULTICALL_PENDING, *txn_retry_count)
+                            .await?;
+                    }
+                    return Ok(false);
+                }
+                MulticallOutcome::SendOneByOne => {}
+            }
+        }
+
+        let mut join_set = JoinSet::new();
+        for (handle, txn_request, txn_retry_count, txn_transport_retry_count) in txn_requests {
             let operation = self.clone();
             join_set.spawn(async move {
                 operation
                     .send_transaction(
-                        &row.handle,
+                        &handle,
                         txn_request,
-                        row.txn_retry_count,
-                        row.txn_transport_retry_count,
+                        txn_retry_count,
+                        txn_transport_retry_count,
                     )
                     .await
             });
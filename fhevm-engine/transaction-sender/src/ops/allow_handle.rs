@@ -1,10 +1,16 @@
 use std::{
     fmt::{Display, Formatter},
     str::FromStr,
-    time::Duration,
 };
 
-use crate::{nonce_managed_provider::NonceManagedProvider, ops::common::try_into_array, REVIEW};
+use crate::{
+    dead_letter::{DeadLetterItem, DeadLetterQueue},
+    ops::common::{
+        send_multicall, try_into_array, MulticallOutcome, RetriedKey, RetriedRows,
+        MULTICALL_PENDING,
+    },
+    signer_pool::SignerPool,
+};
 
 use super::TransactionOperation;
 use alloy::{
@@ -15,12 +21,11 @@ use alloy::{
     sol,
     transports::{RpcError, TransportErrorKind},
 };
-use anyhow::bail;
 use async_trait::async_trait;
 use fhevm_engine_common::{tenant_keys::query_tenant_info, types::AllowEvents, utils::compact_hex};
 use sqlx::{Pool, Postgres};
 use tokio::task::JoinSet;
-use tracing::{debug, error, info, warn};
+use tracing::{error, info};
 use MultichainAcl::MultichainAclErrors;
 
 sol!(
@@ -29,13 +34,27 @@ sol!(
     "artifacts/MultichainAcl.sol/MultichainAcl.json"
 );
 
-struct Key {
+pub(crate) struct Key {
     handle: Vec<u8>,
     account_addr: String,
     tenant_id: i32,
     event_type: AllowEvents,
 }
 
+impl RetriedKey for Key {
+    fn handles(&self) -> Vec<Vec<u8>> {
+        vec![self.handle.clone()]
+    }
+
+    fn dead_letter_item(&self) -> DeadLetterItem {
+        DeadLetterItem::AllowHandle {
+            tenant_id: self.tenant_id,
+            handle: self.handle.clone(),
+            account_address: self.account_addr.clone(),
+        }
+    }
+}
+
 impl Display for Key {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -52,139 +71,17 @@ impl Display for Key {
 #[derive(Clone)]
 pub struct MultichainAclOperation<P: Provider<Ethereum> + Clone + 'static> {
     multichain_acl_address: Address,
-    provider: NonceManagedProvider<P>,
+    providers: SignerPool<P>,
     conf: crate::ConfigSettings,
     gas: Option<u64>,
     db_pool: Pool<Postgres>,
-}
-
-impl<P: Provider<Ethereum> + Clone + 'static> MultichainAclOperation<P> {
-    /// Sends a transaction
-    ///
-    /// TODO: Refactor: Avoid code duplication
-    async fn send_transaction(
-        &self,
-        key: &Key,
-        txn_request: impl Into<TransactionRequest>,
-        current_retry_count: i32,
-        current_transport_retry_count: i32,
-    ) -> anyhow::Result<()> {
-        let h = compact_hex(&key.handle);
-
-        info!("Processing transaction, handle: {}", h);
-
-        let txn_req = txn_request.into();
-        let transaction = match self.provider.send_transaction(txn_req.clone()).await {
-            Ok(txn) => txn,
-            Err(e) if self.already_allowed_error(&e).is_some() => {
-                warn!(
-                    "Coprocessor {} has already added the ACL entry for handle: {}",
-                    self.already_allowed_error(&e).unwrap(),
-                    h
-                );
-                self.set_txn_is_sent(key).await?;
-                return Ok(());
-            }
-            Err(RpcError::Transport(e))
-                if e.is_retry_err() || matches!(e, TransportErrorKind::BackendGone) =>
-            {
-                warn!(
-                    "Transaction {:?} sending failed with transport error: {}, handle: {}",
-                    txn_req, e, h
-                );
-                self.increment_transport_txn_retry_count(
-                    key,
-                    &e.to_string(),
-                    current_transport_retry_count,
-                )
-                .await?;
-                bail!("Transaction sending failed with transport error: {}", e);
-            }
-            Err(e) => {
-                warn!(
-                    "Transaction {:?} sending failed with error: {}, handle: {}",
-                    txn_req, e, h
-                );
-                self.increment_txn_retry_count(key, &e.to_string(), current_retry_count)
-                    .await?;
-                bail!("Transaction sending failed with error: {}", e);
-            }
-        };
-
-        // We assume that if we were able to send the transaction, we will be able to get a receipt, eventually. If there is a transport
-        // error in-between, we rely on the retry logic to handle it.
-        let receipt = match transaction
-            .with_timeout(Some(Duration::from_secs(
-                self.conf.txn_receipt_timeout_secs as u64,
-            )))
-            .with_required_confirmations(self.conf.required_txn_confirmations as u64)
-            .get_receipt()
-            .await
-        {
-            Ok(receipt) => receipt,
-            Err(e) => {
-                error!("Getting receipt failed with error: {}", e);
-                self.increment_txn_retry_count(key, &e.to_string(), current_retry_count)
-                    .await?;
-                return Err(anyhow::Error::new(e));
-            }
-        };
-
-        if receipt.status() {
-            self.set_txn_is_sent(key).await?;
-
-            info!("Allow txn: {} succeeded, {}", receipt.transaction_hash, key,);
-        } else {
-            error!(
-                "allowAccount txn: {} failed with status {}, handle: {}",
-                receipt.transaction_hash,
-                receipt.status(),
-                h
-            );
-
-            self.increment_txn_retry_count(key, "receipt status = false", current_retry_count)
-                .await?;
-
-            return Err(anyhow::anyhow!(
-                "Transaction {} failed with status {}, handle: {}",
-                receipt.transaction_hash,
-                receipt.status(),
-                h,
-            ));
-        }
-        Ok(())
-    }
-
-    fn already_allowed_error(&self, err: &RpcError<TransportErrorKind>) -> Option<Address> {
-        let validate = true;
-        err.as_error_resp()
-            .and_then(|payload| payload.as_decoded_error::<MultichainAclErrors>(validate))
-            .map(|error| match error {
-                MultichainAclErrors::CoprocessorAlreadyAllowed(c) => c.coprocessor,
-            })
-    }
-
-    async fn set_txn_is_sent(&self, key: &Key) -> anyhow::Result<()> {
-        sqlx::query!(
-            "UPDATE allowed_handles
-                 SET txn_is_sent = true
-                 WHERE handle = $1
-                 AND account_address = $2
-                 AND tenant_id = $3",
-            key.handle,
-            key.account_addr,
-            key.tenant_id
-        )
-        .execute(&self.db_pool)
-        .await?;
-        Ok(())
-    }
+    dead_letters: DeadLetterQueue,
 }
 
 impl<P: Provider<Ethereum> + Clone + 'static> MultichainAclOperation<P> {
     pub fn new(
         multichain_acl_address: Address,
-        provider: NonceManagedProvider<P>,
+        providers: SignerPool<P>,
         conf: crate::ConfigSettings,
         gas: Option<u64>,
         db_pool: Pool<Postgres>,
@@ -197,90 +94,105 @@ impl<P: Provider<Ethereum> + Clone + 'static> MultichainAclOperation<P> {
 
         Self {
             multichain_acl_address,
-            provider,
+            providers,
             conf,
             gas,
+            dead_letters: DeadLetterQueue::new(db_pool.clone()),
             db_pool,
         }
     }
+}
 
-    async fn increment_txn_retry_count(
-        &self,
-        key: &Key,
-        err: &str,
-        current_retry_count: i32,
-    ) -> anyhow::Result<()> {
-        debug!("Updating retry count for key {}", key);
+#[async_trait]
+impl<P: Provider<Ethereum> + Clone + 'static> RetriedRows for MultichainAclOperation<P> {
+    type Key = Key;
 
-        if current_retry_count == (self.conf.allow_handle_max_retries as i32) - 1 {
-            error!(
-                action = REVIEW,
-                "Max ({}) retries reached for key {}", key, self.conf.allow_handle_max_retries
-            );
-        } else {
-            warn!(
-                "Updating retry count to {} for key {}",
-                current_retry_count + 1,
-                key
-            );
-        }
+    fn operation(&self) -> &str {
+        &self.conf.allow_handle_db_channel
+    }
+
+    fn conf(&self) -> &crate::ConfigSettings {
+        &self.conf
+    }
+
+    fn max_retries(&self) -> u32 {
+        self.conf.allow_handle_max_retries
+    }
+
+    fn dead_letters(&self) -> &DeadLetterQueue {
+        &self.dead_letters
+    }
+
+    fn already_sent_error(&self, err: &RpcError<TransportErrorKind>) -> Option<Address> {
+        let validate = true;
+        err.as_error_resp()
+            .and_then(|payload| payload.as_decoded_error::<MultichainAclErrors>(validate))
+            .and_then(|error| match error {
+                MultichainAclErrors::CoprocessorAlreadyAllowed(c) => Some(c.coprocessor),
+                _ => None,
+            })
+    }
 
+    async fn set_txn_is_sent(&self, key: &Key) -> anyhow::Result<()> {
         sqlx::query!(
             "UPDATE allowed_handles
-            SET
-            txn_retry_count = txn_retry_count + 1,
-            txn_last_error = $1,
-            txn_last_error_at = NOW()
-            WHERE handle = $2
-            AND account_address = $3
-            AND tenant_id = $4",
-            err,
+                 SET txn_is_sent = true
+                 WHERE handle = $1
+                 AND account_address = $2
+                 AND tenant_id = $3",
             key.handle,
             key.account_addr,
             key.tenant_id
         )
         .execute(&self.db_pool)
         .await?;
+        self.dead_letters
+            .clear_errors(&key.dead_letter_item())
+            .await?;
         Ok(())
     }
 
-    async fn increment_transport_txn_retry_count(
+    async fn update_retry_count(
         &self,
         key: &Key,
         err: &str,
-        current_transport_retry_count: i32,
+        transport: bool,
     ) -> anyhow::Result<()> {
-        debug!("Updating transport retry count, {}", key);
-
-        if current_transport_retry_count == (self.conf.review_after_transport_retries as i32) - 1 {
-            error!(
-                action = REVIEW,
-                "{} transport retries reached for key {}", current_transport_retry_count, key
-            );
+        if transport {
+            sqlx::query!(
+                "UPDATE allowed_handles
+                SET
+                txn_transport_retry_count = txn_transport_retry_count + 1,
+                txn_last_error = $1,
+                txn_last_error_at = NOW()
+                WHERE handle = $2
+                AND account_address = $3
+                AND tenant_id = $4",
+                err,
+                key.handle,
+                key.account_addr,
+                key.tenant_id
+            )
+            .execute(&self.db_pool)
+            .await?;
         } else {
-            warn!(
-                "Updating transport retry count to {}, key {}",
-                current_transport_retry_count + 1,
-                key
-            );
+            sqlx::query!(
+                "UPDATE allowed_handles
+                SET
+                txn_retry_count = txn_retry_count + 1,
+                txn_last_error = $1,
+                txn_last_error_at = NOW()
+                WHERE handle = $2
+                AND account_address = $3
+                AND tenant_id = $4",
+                err,
+                key.handle,
+                key.account_addr,
+                key.tenant_id
+            )
+            .execute(&self.db_pool)
+            .await?;
         }
-
-        sqlx::query!(
-            "UPDATE allowed_handles
-            SET
-            txn_transport_retry_count = txn_transport_retry_count + 1,
-            txn_last_error = $1,
-            txn_last_error_at = NOW()
-            WHERE handle = $2
-            AND account_address = $3
-            AND tenant_id = $4",
-            err,
-            key.handle,
-            key.account_addr,
-            key.tenant_id
-        )
-        .execute(&self.db_pool)
-        .await?;
         Ok(())
     }
 }
@@ -294,6 +206,19 @@ where
         &self.conf.allow_handle_db_channel
     }
 
+    async fn queue_depth(&self) -> anyhow::Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!"
+            FROM allowed_handles
+            WHERE txn_is_sent = false
+            AND txn_retry_count < $1"#,
+            self.conf.allow_handle_max_retries as i32,
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        Ok(count)
+    }
+
     async fn execute(&self) -> anyhow::Result<bool> {
         let rows = sqlx::query!(
             "
@@ -310,13 +235,13 @@ where
         .await?;
 
         let multichain_acl: MultichainAcl::MultichainAclInstance<(), &P> =
-            MultichainAcl::new(self.multichain_acl_address, self.provider.inner());
+            MultichainAcl::new(self.multichain_acl_address, self.providers.first().inner());
 
         info!("Selected {} rows to process", rows.len());
 
         let maybe_has_more_work = rows.len() == self.conf.allow_handle_batch_limit as usize;
 
-        let mut join_set = JoinSet::new();
+        let mut txn_requests = vec![];
         for row in rows.into_iter() {
             let tenant = match query_tenant_info(&self.db_pool, row.tenant_id).await {
                 Ok(res) => res,
@@ -397,14 +322,63 @@ where
                 event_type,
             };
 
+            txn_requests.push((
+                key,
+                txn_request,
+                row.txn_retry_count,
+                row.txn_transport_retry_count,
+            ));
+        }
+
+        if self.conf.allow_handle_multicall && txn_requests.len() > 1 {
+            let handles: Vec<Vec<u8>> = txn_requests.iter().map(|r| r.0.handle.clone()).collect();
+            let items: Vec<DeadLetterItem> = txn_requests
+                .iter()
+                .map(|r| r.0.dead_letter_item())
+                .collect();
+            let calls: Vec<TransactionRequest> = txn_requests.iter().map(|r| r.1.clone()).collect();
+            match send_multicall(
+                self.providers.next_provider(),
+                self.channel(),
+                self.multichain_acl_address,
+                &handles,
+                &items,
+                &calls,
+                self.gas,
+                &self.conf,
+            )
+            .await
+            {
+                MulticallOutcome::Sent => {
+                    for (key, ..) in &txn_requests {
+                        self.set_txn_is_sent(key).await?;
+                    }
+                    return Ok(maybe_has_more_work);
+                }
+                // Counted as an attempt, so a multicall that is never mined is not sent again forever, then wait for
+                // the next notification or polling, so the pending multicall is not sent again right away.
+                MulticallOutcome::Pending => {
+                    for (key, _, txn_retry_count, _) in &txn_requests {
+                        self.increment_txn_retry_count(key, MULTICALL_PENDING, *txn_retry_count)
+                            .await?;
+                    }
+                    return Ok(false);
+                }
+                MulticallOutcome::SendOneByOne => {}
+            }
+        }
+
+        let mut join_set = JoinSet::new();
+        for (key, txn_request, txn_retry_count, txn_transport_retry_count) in txn_requests {
             let operation = self.clone();
             join_set.spawn(async move {
                 operation
-                    .send_transaction(
+                    .send_row_transaction(
+                        operation.providers.next_provider(),
                         &key,
                         txn_request,
-                        row.txn_retry_count,
-                        row.txn_transport_retry_count,
+                        txn_retry_count,
+                        txn_transport_retry_count,
                     )
                     .await
             });
@@ -1,5 +1,29 @@
-use anyhow::{anyhow, Result};
-use std::convert::TryInto;
+use alloy::{
+    network::{Ethereum, TransactionBuilder},
+    primitives::{Address, Bytes, TxHash},
+    providers::{PendingTransactionError, Provider, WatchTxError},
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    sol,
+    transports::{RpcError, TransportErrorKind},
+};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use std::{convert::TryInto, fmt::Display};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    dead_letter::{DeadLetterItem, DeadLetterQueue},
+    nonce_managed_provider::NonceManagedProvider,
+    ConfigSettings, REVIEW,
+};
+
+sol! {
+    /// Calls of a contract to itself in one transaction, keeping the sender, as OpenZeppelin's `Multicall`.
+    #[sol(rpc)]
+    contract Multicall {
+        function multicall(bytes[] calldata data) external returns (bytes[] memory results);
+    }
+}
 
 pub(crate) fn try_into_array<const SIZE: usize>(vec: Vec<u8>) -> Result<[u8; SIZE]> {
     if vec.len() != SIZE {
@@ -13,3 +37,355 @@ pub(crate) fn try_into_array<const SIZE: usize>(vec: Vec<u8>) -> Result<[u8; SIZ
     vec.try_into()
         .map_err(|_| anyhow!("Failed to convert Vec to array"))
 }
+
+/// Key of a row whose transaction is sent by a `RetriedRows` operation.
+pub(crate) trait RetriedKey: Display + Send + Sync {
+    /// Handles the transaction is about, if any.
+    fn handles(&self) -> Vec<Vec<u8>>;
+
+    fn dead_letter_item(&self) -> DeadLetterItem;
+}
+
+/// An operation sending one transaction per row of its table, which keeps the retry counts of the row, as allowed
+/// handles and delegations.
+#[async_trait]
+pub(crate) trait RetriedRows: Send + Sync {
+    type Key: RetriedKey;
+
+    /// Name of the operation in logs, its database channel.
+    fn operation(&self) -> &str;
+
+    fn conf(&self) -> &ConfigSettings;
+
+    fn max_retries(&self) -> u32;
+
+    fn dead_letters(&self) -> &DeadLetterQueue;
+
+    /// The coprocessor that already sent the transaction if `err` tells so.
+    fn already_sent_error(&self, err: &RpcError<TransportErrorKind>) -> Option<Address>;
+
+    async fn set_txn_is_sent(&self, key: &Self::Key) -> Result<()>;
+
+    /// Increments the retry count, or the transport retry count, of the row and records its last error.
+    async fn update_retry_count(&self, key: &Self::Key, err: &str, transport: bool) -> Result<()>;
+
+    /// Sends the transaction of a row, and updates the row from its outcome.
+    async fn send_row_transaction<P: Provider<Ethereum> + Clone + 'static>(
+        &self,
+        provider: &NonceManagedProvider<P>,
+        key: &Self::Key,
+        txn_request: TransactionRequest,
+        current_retry_count: i32,
+        current_transport_retry_count: i32,
+    ) -> Result<()> {
+        let operation = self.operation();
+        info!(operation, "Processing transaction, {}", key);
+
+        let transaction = match provider
+            .send_transaction(
+                operation,
+                &key.handles(),
+                &[key.dead_letter_item()],
+                txn_request.clone(),
+            )
+            .await
+        {
+            Ok(txn) => txn,
+            Err(e) if self.already_sent_error(&e).is_some() => {
+                warn!(
+                    operation,
+                    "Coprocessor {} has already sent the transaction, {}",
+                    self.already_sent_error(&e).unwrap(),
+                    key
+                );
+                self.set_txn_is_sent(key).await?;
+                return Ok(());
+            }
+            Err(RpcError::Transport(e))
+                if e.is_retry_err() || matches!(e, TransportErrorKind::BackendGone) =>
+            {
+                warn!(
+                    operation,
+                    "Transaction {:?} sending failed with transport error: {}, {}",
+                    txn_request,
+                    e,
+                    key
+                );
+                self.increment_transport_txn_retry_count(
+                    key,
+                    &e.to_string(),
+                    current_transport_retry_count,
+                )
+                .await?;
+                bail!("Transaction sending failed with transport error: {}", e);
+            }
+            Err(e) => {
+                warn!(
+                    operation,
+                    "Transaction {:?} sending failed with error: {}, {}", txn_request, e, key
+                );
+                self.increment_txn_retry_count(key, &e.to_string(), current_retry_count)
+                    .await?;
+                bail!("Transaction sending failed with error: {}", e);
+            }
+        };
+
+        // We assume that if we were able to send the transaction, we will be able to get a receipt, eventually. If there is a transport
+        // error in-between, we rely on the retry logic to handle it.
+        let receipt = match provider
+            .get_receipt(operation, transaction, self.conf())
+            .await
+        {
+            Ok(receipt) => receipt,
+            Err(e) => {
+                error!(operation, "Getting receipt failed with error: {}", e);
+                self.increment_txn_retry_count(key, &e.to_string(), current_retry_count)
+                    .await?;
+                return Err(anyhow::Error::new(e));
+            }
+        };
+
+        if receipt.status() {
+            self.set_txn_is_sent(key).await?;
+
+            info!(
+                operation,
+                "Txn: {} succeeded, {}", receipt.transaction_hash, key
+            );
+        } else {
+            error!(
+                operation,
+                "Txn: {} failed with status {}, {}",
+                receipt.transaction_hash,
+                receipt.status(),
+                key
+            );
+
+            self.increment_txn_retry_count(key, "receipt status = false", current_retry_count)
+                .await?;
+
+            bail!(
+                "Transaction {} failed with status {}, {}",
+                receipt.transaction_hash,
+                receipt.status(),
+                key,
+            );
+        }
+        Ok(())
+    }
+
+    /// Counts a failed attempt, the row is dead-lettered at its last one.
+    async fn increment_txn_retry_count(
+        &self,
+        key: &Self::Key,
+        err: &str,
+        current_retry_count: i32,
+    ) -> Result<()> {
+        debug!("Updating retry count for key {}", key);
+
+        let item = key.dead_letter_item();
+        self.update_retry_count(key, err, false).await?;
+        self.dead_letters().record_error(&item, err, false).await?;
+        if current_retry_count == (self.max_retries() as i32) - 1 {
+            self.dead_letters()
+                .add(&item, current_retry_count + 1, err)
+                .await?;
+        } else {
+            warn!(
+                "Updating retry count to {} for key {}",
+                current_retry_count + 1,
+                key
+            );
+        }
+        Ok(())
+    }
+
+    /// Counts a transport failure, which is retried indefinitely but reviewed after
+    /// `review_after_transport_retries`.
+    async fn increment_transport_txn_retry_count(
+        &self,
+        key: &Self::Key,
+        err: &str,
+        current_transport_retry_count: i32,
+    ) -> Result<()> {
+        debug!("Updating transport retry count, {}", key);
+
+        self.update_retry_count(key, err, true).await?;
+        self.dead_letters()
+            .record_error(&key.dead_letter_item(), err, true)
+            .await?;
+
+        if current_transport_retry_count == (self.conf().review_after_transport_retries as i32) - 1
+        {
+            error!(
+                action = REVIEW,
+                "{} transport retries reached for key {}", current_transport_retry_count, key
+            );
+        } else {
+            warn!(
+                "Updating transport retry count to {}, key {}",
+                current_transport_retry_count + 1,
+                key
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Error recorded for the calls of a multicall that may still be mined, each counting as an attempt.
+pub(crate) const MULTICALL_PENDING: &str = "multicall pending";
+
+/// Outcome of a multicall transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MulticallOutcome {
+    /// The multicall succeeded, all calls are sent.
+    Sent,
+    /// The calls have to be sent one by one, e.g. when one of them reverts, to get a result per call.
+    SendOneByOne,
+    /// The multicall may still be mined, the calls are left to be sent again later, as sending them one by one would
+    /// duplicate them, and count as a failed attempt.
+    Pending,
+}
+
+/// Sends the calls to the given contract in a single multicall transaction.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn send_multicall<P: Provider<Ethereum> + Clone + 'static>(
+    provider: &NonceManagedProvider<P>,
+    operation: &str,
+    contract_address: Address,
+    handles: &[Vec<u8>],
+    items: &[DeadLetterItem],
+    calls: &[TransactionRequest],
+    gas: Option<u64>,
+    conf: &ConfigSettings,
+) -> MulticallOutcome {
+    let data: Vec<Bytes> = calls
+        .iter()
+        .map(|call| call.input.input().cloned().unwrap_or_default())
+        .collect();
+    let multicall = Multicall::new(contract_address, provider.inner());
+    let txn_request = match gas {
+        Some(gas_limit) => multicall
+            .multicall(data)
+            .into_transaction_request()
+            .with_gas_limit(gas_limit * calls.len() as u64),
+        None => multicall.multicall(data).into_transaction_request(),
+    };
+    info!(operation, "Sending {} calls in a multicall", calls.len());
+    let transaction = match provider
+        .send_transaction(operation, handles, items, txn_request)
+        .await
+    {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            warn!(
+                operation,
+                "Multicall sending failed with error: {}, sending calls one by one", e
+            );
+            return MulticallOutcome::SendOneByOne;
+        }
+    };
+    let tx_hash = *transaction.tx_hash();
+    let nonce = provider.sent_nonce(&tx_hash).await;
+    match provider.get_receipt(operation, transaction, conf).await {
+        Ok(receipt) => receipt_outcome(operation, &receipt, calls.len()),
+        Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout)) => {
+            timeout_outcome(provider, operation, tx_hash, nonce, calls.len()).await
+        }
+        Err(e) => {
+            warn!(
+                operation,
+                "Getting multicall receipt failed with error: {}, sending calls one by one", e
+            );
+            MulticallOutcome::SendOneByOne
+        }
+    }
+}
+
+fn receipt_outcome(
+    operation: &str,
+    receipt: &TransactionReceipt,
+    calls: usize,
+) -> MulticallOutcome {
+    if receipt.status() {
+        info!(
+            operation,
+            "Multicall txn: {} succeeded, {} calls", receipt.transaction_hash, calls
+        );
+        MulticallOutcome::Sent
+    } else {
+        warn!(
+            operation,
+            "Multicall txn: {} failed with status {}, sending calls one by one",
+            receipt.transaction_hash,
+            receipt.status()
+        );
+        MulticallOutcome::SendOneByOne
+    }
+}
+
+/// Checks whether a multicall whose receipt timed out was mined, or if its nonce was used, before its calls are sent
+/// one by one.
+async fn timeout_outcome<P: Provider<Ethereum> + Clone + 'static>(
+    provider: &NonceManagedProvider<P>,
+    operation: &str,
+    tx_hash: TxHash,
+    nonce: Option<u64>,
+    calls: usize,
+) -> MulticallOutcome {
+    match provider.inner().get_transaction_receipt(tx_hash).await {
+        Ok(Some(receipt)) => return receipt_outcome(operation, &receipt, calls),
+        Ok(None) => {}
+        Err(e) => {
+            warn!(
+                operation,
+                "Getting multicall txn: {} receipt after a timeout failed with error: {}, sending calls later",
+                tx_hash,
+                e
+            );
+            return MulticallOutcome::Pending;
+        }
+    }
+    let (Some(signer_address), Some(nonce)) = (provider.signer_address(), nonce) else {
+        warn!(
+            operation,
+            "Multicall txn: {} timed out with an unknown nonce, sending calls later", tx_hash
+        );
+        return MulticallOutcome::Pending;
+    };
+    match provider
+        .inner()
+        .get_transaction_count(signer_address)
+        .latest()
+        .await
+    {
+        // Only the replacements of the multicall can use its nonce, the calls already applied revert when sent again.
+        Ok(confirmed_nonce) if confirmed_nonce > nonce => {
+            warn!(
+                operation,
+                "Multicall txn: {} timed out and its nonce {} was used, sending calls one by one",
+                tx_hash,
+                nonce
+            );
+            MulticallOutcome::SendOneByOne
+        }
+        Ok(_) => {
+            warn!(
+                operation,
+                "Multicall txn: {} timed out and is still pending at nonce {}, sending calls later",
+                tx_hash,
+                nonce
+            );
+            MulticallOutcome::Pending
+        }
+        Err(e) => {
+            warn!(
+                operation,
+                "Getting the nonce of multicall txn: {} failed with error: {}, sending calls later",
+                tx_hash,
+                e
+            );
+            MulticallOutcome::Pending
+        }
+    }
+}
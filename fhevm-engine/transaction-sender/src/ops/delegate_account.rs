@@ -0,0 +1,318 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{
+    dead_letter::{DeadLetterItem, DeadLetterQueue},
+    signer_pool::SignerPool,
+};
+
+use super::allow_handle::MultichainAcl;
+use super::common::{RetriedKey, RetriedRows};
+use super::TransactionOperation;
+use alloy::{
+    network::{Ethereum, TransactionBuilder},
+    primitives::{Address, U256},
+    providers::Provider,
+    rpc::types::TransactionRequest,
+    transports::{RpcError, TransportErrorKind},
+};
+use async_trait::async_trait;
+use fhevm_engine_common::tenant_keys::query_tenant_info;
+use sqlx::{Pool, Postgres};
+use tokio::task::JoinSet;
+use tracing::{error, info};
+use MultichainAcl::{DelegationAccounts, MultichainAclErrors};
+
+pub(crate) struct Key {
+    tenant_id: i32,
+    delegator: String,
+    delegatee: String,
+    contract_address: String,
+    is_revoked: bool,
+}
+
+impl RetriedKey for Key {
+    fn handles(&self) -> Vec<Vec<u8>> {
+        vec![]
+    }
+
+    fn dead_letter_item(&self) -> DeadLetterItem {
+        DeadLetterItem::Delegation {
+            tenant_id: self.tenant_id,
+            delegator: self.delegator.clone(),
+            delegatee: self.delegatee.clone(),
+            contract_address: self.contract_address.clone(),
+        }
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Key {{ tenant_id: {}, delegator: {}, delegatee: {}, contract_address: {}, is_revoked: {} }}",
+            self.tenant_id, self.delegator, self.delegatee, self.contract_address, self.is_revoked
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct DelegateAccountOperation<P: Provider<Ethereum> + Clone + 'static> {
+    multichain_acl_address: Address,
+    providers: SignerPool<P>,
+    conf: crate::ConfigSettings,
+    gas: Option<u64>,
+    db_pool: Pool<Postgres>,
+    dead_letters: DeadLetterQueue,
+}
+
+impl<P: Provider<Ethereum> + Clone + 'static> DelegateAccountOperation<P> {
+    pub fn new(
+        multichain_acl_address: Address,
+        providers: SignerPool<P>,
+        conf: crate::ConfigSettings,
+        gas: Option<u64>,
+        db_pool: Pool<Postgres>,
+    ) -> Self {
+        info!(
+            "Creating DelegateAccountOperation with gas: {} and MultichainAcl address: {}",
+            gas.unwrap_or(0),
+            multichain_acl_address,
+        );
+
+        Self {
+            multichain_acl_address,
+            providers,
+            conf,
+            gas,
+            dead_letters: DeadLetterQueue::new(db_pool.clone()),
+            db_pool,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Provider<Ethereum> + Clone + 'static> RetriedRows for DelegateAccountOperation<P> {
+    type Key = Key;
+
+    fn operation(&self) -> &str {
+        &self.conf.delegation_db_channel
+    }
+
+    fn conf(&self) -> &crate::ConfigSettings {
+        &self.conf
+    }
+
+    fn max_retries(&self) -> u32 {
+        self.conf.delegation_max_retries
+    }
+
+    fn dead_letters(&self) -> &DeadLetterQueue {
+        &self.dead_letters
+    }
+
+    fn already_sent_error(&self, err: &RpcError<TransportErrorKind>) -> Option<Address> {
+        let validate = true;
+        err.as_error_resp()
+            .and_then(|payload| payload.as_decoded_error::<MultichainAclErrors>(validate))
+            .and_then(|error| match error {
+                MultichainAclErrors::CoprocessorAlreadyDelegated(c) => Some(c.coprocessor),
+                _ => None,
+            })
+    }
+
+    /// Marks the delegation as sent, unless it has been revoked or re-delegated in the meantime.
+    async fn set_txn_is_sent(&self, key: &Key) -> anyhow::Result<()> {
+        sqlx::query!(
+            "UPDATE delegations
+                 SET txn_is_sent = true
+                 WHERE tenant_id = $1
+                 AND delegator = $2
+                 AND delegatee = $3
+                 AND contract_address = $4
+                 AND is_revoked = $5",
+            key.tenant_id,
+            key.delegator,
+            key.delegatee,
+            key.contract_address,
+            key.is_revoked,
+        )
+        .execute(&self.db_pool)
+        .await?;
+        self.dead_letters
+            .clear_errors(&key.dead_letter_item())
+            .await?;
+        Ok(())
+    }
+
+    async fn update_retry_count(
+        &self,
+        key: &Key,
+        err: &str,
+        transport: bool,
+    ) -> anyhow::Result<()> {
+        if transport {
+            sqlx::query!(
+                "UPDATE delegations
+                SET
+                txn_transport_retry_count = txn_transport_retry_count + 1,
+                txn_last_error = $1,
+                txn_last_error_at = NOW()
+                WHERE tenant_id = $2
+                AND delegator = $3
+                AND delegatee = $4
+                AND contract_address = $5",
+                err,
+                key.tenant_id,
+                key.delegator,
+                key.delegatee,
+                key.contract_address,
+            )
+            .execute(&self.db_pool)
+            .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE delegations
+                SET
+                txn_retry_count = txn_retry_count + 1,
+                txn_last_error = $1,
+                txn_last_error_at = NOW()
+                WHERE tenant_id = $2
+                AND delegator = $3
+                AND delegatee = $4
+                AND contract_address = $5",
+                err,
+                key.tenant_id,
+                key.delegator,
+                key.delegatee,
+                key.contract_address,
+            )
+            .execute(&self.db_pool)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<P> TransactionOperation<P> for DelegateAccountOperation<P>
+where
+    P: alloy::providers::Provider<Ethereum> + Clone + 'static,
+{
+    fn channel(&self) -> &str {
+        &self.conf.delegation_db_channel
+    }
+
+    async fn queue_depth(&self) -> anyhow::Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!"
+            FROM delegations
+            WHERE txn_is_sent = false
+            AND txn_retry_count < $1"#,
+            self.conf.delegation_max_retries as i32,
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        Ok(count)
+    }
+
+    async fn execute(&self) -> anyhow::Result<bool> {
+        let rows = sqlx::query!(
+            "
+            SELECT tenant_id, delegator, delegatee, contract_address, is_revoked, txn_retry_count, txn_transport_retry_count
+            FROM delegations
+            WHERE txn_is_sent = false
+            AND txn_retry_count < $1
+            LIMIT $2;
+            ",
+            self.conf.delegation_max_retries as i32,
+            self.conf.delegation_batch_limit as i32,
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let multichain_acl: MultichainAcl::MultichainAclInstance<(), &P> =
+            MultichainAcl::new(self.multichain_acl_address, self.providers.first().inner());
+
+        info!("Selected {} delegation rows to process", rows.len());
+
+        let maybe_has_more_work = rows.len() == self.conf.delegation_batch_limit as usize;
+
+        let mut join_set = JoinSet::new();
+        for row in rows.into_iter() {
+            let tenant = match query_tenant_info(&self.db_pool, row.tenant_id).await {
+                Ok(res) => res,
+                Err(_) => {
+                    error!("Failed to get chain_id for tenant id: {}", row.tenant_id);
+                    continue;
+                }
+            };
+
+            let key = Key {
+                tenant_id: row.tenant_id,
+                delegator: row.delegator,
+                delegatee: row.delegatee,
+                contract_address: row.contract_address,
+                is_revoked: row.is_revoked,
+            };
+
+            let (delegator, delegatee, contract_address) = match (
+                Address::from_str(&key.delegator),
+                Address::from_str(&key.delegatee),
+                Address::from_str(&key.contract_address),
+            ) {
+                (Ok(delegator), Ok(delegatee), Ok(contract_address)) => {
+                    (delegator, delegatee, contract_address)
+                }
+                _ => {
+                    error!("Invalid address in delegation {}", key);
+                    continue;
+                }
+            };
+
+            let chain_id = U256::from(tenant.chain_id);
+            let delegation_accounts = DelegationAccounts {
+                delegatorAddress: delegator,
+                delegatedAddress: delegatee,
+            };
+            let contract_addresses = vec![contract_address];
+
+            info!("Delegation: {}, chain_id: {}", key, chain_id);
+
+            let txn_request = if key.is_revoked {
+                multichain_acl
+                    .revokeDelegation(chain_id, delegation_accounts, contract_addresses)
+                    .into_transaction_request()
+            } else {
+                multichain_acl
+                    .delegateAccount(chain_id, delegation_accounts, contract_addresses)
+                    .into_transaction_request()
+            };
+            let txn_request: TransactionRequest = match &self.gas {
+                Some(gas_limit) => txn_request.with_gas_limit(*gas_limit),
+                None => txn_request,
+            };
+
+            let operation = self.clone();
+            join_set.spawn(async move {
+                operation
+                    .send_row_transaction(
+                        operation.providers.next_provider(),
+                        &key,
+                        txn_request,
+                        row.txn_retry_count,
+                        row.txn_transport_retry_count,
+                    )
+                    .await
+            });
+        }
+
+        while let Some(res) = join_set.join_next().await {
+            res??;
+        }
+
+        Ok(maybe_has_more_work)
+    }
+}
@@ -1,6 +1,13 @@
 use alloy::network::Ethereum;
 use async_trait::async_trait;
 
+/// Health of an operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OperationHealth {
+    Healthy,
+    Unhealthy(String),
+}
+
 #[async_trait]
 pub trait TransactionOperation<P>: Send + Sync
 where
@@ -9,10 +16,23 @@ where
     fn channel(&self) -> &str;
 
     async fn execute(&self) -> anyhow::Result<bool>;
+
+    /// Number of items waiting to be sent, excluding the ones that exhausted their retries.
+    async fn queue_depth(&self) -> anyhow::Result<i64>;
+
+    /// Unhealthy if its queue cannot be read.
+    async fn health(&self) -> OperationHealth {
+        match self.queue_depth().await {
+            Ok(_) => OperationHealth::Healthy,
+            Err(e) => OperationHealth::Unhealthy(e.to_string()),
+        }
+    }
 }
 
 pub(crate) mod add_ciphertext;
 pub(crate) mod allow_handle;
+pub(crate) mod delegate_account;
+pub(crate) mod registry;
 pub(crate) mod verify_proof;
 
 mod common;
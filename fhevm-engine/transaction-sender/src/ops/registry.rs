@@ -0,0 +1,388 @@
+use std::{collections::BTreeMap, path::Path, str::FromStr, sync::Arc};
+
+use alloy::{network::Ethereum, primitives::Address, providers::Provider};
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use fhevm_engine_common::signer::KeySigner;
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use super::{
+    add_ciphertext::AddCiphertextOperation, allow_handle::MultichainAclOperation,
+    delegate_account::DelegateAccountOperation, verify_proof::VerifyProofOperation,
+    TransactionOperation,
+};
+use crate::{
+    dead_letter::{ADD_CIPHERTEXT, ALLOW_HANDLE, DELEGATION, VERIFY_PROOF},
+    signer_pool::SignerPool,
+    ConfigSettings,
+};
+
+/// Configuration of the operations, by name.
+///
+/// ```json
+/// {
+///   "operations": {
+///     "verify_proof": { "batch_limit": 64, "max_retries": 5 },
+///     "add_ciphertext": { "enabled": false },
+///     "allow_handle": { "enabled": false },
+///     "delegation": { "enabled": false }
+///   }
+/// }
+/// ```
+///
+/// Omitted operations are enabled with the command-line settings.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct OperationsConfig {
+    #[serde(default)]
+    pub operations: BTreeMap<String, OperationConfig>,
+}
+
+impl OperationsConfig {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content).map_err(|e| anyhow!("invalid {}: {}", path.display(), e))
+    }
+}
+
+/// Configuration of one operation, the omitted fields are taken from `ConfigSettings`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct OperationConfig {
+    pub enabled: bool,
+    pub contract_address: Option<String>,
+    pub db_channel: Option<String>,
+    pub batch_limit: Option<u32>,
+    pub max_retries: Option<u32>,
+    pub multicall: Option<bool>,
+    pub gas: Option<u64>,
+    /// Settings of the operations registered outside this crate.
+    pub options: serde_json::Value,
+}
+
+impl Default for OperationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            contract_address: None,
+            db_channel: None,
+            batch_limit: None,
+            max_retries: None,
+            multicall: None,
+            gas: None,
+            options: serde_json::Value::Null,
+        }
+    }
+}
+
+impl OperationConfig {
+    /// The configured contract address or `default`.
+    pub fn contract_address(&self, default: Address) -> anyhow::Result<Address> {
+        self.contract_address
+            .as_deref()
+            .map_or(Ok(default), Address::from_str)
+            .map_err(|e| anyhow!("invalid contract address: {}", e))
+    }
+}
+
+/// What operations are built from.
+#[derive(Clone)]
+pub struct OperationContext<P: Provider<Ethereum> + Clone + 'static> {
+    pub input_verification_address: Address,
+    pub ciphertext_commits_address: Address,
+    pub multichain_acl_address: Address,
+    pub signer: KeySigner,
+    pub providers: SignerPool<P>,
+    pub conf: ConfigSettings,
+    pub gas: Option<u64>,
+    pub db_pool: Pool<Postgres>,
+}
+
+/// Builds an operation from the context and its configuration.
+#[async_trait]
+pub trait OperationBuilder<P>: Send + Sync
+where
+    P: Provider<Ethereum> + Clone + 'static,
+{
+    async fn build(
+        &self,
+        context: &OperationContext<P>,
+        config: &OperationConfig,
+    ) -> anyhow::Result<Arc<dyn TransactionOperation<P>>>;
+
+    /// The database channel the operation is notified on, if any.
+    fn db_channel(&self, _conf: &ConfigSettings, config: &OperationConfig) -> Option<String> {
+        config.db_channel.clone()
+    }
+
+    /// Applies the configuration of the operation to its settings, e.g. its `max_retries`, used by the operation and
+    /// by the reconciliation of its journaled transactions.
+    fn configure(&self, _conf: &mut ConfigSettings, _config: &OperationConfig) {}
+}
+
+/// Operations by name, built and run by the `TransactionSender` when enabled in the `OperationsConfig`.
+pub struct OperationRegistry<P: Provider<Ethereum> + Clone + 'static> {
+    builders: BTreeMap<String, Arc<dyn OperationBuilder<P>>>,
+    config: OperationsConfig,
+}
+
+impl<P: Provider<Ethereum> + Clone + 'static> OperationRegistry<P> {
+    /// A registry without operations.
+    pub fn empty() -> Self {
+        Self {
+            builders: BTreeMap::new(),
+            config: OperationsConfig::default(),
+        }
+    }
+
+    /// Registers an operation, replacing the one with the same name if any.
+    pub fn register(mut self, name: &str, builder: impl OperationBuilder<P> + 'static) -> Self {
+        self.builders.insert(name.to_owned(), Arc::new(builder));
+        self
+    }
+
+    pub fn with_config(mut self, config: OperationsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.builders.keys().map(String::as_str)
+    }
+
+    /// The database channels of the registered operations, by name.
+    pub fn db_channels(&self, conf: &ConfigSettings) -> BTreeMap<String, String> {
+        self.builders
+            .iter()
+            .filter_map(|(name, builder)| {
+                builder
+                    .db_channel(conf, &self.operation_config(name))
+                    .map(|channel| (name.clone(), channel))
+            })
+            .collect()
+    }
+
+    /// The settings with the configuration of each registered operation applied, see `OperationBuilder::configure`.
+    pub fn conf(&self, conf: &ConfigSettings) -> ConfigSettings {
+        let mut conf = conf.clone();
+        for (name, builder) in &self.builders {
+            builder.configure(&mut conf, &self.operation_config(name));
+        }
+        conf
+    }
+
+    fn operation_config(&self, name: &str) -> OperationConfig {
+        self.config
+            .operations
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Builds the enabled operations, failing if the configuration names an unknown one.
+    pub(crate) async fn build(
+        &self,
+        context: &OperationContext<P>,
+    ) -> anyhow::Result<Vec<(String, Arc<dyn TransactionOperation<P>>)>> {
+        if let Some(name) = self
+            .config
+            .operations
+            .keys()
+            .find(|name| !self.builders.contains_key(*name))
+        {
+            bail!("unknown operation {} in configuration", name);
+        }
+        let mut operations = vec![];
+        for (name, builder) in &self.builders {
+            let config = self.operation_config(name);
+            if !config.enabled {
+                continue;
+            }
+            let operation = builder
+                .build(context, &config)
+                .await
+                .map_err(|e| anyhow!("cannot build operation {}: {}", name, e))?;
+            operations.push((name.clone(), operation));
+        }
+        Ok(operations)
+    }
+}
+
+/// The operations of this crate.
+impl<P: Provider<Ethereum> + Clone + 'static> Default for OperationRegistry<P> {
+    fn default() -> Self {
+        Self::empty()
+            .register(VERIFY_PROOF, VerifyProofBuilder)
+            .register(ADD_CIPHERTEXT, AddCiphertextBuilder)
+            .register(ALLOW_HANDLE, AllowHandleBuilder)
+            .register(DELEGATION, DelegationBuilder)
+    }
+}
+
+struct VerifyProofBuilder;
+
+#[async_trait]
+impl<P: Provider<Ethereum> + Clone + 'static> OperationBuilder<P> for VerifyProofBuilder {
+    async fn build(
+        &self,
+        context: &OperationContext<P>,
+        config: &OperationConfig,
+    ) -> anyhow::Result<Arc<dyn TransactionOperation<P>>> {
+        let mut conf = context.conf.clone();
+        OperationBuilder::<P>::configure(self, &mut conf, config);
+        Ok(Arc::new(
+            VerifyProofOperation::new(
+                config.contract_address(context.input_verification_address)?,
+                context.providers.clone(),
+                context.signer.clone(),
+                conf,
+                config.gas.or(context.gas),
+                context.db_pool.clone(),
+            )
+            .await?,
+        ))
+    }
+
+    fn db_channel(&self, conf: &ConfigSettings, config: &OperationConfig) -> Option<String> {
+        Some(
+            config
+                .db_channel
+                .clone()
+                .unwrap_or_else(|| conf.verify_proof_resp_db_channel.clone()),
+        )
+    }
+
+    fn configure(&self, conf: &mut ConfigSettings, config: &OperationConfig) {
+        if let Some(db_channel) = &config.db_channel {
+            conf.verify_proof_resp_db_channel = db_channel.clone();
+        }
+        conf.verify_proof_resp_batch_limit = config
+            .batch_limit
+            .unwrap_or(conf.verify_proof_resp_batch_limit);
+        conf.verify_proof_resp_max_retries = config
+            .max_retries
+            .unwrap_or(conf.verify_proof_resp_max_retries);
+    }
+}
+
+struct AddCiphertextBuilder;
+
+#[async_trait]
+impl<P: Provider<Ethereum> + Clone + 'static> OperationBuilder<P> for AddCiphertextBuilder {
+    async fn build(
+        &self,
+        context: &OperationContext<P>,
+        config: &OperationConfig,
+    ) -> anyhow::Result<Arc<dyn TransactionOperation<P>>> {
+        let mut conf = context.conf.clone();
+        OperationBuilder::<P>::configure(self, &mut conf, config);
+        Ok(Arc::new(AddCiphertextOperation::new(
+            config.contract_address(context.ciphertext_commits_address)?,
+            context.providers.clone(),
+            conf,
+            config.gas.or(context.gas),
+            context.db_pool.clone(),
+        )))
+    }
+
+    fn db_channel(&self, conf: &ConfigSettings, config: &OperationConfig) -> Option<String> {
+        Some(
+            config
+                .db_channel
+                .clone()
+                .unwrap_or_else(|| conf.add_ciphertexts_db_channel.clone()),
+        )
+    }
+
+    fn configure(&self, conf: &mut ConfigSettings, config: &OperationConfig) {
+        if let Some(db_channel) = &config.db_channel {
+            conf.add_ciphertexts_db_channel = db_channel.clone();
+        }
+        conf.add_ciphertexts_batch_limit = config
+            .batch_limit
+            .unwrap_or(conf.add_ciphertexts_batch_limit);
+        conf.add_ciphertexts_max_retries = config
+            .max_retries
+            .unwrap_or(conf.add_ciphertexts_max_retries);
+        conf.add_ciphertexts_multicall = config.multicall.unwrap_or(conf.add_ciphertexts_multicall);
+    }
+}
+
+struct AllowHandleBuilder;
+
+#[async_trait]
+impl<P: Provider<Ethereum> + Clone + 'static> OperationBuilder<P> for AllowHandleBuilder {
+    async fn build(
+        &self,
+        context: &OperationContext<P>,
+        config: &OperationConfig,
+    ) -> anyhow::Result<Arc<dyn TransactionOperation<P>>> {
+        let mut conf = context.conf.clone();
+        OperationBuilder::<P>::configure(self, &mut conf, config);
+        Ok(Arc::new(MultichainAclOperation::new(
+            config.contract_address(context.multichain_acl_address)?,
+            context.providers.clone(),
+            conf,
+            config.gas.or(context.gas),
+            context.db_pool.clone(),
+        )))
+    }
+
+    fn db_channel(&self, conf: &ConfigSettings, config: &OperationConfig) -> Option<String> {
+        Some(
+            config
+                .db_channel
+                .clone()
+                .unwrap_or_else(|| conf.allow_handle_db_channel.clone()),
+        )
+    }
+
+    fn configure(&self, conf: &mut ConfigSettings, config: &OperationConfig) {
+        if let Some(db_channel) = &config.db_channel {
+            conf.allow_handle_db_channel = db_channel.clone();
+        }
+        conf.allow_handle_batch_limit = config.batch_limit.unwrap_or(conf.allow_handle_batch_limit);
+        conf.allow_handle_max_retries = config.max_retries.unwrap_or(conf.allow_handle_max_retries);
+        conf.allow_handle_multicall = config.multicall.unwrap_or(conf.allow_handle_multicall);
+    }
+}
+
+struct DelegationBuilder;
+
+#[async_trait]
+impl<P: Provider<Ethereum> + Clone + 'static> OperationBuilder<P> for DelegationBuilder {
+    async fn build(
+        &self,
+        context: &OperationContext<P>,
+        config: &OperationConfig,
+    ) -> anyhow::Result<Arc<dyn TransactionOperation<P>>> {
+        let mut conf = context.conf.clone();
+        OperationBuilder::<P>::configure(self, &mut conf, config);
+        Ok(Arc::new(DelegateAccountOperation::new(
+            config.contract_address(context.multichain_acl_address)?,
+            context.providers.clone(),
+            conf,
+            config.gas.or(context.gas),
+            context.db_pool.clone(),
+        )))
+    }
+
+    fn db_channel(&self, conf: &ConfigSettings, config: &OperationConfig) -> Option<String> {
+        Some(
+            config
+                .db_channel
+                .clone()
+                .unwrap_or_else(|| conf.delegation_db_channel.clone()),
+        )
+    }
+
+    fn configure(&self, conf: &mut ConfigSettings, config: &OperationConfig) {
+        if let Some(db_channel) = &config.db_channel {
+            conf.delegation_db_channel = db_channel.clone();
+        }
+        conf.delegation_batch_limit = config.batch_limit.unwrap_or(conf.delegation_batch_limit);
+        conf.delegation_max_retries = config.max_retries.unwrap_or(conf.delegation_max_retries);
+    }
+}
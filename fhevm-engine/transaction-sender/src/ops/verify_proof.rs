@@ -1,17 +1,16 @@
 use super::TransactionOperation;
-use crate::nonce_managed_provider::NonceManagedProvider;
+use crate::dead_letter::{DeadLetterItem, DeadLetterQueue};
+use crate::signer_pool::SignerPool;
 use alloy::network::TransactionBuilder;
 use alloy::primitives::{Address, U256};
 use alloy::providers::Provider;
 use alloy::rpc::types::TransactionRequest;
-use alloy::signers::local::PrivateKeySigner;
-use alloy::signers::SignerSync;
 use alloy::sol;
 use alloy::{network::Ethereum, primitives::FixedBytes, sol_types::SolStruct};
 use async_trait::async_trait;
+use fhevm_engine_common::signer::KeySigner;
 use sqlx::{Pool, Postgres};
 use std::convert::TryInto;
-use std::time::Duration;
 use tokio::task::JoinSet;
 use tracing::{debug, error, info, warn};
 use InputVerification::InputVerificationErrors;
@@ -34,31 +33,33 @@ sol!(
 #[derive(Clone)]
 pub(crate) struct VerifyProofOperation<P: Provider<Ethereum> + Clone + 'static> {
     input_verification_address: Address,
-    provider: NonceManagedProvider<P>,
-    signer: PrivateKeySigner,
+    providers: SignerPool<P>,
+    signer: KeySigner,
     conf: crate::ConfigSettings,
     gas: Option<u64>,
     gw_chain_id: u64,
     db_pool: Pool<Postgres>,
+    dead_letters: DeadLetterQueue,
 }
 
 impl<P: alloy::providers::Provider<Ethereum> + Clone + 'static> VerifyProofOperation<P> {
     pub(crate) async fn new(
         input_verification_address: Address,
-        provider: NonceManagedProvider<P>,
-        signer: PrivateKeySigner,
+        providers: SignerPool<P>,
+        signer: KeySigner,
         conf: crate::ConfigSettings,
         gas: Option<u64>,
         db_pool: Pool<Postgres>,
     ) -> anyhow::Result<Self> {
-        let gw_chain_id = provider.get_chain_id().await?;
+        let gw_chain_id = providers.first().get_chain_id().await?;
         Ok(Self {
             input_verification_address,
-            provider,
+            providers,
             signer,
             conf,
             gas,
             gw_chain_id,
+            dead_letters: DeadLetterQueue::new(db_pool.clone()),
             db_pool,
         })
     }
@@ -71,6 +72,9 @@ impl<P: alloy::providers::Provider<Ethereum> + Clone + 'static> VerifyProofOpera
         )
         .execute(&self.db_pool)
         .await?;
+        self.dead_letters
+            .clear_errors(&DeadLetterItem::VerifyProof { zk_proof_id })
+            .await?;
         Ok(())
     }
 
@@ -80,9 +84,7 @@ impl<P: alloy::providers::Provider<Ethereum> + Clone + 'static> VerifyProofOpera
         current_retry_count: i32,
         error: &str,
     ) -> anyhow::Result<()> {
-        if current_retry_count == (self.conf.verify_proof_resp_max_retries as i32) - 1 {
-            error!("Max retries reached for proof with ID {}", zk_proof_id);
-        }
+        let item = DeadLetterItem::VerifyProof { zk_proof_id };
         debug!("Updating retry count of proof with ID {}", zk_proof_id);
         sqlx::query!(
             "UPDATE verify_proofs
@@ -96,6 +98,17 @@ impl<P: alloy::providers::Provider<Ethereum> + Clone + 'static> VerifyProofOpera
         )
         .execute(&self.db_pool)
         .await?;
+        self.dead_letters.record_error(&item, error, false).await?;
+        if current_retry_count == (self.conf.verify_proof_resp_max_retries as i32) - 1 {
+            if self.conf.verify_proof_remove_after_max_retries {
+                // Moved to dead letters when removed.
+                error!("Max retries reached for proof with ID {}", zk_proof_id);
+            } else {
+                self.dead_letters
+                    .add(&item, current_retry_count + 1, error)
+                    .await?;
+            }
+        }
         Ok(())
     }
 
@@ -104,23 +117,28 @@ impl<P: alloy::providers::Provider<Ethereum> + Clone + 'static> VerifyProofOpera
             "Removing proof with retry count >= {}",
             self.conf.verify_proof_resp_max_retries
         );
-        sqlx::query!(
-            "DELETE FROM verify_proofs WHERE retry_count >= $1",
-            self.conf.verify_proof_resp_max_retries as i64
-        )
-        .execute(&self.db_pool)
-        .await?;
+        self.dead_letters
+            .remove_exhausted_proofs(self.conf.verify_proof_resp_max_retries)
+            .await?;
         Ok(())
     }
 
     async fn process_proof(
         &self,
         txn_request: (i64, impl Into<TransactionRequest>),
+        handles: Vec<Vec<u8>>,
         current_retry_count: i32,
     ) -> anyhow::Result<()> {
         info!("Processing proof with proof ID {}", txn_request.0);
+        let item = DeadLetterItem::VerifyProof {
+            zk_proof_id: txn_request.0,
+        };
         let txn_req = txn_request.1.into();
-        let transaction = match self.provider.send_transaction(txn_req.clone()).await {
+        let provider = self.providers.next_provider();
+        let transaction = match provider
+            .send_transaction(self.channel(), &handles, &[item], txn_req.clone())
+            .await
+        {
             Ok(txn) => txn,
             Err(e) => {
                 if let Some(InputVerificationErrors::CoprocessorSignerAlreadyVerified(_)) = e
@@ -150,12 +168,8 @@ impl<P: alloy::providers::Provider<Ethereum> + Clone + 'static> VerifyProofOpera
             }
         };
 
-        let receipt = match transaction
-            .with_timeout(Some(Duration::from_secs(
-                self.conf.txn_receipt_timeout_secs as u64,
-            )))
-            .with_required_confirmations(self.conf.required_txn_confirmations as u64)
-            .get_receipt()
+        let receipt = match provider
+            .get_receipt(self.channel(), transaction, &self.conf)
             .await
         {
             Ok(receipt) => receipt,
@@ -205,16 +219,30 @@ where
         &self.conf.verify_proof_resp_db_channel
     }
 
+    async fn queue_depth(&self) -> anyhow::Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!"
+            FROM verify_proofs
+            WHERE verified IS NOT NULL AND NOT orphaned AND retry_count < $1"#,
+            self.conf.verify_proof_resp_max_retries as i32,
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        Ok(count)
+    }
+
     async fn execute(&self) -> anyhow::Result<bool> {
-        let input_verification =
-            InputVerification::new(self.input_verification_address, self.provider.inner());
+        let input_verification = InputVerification::new(
+            self.input_verification_address,
+            self.providers.first().inner(),
+        );
         if self.conf.verify_proof_remove_after_max_retries {
             self.remove_proofs_by_retry_count().await?;
         }
         let rows = sqlx::query!(
             "SELECT zk_proof_id, chain_id, contract_address, user_address, handles, verified, retry_count
              FROM verify_proofs
-             WHERE verified IS NOT NULL AND retry_count < $1
+             WHERE verified IS NOT NULL AND NOT orphaned AND retry_count < $1
              ORDER BY zk_proof_id
              LIMIT $2",
             self.conf.verify_proof_resp_max_retries as i64,
@@ -226,6 +254,7 @@ where
         let maybe_has_more_work = rows.len() == self.conf.verify_proof_resp_batch_limit as usize;
         let mut join_set = JoinSet::new();
         for row in rows.into_iter() {
+            let mut journal_handles = vec![];
             let txn_request = match row.verified {
                 Some(true) => {
                     info!("Processing verified proof with ID {}", row.zk_proof_id);
@@ -247,6 +276,7 @@ where
                             FixedBytes(array)
                         })
                         .collect();
+                    journal_handles = handles.iter().map(|h| h.to_vec()).collect();
                     let domain = alloy::sol_types::eip712_domain! {
                         name: "InputVerification",
                         version: "1",
@@ -263,10 +293,16 @@ where
                         contractChainId: U256::from(row.chain_id),
                     }
                     .eip712_signing_hash(&domain);
-                    let signature = self
-                        .signer
-                        .sign_hash_sync(&signing_hash)
-                        .expect("signing failed");
+                    let signature = match self.signer.sign_hash(&signing_hash).await {
+                        Ok(signature) => signature,
+                        Err(e) => {
+                            error!(
+                                "Signing failed for proof with ID {}: {}",
+                                row.zk_proof_id, e
+                            );
+                            continue;
+                        }
+                    };
 
                     if let Some(gas) = self.gas {
                         (
@@ -322,8 +358,11 @@ where
             };
 
             let self_clone = self.clone();
-            join_set
-                .spawn(async move { self_clone.process_proof(txn_request, row.retry_count).await });
+            join_set.spawn(async move {
+                self_clone
+                    .process_proof(txn_request, journal_handles, row.retry_count)
+                    .await
+            });
         }
         while let Some(res) = join_set.join_next().await {
             res??;
@@ -0,0 +1,115 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use alloy::{network::Ethereum, primitives::U256, providers::Provider};
+use tracing::{error, info};
+
+use crate::{metrics, nonce_managed_provider::NonceManagedProvider, ConfigSettings, REVIEW};
+
+struct Lane<P: Provider<Ethereum> + Clone + 'static> {
+    provider: NonceManagedProvider<P>,
+    low_balance: AtomicBool,
+}
+
+/// Transaction senders, each with its own nonce sequence. Items are assigned to them in a round-robin fashion,
+/// skipping the ones with a low balance unless all of them are.
+#[derive(Clone)]
+pub struct SignerPool<P: Provider<Ethereum> + Clone + 'static> {
+    lanes: Arc<Vec<Lane<P>>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl<P: Provider<Ethereum> + Clone + 'static> SignerPool<P> {
+    /// Panics if no provider is given.
+    pub fn new(providers: Vec<NonceManagedProvider<P>>) -> Self {
+        assert!(!providers.is_empty(), "At least one provider is required");
+        Self {
+            lanes: Arc::new(
+                providers
+                    .into_iter()
+                    .map(|provider| Lane {
+                        provider,
+                        low_balance: AtomicBool::new(false),
+                    })
+                    .collect(),
+            ),
+            next: Default::default(),
+        }
+    }
+
+    /// Applies `f` to all the providers.
+    pub(crate) fn map_providers(
+        self,
+        f: impl Fn(NonceManagedProvider<P>) -> NonceManagedProvider<P>,
+    ) -> Self {
+        Self::new(self.providers().map(|p| f(p.clone())).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.lanes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lanes.is_empty()
+    }
+
+    /// The provider to read from the Gateway or build contract calls.
+    pub fn first(&self) -> &NonceManagedProvider<P> {
+        &self.lanes[0].provider
+    }
+
+    pub fn providers(&self) -> impl Iterator<Item = &NonceManagedProvider<P>> {
+        self.lanes.iter().map(|lane| &lane.provider)
+    }
+
+    /// The provider to send the next transaction with.
+    pub fn next_provider(&self) -> &NonceManagedProvider<P> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let len = self.lanes.len();
+        let lane = (0..len)
+            .map(|i| &self.lanes[(start + i) % len])
+            .find(|lane| !lane.low_balance.load(Ordering::Relaxed))
+            .unwrap_or(&self.lanes[start % len]);
+        &lane.provider
+    }
+
+    /// Checks the balance of each signer, alerting and skipping the ones below `low_balance_threshold_wei`. The balances
+    /// are exported as metrics.
+    pub async fn check_balances(&self, conf: &ConfigSettings) {
+        let threshold = U256::from(conf.low_balance_threshold_wei);
+        for lane in self.lanes.iter() {
+            let Some(address) = lane.provider.signer_address() else {
+                continue;
+            };
+            let balance = match lane.provider.get_balance(address).await {
+                Ok(balance) => balance,
+                Err(e) => {
+                    error!("Getting balance of signer {} failed: {}", address, e);
+                    continue;
+                }
+            };
+            let low_balance = balance < threshold;
+            if low_balance {
+                error!(
+                    action = REVIEW,
+                    "Balance of signer {} is low: {} wei, threshold: {} wei",
+                    address,
+                    balance,
+                    threshold
+                );
+            } else {
+                info!("Balance of signer {}: {} wei", address, balance);
+            }
+            metrics::set_signer_balance(&address.to_string(), f64::from(balance), low_balance);
+            lane.low_balance.store(low_balance, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<P: Provider<Ethereum> + Clone + 'static> From<NonceManagedProvider<P>> for SignerPool<P> {
+    fn from(provider: NonceManagedProvider<P>) -> Self {
+        Self::new(vec![provider])
+    }
+}
@@ -1,6 +1,5 @@
-use alloy::{
-    network::Ethereum, primitives::Address, providers::Provider, signers::local::PrivateKeySigner,
-};
+use alloy::{network::Ethereum, primitives::Address, providers::Provider};
+use fhevm_engine_common::signer::KeySigner;
 use futures_util::FutureExt;
 use sqlx::{postgres::PgListener, Pool, Postgres};
 use std::{sync::Arc, time::Duration};
@@ -8,13 +7,32 @@ use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 
-use crate::{nonce_managed_provider::NonceManagedProvider, ops, ConfigSettings};
+use crate::{
+    dead_letter::DeadLetterQueue,
+    journal::TransactionJournal,
+    metrics,
+    ops::{
+        registry::{OperationContext, OperationRegistry},
+        OperationHealth, TransactionOperation,
+    },
+    signer_pool::SignerPool,
+    ConfigSettings,
+};
+
+/// Health and queue depth of an operation.
+#[derive(Clone, Debug)]
+pub struct OperationStatus {
+    pub name: String,
+    pub health: OperationHealth,
+    pub queue_depth: Option<i64>,
+}
 
 #[derive(Clone)]
 pub struct TransactionSender<P: Provider<Ethereum> + Clone + 'static> {
     cancel_token: CancellationToken,
     conf: ConfigSettings,
-    operations: Vec<Arc<dyn ops::TransactionOperation<P>>>,
+    operations: Vec<(String, Arc<dyn TransactionOperation<P>>)>,
+    providers: SignerPool<P>,
     input_verification_address: Address,
     ciphertext_commits_address: Address,
     multichain_acl_address: Address,
@@ -22,53 +40,83 @@ pub struct TransactionSender<P: Provider<Ethereum> + Clone + 'static> {
 }
 
 impl<P: Provider<Ethereum> + Clone + 'static> TransactionSender<P> {
+    /// Runs all the operations of this crate.
     #[allow(clippy::too_many_arguments)]
     pub async fn new(
         input_verification_address: Address,
         ciphertext_commits_address: Address,
         multichain_acl_address: Address,
-        signer: PrivateKeySigner,
-        provider: NonceManagedProvider<P>,
+        signer: impl Into<KeySigner>,
+        providers: impl Into<SignerPool<P>>,
+        cancel_token: CancellationToken,
+        conf: ConfigSettings,
+        gas: Option<u64>,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_registry(
+            input_verification_address,
+            ciphertext_commits_address,
+            multichain_acl_address,
+            signer,
+            providers,
+            cancel_token,
+            conf,
+            gas,
+            OperationRegistry::default(),
+        )
+        .await
+    }
+
+    /// Runs the operations of the registry enabled in its configuration.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new_with_registry(
+        input_verification_address: Address,
+        ciphertext_commits_address: Address,
+        multichain_acl_address: Address,
+        signer: impl Into<KeySigner>,
+        providers: impl Into<SignerPool<P>>,
         cancel_token: CancellationToken,
         conf: ConfigSettings,
         gas: Option<u64>,
+        registry: OperationRegistry<P>,
     ) -> anyhow::Result<Self> {
         let db_pool = sqlx::postgres::PgPoolOptions::new()
             .max_connections(conf.database_pool_size)
             .connect(&conf.database_url)
             .await?;
+        let signer = signer.into();
+        let journal = TransactionJournal::new(db_pool.clone());
+        let providers = providers.into().map_providers(|provider| {
+            provider
+                .with_fee_strategy(conf.fee_strategy.clone())
+                .with_journal(journal.clone())
+        });
+        // The journaled transactions are reconciled with the settings of their operation, e.g. its max retries.
+        let operations_conf = registry.conf(&conf);
+        for provider in providers.providers() {
+            provider.reconcile(&operations_conf).await?;
+        }
 
-        let operations: Vec<Arc<dyn ops::TransactionOperation<P>>> = vec![
-            Arc::new(
-                ops::verify_proof::VerifyProofOperation::new(
-                    input_verification_address,
-                    provider.clone(),
-                    signer.clone(),
-                    conf.clone(),
-                    gas,
-                    db_pool.clone(),
-                )
-                .await?,
-            ),
-            Arc::new(ops::add_ciphertext::AddCiphertextOperation::new(
+        let operations = registry
+            .build(&OperationContext {
+                input_verification_address,
                 ciphertext_commits_address,
-                provider.clone(),
-                conf.clone(),
-                gas,
-                db_pool.clone(),
-            )),
-            Arc::new(ops::allow_handle::MultichainAclOperation::new(
                 multichain_acl_address,
-                provider.clone(),
-                conf.clone(),
+                signer,
+                providers: providers.clone(),
+                conf: conf.clone(),
                 gas,
-                db_pool.clone(),
-            )),
-        ];
+                db_pool: db_pool.clone(),
+            })
+            .await?;
+        info!(
+            "Enabled operations: {:?}",
+            operations.iter().map(|(name, _)| name).collect::<Vec<_>>()
+        );
         Ok(Self {
             cancel_token,
             conf,
             operations,
+            providers,
             input_verification_address,
             ciphertext_commits_address,
             multichain_acl_address,
@@ -82,7 +130,58 @@ impl<P: Provider<Ethereum> + Clone + 'static> TransactionSender<P> {
 
         let mut join_set = JoinSet::new();
 
-        for op in self.operations.clone() {
+        join_set.spawn({
+            let sender = self.clone();
+            let interval = Duration::from_secs(self.conf.balance_check_interval_secs.into());
+            async move {
+                loop {
+                    sender.providers.check_balances(&sender.conf).await;
+                    tokio::select! {
+                        _ = sender.cancel_token.cancelled() => break,
+                        _ = tokio::time::sleep(interval) => {}
+                    }
+                }
+                Ok::<(), anyhow::Error>(())
+            }
+        });
+
+        join_set.spawn({
+            let sender = self.clone();
+            let dead_letters = DeadLetterQueue::new(self.db_pool.clone());
+            let interval = Duration::from_secs(self.conf.dead_letter_metrics_interval_secs.into());
+            async move {
+                loop {
+                    if let Err(e) = dead_letters.refresh_metrics().await {
+                        error!("Refreshing dead letter metrics failed: {}", e);
+                    }
+                    match dead_letters
+                        .prune_errors(sender.conf.errors_retention_secs)
+                        .await
+                    {
+                        Ok(pruned) if pruned > 0 => info!("Pruned {} old errors", pruned),
+                        Ok(_) => {}
+                        Err(e) => error!("Pruning old errors failed: {}", e),
+                    }
+                    for status in sender.operations_status().await {
+                        if let OperationHealth::Unhealthy(reason) = &status.health {
+                            error!("Operation {} is unhealthy: {}", status.name, reason);
+                        }
+                        metrics::set_operation_status(
+                            &status.name,
+                            status.health == OperationHealth::Healthy,
+                            status.queue_depth,
+                        );
+                    }
+                    tokio::select! {
+                        _ = sender.cancel_token.cancelled() => break,
+                        _ = tokio::time::sleep(interval) => {}
+                    }
+                }
+                Ok::<(), anyhow::Error>(())
+            }
+        });
+
+        for (_, op) in self.operations.clone() {
             let op_channel = op.channel().to_owned();
             let token = self.cancel_token.clone();
             let db_polling_interval_secs = self.conf.db_polling_interval_secs;
@@ -162,6 +261,19 @@ impl<P: Provider<Ethereum> + Clone + 'static> TransactionSender<P> {
         Ok(())
     }
 
+    /// Health and queue depth of the enabled operations.
+    pub async fn operations_status(&self) -> Vec<OperationStatus> {
+        let mut statuses = vec![];
+        for (name, op) in &self.operations {
+            statuses.push(OperationStatus {
+                name: name.clone(),
+                health: op.health().await,
+                queue_depth: op.queue_depth().await.ok(),
+            });
+        }
+        statuses
+    }
+
     fn reset_sleep_duration(&self, sleep_duration: &mut u64) {
         *sleep_duration = self.conf.error_sleep_initial_secs as u64;
     }
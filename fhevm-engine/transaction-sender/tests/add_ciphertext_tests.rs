@@ -1,6 +1,7 @@
 mod common;
 
-use alloy::providers::{ProviderBuilder, WsConnect};
+use alloy::network::EthereumWallet;
+use alloy::providers::{Provider, ProviderBuilder, WsConnect};
 use alloy::signers::local::PrivateKeySigner;
 use common::{CiphertextCommits, TestEnvironment};
 
@@ -11,7 +12,8 @@ use std::time::Duration;
 use test_harness::db_utils::insert_random_tenant;
 use tokio::time::sleep;
 use transaction_sender::{
-    ConfigSettings, FillersWithoutNonceManagement, NonceManagedProvider, TransactionSender,
+    ConfigSettings, FillersWithoutNonceManagement, NonceManagedProvider, SignerPool,
+    TransactionSender,
 };
 
 #[tokio::test]
@@ -106,6 +108,18 @@ async fn add_ciphertext_digests() -> anyhow::Result<()> {
         "Expected a new transaction to be sent"
     );
 
+    // Verify that the transaction has been journaled as mined.
+    let journaled = sqlx::query!(
+        "SELECT operation, handles, status, nonce
+         FROM gw_transactions",
+    )
+    .fetch_one(&env.db_pool)
+    .await?;
+    assert_eq!(journaled.operation, env.conf.add_ciphertexts_db_channel);
+    assert_eq!(journaled.handles, vec![handle.to_vec()]);
+    assert_eq!(journaled.status, "mined");
+    assert_eq!(journaled.nonce as u64, initial_tx_count);
+
     env.cancel_token.cancel();
     run_handle.await??;
     Ok(())
@@ -480,6 +494,247 @@ async fn retry_mechanism() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+#[serial(db)]
+async fn add_ciphertext_digests_multicall() -> anyhow::Result<()> {
+    let conf = ConfigSettings {
+        add_ciphertexts_multicall: true,
+        ..Default::default()
+    };
+    let env = TestEnvironment::new_with_config(conf).await?;
+    let provider_deploy = ProviderBuilder::new()
+        .wallet(env.wallet.clone())
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+
+    let already_added_revert = false;
+    let ciphertext_commits =
+        CiphertextCommits::deploy(&provider_deploy, already_added_revert).await?;
+    let txn_sender = TransactionSender::new(
+        PrivateKeySigner::random().address(),
+        *ciphertext_commits.address(),
+        PrivateKeySigner::random().address(),
+        env.signer.clone(),
+        provider.clone(),
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+    )
+    .await?;
+
+    let tenant_id = insert_random_tenant(&env.db_pool).await?;
+    let initial_tx_count = provider.get_transaction_count(env.signer.address()).await?;
+
+    // Insert the digests before starting, so they are selected together.
+    let handles: Vec<[u8; 32]> = (0..3).map(|_| random::<[u8; 32]>()).collect();
+    for handle in &handles {
+        insert_ciphertext_digest(
+            &env.db_pool,
+            tenant_id,
+            handle,
+            &random::<[u8; 32]>(),
+            &random::<[u8; 32]>(),
+            0,
+        )
+        .await?;
+    }
+
+    let run_handle = tokio::spawn(async move { txn_sender.run().await });
+
+    // Make sure all the digests were tagged as sent.
+    loop {
+        let sent = sqlx::query_scalar!(
+            "SELECT COUNT(*)
+             FROM ciphertext_digest
+             WHERE tenant_id = $1 AND txn_is_sent = true",
+            tenant_id,
+        )
+        .fetch_one(&env.db_pool)
+        .await?;
+        if sent == Some(handles.len() as i64) {
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    sqlx::query!(
+        "
+        delete from tenants where tenant_id = $1",
+        tenant_id
+    )
+    .execute(&env.db_pool)
+    .await?;
+
+    // Verify that a single transaction has been sent.
+    let tx_count = provider.get_transaction_count(env.signer.address()).await?;
+    assert_eq!(
+        tx_count,
+        initial_tx_count + 1,
+        "Expected a single multicall transaction to be sent"
+    );
+
+    env.cancel_token.cancel();
+    run_handle.await??;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn add_ciphertext_digests_signer_pool() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider_deploy = ProviderBuilder::new()
+        .wallet(env.wallet.clone())
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let other_wallet = EthereumWallet::new(env.other_signer());
+    let mut providers = vec![];
+    for wallet in [env.wallet.clone(), other_wallet] {
+        providers.push(NonceManagedProvider::new(
+            ProviderBuilder::default()
+                .filler(FillersWithoutNonceManagement::default())
+                .wallet(wallet.clone())
+                .on_ws(WsConnect::new(env.ws_endpoint_url()))
+                .await?,
+            Some(wallet.default_signer().address()),
+        ));
+    }
+    let signer_pool = SignerPool::new(providers.clone());
+
+    let already_added_revert = false;
+    let ciphertext_commits =
+        CiphertextCommits::deploy(&provider_deploy, already_added_revert).await?;
+    let txn_sender = TransactionSender::new(
+        PrivateKeySigner::random().address(),
+        *ciphertext_commits.address(),
+        PrivateKeySigner::random().address(),
+        env.signer.clone(),
+        signer_pool,
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+    )
+    .await?;
+
+    let tenant_id = insert_random_tenant(&env.db_pool).await?;
+    let mut initial_tx_counts = vec![];
+    for provider in &providers {
+        initial_tx_counts.push(
+            provider
+                .get_transaction_count(provider.signer_address().unwrap())
+                .await?,
+        );
+    }
+
+    // Insert the digests before starting, so they are selected together.
+    for _ in 0..2 {
+        insert_ciphertext_digest(
+            &env.db_pool,
+            tenant_id,
+            &random::<[u8; 32]>(),
+            &random::<[u8; 32]>(),
+            &random::<[u8; 32]>(),
+            0,
+        )
+        .await?;
+    }
+
+    let run_handle = tokio::spawn(async move { txn_sender.run().await });
+
+    // Make sure all the digests were tagged as sent.
+    loop {
+        let sent = sqlx::query_scalar!(
+            "SELECT COUNT(*)
+             FROM ciphertext_digest
+             WHERE tenant_id = $1 AND txn_is_sent = true",
+            tenant_id,
+        )
+        .fetch_one(&env.db_pool)
+        .await?;
+        if sent == Some(2) {
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    sqlx::query!(
+        "
+        delete from tenants where tenant_id = $1",
+        tenant_id
+    )
+    .execute(&env.db_pool)
+    .await?;
+
+    // Verify that each signer sent one transaction.
+    for (provider, initial_tx_count) in providers.iter().zip(initial_tx_counts) {
+        let tx_count = provider
+            .get_transaction_count(provider.signer_address().unwrap())
+            .await?;
+        assert_eq!(
+            tx_count,
+            initial_tx_count + 1,
+            "Expected a new transaction from each signer"
+        );
+    }
+
+    env.cancel_token.cancel();
+    run_handle.await??;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn signer_balances_are_exported() -> anyhow::Result<()> {
+    let conf = ConfigSettings {
+        low_balance_threshold_wei: u128::MAX,
+        ..Default::default()
+    };
+    let env = TestEnvironment::new_with_config(conf).await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+    let signer = env.signer.address().to_string();
+    let balance = provider.get_balance(env.signer.address()).await?;
+    let signer_pool = SignerPool::new(vec![provider]);
+
+    signer_pool.check_balances(&env.conf).await;
+
+    let signer_metric = |name: &str| {
+        prometheus::gather()
+            .into_iter()
+            .find(|family| family.name() == name)
+            .and_then(|family| {
+                family.get_metric().iter().find_map(|metric| {
+                    metric
+                        .get_label()
+                        .iter()
+                        .any(|label| label.name() == "signer" && label.value() == signer)
+                        .then(|| metric.get_gauge().value())
+                })
+            })
+    };
+    assert_eq!(
+        signer_metric("transaction_sender_signer_balance_wei"),
+        Some(f64::from(balance))
+    );
+    assert_eq!(
+        signer_metric("transaction_sender_signer_low_balance"),
+        Some(1.0),
+        "Expected the signer to be below the threshold"
+    );
+    Ok(())
+}
+
 async fn insert_ciphertext_digest(
     pool: &PgPool,
     tenant_id: i32,
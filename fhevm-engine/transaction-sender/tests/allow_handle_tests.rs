@@ -226,6 +226,100 @@ async fn retry_on_transport_error() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+#[serial(db)]
+async fn allow_handles_multicall() -> anyhow::Result<()> {
+    let conf = ConfigSettings {
+        allow_handle_multicall: true,
+        ..Default::default()
+    };
+    let env = TestEnvironment::new_with_config(conf).await?;
+    let provider_deploy = ProviderBuilder::new()
+        .wallet(env.wallet.clone())
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+    let already_allowed_revert = false;
+    let multichain_acl = MultichainAcl::deploy(&provider_deploy, already_allowed_revert).await?;
+
+    let txn_sender = TransactionSender::new(
+        PrivateKeySigner::random().address(),
+        PrivateKeySigner::random().address(),
+        *multichain_acl.address(),
+        env.signer.clone(),
+        provider.clone(),
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+    )
+    .await?;
+
+    let tenant_id = insert_random_tenant(&env.db_pool).await?;
+    let initial_tx_count = provider.get_transaction_count(env.signer.address()).await?;
+
+    // Insert the ACL entries before starting, so they are selected together.
+    let handles: Vec<[u8; 32]> = (0..3).map(|_| random::<[u8; 32]>()).collect();
+    for (i, handle) in handles.iter().enumerate() {
+        let event_type = if i % 2 == 0 {
+            AllowEvents::AllowedAccount
+        } else {
+            AllowEvents::AllowedForDecryption
+        };
+        insert_allowed_handle(
+            &env.db_pool,
+            tenant_id,
+            handle,
+            PrivateKeySigner::random().address(),
+            event_type,
+        )
+        .await?;
+    }
+
+    let run_handle = tokio::spawn(async move { txn_sender.run().await });
+
+    // Make sure all the ACL entries were tagged as sent.
+    loop {
+        let sent = sqlx::query_scalar!(
+            "SELECT COUNT(*)
+             FROM allowed_handles
+             WHERE tenant_id = $1 AND txn_is_sent = true",
+            tenant_id,
+        )
+        .fetch_one(&env.db_pool)
+        .await?;
+        if sent == Some(handles.len() as i64) {
+            break;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    sqlx::query!(
+        "
+        delete from tenants where tenant_id = $1",
+        tenant_id
+    )
+    .execute(&env.db_pool)
+    .await?;
+
+    // Verify that a single transaction has been sent.
+    let tx_count = provider.get_transaction_count(env.signer.address()).await?;
+    assert_eq!(
+        tx_count,
+        initial_tx_count + 1,
+        "Expected a single multicall transaction to be sent"
+    );
+
+    env.cancel_token.cancel();
+    run_handle.await??;
+    Ok(())
+}
+
 async fn insert_allowed_handle(
     pool: &PgPool,
     tenant_id: i32,
@@ -30,9 +30,13 @@ sol!(
 );
 
 pub struct TestEnvironment {
+    #[allow(dead_code)]
     pub signer: PrivateKeySigner,
+    #[allow(dead_code)]
     pub conf: ConfigSettings,
+    #[allow(dead_code)]
     pub cancel_token: CancellationToken,
+    #[allow(dead_code)]
     pub db_pool: Pool<Postgres>,
     #[allow(dead_code)]
     pub contract_address: Address,
@@ -63,7 +67,15 @@ impl TestEnvironment {
 
         Self::truncate_tables(
             &db_pool,
-            vec!["verify_proofs", "ciphertext_digest", "allowed_handles"],
+            vec![
+                "verify_proofs",
+                "ciphertext_digest",
+                "allowed_handles",
+                "delegations",
+                "gw_transactions",
+                "tx_sender_errors",
+                "tx_sender_dead_letters",
+            ],
         )
         .await?;
 
@@ -86,6 +98,12 @@ impl TestEnvironment {
         self.anvil.as_ref().unwrap().ws_endpoint_url()
     }
 
+    /// A funded signer other than `signer`.
+    #[allow(dead_code)]
+    pub fn other_signer(&self) -> PrivateKeySigner {
+        self.anvil.as_ref().unwrap().keys()[1].clone().into()
+    }
+
     #[allow(dead_code)]
     pub fn recreate_anvil(&mut self) -> anyhow::Result<()> {
         if let Some(old) = self.anvil.take() {
@@ -0,0 +1,243 @@
+use alloy::providers::{ProviderBuilder, RootProvider, WsConnect};
+use alloy::signers::local::PrivateKeySigner;
+use common::{CiphertextCommits, InputVerification, TestEnvironment};
+use rand::random;
+use serial_test::serial;
+use std::time::Duration;
+use tokio::time::sleep;
+use transaction_sender::{
+    DeadLetterItem, DeadLetterQueue, FillersWithoutNonceManagement, NonceManagedProvider,
+    OperationRegistry, TransactionSender, MAX_ERRORS_PER_ITEM,
+};
+
+mod common;
+
+#[test]
+fn dead_letter_item_key_roundtrip() {
+    let items = [
+        DeadLetterItem::VerifyProof { zk_proof_id: 42 },
+        DeadLetterItem::AddCiphertext {
+            handle: vec![1u8; 32],
+        },
+        DeadLetterItem::AllowHandle {
+            tenant_id: 1,
+            handle: vec![2u8; 32],
+            account_address: "".to_owned(),
+        },
+        DeadLetterItem::Delegation {
+            tenant_id: 1,
+            delegator: PrivateKeySigner::random().address().to_string(),
+            delegatee: PrivateKeySigner::random().address().to_string(),
+            contract_address: PrivateKeySigner::random().address().to_string(),
+        },
+    ];
+    for item in items {
+        assert_eq!(
+            DeadLetterItem::parse(item.operation(), &item.key()).unwrap(),
+            item
+        );
+    }
+    assert!(DeadLetterItem::parse("allow_handle", "1:zz:").is_err());
+    assert!(DeadLetterItem::parse("unknown", "1").is_err());
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn verify_proof_removed_entry_dead_lettered_and_resubmitted() -> anyhow::Result<()> {
+    let mut env = TestEnvironment::new().await?;
+    env.conf.verify_proof_remove_after_max_retries = true;
+    env.conf.verify_proof_resp_max_retries = 2;
+    let provider_deploy = ProviderBuilder::new()
+        .wallet(env.wallet.clone())
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+    let already_verified_revert = false;
+    let already_rejected_revert = false;
+    let other_revert = true;
+    let input_verification = InputVerification::deploy(
+        &provider_deploy,
+        already_verified_revert,
+        already_rejected_revert,
+        other_revert,
+    )
+    .await?;
+    let already_added_revert = false;
+    let ciphertext_commits =
+        CiphertextCommits::deploy(&provider_deploy, already_added_revert).await?;
+    let txn_sender = TransactionSender::new(
+        *input_verification.address(),
+        *ciphertext_commits.address(),
+        PrivateKeySigner::random().address(),
+        env.signer.clone(),
+        provider.clone(),
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+    )
+    .await?;
+
+    let proof_id: u32 = random();
+
+    let run_handle = tokio::spawn(async move { txn_sender.run().await });
+
+    // Insert a proof into the database and notify the sender.
+    sqlx::query!(
+        "WITH ins AS (
+            INSERT INTO verify_proofs (zk_proof_id, chain_id, contract_address, user_address, handles, verified)
+            VALUES ($1, $2, $3, $4, $5, true)
+        )
+        SELECT pg_notify($6, '')",
+        proof_id as i64,
+        42,
+        env.contract_address.to_string(),
+        env.user_address.to_string(),
+        &[1u8; 64],
+        env.conf.verify_proof_resp_db_channel
+    )
+    .execute(&env.db_pool)
+    .await?;
+
+    // Wait until the proof is moved to dead letters.
+    let dead_letters = DeadLetterQueue::new(env.db_pool.clone());
+    let dead_letter = loop {
+        if let Some(dead_letter) = dead_letters
+            .list(Some("verify_proof"), 10)
+            .await?
+            .into_iter()
+            .next()
+        {
+            break dead_letter;
+        }
+        sleep(Duration::from_millis(500)).await;
+    };
+
+    env.cancel_token.cancel();
+    run_handle.await??;
+
+    assert_eq!(
+        dead_letter.item,
+        DeadLetterItem::VerifyProof {
+            zk_proof_id: proof_id as i64
+        }
+    );
+    assert!(dead_letter.removed);
+    assert_eq!(dead_letter.retry_count, 2);
+    let errors = dead_letters.errors(&dead_letter.item).await?;
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|e| !e.is_transport));
+    let rows = sqlx::query!(
+        "SELECT zk_proof_id FROM verify_proofs WHERE zk_proof_id = $1",
+        proof_id as i64,
+    )
+    .fetch_all(&env.db_pool)
+    .await?;
+    assert!(rows.is_empty());
+
+    // Re-submit it, the proof is restored with no retries.
+    let db_channels = OperationRegistry::<RootProvider>::default().db_channels(&env.conf);
+    dead_letters.resubmit(dead_letter.id, &db_channels).await?;
+    let row = sqlx::query!(
+        "SELECT retry_count, verified, handles, user_address FROM verify_proofs WHERE zk_proof_id = $1",
+        proof_id as i64,
+    )
+    .fetch_one(&env.db_pool)
+    .await?;
+    assert_eq!(row.retry_count, 0);
+    assert_eq!(row.verified, Some(true));
+    assert_eq!(row.handles, Some(vec![1u8; 64]));
+    assert_eq!(row.user_address, env.user_address.to_string());
+    assert!(dead_letters.list(None, 10).await?.is_empty());
+    assert!(dead_letters
+        .resubmit(dead_letter.id, &db_channels)
+        .await
+        .is_err());
+
+    // Make sure the entry is removed at the end of the test.
+    sqlx::query("DELETE FROM verify_proofs WHERE zk_proof_id = $1")
+        .bind(proof_id as i64)
+        .execute(&env.db_pool)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn errors_capped_per_item() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let dead_letters = DeadLetterQueue::new(env.db_pool.clone());
+    let item = DeadLetterItem::AddCiphertext {
+        handle: random::<[u8; 32]>().to_vec(),
+    };
+    let other = DeadLetterItem::AddCiphertext {
+        handle: random::<[u8; 32]>().to_vec(),
+    };
+    dead_letters
+        .record_error(&other, "other error", false)
+        .await?;
+
+    for i in 0..MAX_ERRORS_PER_ITEM + 5 {
+        dead_letters
+            .record_error(&item, &format!("error {}", i), i % 2 == 0)
+            .await?;
+    }
+
+    // Only the last errors of the item are kept.
+    let errors = dead_letters.errors(&item).await?;
+    assert_eq!(errors.len(), MAX_ERRORS_PER_ITEM as usize);
+    assert_eq!(errors[0].error, "error 5");
+    assert_eq!(
+        errors.last().unwrap().error,
+        format!("error {}", MAX_ERRORS_PER_ITEM + 4)
+    );
+    assert!(errors
+        .windows(2)
+        .all(|w| w[0].created_at <= w[1].created_at));
+    assert_eq!(dead_letters.errors(&other).await?.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn errors_cleared_and_pruned() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let dead_letters = DeadLetterQueue::new(env.db_pool.clone());
+    let sent = DeadLetterItem::AddCiphertext {
+        handle: random::<[u8; 32]>().to_vec(),
+    };
+    let old = DeadLetterItem::AddCiphertext {
+        handle: random::<[u8; 32]>().to_vec(),
+    };
+    let recent = DeadLetterItem::AddCiphertext {
+        handle: random::<[u8; 32]>().to_vec(),
+    };
+    for item in [&sent, &old, &recent] {
+        dead_letters.record_error(item, "error", false).await?;
+    }
+
+    // The errors of a sent item are removed.
+    dead_letters.clear_errors(&sent).await?;
+    assert!(dead_letters.errors(&sent).await?.is_empty());
+    assert_eq!(dead_letters.errors(&old).await?.len(), 1);
+
+    // Only the errors older than the retention are pruned.
+    sqlx::query!(
+        "UPDATE tx_sender_errors SET created_at = NOW() - INTERVAL '2 hours'
+        WHERE operation = $1 AND item_key = $2",
+        old.operation(),
+        old.key(),
+    )
+    .execute(&env.db_pool)
+    .await?;
+    assert!(dead_letters.prune_errors(3600).await? >= 1);
+    assert!(dead_letters.errors(&old).await?.is_empty());
+    assert_eq!(dead_letters.errors(&recent).await?.len(), 1);
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use alloy::providers::ProviderBuilder;
+use alloy::signers::local::PrivateKeySigner;
+use alloy::{
+    primitives::{Address, U256},
+    providers::WsConnect,
+};
+use common::{MultichainAcl, TestEnvironment};
+
+use serial_test::serial;
+use sqlx::PgPool;
+use std::time::Duration;
+use test_harness::db_utils::insert_random_tenant;
+use tokio::time::sleep;
+use transaction_sender::{FillersWithoutNonceManagement, NonceManagedProvider, TransactionSender};
+
+mod common;
+
+#[tokio::test]
+#[serial(db)]
+async fn delegate_account() -> anyhow::Result<()> {
+    let already_delegated_revert = false;
+    delegation_call(false, already_delegated_revert).await
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn revoke_delegation() -> anyhow::Result<()> {
+    let already_delegated_revert = false;
+    delegation_call(true, already_delegated_revert).await
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn delegate_account_already_delegated() -> anyhow::Result<()> {
+    let already_delegated_revert = true;
+    delegation_call(false, already_delegated_revert).await
+}
+
+async fn delegation_call(is_revoked: bool, already_delegated_revert: bool) -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider_deploy = ProviderBuilder::new()
+        .wallet(env.wallet.clone())
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+    let multichain_acl = MultichainAcl::deploy(&provider_deploy, already_delegated_revert).await?;
+
+    let txn_sender = TransactionSender::new(
+        PrivateKeySigner::random().address(),
+        PrivateKeySigner::random().address(),
+        *multichain_acl.address(),
+        env.signer.clone(),
+        provider.clone(),
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+    )
+    .await?;
+
+    let run_handle = tokio::spawn(async move { txn_sender.run().await });
+
+    let tenant_id = insert_random_tenant(&env.db_pool).await?;
+
+    // Record initial transaction count.
+    let initial_tx_count = provider.get_transaction_count(env.signer.address()).await?;
+
+    let chain_id = sqlx::query_scalar!(
+        "SELECT chain_id FROM tenants WHERE tenant_id = $1",
+        tenant_id
+    )
+    .fetch_one(&env.db_pool)
+    .await?;
+
+    let delegator = PrivateKeySigner::random().address();
+    let delegatee = PrivateKeySigner::random().address();
+    insert_delegation(
+        &env.db_pool,
+        tenant_id,
+        delegator,
+        delegatee,
+        env.contract_address,
+        is_revoked,
+    )
+    .await?;
+
+    sqlx::query!(
+        "
+        SELECT pg_notify($1, '')",
+        env.conf.delegation_db_channel
+    )
+    .execute(&env.db_pool)
+    .await?;
+
+    // Make sure the delegation was tagged as sent.
+    loop {
+        let rows = sqlx::query!(
+            "SELECT txn_is_sent
+             FROM delegations
+             WHERE delegator = $1",
+            delegator.to_string(),
+        )
+        .fetch_one(&env.db_pool)
+        .await?;
+        if rows.txn_is_sent {
+            break;
+        }
+
+        sleep(Duration::from_millis(500)).await;
+    }
+    sqlx::query!(
+        "
+        delete from tenants where tenant_id = $1",
+        tenant_id
+    )
+    .execute(&env.db_pool)
+    .await?;
+
+    // Verify that a transaction has been sent if not reverted during gas estimation.
+    if !already_delegated_revert {
+        let tx_count = provider.get_transaction_count(env.signer.address()).await?;
+        assert_eq!(
+            tx_count,
+            initial_tx_count + 1,
+            "Expected a new transaction to be sent"
+        );
+    }
+
+    // Verify that the right function has been called with the delegation.
+    let delegated = multichain_acl
+        .DelegateAccount_filter()
+        .from_block(0)
+        .query()
+        .await?;
+    let revoked = multichain_acl
+        .RevokeDelegation_filter()
+        .from_block(0)
+        .query()
+        .await?;
+    if already_delegated_revert {
+        assert!(delegated.is_empty() && revoked.is_empty());
+    } else {
+        let (event_chain_id, accounts, contract_addresses) =
+            match (is_revoked, delegated.as_slice(), revoked.as_slice()) {
+                (false, [(e, _)], []) => (e.chainId, &e.delegationAccounts, &e.contractAddresses),
+                (true, [], [(e, _)]) => (e.chainId, &e.delegationAccounts, &e.contractAddresses),
+                _ => panic!(
+                    "Expected one {} event, got {} DelegateAccount and {} RevokeDelegation",
+                    if is_revoked {
+                        "RevokeDelegation"
+                    } else {
+                        "DelegateAccount"
+                    },
+                    delegated.len(),
+                    revoked.len()
+                ),
+            };
+        assert_eq!(event_chain_id, U256::from(chain_id));
+        assert_eq!(accounts.delegatorAddress, delegator);
+        assert_eq!(accounts.delegatedAddress, delegatee);
+        assert_eq!(contract_addresses, &vec![env.contract_address]);
+    }
+
+    env.cancel_token.cancel();
+    run_handle.await??;
+
+    Ok(())
+}
+
+async fn insert_delegation(
+    pool: &PgPool,
+    tenant_id: i32,
+    delegator: Address,
+    delegatee: Address,
+    contract_address: Address,
+    is_revoked: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO delegations (tenant_id, delegator, delegatee, contract_address, is_revoked)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        tenant_id,
+        delegator.to_string(),
+        delegatee.to_string(),
+        contract_address.to_string(),
+        is_revoked,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
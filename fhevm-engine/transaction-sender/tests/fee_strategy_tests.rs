@@ -0,0 +1,205 @@
+mod common;
+
+use std::time::Duration;
+
+use alloy::consensus::Transaction;
+use alloy::network::TransactionBuilder;
+use alloy::primitives::U256;
+use alloy::providers::ext::AnvilApi;
+use alloy::providers::{Provider, ProviderBuilder, WsConnect};
+use alloy::rpc::types::TransactionRequest;
+use alloy::signers::local::PrivateKeySigner;
+use common::TestEnvironment;
+use serial_test::serial;
+use tokio::time::sleep;
+use transaction_sender::{
+    ConfigSettings, FeeStrategy, FillersWithoutNonceManagement, NonceManagedProvider,
+    TransactionJournal,
+};
+
+#[test]
+fn parse_fee_strategies() {
+    assert_eq!("provider".parse(), Ok(FeeStrategy::Provider));
+    assert_eq!(
+        "fixed:100:2".parse(),
+        Ok(FeeStrategy::Fixed {
+            max_fee_per_gas: 100,
+            max_priority_fee_per_gas: 2
+        })
+    );
+    assert_eq!(
+        "percentile:50".parse(),
+        Ok(FeeStrategy::Percentile {
+            reward_percentile: 50.0,
+            blocks: 10
+        })
+    );
+    assert!("percentile:150".parse::<FeeStrategy>().is_err());
+    assert!("capped:100".parse::<FeeStrategy>().is_err());
+}
+
+#[test]
+fn bump_fees() {
+    let tx = TransactionRequest {
+        max_fee_per_gas: Some(100),
+        max_priority_fee_per_gas: Some(10),
+        ..Default::default()
+    };
+    let bumped = FeeStrategy::Provider.bump(&tx, 20).unwrap();
+    assert_eq!(bumped.max_fee_per_gas, Some(120));
+    assert_eq!(bumped.max_priority_fee_per_gas, Some(12));
+
+    let capped = FeeStrategy::Capped {
+        max_fee_per_gas: 110,
+        max_priority_fee_per_gas: 20,
+    };
+    let bumped = capped.bump(&tx, 20).unwrap();
+    assert_eq!(bumped.max_fee_per_gas, Some(110));
+    assert!(capped.bump(&bumped, 20).is_none());
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn stalled_transaction_replaced_with_bumped_fees() -> anyhow::Result<()> {
+    let conf = ConfigSettings {
+        txn_receipt_timeout_secs: 2,
+        fee_bump_percent: 20,
+        max_fee_bumps: 3,
+        ..Default::default()
+    };
+    let env = TestEnvironment::new().await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    )
+    .with_journal(TransactionJournal::new(env.db_pool.clone()));
+
+    // Transactions stay in the pool until a block is mined explicitly.
+    provider.inner().anvil_set_auto_mine(false).await?;
+    let pending = provider
+        .send_transaction(
+            "test",
+            &[],
+            &[],
+            TransactionRequest::default()
+                .with_to(PrivateKeySigner::random().address())
+                .with_value(U256::from(1)),
+        )
+        .await?;
+    let stalled_tx_hash = *pending.tx_hash();
+    let stalled_tx = provider
+        .get_transaction_by_hash(stalled_tx_hash)
+        .await?
+        .unwrap();
+
+    // Mine after the first replacement, before the receipt timeout of the replacement.
+    let receipt = {
+        let provider = provider.clone();
+        let conf = conf.clone();
+        tokio::spawn(async move { provider.get_receipt("test", pending, &conf).await })
+    };
+    sleep(Duration::from_secs(3)).await;
+    provider.inner().anvil_mine(Some(1), None).await?;
+    let receipt = receipt.await??;
+    assert!(receipt.status());
+    assert_ne!(receipt.transaction_hash, stalled_tx_hash);
+    let mined_tx = provider
+        .get_transaction_by_hash(receipt.transaction_hash)
+        .await?
+        .unwrap();
+    assert_eq!(mined_tx.nonce(), stalled_tx.nonce());
+    assert!(mined_tx.max_fee_per_gas() > stalled_tx.max_fee_per_gas());
+
+    let statuses = sqlx::query!(
+        "SELECT tx_hash, status FROM gw_transactions WHERE operation = 'test' ORDER BY id"
+    )
+    .fetch_all(&env.db_pool)
+    .await?;
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses[0].tx_hash, stalled_tx_hash.to_vec());
+    assert_eq!(statuses[0].status, "replaced");
+    assert_eq!(statuses[1].tx_hash, receipt.transaction_hash.to_vec());
+    assert_eq!(statuses[1].status, "mined");
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn stuck_transaction_cancelled_after_max_fee_bumps() -> anyhow::Result<()> {
+    let conf = ConfigSettings {
+        txn_receipt_timeout_secs: 2,
+        fee_bump_percent: 20,
+        max_fee_bumps: 1,
+        ..Default::default()
+    };
+    let env = TestEnvironment::new().await?;
+    let signer_address = env.wallet.default_signer().address();
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(signer_address),
+    )
+    .with_journal(TransactionJournal::new(env.db_pool.clone()));
+
+    // Transactions stay in the pool until a block is mined explicitly.
+    provider.inner().anvil_set_auto_mine(false).await?;
+    let pending = provider
+        .send_transaction(
+            "test",
+            &[],
+            &[],
+            TransactionRequest::default()
+                .with_to(PrivateKeySigner::random().address())
+                .with_value(U256::from(1)),
+        )
+        .await?;
+    let stuck_tx = provider
+        .get_transaction_by_hash(*pending.tx_hash())
+        .await?
+        .unwrap();
+
+    // Mine after the cancellation, sent once the receipt timeout of the replacement expires.
+    let receipt = {
+        let provider = provider.clone();
+        let conf = conf.clone();
+        tokio::spawn(async move { provider.get_receipt("test", pending, &conf).await })
+    };
+    sleep(Duration::from_secs(5)).await;
+    provider.inner().anvil_mine(Some(1), None).await?;
+    assert!(receipt.await?.is_err());
+
+    // The nonce is used by a 0-value transfer to the signer.
+    let block = provider
+        .get_block_by_number(
+            alloy::eips::BlockNumberOrTag::Latest,
+            alloy::rpc::types::BlockTransactionsKind::Full,
+        )
+        .await?
+        .unwrap();
+    let cancel_tx = block
+        .transactions
+        .into_transactions()
+        .find(|tx| tx.nonce() == stuck_tx.nonce())
+        .expect("cancellation mined");
+    assert_eq!(cancel_tx.to(), Some(signer_address));
+    assert_eq!(cancel_tx.value(), U256::ZERO);
+
+    let statuses = sqlx::query!(
+        "SELECT status, error FROM gw_transactions WHERE operation = 'test' ORDER BY id"
+    )
+    .fetch_all(&env.db_pool)
+    .await?;
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses.iter().all(|s| s.status == "dropped"
+        && s.error
+            .as_deref()
+            .is_some_and(|e| e.starts_with("cancelled by"))));
+    Ok(())
+}
@@ -0,0 +1,275 @@
+mod common;
+
+use alloy::primitives::{FixedBytes, U256};
+use alloy::providers::{ProviderBuilder, WsConnect};
+use alloy::signers::local::PrivateKeySigner;
+use common::{CiphertextCommits, TestEnvironment};
+
+use rand::random;
+use serial_test::serial;
+use sqlx::PgPool;
+use test_harness::db_utils::insert_random_tenant;
+use transaction_sender::{
+    DeadLetterItem, FillersWithoutNonceManagement, NonceManagedProvider, TransactionSender,
+};
+
+#[tokio::test]
+#[serial(db)]
+async fn pending_transactions_reconciled_on_startup() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider_deploy = ProviderBuilder::new()
+        .wallet(env.wallet.clone())
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+
+    // The deployment uses nonce 0.
+    let already_added_revert = false;
+    let ciphertext_commits =
+        CiphertextCommits::deploy(&provider_deploy, already_added_revert).await?;
+
+    // A transaction journaled at nonce 0 before a crash, never mined.
+    let dropped_tx_hash = FixedBytes::<32>::from([42u8; 32]);
+    sqlx::query!(
+        "INSERT INTO gw_transactions (signer_address, nonce, tx_hash, operation, tx_request)
+         VALUES ($1, 0, $2, $3, '')",
+        env.signer.address().to_string(),
+        dropped_tx_hash.as_slice(),
+        env.conf.add_ciphertexts_db_channel,
+    )
+    .execute(&env.db_pool)
+    .await?;
+
+    let _txn_sender = TransactionSender::new(
+        PrivateKeySigner::random().address(),
+        *ciphertext_commits.address(),
+        PrivateKeySigner::random().address(),
+        env.signer.clone(),
+        provider.clone(),
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+    )
+    .await?;
+
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM gw_transactions WHERE tx_hash = $1",
+        dropped_tx_hash.as_slice(),
+    )
+    .fetch_one(&env.db_pool)
+    .await?;
+    assert_eq!(status, "dropped");
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn mined_and_replaced_transactions_reconciled_with_their_rows() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider_deploy = ProviderBuilder::new()
+        .wallet(env.wallet.clone())
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+
+    // The deployment uses nonce 0.
+    let already_added_revert = false;
+    let ciphertext_commits =
+        CiphertextCommits::deploy(&provider_deploy, already_added_revert).await?;
+    let tenant_id = insert_random_tenant(&env.db_pool).await?;
+    let handle = random::<[u8; 32]>().to_vec();
+    insert_ciphertext_digest(&env.db_pool, tenant_id, &handle).await?;
+
+    // Mined at nonce 1 while the sender was down, after replacing a stuck transaction.
+    let receipt = ciphertext_commits
+        .addCiphertextMaterial(
+            FixedBytes::from_slice(&handle),
+            U256::ZERO,
+            FixedBytes::from([1u8; 32]),
+            FixedBytes::from([2u8; 32]),
+        )
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    assert!(receipt.status());
+    let replaced_tx_hash = FixedBytes::<32>::from([43u8; 32]);
+    let item = DeadLetterItem::AddCiphertext {
+        handle: handle.clone(),
+    };
+    for tx_hash in [replaced_tx_hash, receipt.transaction_hash] {
+        journal_transaction(&env, 1, tx_hash, &item).await?;
+    }
+
+    let _txn_sender = TransactionSender::new(
+        PrivateKeySigner::random().address(),
+        *ciphertext_commits.address(),
+        PrivateKeySigner::random().address(),
+        env.signer.clone(),
+        provider.clone(),
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+    )
+    .await?;
+
+    assert_eq!(status(&env.db_pool, replaced_tx_hash).await?, "replaced");
+    assert_eq!(
+        status(&env.db_pool, receipt.transaction_hash).await?,
+        "mined"
+    );
+    let row = sqlx::query!(
+        "SELECT txn_is_sent, txn_retry_count FROM ciphertext_digest WHERE handle = $1",
+        handle,
+    )
+    .fetch_one(&env.db_pool)
+    .await?;
+    assert!(row.txn_is_sent);
+    assert_eq!(row.txn_retry_count, 0);
+    Ok(())
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn reverted_transaction_reconciled_with_its_rows() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider_deploy = ProviderBuilder::new()
+        .wallet(env.wallet.clone())
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+
+    let already_added_revert = true;
+    let ciphertext_commits =
+        CiphertextCommits::deploy(&provider_deploy, already_added_revert).await?;
+    let tenant_id = insert_random_tenant(&env.db_pool).await?;
+    let handle = random::<[u8; 32]>().to_vec();
+    insert_ciphertext_digest(&env.db_pool, tenant_id, &handle).await?;
+
+    // Reverted at nonce 1 while the sender was down, the gas limit skips the estimation that would fail.
+    let receipt = ciphertext_commits
+        .addCiphertextMaterial(
+            FixedBytes::from_slice(&handle),
+            U256::ZERO,
+            FixedBytes::from([1u8; 32]),
+            FixedBytes::from([2u8; 32]),
+        )
+        .gas(1_000_000)
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    assert!(!receipt.status());
+    let item = DeadLetterItem::AddCiphertext {
+        handle: handle.clone(),
+    };
+    journal_transaction(&env, 1, receipt.transaction_hash, &item).await?;
+
+    let _txn_sender = TransactionSender::new(
+        PrivateKeySigner::random().address(),
+        *ciphertext_commits.address(),
+        PrivateKeySigner::random().address(),
+        env.signer.clone(),
+        provider.clone(),
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+    )
+    .await?;
+
+    assert_eq!(
+        status(&env.db_pool, receipt.transaction_hash).await?,
+        "reverted"
+    );
+    let row = sqlx::query!(
+        "SELECT txn_is_sent, txn_retry_count, txn_last_error FROM ciphertext_digest WHERE handle = $1",
+        handle,
+    )
+    .fetch_one(&env.db_pool)
+    .await?;
+    assert!(!row.txn_is_sent);
+    assert_eq!(row.txn_retry_count, 1);
+    assert_eq!(
+        row.txn_last_error.as_deref(),
+        Some("receipt status = false")
+    );
+    let errors = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM tx_sender_errors WHERE operation = $1 AND item_key = $2",
+        item.operation(),
+        item.key(),
+    )
+    .fetch_one(&env.db_pool)
+    .await?;
+    assert_eq!(errors, Some(1));
+    Ok(())
+}
+
+async fn insert_ciphertext_digest(
+    pool: &PgPool,
+    tenant_id: i32,
+    handle: &[u8],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO ciphertext_digest (tenant_id, handle, ciphertext, ciphertext128)
+         VALUES ($1, $2, $3, $4)",
+        tenant_id,
+        handle,
+        vec![1u8; 32],
+        vec![2u8; 32],
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn journal_transaction(
+    env: &TestEnvironment,
+    nonce: i64,
+    tx_hash: FixedBytes<32>,
+    item: &DeadLetterItem,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO gw_transactions (signer_address, nonce, tx_hash, operation, tx_request,
+            item_operation, item_keys)
+         VALUES ($1, $2, $3, $4, '', $5, $6)",
+        env.signer.address().to_string(),
+        nonce,
+        tx_hash.as_slice(),
+        env.conf.add_ciphertexts_db_channel,
+        item.operation(),
+        &vec![item.key()],
+    )
+    .execute(&env.db_pool)
+    .await?;
+    Ok(())
+}
+
+async fn status(pool: &PgPool, tx_hash: FixedBytes<32>) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT status FROM gw_transactions WHERE tx_hash = $1",
+        tx_hash.as_slice(),
+    )
+    .fetch_one(pool)
+    .await
+}
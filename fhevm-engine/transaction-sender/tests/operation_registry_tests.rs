@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use alloy::network::Ethereum;
+use alloy::providers::{Provider, ProviderBuilder, RootProvider, WsConnect};
+use alloy::signers::local::PrivateKeySigner;
+use async_trait::async_trait;
+use common::TestEnvironment;
+use serial_test::serial;
+use transaction_sender::{
+    ConfigSettings, FillersWithoutNonceManagement, NonceManagedProvider, OperationBuilder,
+    OperationConfig, OperationContext, OperationHealth, OperationRegistry, OperationsConfig,
+    TransactionOperation, TransactionSender,
+};
+
+mod common;
+
+struct StaticOperation {
+    queue_depth: i64,
+}
+
+#[async_trait]
+impl<P: Provider<Ethereum> + Clone + 'static> TransactionOperation<P> for StaticOperation {
+    fn channel(&self) -> &str {
+        "static_operation"
+    }
+
+    async fn execute(&self) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    async fn queue_depth(&self) -> anyhow::Result<i64> {
+        Ok(self.queue_depth)
+    }
+}
+
+struct StaticOperationBuilder;
+
+#[async_trait]
+impl<P: Provider<Ethereum> + Clone + 'static> OperationBuilder<P> for StaticOperationBuilder {
+    async fn build(
+        &self,
+        _context: &OperationContext<P>,
+        config: &OperationConfig,
+    ) -> anyhow::Result<Arc<dyn TransactionOperation<P>>> {
+        let queue_depth = config.options["queue_depth"].as_i64().unwrap_or_default();
+        Ok(Arc::new(StaticOperation { queue_depth }))
+    }
+}
+
+#[test]
+fn operations_config_parsing() {
+    let config: OperationsConfig = serde_json::from_str(
+        r#"{
+            "operations": {
+                "verify_proof": { "batch_limit": 64, "gas": 1000000 },
+                "add_ciphertext": { "enabled": false }
+            }
+        }"#,
+    )
+    .unwrap();
+    let verify_proof = &config.operations["verify_proof"];
+    assert!(verify_proof.enabled);
+    assert_eq!(verify_proof.batch_limit, Some(64));
+    assert_eq!(verify_proof.gas, Some(1000000));
+    assert!(!config.operations["add_ciphertext"].enabled);
+    assert!(serde_json::from_str::<OperationsConfig>(
+        r#"{ "operations": { "verify_proof": { "batch_size": 64 } } }"#
+    )
+    .is_err());
+}
+
+#[test]
+fn db_channels_follow_operations_config() {
+    let config: OperationsConfig = serde_json::from_str(
+        r#"{ "operations": { "add_ciphertext": { "db_channel": "custom_channel" } } }"#,
+    )
+    .unwrap();
+    let conf = ConfigSettings::default();
+    let db_channels = OperationRegistry::<RootProvider>::default()
+        .with_config(config)
+        .register("static", StaticOperationBuilder)
+        .db_channels(&conf);
+    assert_eq!(db_channels["add_ciphertext"], "custom_channel");
+    assert_eq!(
+        db_channels["verify_proof"],
+        conf.verify_proof_resp_db_channel
+    );
+    assert_eq!(db_channels["allow_handle"], conf.allow_handle_db_channel);
+    assert!(!db_channels.contains_key("static"));
+}
+
+#[test]
+fn conf_follows_operations_config() {
+    let config: OperationsConfig = serde_json::from_str(
+        r#"{
+            "operations": {
+                "allow_handle": { "max_retries": 2 },
+                "delegation": { "max_retries": 4, "db_channel": "custom_channel" }
+            }
+        }"#,
+    )
+    .unwrap();
+    let conf = ConfigSettings::default();
+    let operations_conf = OperationRegistry::<RootProvider>::default()
+        .with_config(config)
+        .register("static", StaticOperationBuilder)
+        .conf(&conf);
+    assert_eq!(operations_conf.allow_handle_max_retries, 2);
+    assert_eq!(operations_conf.delegation_max_retries, 4);
+    assert_eq!(operations_conf.delegation_db_channel, "custom_channel");
+    assert_eq!(
+        operations_conf.add_ciphertexts_max_retries,
+        conf.add_ciphertexts_max_retries
+    );
+}
+
+#[tokio::test]
+#[serial(db)]
+async fn only_enabled_operations_are_run() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+    let provider = NonceManagedProvider::new(
+        ProviderBuilder::default()
+            .filler(FillersWithoutNonceManagement::default())
+            .wallet(env.wallet.clone())
+            .on_ws(WsConnect::new(env.ws_endpoint_url()))
+            .await?,
+        Some(env.wallet.default_signer().address()),
+    );
+    let config: OperationsConfig = serde_json::from_str(
+        r#"{
+            "operations": {
+                "add_ciphertext": { "enabled": false },
+                "allow_handle": { "enabled": false },
+                "delegation": { "enabled": false },
+                "static": { "options": { "queue_depth": 7 } }
+            }
+        }"#,
+    )?;
+    let txn_sender = TransactionSender::new_with_registry(
+        PrivateKeySigner::random().address(),
+        PrivateKeySigner::random().address(),
+        PrivateKeySigner::random().address(),
+        env.signer.clone(),
+        provider.clone(),
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+        OperationRegistry::default()
+            .register("static", StaticOperationBuilder)
+            .with_config(config),
+    )
+    .await?;
+
+    let statuses = txn_sender.operations_status().await;
+    assert_eq!(
+        statuses.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+        vec!["static", "verify_proof"]
+    );
+    assert!(statuses
+        .iter()
+        .all(|s| s.health == OperationHealth::Healthy));
+    assert_eq!(statuses[0].queue_depth, Some(7));
+    assert_eq!(statuses[1].queue_depth, Some(0));
+
+    // Unknown operations are rejected.
+    let config: OperationsConfig =
+        serde_json::from_str(r#"{ "operations": { "unknown": { "enabled": false } } }"#)?;
+    assert!(TransactionSender::new_with_registry(
+        PrivateKeySigner::random().address(),
+        PrivateKeySigner::random().address(),
+        PrivateKeySigner::random().address(),
+        env.signer.clone(),
+        provider,
+        env.cancel_token.clone(),
+        env.conf.clone(),
+        None,
+        OperationRegistry::default().with_config(config),
+    )
+    .await
+    .is_err());
+    Ok(())
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use alloy::consensus::TxEip1559;
+use alloy::network::{EthereumWallet, TransactionBuilder, TxSigner};
+use alloy::primitives::{keccak256, U256};
+use alloy::providers::{Provider, ProviderBuilder, WsConnect};
+use alloy::rpc::types::TransactionRequest;
+use alloy::signers::local::PrivateKeySigner;
+use common::TestEnvironment;
+use fhevm_engine_common::signer::proto::{
+    remote_signer_server::{self, RemoteSignerServer},
+    GetAddressRequest, GetAddressResponse, SignHashRequest, SignHashResponse,
+};
+use fhevm_engine_common::signer::{KeySigner, LocalRemoteSigner, SignerSource};
+use serial_test::serial;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[tokio::test]
+#[serial(db)]
+async fn remote_signer_signs_hashes_and_transactions() -> anyhow::Result<()> {
+    let env = TestEnvironment::new().await?;
+
+    // Stand-in of the remote signing service, keeping the key of the funded signer.
+    let addr: SocketAddr = "127.0.0.1:50091".parse()?;
+    let service = LocalRemoteSigner::new(env.signer.clone()).into_service();
+    let cancel_token = env.cancel_token.clone();
+    let server = tokio::spawn(async move {
+        tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_shutdown(addr, cancel_token.cancelled())
+            .await
+    });
+
+    let source = SignerSource::Remote(format!("http://{}", addr));
+    let mut signer = KeySigner::new(&source).await;
+    for _ in 0..10 {
+        if signer.is_ok() {
+            break;
+        }
+        // The server may not be listening yet.
+        sleep(Duration::from_millis(200)).await;
+        signer = KeySigner::new(&source).await;
+    }
+    let signer = signer?;
+    assert_eq!(signer.address(), env.signer.address());
+
+    let hash = keccak256(b"verify proof response");
+    let signature = signer.sign_hash(&hash).await?;
+    assert_eq!(
+        signature.recover_address_from_prehash(&hash)?,
+        env.signer.address()
+    );
+
+    let chain_id = ProviderBuilder::new()
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?
+        .get_chain_id()
+        .await?;
+    let signer = signer.with_chain_id(Some(chain_id));
+    let provider = ProviderBuilder::new()
+        .wallet(EthereumWallet::new(signer.clone()))
+        .on_ws(WsConnect::new(env.ws_endpoint_url()))
+        .await?;
+    let receipt = provider
+        .send_transaction(
+            TransactionRequest::default()
+                .with_to(PrivateKeySigner::random().address())
+                .with_value(U256::from(1)),
+        )
+        .await?
+        .get_receipt()
+        .await?;
+    assert!(receipt.status());
+    assert_eq!(receipt.from, env.signer.address());
+
+    // Transactions of another chain are rejected, as with a local key.
+    let mut tx = TxEip1559 {
+        chain_id: chain_id + 1,
+        ..Default::default()
+    };
+    assert!(matches!(
+        signer.sign_transaction(&mut tx).await,
+        Err(alloy::signers::Error::TransactionChainIdMismatch { .. })
+    ));
+
+    env.cancel_token.cancel();
+    server.await??;
+    Ok(())
+}
+
+/// Service announcing the address of one key and signing with another, as a misconfigured KMS.
+struct MismatchedRemoteSigner {
+    announced: LocalRemoteSigner,
+    signing: LocalRemoteSigner,
+}
+
+#[tonic::async_trait]
+impl remote_signer_server::RemoteSigner for MismatchedRemoteSigner {
+    async fn get_address(
+        &self,
+        request: tonic::Request<GetAddressRequest>,
+    ) -> Result<tonic::Response<GetAddressResponse>, tonic::Status> {
+        self.announced.get_address(request).await
+    }
+
+    async fn sign_hash(
+        &self,
+        request: tonic::Request<SignHashRequest>,
+    ) -> Result<tonic::Response<SignHashResponse>, tonic::Status> {
+        self.signing.sign_hash(request).await
+    }
+}
+
+#[tokio::test]
+async fn remote_signer_rejects_signatures_of_another_key() -> anyhow::Result<()> {
+    let announced = PrivateKeySigner::random();
+    let service = RemoteSignerServer::new(MismatchedRemoteSigner {
+        announced: LocalRemoteSigner::new(announced.clone()),
+        signing: LocalRemoteSigner::new(PrivateKeySigner::random()),
+    });
+    let addr: SocketAddr = "127.0.0.1:50092".parse()?;
+    let cancel_token = tokio_util::sync::CancellationToken::new();
+    let shutdown = cancel_token.clone();
+    let server = tokio::spawn(async move {
+        tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_shutdown(addr, shutdown.cancelled())
+            .await
+    });
+
+    let source = SignerSource::Remote(format!("http://{}", addr));
+    let mut signer = KeySigner::new(&source).await;
+    for _ in 0..10 {
+        if signer.is_ok() {
+            break;
+        }
+        // The server may not be listening yet.
+        sleep(Duration::from_millis(200)).await;
+        signer = KeySigner::new(&source).await;
+    }
+    let signer = signer?;
+    assert_eq!(signer.address(), announced.address());
+
+    let hash = keccak256(b"verify proof response");
+    let err = signer
+        .sign_hash(&hash)
+        .await
+        .expect_err("signature of another key");
+    assert!(err.to_string().contains("expected"), "{}", err);
+
+    cancel_token.cancel();
+    server.await??;
+    Ok(())
+}
@@ -141,7 +141,7 @@ async fn execute_verify_proof_routine(
     if let Ok(row) = sqlx::query(
         "SELECT zk_proof_id, input, chain_id, contract_address, user_address
             FROM verify_proofs
-            WHERE verified IS NULL
+            WHERE verified IS NULL AND NOT orphaned
             ORDER BY zk_proof_id ASC
             LIMIT 1 FOR UPDATE SKIP LOCKED",
     )
@@ -369,7 +369,7 @@ async fn get_remaining_tasks(pool: &PgPool) -> Result<i64, ExecutionError> {
         FROM (
             SELECT 1
             FROM verify_proofs
-            WHERE verified IS NULL
+            WHERE verified IS NULL AND NOT orphaned
             ORDER BY zk_proof_id ASC
             FOR UPDATE SKIP LOCKED
         ) AS unlocked_rows;